@@ -16,8 +16,10 @@ class CommandMaker:
         return f'rm -r {PathMaker.logs_path()} ; mkdir -p {PathMaker.logs_path()}'
 
     @staticmethod
-    def compile():
-        return 'cargo build --quiet --release --features benchmark'
+    def compile(byzantine=False):
+        assert isinstance(byzantine, bool)
+        features = 'benchmark,byzantine' if byzantine else 'benchmark'
+        return f'cargo build --quiet --release --features {features}'
 
     @staticmethod
     def generate_key(filename):
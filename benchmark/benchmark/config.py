@@ -6,8 +6,9 @@ class ConfigError(Exception):
 
 
 class Key:
-    def __init__(self, name, secret):
+    def __init__(self, name, bls_name, secret):
         self.name = name
+        self.bls_name = bls_name
         self.secret = secret
 
     @classmethod
@@ -15,17 +16,18 @@ class Key:
         assert isinstance(filename, str)
         with open(filename, 'r') as f:
             data = load(f)
-        return cls(data['name'], data['secret'])
+        return cls(data['name'], data['bls_name'], data['secret'])
 
 
 class Committee:
-    def __init__(self, names, consensus_addr, transactions_addr, mempool_addr):
-        inputs = [names, consensus_addr, transactions_addr, mempool_addr]
+    def __init__(self, names, bls_names, consensus_addr, transactions_addr, mempool_addr):
+        inputs = [names, bls_names, consensus_addr, transactions_addr, mempool_addr]
         assert all(isinstance(x, list) for x in inputs)
         assert all(isinstance(x, str) for y in inputs for x in y)
         assert len({len(x) for x in inputs}) == 1
 
         self.names = names
+        self.bls_names = bls_names
         self.consensus = consensus_addr
         self.front = transactions_addr
         self.mempool = mempool_addr
@@ -37,8 +39,8 @@ class Committee:
 
     def _build_consensus(self):
         node = {}
-        for a, n in zip(self.consensus, self.names):
-            node[n] = {'name': n, 'stake': 1, 'address': a}
+        for a, n, b in zip(self.consensus, self.names, self.bls_names):
+            node[n] = {'name': n, 'stake': 1, 'bls_key': b, 'address': a}
         return {'authorities': node, 'epoch': 1}
 
     def _build_mempool(self):
@@ -70,24 +72,27 @@ class Committee:
         mempool_authorities = data['mempool']['authorities'].values()
 
         names = [x['name'] for x in consensus_authorities]
+        bls_names = [x['bls_key'] for x in consensus_authorities]
         consensus_addr = [x['address'] for x in consensus_authorities]
         transactions_addr = [
             x['transactions_address'] for x in mempool_authorities
         ]
         mempool_addr = [x['mempool_address'] for x in mempool_authorities]
-        return cls(names, consensus_addr, transactions_addr, mempool_addr)
+        return cls(names, bls_names, consensus_addr, transactions_addr, mempool_addr)
 
 
 class LocalCommittee(Committee):
-    def __init__(self, names, port):
+    def __init__(self, names, bls_names, port):
         assert isinstance(names, list) and all(
             isinstance(x, str) for x in names)
+        assert isinstance(bls_names, list) and all(
+            isinstance(x, str) for x in bls_names)
         assert isinstance(port, int)
         size = len(names)
         consensus = [f'127.0.0.1:{port + i}' for i in range(size)]
         front = [f'127.0.0.1:{port + i + size}' for i in range(size)]
         mempool = [f'127.0.0.1:{port + i + 2*size}' for i in range(size)]
-        super().__init__(names, consensus, front, mempool)
+        super().__init__(names, bls_names, consensus, front, mempool)
 
 
 class NodeParameters:
@@ -95,25 +100,45 @@ class NodeParameters:
         inputs = []
         try:
             inputs += [json['consensus']['timeout_delay']]
+            inputs += [json['consensus']['max_timeout_delay']]
             inputs += [json['consensus']['sync_retry_delay']]
+            inputs += [json['consensus']['sync_max_requests']]
+            inputs += [json['consensus']['sync_max_bytes']]
+            inputs += [json['consensus']['checkpoint_interval']]
+            inputs += [json['consensus']['retention']]
             inputs += [json['mempool']['gc_depth']]
             inputs += [json['mempool']['sync_retry_delay']]
             inputs += [json['mempool']['sync_retry_nodes']]
+            inputs += [json['mempool']['sync_max_requests']]
+            inputs += [json['mempool']['sync_max_bytes']]
             inputs += [json['mempool']['batch_size']]
             inputs += [json['mempool']['max_batch_delay']]
+            inputs += [json['mempool']['dedup_capacity']]
+            inputs += [json['mempool']['max_transaction_size']]
+            inputs += [json['mempool']['signed_transactions']]
         except KeyError as e:
             raise ConfigError(f'Malformed parameters: missing key {e}')
 
         if not all(isinstance(x, int) for x in inputs):
             raise ConfigError('Invalid parameters type')
 
+        # The behaviours of the Byzantine nodes (if any).
+        self.byzantine = json.get('byzantine', {})
+        if not isinstance(self.byzantine, dict):
+            raise ConfigError('Invalid Byzantine behaviours')
+
         self.timeout_delay = json['consensus']['timeout_delay']
-        self.json = json
+        self.json = {k: v for k, v in json.items() if k != 'byzantine'}
 
-    def print(self, filename):
+    def print(self, filename, byzantine=False):
         assert isinstance(filename, str)
+        assert isinstance(byzantine, bool)
+        json = self.json
+        if byzantine:
+            consensus = {**json['consensus'], 'byzantine': self.byzantine}
+            json = {**json, 'consensus': consensus}
         with open(filename, 'w') as f:
-            dump(self.json, f, indent=4, sort_keys=True)
+            dump(json, f, indent=4, sort_keys=True)
 
 
 class BenchParameters:
@@ -133,6 +158,7 @@ class BenchParameters:
             self.rate = [int(x) for x in rate]
             self.tx_size = int(json['tx_size'])
             self.faults = int(json['faults'])
+            self.byzantine = int(json['byzantine']) if 'byzantine' in json else 0
             self.duration = int(json['duration'])
             self.runs = int(json['runs']) if 'runs' in json else 1
         except KeyError as e:
@@ -141,7 +167,7 @@ class BenchParameters:
         except ValueError:
             raise ConfigError('Invalid parameters type')
 
-        if min(self.nodes) <= self.faults:
+        if min(self.nodes) <= self.faults + self.byzantine:
             raise ConfigError('There should be more nodes than faults')
 
 
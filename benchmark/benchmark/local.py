@@ -51,7 +51,7 @@ class LocalBench:
             sleep(0.5)  # Removing the store may take time.
 
             # Recompile the latest code.
-            cmd = CommandMaker.compile().split()
+            cmd = CommandMaker.compile(byzantine=self.byzantine > 0).split()
             subprocess.run(cmd, check=True, cwd=PathMaker.node_crate_path())
 
             # Create alias for the client and nodes binary.
@@ -67,10 +67,14 @@ class LocalBench:
                 keys += [Key.from_file(filename)]
 
             names = [x.name for x in keys]
-            committee = LocalCommittee(names, self.BASE_PORT)
+            bls_names = [x.bls_name for x in keys]
+            committee = LocalCommittee(names, bls_names, self.BASE_PORT)
             committee.print(PathMaker.committee_file())
 
             self.node_parameters.print(PathMaker.parameters_file())
+            self.node_parameters.print(
+                PathMaker.byzantine_parameters_file(), byzantine=True
+            )
 
             # Do not boot faulty nodes.
             nodes = nodes - self.faults
@@ -89,15 +93,17 @@ class LocalBench:
                 )
                 self._background_run(cmd, log_file)
 
-            # Run the nodes.
+            # Run the nodes (the first ones are Byzantine).
             dbs = [PathMaker.db_path(i) for i in range(nodes)]
             node_logs = [PathMaker.node_log_file(i) for i in range(nodes)]
-            for key_file, db, log_file in zip(key_files, dbs, node_logs):
+            for i, (key_file, db, log_file) in enumerate(zip(key_files, dbs, node_logs)):
+                parameters = PathMaker.byzantine_parameters_file() \
+                    if i < self.byzantine else PathMaker.parameters_file()
                 cmd = CommandMaker.run_node(
                     key_file,
                     PathMaker.committee_file(),
                     db,
-                    PathMaker.parameters_file(),
+                    parameters,
                     debug=debug
                 )
                 self._background_run(cmd, log_file)
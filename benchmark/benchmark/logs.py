@@ -41,14 +41,17 @@ class LogParser:
                 results = p.map(self._parse_nodes, nodes)
         except (ValueError, IndexError) as e:
             raise ParseError(f'Failed to parse node logs: {e}')
-        proposals, commits, sizes, self.received_samples, timeouts, self.configs \
-            = zip(*results)
+        proposals, commits, sizes, self.received_samples, timeouts, \
+            timeout_delays, rejections, divergences, self.configs = zip(*results)
         self.proposals = self._merge_results([x.items() for x in proposals])
         self.commits = self._merge_results([x.items() for x in commits])
         self.sizes = {
             k: v for x in sizes for k, v in x.items() if k in self.commits
         }
         self.timeouts = max(timeouts)
+        self.max_timeout_delay = max(timeout_delays)
+        self.rejections = sum(rejections)
+        self.divergences = sum(divergences)
 
         # Check whether clients missed their target rate.
         if self.misses != 0:
@@ -61,6 +64,14 @@ class LogParser:
         if self.timeouts > 2:
             Print.warn(f'Nodes timed out {self.timeouts:,} time(s)')
 
+        # Check whether the application rejected proposals.
+        if self.rejections != 0:
+            Print.warn(f'Nodes rejected {self.rejections:,} proposal(s)')
+
+        # Check whether the nodes' executions diverged from the leaders'.
+        if self.divergences != 0:
+            Print.warn(f'Execution diverged {self.divergences:,} time(s)')
+
     def _merge_results(self, input):
         # Keep the earliest timestamp.
         merged = {}
@@ -108,6 +119,15 @@ class LogParser:
         tmp = findall(r'.* WARN .* Timeout', log)
         timeouts = len(tmp)
 
+        tmp = findall(r'Pacemaker timeout set to (\d+) ms', log)
+        timeout_delay = max((int(x) for x in tmp), default=0)
+
+        tmp = findall(r'.* WARN .* Rejected proposal', log)
+        rejections = len(tmp)
+
+        tmp = findall(r'.* ERROR .* Execution diverged', log)
+        divergences = len(tmp)
+
         configs = {
             'consensus': {
                 'timeout_delay': int(
@@ -138,7 +158,8 @@ class LogParser:
             }
         }
 
-        return proposals, commits, sizes, samples, timeouts, configs
+        return proposals, commits, sizes, samples, timeouts, timeout_delay, \
+            rejections, divergences, configs
 
     def _to_posix(self, string):
         x = datetime.fromisoformat(string.replace('Z', '+00:00'))
@@ -221,6 +242,8 @@ class LogParser:
             f' End-to-end TPS: {round(end_to_end_tps):,} tx/s\n'
             f' End-to-end BPS: {round(end_to_end_bps):,} B/s\n'
             f' End-to-end latency: {round(end_to_end_latency):,} ms\n'
+            '\n'
+            f' Max pacemaker timeout: {self.max_timeout_delay:,} ms\n'
             '-----------------------------------------\n'
         )
 
@@ -112,7 +112,7 @@ class Bench:
         output = c.run(cmd, hide=True)
         self._check_stderr(output)
 
-    def _update(self, hosts):
+    def _update(self, hosts, byzantine=False):
         Print.info(
             f'Updating {len(hosts)} nodes (branch "{self.settings.branch}")...'
         )
@@ -121,7 +121,7 @@ class Bench:
             f'(cd {self.settings.repo_name} && git checkout -f {self.settings.branch})',
             f'(cd {self.settings.repo_name} && git pull -f)',
             'source $HOME/.cargo/env',
-            f'(cd {self.settings.repo_name}/node && {CommandMaker.compile()})',
+            f'(cd {self.settings.repo_name}/node && {CommandMaker.compile(byzantine)})',
             CommandMaker.alias_binaries(
                 f'./{self.settings.repo_name}/target/release/'
             )
@@ -129,7 +129,7 @@ class Bench:
         g = Group(*hosts, user='ubuntu', connect_kwargs=self.connect)
         g.run(' && '.join(cmd), hide=True)
 
-    def _config(self, hosts, node_parameters):
+    def _config(self, hosts, node_parameters, byzantine=False):
         Print.info('Generating configuration files...')
 
         # Cleanup all local configuration files.
@@ -137,7 +137,7 @@ class Bench:
         subprocess.run([cmd], shell=True, stderr=subprocess.DEVNULL)
 
         # Recompile the latest code.
-        cmd = CommandMaker.compile().split()
+        cmd = CommandMaker.compile(byzantine).split()
         subprocess.run(cmd, check=True, cwd=PathMaker.node_crate_path())
 
         # Create alias for the client and nodes binary.
@@ -153,13 +153,19 @@ class Bench:
             keys += [Key.from_file(filename)]
 
         names = [x.name for x in keys]
+        bls_names = [x.bls_name for x in keys]
         consensus_addr = [f'{x}:{self.settings.consensus_port}' for x in hosts]
         front_addr = [f'{x}:{self.settings.front_port}' for x in hosts]
         mempool_addr = [f'{x}:{self.settings.mempool_port}' for x in hosts]
-        committee = Committee(names, consensus_addr, front_addr, mempool_addr)
+        committee = Committee(
+            names, bls_names, consensus_addr, front_addr, mempool_addr
+        )
         committee.print(PathMaker.committee_file())
 
         node_parameters.print(PathMaker.parameters_file())
+        node_parameters.print(
+            PathMaker.byzantine_parameters_file(), byzantine=True
+        )
 
         # Cleanup all nodes.
         cmd = f'{CommandMaker.cleanup()} || true'
@@ -173,6 +179,7 @@ class Bench:
             c.put(PathMaker.committee_file(), '.')
             c.put(PathMaker.key_file(i), '.')
             c.put(PathMaker.parameters_file(), '.')
+            c.put(PathMaker.byzantine_parameters_file(), '.')
 
         return committee
 
@@ -200,16 +207,19 @@ class Bench:
             )
             self._background_run(host, cmd, log_file)
 
-        # Run the nodes.
+        # Run the nodes (the first ones are Byzantine).
         key_files = [PathMaker.key_file(i) for i in range(len(hosts))]
         dbs = [PathMaker.db_path(i) for i in range(len(hosts))]
         node_logs = [PathMaker.node_log_file(i) for i in range(len(hosts))]
-        for host, key_file, db, log_file in zip(hosts, key_files, dbs, node_logs):
+        nodes = zip(hosts, key_files, dbs, node_logs)
+        for i, (host, key_file, db, log_file) in enumerate(nodes):
+            parameters = PathMaker.byzantine_parameters_file() \
+                if i < bench_parameters.byzantine else PathMaker.parameters_file()
             cmd = CommandMaker.run_node(
                 key_file,
                 PathMaker.committee_file(),
                 db,
-                PathMaker.parameters_file(),
+                parameters,
                 debug=debug
             )
             self._background_run(host, cmd, log_file)
@@ -259,7 +269,7 @@ class Bench:
 
         # Update nodes.
         try:
-            self._update(selected_hosts)
+            self._update(selected_hosts, bench_parameters.byzantine > 0)
         except (GroupException, ExecutionError) as e:
             e = FabricError(e) if isinstance(e, GroupException) else e
             raise BenchError('Failed to update nodes', e)
@@ -272,7 +282,9 @@ class Bench:
 
                 # Upload all configuration files.
                 try:
-                    self._config(hosts, node_parameters)
+                    self._config(
+                        hosts, node_parameters, bench_parameters.byzantine > 0
+                    )
                 except (subprocess.SubprocessError, GroupException) as e:
                     e = FabricError(e) if isinstance(e, GroupException) else e
                     Print.error(BenchError('Failed to configure nodes', e))
@@ -26,6 +26,10 @@ class PathMaker:
     def parameters_file():
         return '.parameters.json'
 
+    @staticmethod
+    def byzantine_parameters_file():
+        return '.byzantine-parameters.json'
+
     @staticmethod
     def key_file(i):
         assert isinstance(i, int) and i >= 0
@@ -13,6 +13,7 @@ def local(ctx):
     ''' Run benchmarks on localhost '''
     bench_params = {
         'faults': 0,
+        'byzantine': 0,
         'nodes': 4,
         'rate': 1_000,
         'tx_size': 512,
@@ -21,14 +22,31 @@ def local(ctx):
     node_params = {
         'consensus': {
             'timeout_delay': 1_000,
+            'max_timeout_delay': 10_000,
             'sync_retry_delay': 10_000,
+            'sync_max_requests': 100,
+            'sync_max_bytes': 10_000_000,
+            'checkpoint_interval': 100,
+            'retention': 200,
         },
         'mempool': {
             'gc_depth': 50,
             'sync_retry_delay': 5_000,
             'sync_retry_nodes': 3,
+            'sync_max_requests': 1_000,
+            'sync_max_bytes': 100_000_000,
             'batch_size': 15_000,
-            'max_batch_delay': 10
+            'max_batch_delay': 10,
+            'dedup_capacity': 100_000,
+            'max_transaction_size': 100_000,
+            'signed_transactions': False
+        },
+        'byzantine': {
+            'equivocate': True,
+            'withhold_votes': False,
+            'vote_stale_blocks': True,
+            'inflate_tc': True,
+            'flood_sync_requests': 10,
         }
     }
     try:
@@ -97,6 +115,7 @@ def remote(ctx):
     ''' Run benchmarks on AWS '''
     bench_params = {
         'faults': 0,
+        'byzantine': 0,
         'nodes': [10, 20],
         'rate': [10_000, 30_000],
         'tx_size': 512,
@@ -106,14 +125,31 @@ def remote(ctx):
     node_params = {
         'consensus': {
             'timeout_delay': 5_000,
+            'max_timeout_delay': 60_000,
             'sync_retry_delay': 5_000,
+            'sync_max_requests': 100,
+            'sync_max_bytes': 10_000_000,
+            'checkpoint_interval': 1_000,
+            'retention': 2_000,
         },
         'mempool': {
             'gc_depth': 50,
             'sync_retry_delay': 5_000,
             'sync_retry_nodes': 3,
+            'sync_max_requests': 1_000,
+            'sync_max_bytes': 100_000_000,
             'batch_size': 500_000,
-            'max_batch_delay': 100
+            'max_batch_delay': 100,
+            'dedup_capacity': 100_000,
+            'max_transaction_size': 100_000,
+            'signed_transactions': False
+        },
+        'byzantine': {
+            'equivocate': True,
+            'withhold_votes': False,
+            'vote_stale_blocks': True,
+            'inflate_tc': True,
+            'flood_sync_requests': 10,
         }
     }
     try:
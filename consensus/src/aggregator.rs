@@ -1,33 +1,87 @@
 use crate::config::{Committee, Stake};
 use crate::consensus::Round;
 use crate::error::{ConsensusError, ConsensusResult};
-use crate::messages::{Timeout, Vote, QC, TC};
+use crate::evidence::Evidence;
+use crate::messages::{Signers, Timeout, Vote, QC, TC};
 use crypto::Hash as _;
-use crypto::{Digest, PublicKey, Signature};
+use crypto::{BlsSignature, Digest, PublicKey};
 use std::collections::{HashMap, HashSet};
 
 #[cfg(test)]
 #[path = "tests/aggregator_tests.rs"]
 pub mod aggregator_tests;
 
+/// We reject votes and timeouts for rounds beyond our current round plus this value.
+pub const MAX_ROUND_LOOKAHEAD: Round = 100;
+
+/// The maximum number of votes and timeouts (in total) we hold for a single authority.
+pub const MAX_PENDING_PER_AUTHORITY: usize = 50;
+
 pub struct Aggregator {
     committee: Committee,
+    /// The round of the core (the aggregator only holds messages of this round or later ones).
+    round: Round,
     votes_aggregators: HashMap<Round, HashMap<Digest, Box<QCMaker>>>,
     timeouts_aggregators: HashMap<Round, Box<TCMaker>>,
+    /// The first vote of each authority at each round (to detect double votes).
+    first_votes: HashMap<Round, HashMap<PublicKey, Vote>>,
+    /// The number of votes and timeouts we hold for each authority.
+    pending: HashMap<PublicKey, usize>,
+    /// The number of votes and timeouts we rejected from each authority.
+    rejected: HashMap<PublicKey, u64>,
 }
 
 impl Aggregator {
     pub fn new(committee: Committee) -> Self {
         Self {
             committee,
+            round: 0,
             votes_aggregators: HashMap::new(),
             timeouts_aggregators: HashMap::new(),
+            first_votes: HashMap::new(),
+            pending: HashMap::new(),
+            rejected: HashMap::new(),
+        }
+    }
+
+    /// Ensure we can hold one more message from the specified authority.
+    fn admit(&mut self, author: &PublicKey, round: Round) -> ConsensusResult<()> {
+        let pending = self.pending.entry(*author).or_insert(0);
+        if round <= self.round + MAX_ROUND_LOOKAHEAD && *pending < MAX_PENDING_PER_AUTHORITY {
+            *pending += 1;
+            return Ok(());
         }
+        let rejected = self.rejected.entry(*author).or_insert(0);
+        *rejected += 1;
+        Err(ConsensusError::MessageRejected {
+            author: *author,
+            round,
+            rejected: *rejected,
+        })
     }
 
     pub fn add_vote(&mut self, vote: Vote) -> ConsensusResult<Option<QC>> {
-        // TODO [issue #7]: A bad node may make us run out of memory by sending many votes
-        // with different round numbers or different digests.
+        // Ensure the author did not vote for another block at this round. Authorities can thus
+        // make us hold at most one vote per round.
+        match self
+            .first_votes
+            .get(&vote.round)
+            .and_then(|x| x.get(&vote.author))
+        {
+            Some(first) => {
+                if first.hash != vote.hash {
+                    let evidence = Evidence::DoubleVote(first.clone(), vote);
+                    bail!(ConsensusError::Equivocation(Box::new(evidence)));
+                }
+            }
+            None => {
+                self.admit(&vote.author, vote.round)?;
+                self.first_votes
+                    .entry(vote.round)
+                    .or_default()
+                    .insert(vote.author, vote.clone());
+            }
+        }
 
         // Add the new vote to our aggregator and see if we have a QC.
         self.votes_aggregators
@@ -39,8 +93,14 @@ impl Aggregator {
     }
 
     pub fn add_timeout(&mut self, timeout: Timeout) -> ConsensusResult<Option<TC>> {
-        // TODO: A bad node may make us run out of memory by sending many timeouts
-        // with different round numbers.
+        // Authorities can make us hold at most one timeout per round.
+        let known = self
+            .timeouts_aggregators
+            .get(&timeout.round)
+            .is_some_and(|x| x.used.contains_key(&timeout.author));
+        if !known {
+            self.admit(&timeout.author, timeout.round)?;
+        }
 
         // Add the new timeout to our aggregator and see if we have a TC.
         self.timeouts_aggregators
@@ -50,14 +110,35 @@ impl Aggregator {
     }
 
     pub fn cleanup(&mut self, round: &Round) {
+        self.round = *round;
         self.votes_aggregators.retain(|k, _| k >= round);
-        self.timeouts_aggregators.retain(|k, _| k >= round);
+
+        // Release the messages of past rounds from the authorities' quotas.
+        let mut released = Vec::new();
+        self.first_votes.retain(|k, votes| {
+            if k < round {
+                released.extend(votes.keys().cloned());
+            }
+            k >= round
+        });
+        self.timeouts_aggregators.retain(|k, maker| {
+            if k < round {
+                released.extend(maker.used.keys().cloned());
+            }
+            k >= round
+        });
+        for name in released {
+            if let Some(x) = self.pending.get_mut(&name) {
+                *x = x.saturating_sub(1);
+            }
+        }
+        self.pending.retain(|_, x| *x > 0);
     }
 }
 
 struct QCMaker {
     weight: Stake,
-    votes: Vec<(PublicKey, Signature)>,
+    votes: Vec<(PublicKey, BlsSignature)>,
     used: HashSet<PublicKey>,
 }
 
@@ -84,10 +165,13 @@ impl QCMaker {
         self.weight += committee.stake(&author);
         if self.weight >= committee.quorum_threshold() {
             self.weight = 0; // Ensures QC is only made once.
+            let signature = BlsSignature::aggregate(self.votes.iter().map(|(_, x)| x))?;
             return Ok(Some(QC {
                 hash: vote.hash.clone(),
                 round: vote.round,
-                votes: self.votes.clone(),
+                epoch: vote.epoch,
+                signers: Signers::new(self.votes.iter().map(|(name, _)| name), committee),
+                signature,
             }));
         }
         Ok(None)
@@ -96,8 +180,8 @@ impl QCMaker {
 
 struct TCMaker {
     weight: Stake,
-    votes: Vec<(PublicKey, Signature, Round)>,
-    used: HashSet<PublicKey>,
+    votes: Vec<(PublicKey, BlsSignature, Round)>,
+    used: HashMap<PublicKey, Timeout>,
 }
 
 impl TCMaker {
@@ -105,7 +189,7 @@ impl TCMaker {
         Self {
             weight: 0,
             votes: Vec::new(),
-            used: HashSet::new(),
+            used: HashMap::new(),
         }
     }
 
@@ -117,11 +201,16 @@ impl TCMaker {
     ) -> ConsensusResult<Option<TC>> {
         let author = timeout.author;
 
-        // Ensure it is the first time this authority votes.
-        ensure!(
-            self.used.insert(author),
-            ConsensusError::AuthorityReuse(author)
-        );
+        // Ensure it is the first time this authority votes, and that it did not sign another
+        // timeout for this round.
+        if let Some(first) = self.used.get(&author) {
+            if first.digest() != timeout.digest() {
+                let evidence = Evidence::DoubleTimeout(first.clone(), timeout);
+                bail!(ConsensusError::Equivocation(Box::new(evidence)));
+            }
+            bail!(ConsensusError::AuthorityReuse(author));
+        }
+        self.used.insert(author, timeout.clone());
 
         // Add the timeout to the accumulator.
         self.votes
@@ -129,9 +218,16 @@ impl TCMaker {
         self.weight += committee.stake(&author);
         if self.weight >= committee.quorum_threshold() {
             self.weight = 0; // Ensures TC is only created once.
+
+            // List the high QC rounds in the order of the signers bitmap.
+            let mut votes = self.votes.clone();
+            votes.sort_by_key(|(name, _, _)| *name);
             return Ok(Some(TC {
                 round: timeout.round,
-                votes: self.votes.clone(),
+                epoch: timeout.epoch,
+                signers: Signers::new(votes.iter().map(|(name, _, _)| name), committee),
+                high_qc_rounds: votes.iter().map(|(_, _, r)| *r).collect(),
+                signature: BlsSignature::aggregate(votes.iter().map(|(_, x, _)| x))?,
             }));
         }
         Ok(None)
@@ -0,0 +1,34 @@
+use crate::consensus::Round;
+use async_trait::async_trait;
+use crypto::Digest;
+
+/// The content of one of our blocks, as chosen by the application.
+#[derive(Clone, Debug, Default)]
+pub struct Proposal {
+    /// The digests of the batches to include, in order.
+    pub payload: Vec<Digest>,
+    /// Application-defined data to include in the block (if any).
+    pub metadata: Option<Vec<u8>>,
+}
+
+/// Lets the application choose what we propose when we are the leader.
+#[async_trait]
+pub trait ProposalBuilder: Send + 'static {
+    /// Build our block for the specified round, given the digests of the batches we did not
+    /// propose yet (in no particular order). The payload may only include candidates: the others
+    /// remain candidates for our next blocks, until another block commits them.
+    async fn build(&mut self, round: Round, candidates: Vec<Digest>) -> Proposal;
+}
+
+/// Proposes every candidate, without metadata.
+pub struct ProposeAll;
+
+#[async_trait]
+impl ProposalBuilder for ProposeAll {
+    async fn build(&mut self, _round: Round, candidates: Vec<Digest>) -> Proposal {
+        Proposal {
+            payload: candidates,
+            metadata: None,
+        }
+    }
+}
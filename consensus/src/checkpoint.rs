@@ -0,0 +1,56 @@
+use crate::config::{CommitRule, Committee};
+use crate::error::ConsensusResult;
+use crate::messages::CommittedBlock;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use store::keys::CHECKPOINT_KEY;
+use store::Store;
+
+#[cfg(test)]
+#[path = "tests/checkpoint_tests.rs"]
+pub mod checkpoint_tests;
+
+/// A committed block along with the proof that it is committed. Fresh (or badly lagging) nodes
+/// start from a checkpoint downloaded from their peers rather than from the genesis.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub committed: CommittedBlock,
+    /// The application's state after executing the committed block (if any).
+    pub snapshot: Option<Vec<u8>>,
+    /// A later committed block attesting the snapshot (if any). Consensus does not interpret it:
+    /// the application checks the snapshot against the metadata of the attestation.
+    pub attestation: Option<CommittedBlock>,
+}
+
+impl Checkpoint {
+    /// Check the checkpoint against the committee of its epoch. Checkpoints cannot span epochs:
+    /// their proof (and the attestation of their snapshot) must be certified by the committee
+    /// that committed the block.
+    pub fn verify(&self, committee: &Committee, commit_rule: CommitRule) -> ConsensusResult<()> {
+        self.committed.verify(committee, commit_rule)?;
+        if let Some(attestation) = &self.attestation {
+            attestation.verify(committee, commit_rule)?;
+        }
+        Ok(())
+    }
+
+    /// Load our latest checkpoint from storage (if any).
+    pub async fn load(store: &mut Store) -> ConsensusResult<Option<Self>> {
+        match store.read(CHECKPOINT_KEY.to_vec()).await? {
+            Some(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Write the checkpoint to storage, replacing the previous one.
+    pub async fn persist(&self, store: &mut Store) {
+        let value = bincode::serialize(self).expect("Failed to serialize checkpoint");
+        store.write(CHECKPOINT_KEY.to_vec(), value).await;
+    }
+}
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "Checkpoint({:?})", self.committed)
+    }
+}
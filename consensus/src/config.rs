@@ -1,5 +1,5 @@
-use crypto::PublicKey;
-use log::info;
+use crypto::{BlsPublicKey, PublicKey};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -7,48 +7,198 @@ use std::net::SocketAddr;
 pub type Stake = u32;
 pub type EpochNumber = u128;
 
+/// The rule used to commit blocks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CommitRule {
+    /// Commit the head of a 2-chain of blocks with consecutive rounds.
+    #[default]
+    TwoChain,
+    /// Classic HotStuff: lock on the head of 2-chains and commit the head of 3-chains of blocks
+    /// with consecutive rounds.
+    ThreeChain,
+}
+
+impl CommitRule {
+    /// The number of descendants (at consecutive rounds) a certified block needs to be committed.
+    pub fn depth(&self) -> usize {
+        match self {
+            Self::TwoChain => 1,
+            Self::ThreeChain => 2,
+        }
+    }
+}
+
+/// The rule used to elect the leader of each round (see `LeaderElector`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LeaderElection {
+    /// Rotate the leadership through the committee.
+    #[default]
+    RoundRobin,
+    /// Favour the authorities that authored committed blocks in the `window` rounds ending `lag`
+    /// rounds before the elected one (see `ReputationLeaderElector`).
+    Reputation { window: u64, lag: u64 },
+}
+
+/// Adversarial behaviours, to test the protocol against Byzantine nodes. They are only enacted
+/// by nodes compiled with the `byzantine` feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Byzantine {
+    /// Send conflicting blocks to different halves of the committee when leading a round.
+    pub equivocate: bool,
+    /// Never vote.
+    pub withhold_votes: bool,
+    /// Vote for every block of the current round, ignoring the safety rules.
+    pub vote_stale_blocks: bool,
+    /// Upon timing out, broadcast a (forged) TC claiming the highest possible high-QC rounds.
+    pub inflate_tc: bool,
+    /// The number of sync requests sent to every other node upon timing out.
+    pub flood_sync_requests: u64,
+}
+
+impl Byzantine {
+    pub fn is_honest(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// The defaults of the optional parameters (shared by parameter files and `Parameters::default`).
+fn default_max_timeout_delay() -> u64 {
+    60_000
+}
+
+fn default_sync_max_requests() -> u64 {
+    100
+}
+
+fn default_sync_max_bytes() -> u64 {
+    10_000_000
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Parameters {
     pub timeout_delay: u64,
+    /// The cap of the pacemaker's timeout delay (in ms). It cannot be lower than `timeout_delay`.
+    #[serde(default = "default_max_timeout_delay")]
+    pub max_timeout_delay: u64,
     pub sync_retry_delay: u64,
+    /// The maximum number of sync requests we serve each second to a single node (zero removes
+    /// the limit).
+    #[serde(default = "default_sync_max_requests")]
+    pub sync_max_requests: u64,
+    /// The maximum number of bytes we send each second to a single node in reply to its sync
+    /// requests (zero removes the limit).
+    #[serde(default = "default_sync_max_bytes")]
+    pub sync_max_bytes: u64,
+    /// The number of rounds between our checkpoints, and how far behind a proposal we must be
+    /// to request a checkpoint. Zero (the default) disables checkpoints: nodes then rebuild the
+    /// entire history when they join or lag behind. Nodes only catch up through the checkpoints
+    /// of their peers: every node of the committee must set it.
+    #[serde(default)]
+    pub checkpoint_interval: u64,
+    /// The number of committed rounds whose blocks (and batches) we keep in storage. Zero keeps
+    /// the entire history (archive mode). We never prune the blocks following the application's
+    /// latest snapshot: pruning thus requires checkpoints (see `checkpoint_interval`), and the
+    /// application must support snapshots (we snapshot its state at every checkpoint).
+    #[serde(default)]
+    pub retention: u64,
+    #[serde(default)]
+    pub commit_rule: CommitRule,
+    #[serde(default)]
+    pub leader_election: LeaderElection,
+    #[serde(default)]
+    pub byzantine: Byzantine,
 }
 
 impl Default for Parameters {
     fn default() -> Self {
         Self {
             timeout_delay: 5_000,
+            max_timeout_delay: default_max_timeout_delay(),
             sync_retry_delay: 10_000,
+            sync_max_requests: default_sync_max_requests(),
+            sync_max_bytes: default_sync_max_bytes(),
+            checkpoint_interval: 0,
+            retention: 0,
+            commit_rule: CommitRule::default(),
+            leader_election: LeaderElection::default(),
+            byzantine: Byzantine::default(),
         }
     }
 }
 
 impl Parameters {
+    /// Check the consistency of the parameters.
+    pub fn check(&self) -> Result<(), String> {
+        if self.retention > 0 && self.checkpoint_interval == 0 {
+            return Err("Pruning (retention) requires checkpoints (checkpoint_interval)".into());
+        }
+        Ok(())
+    }
+
     pub fn log(&self) {
         // NOTE: These log entries are used to compute performance.
         info!("Timeout delay set to {} rounds", self.timeout_delay);
+        info!("Max timeout delay set to {} ms", self.max_timeout_delay);
         info!("Sync retry delay set to {} ms", self.sync_retry_delay);
+        info!(
+            "Sync requests quota set to {} per s",
+            self.sync_max_requests
+        );
+        info!("Sync bytes quota set to {} B per s", self.sync_max_bytes);
+        if self.checkpoint_interval == 0 {
+            info!("Checkpoints disabled");
+        } else {
+            info!(
+                "Checkpoint interval set to {} rounds",
+                self.checkpoint_interval
+            );
+        }
+        if self.retention == 0 {
+            info!("Retention set to the entire history (archive mode)");
+        } else {
+            info!("Retention set to {} rounds", self.retention);
+        }
+        info!("Commit rule set to {:?}", self.commit_rule);
+        info!("Leader election set to {:?}", self.leader_election);
+        if !self.byzantine.is_honest() {
+            if cfg!(feature = "byzantine") {
+                warn!("Byzantine behaviours set to {:?}", self.byzantine);
+            } else {
+                warn!("Byzantine behaviours ignored: the node is compiled without the feature");
+            }
+        }
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Authority {
     pub stake: Stake,
+    /// The key used to sign votes and timeouts (they are aggregated into certificates).
+    pub bls_key: BlsPublicKey,
     pub address: SocketAddr,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Committee {
     pub authorities: HashMap<PublicKey, Authority>,
     pub epoch: EpochNumber,
 }
 
 impl Committee {
-    pub fn new(info: Vec<(PublicKey, Stake, SocketAddr)>, epoch: EpochNumber) -> Self {
+    pub fn new(
+        info: Vec<(PublicKey, Stake, BlsPublicKey, SocketAddr)>,
+        epoch: EpochNumber,
+    ) -> Self {
         Self {
             authorities: info
                 .into_iter()
-                .map(|(name, stake, address)| {
-                    let authority = Authority { stake, address };
+                .map(|(name, stake, bls_key, address)| {
+                    let authority = Authority {
+                        stake,
+                        bls_key,
+                        address,
+                    };
                     (name, authority)
                 })
                 .collect(),
@@ -64,6 +214,18 @@ impl Committee {
         self.authorities.get(name).map_or_else(|| 0, |x| x.stake)
     }
 
+    pub fn bls_key(&self, name: &PublicKey) -> Option<&BlsPublicKey> {
+        self.authorities.get(name).map(|x| &x.bls_key)
+    }
+
+    /// The names of the authorities, sorted. Certificates refer to signers by their index in
+    /// this list.
+    pub fn sorted_names(&self) -> Vec<PublicKey> {
+        let mut names: Vec<_> = self.authorities.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
     pub fn quorum_threshold(&self) -> Stake {
         // If N = 3f + 1 + k (0 <= k < 3)
         // then (2 N + 3) / 3 = 2f + 1 + (2k + 2)/3 = 2f + 1 + k = N - f
@@ -75,11 +237,15 @@ impl Committee {
         self.authorities.get(name).map(|x| x.address)
     }
 
+    /// The addresses of all the other authorities, sorted by name.
     pub fn broadcast_addresses(&self, myself: &PublicKey) -> Vec<(PublicKey, SocketAddr)> {
-        self.authorities
+        let mut addresses: Vec<_> = self
+            .authorities
             .iter()
             .filter(|(name, _)| name != &myself)
             .map(|(name, x)| (*name, x.address))
-            .collect()
+            .collect();
+        addresses.sort();
+        addresses
     }
 }
@@ -1,12 +1,16 @@
+use crate::builder::ProposalBuilder;
+use crate::checkpoint::Checkpoint;
 use crate::config::{Committee, Parameters};
 use crate::core::Core;
 use crate::error::ConsensusError;
+use crate::evidence::Evidence;
 use crate::helper::Helper;
 use crate::leader::LeaderElector;
 use crate::mempool::MempoolDriver;
-use crate::messages::{Block, Timeout, Vote, TC};
+use crate::messages::{Block, CommittedBlock, Reconfiguration, SignedRequest, Timeout, Vote, TC};
 use crate::proposer::Proposer;
 use crate::synchronizer::Synchronizer;
+use crate::validator::ProposalValidator;
 use async_trait::async_trait;
 use bytes::Bytes;
 use crypto::{Digest, PublicKey, SignatureService};
@@ -18,6 +22,7 @@ use serde::{Deserialize, Serialize};
 use std::error::Error;
 use store::Store;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::watch;
 
 #[cfg(test)]
 #[path = "tests/consensus_tests.rs"]
@@ -35,22 +40,34 @@ pub enum ConsensusMessage {
     Vote(Vote),
     Timeout(Timeout),
     TC(TC),
-    SyncRequest(Digest, PublicKey),
+    /// Request the ancestors of a block or the latest checkpoint of a peer.
+    Request(SignedRequest),
+    /// A chunk of ancestors, ordered from the most recent one.
+    SyncReply(Vec<Block>),
+    CheckpointReply(Checkpoint),
 }
 
 pub struct Consensus;
 
 impl Consensus {
     #[allow(clippy::too_many_arguments)]
-    pub fn spawn(
+    pub fn spawn<L: LeaderElector, V: ProposalValidator, B: ProposalBuilder>(
         name: PublicKey,
         committee: Committee,
         parameters: Parameters,
         signature_service: SignatureService,
         store: Store,
+        leader_elector: L,
+        validator: V,
+        builder: B,
         rx_mempool: Receiver<Digest>,
         tx_mempool: Sender<ConsensusMempoolMessage>,
-        tx_commit: Sender<Block>,
+        rx_reconfiguration: Receiver<Reconfiguration>,
+        rx_snapshot: Receiver<(Digest, Vec<u8>, CommittedBlock)>,
+        tx_commit: Sender<CommittedBlock>,
+        tx_evidence: Sender<Evidence>,
+        tx_checkpoint: Sender<Checkpoint>,
+        last_applied: Option<Digest>,
     ) {
         // NOTE: This log entry is used to compute performance.
         parameters.log();
@@ -59,6 +76,8 @@ impl Consensus {
         let (tx_loopback, rx_loopback) = channel(CHANNEL_CAPACITY);
         let (tx_proposer, rx_proposer) = channel(CHANNEL_CAPACITY);
         let (tx_helper, rx_helper) = channel(CHANNEL_CAPACITY);
+        let (tx_sync_reply, rx_sync_reply) = channel(CHANNEL_CAPACITY);
+        let (tx_committee, rx_committee) = watch::channel(committee.clone());
 
         // Spawn the network receiver.
         let mut address = committee
@@ -71,6 +90,7 @@ impl Consensus {
             ConsensusReceiverHandler {
                 tx_consensus,
                 tx_helper,
+                tx_sync_reply,
             },
         );
         info!(
@@ -78,17 +98,16 @@ impl Consensus {
             name, address
         );
 
-        // Make the leader election module.
-        let leader_elector = LeaderElector::new(committee.clone());
-
         // Make the mempool driver.
         let mempool_driver = MempoolDriver::new(store.clone(), tx_mempool, tx_loopback.clone());
 
         // Make the synchronizer.
         let synchronizer = Synchronizer::new(
             name,
-            committee.clone(),
+            signature_service.clone(),
+            rx_committee.clone(),
             store.clone(),
+            rx_sync_reply,
             tx_loopback.clone(),
             parameters.sync_retry_delay,
         );
@@ -100,27 +119,48 @@ impl Consensus {
             signature_service.clone(),
             store.clone(),
             leader_elector,
+            validator,
             mempool_driver,
             synchronizer,
             parameters.timeout_delay,
+            parameters.max_timeout_delay,
+            parameters.commit_rule,
+            parameters.checkpoint_interval,
+            parameters.retention,
+            parameters.byzantine.clone(),
             /* rx_message */ rx_consensus,
             rx_loopback,
+            rx_reconfiguration,
+            rx_snapshot,
             tx_proposer,
             tx_commit,
+            tx_evidence,
+            tx_checkpoint,
+            tx_committee,
+            last_applied,
         );
 
         // Spawn the block proposer.
         Proposer::spawn(
             name,
-            committee.clone(),
+            rx_committee.clone(),
             signature_service,
+            builder,
             rx_mempool,
             /* rx_message */ rx_proposer,
             tx_loopback,
+            parameters.byzantine,
         );
 
         // Spawn the helper module.
-        Helper::spawn(committee, store, /* rx_requests */ rx_helper);
+        Helper::spawn(
+            name,
+            rx_committee,
+            store,
+            /* rx_requests */ rx_helper,
+            parameters.sync_max_requests,
+            parameters.sync_max_bytes,
+        );
     }
 }
 
@@ -128,7 +168,8 @@ impl Consensus {
 #[derive(Clone)]
 struct ConsensusReceiverHandler {
     tx_consensus: Sender<ConsensusMessage>,
-    tx_helper: Sender<(Digest, PublicKey)>,
+    tx_helper: Sender<SignedRequest>,
+    tx_sync_reply: Sender<Vec<Block>>,
 }
 
 #[async_trait]
@@ -136,11 +177,16 @@ impl MessageHandler for ConsensusReceiverHandler {
     async fn dispatch(&self, writer: &mut Writer, serialized: Bytes) -> Result<(), Box<dyn Error>> {
         // Deserialize and parse the message.
         match bincode::deserialize(&serialized).map_err(ConsensusError::SerializationError)? {
-            ConsensusMessage::SyncRequest(missing, origin) => self
+            ConsensusMessage::Request(request) => self
                 .tx_helper
-                .send((missing, origin))
+                .send(request)
                 .await
                 .expect("Failed to send consensus message"),
+            ConsensusMessage::SyncReply(blocks) => self
+                .tx_sync_reply
+                .send(blocks)
+                .await
+                .expect("Failed to send sync reply"),
             message @ ConsensusMessage::Propose(..) => {
                 // Reply with an ACK.
                 let _ = writer.send(Bytes::from("Ack")).await;
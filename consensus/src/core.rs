@@ -1,88 +1,221 @@
-use crate::aggregator::Aggregator;
-use crate::config::Committee;
+use crate::aggregator::{Aggregator, MAX_ROUND_LOOKAHEAD};
+use crate::checkpoint::Checkpoint;
+use crate::config::{Byzantine, CommitRule, Committee};
 use crate::consensus::{ConsensusMessage, Round};
 use crate::error::{ConsensusError, ConsensusResult};
+use crate::evidence::Evidence;
 use crate::leader::LeaderElector;
 use crate::mempool::MempoolDriver;
-use crate::messages::{Block, Timeout, Vote, QC, TC};
+#[cfg(feature = "byzantine")]
+use crate::messages::Signers;
+use crate::messages::{Block, CommittedBlock, Reconfiguration, Request, Timeout, Vote, QC, TC};
 use crate::proposer::ProposerMessage;
+use crate::pruner::{Position, Pruner};
+use crate::state::{committed_key, ConsensusState};
 use crate::synchronizer::Synchronizer;
 use crate::timer::Timer;
+use crate::validator::ProposalValidator;
 use async_recursion::async_recursion;
 use bytes::Bytes;
+#[cfg(feature = "byzantine")]
+use crypto::BlsSignature;
 use crypto::Hash as _;
-use crypto::{PublicKey, SignatureService};
+use crypto::{Digest, PublicKey, SignatureService};
 use log::{debug, error, info, warn};
 use network::SimpleSender;
 use std::cmp::max;
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
 use store::Store;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::watch;
 
 #[cfg(test)]
 #[path = "tests/core_tests.rs"]
 pub mod core_tests;
 
-pub struct Core {
+pub struct Core<L: LeaderElector, V: ProposalValidator> {
     name: PublicKey,
     committee: Committee,
     store: Store,
     signature_service: SignatureService,
-    leader_elector: LeaderElector,
+    leader_elector: L,
+    validator: V,
     mempool_driver: MempoolDriver,
     synchronizer: Synchronizer,
+    pruner: Pruner,
+    commit_rule: CommitRule,
+    checkpoint_interval: u64,
+    #[cfg_attr(not(feature = "byzantine"), allow(dead_code))]
+    byzantine: Byzantine,
     rx_message: Receiver<ConsensusMessage>,
     rx_loopback: Receiver<Block>,
+    rx_reconfiguration: Receiver<Reconfiguration>,
+    rx_snapshot: Receiver<(Digest, Vec<u8>, CommittedBlock)>,
     tx_proposer: Sender<ProposerMessage>,
-    tx_commit: Sender<Block>,
+    tx_commit: Sender<CommittedBlock>,
+    tx_evidence: Sender<Evidence>,
+    tx_checkpoint: Sender<Checkpoint>,
+    tx_committee: watch::Sender<Committee>,
     round: Round,
     last_voted_round: Round,
+    locked_round: Round,
     last_committed_round: Round,
+    last_committed: Digest,
     high_qc: QC,
+    /// The TC of the highest round that ended with a timeout (in the current epoch).
+    high_tc: Option<TC>,
+    epoch_start: Digest,
+    reconfiguration: Option<Reconfiguration>,
+    pending_reconfiguration: Option<Reconfiguration>,
+    last_proposed_round: Round,
+    last_timeout: Option<Timeout>,
+    /// The round of our latest checkpoint (in the current epoch).
+    checkpoint_round: Round,
+    /// The round of the proposal that made us request a checkpoint (in the current epoch).
+    checkpoint_request: Round,
+    /// The position of the block after which the application took its latest snapshot.
+    last_snapshot: Position,
+    /// The first block proposed by each authority at each (recent) round.
+    proposals: HashMap<(Round, PublicKey), Block>,
+    /// The number of proposals the application rejected from each authority.
+    rejected: HashMap<PublicKey, u64>,
     timer: Timer,
     aggregator: Aggregator,
     network: SimpleSender,
 }
 
-impl Core {
+impl<L: LeaderElector, V: ProposalValidator> Core<L, V> {
     #[allow(clippy::too_many_arguments)]
     pub fn spawn(
         name: PublicKey,
         committee: Committee,
         signature_service: SignatureService,
         store: Store,
-        leader_elector: LeaderElector,
+        leader_elector: L,
+        validator: V,
         mempool_driver: MempoolDriver,
         synchronizer: Synchronizer,
         timeout_delay: u64,
+        max_timeout_delay: u64,
+        commit_rule: CommitRule,
+        checkpoint_interval: u64,
+        retention: u64,
+        byzantine: Byzantine,
         rx_message: Receiver<ConsensusMessage>,
         rx_loopback: Receiver<Block>,
+        rx_reconfiguration: Receiver<Reconfiguration>,
+        rx_snapshot: Receiver<(Digest, Vec<u8>, CommittedBlock)>,
         tx_proposer: Sender<ProposerMessage>,
-        tx_commit: Sender<Block>,
+        tx_commit: Sender<CommittedBlock>,
+        tx_evidence: Sender<Evidence>,
+        tx_checkpoint: Sender<Checkpoint>,
+        tx_committee: watch::Sender<Committee>,
+        last_applied: Option<Digest>,
     ) {
         tokio::spawn(async move {
-            Self {
+            // Reload the state we persisted before crashing (if any).
+            let mut store = store;
+            let state = ConsensusState::load(&mut store)
+                .await
+                .expect("Failed to load consensus state")
+                .unwrap_or_default();
+            if state.last_voted_round > 0 {
+                info!("Recovered consensus state at round {}", state.round);
+            }
+
+            let mut core = Self {
                 name,
                 committee: committee.clone(),
                 signature_service,
+                pruner: Pruner::new(store.clone(), retention),
                 store,
                 leader_elector,
+                validator,
                 mempool_driver,
                 synchronizer,
+                commit_rule,
+                checkpoint_interval,
+                byzantine,
                 rx_message,
                 rx_loopback,
+                rx_reconfiguration,
+                rx_snapshot,
                 tx_proposer,
                 tx_commit,
-                round: 1,
-                last_voted_round: 0,
-                last_committed_round: 0,
-                high_qc: QC::genesis(),
-                timer: Timer::new(timeout_delay),
+                tx_evidence,
+                tx_checkpoint,
+                tx_committee,
+                round: state.round,
+                last_voted_round: state.last_voted_round,
+                locked_round: state.locked_round,
+                last_committed_round: state.last_committed_round,
+                last_committed: state.last_committed,
+                high_qc: state.high_qc,
+                high_tc: None,
+                epoch_start: state.epoch_start,
+                reconfiguration: None,
+                pending_reconfiguration: None,
+                last_proposed_round: 0,
+                last_timeout: state.last_timeout,
+                checkpoint_round: 0,
+                checkpoint_request: 0,
+                last_snapshot: Position::default(),
+                proposals: HashMap::new(),
+                rejected: HashMap::new(),
+                timer: Timer::new(timeout_delay, max_timeout_delay),
                 aggregator: Aggregator::new(committee),
                 network: SimpleSender::new(),
+            };
+
+            // The configuration files only hold the committees of the first epoch.
+            let reconfiguration = state
+                .reconfiguration
+                .filter(|x| x.epoch() > core.committee.epoch);
+            if let Some(reconfiguration) = reconfiguration {
+                info!("Recovered committee of epoch {}", reconfiguration.epoch());
+                core.apply_reconfiguration(reconfiguration).await;
             }
-            .run()
-            .await
+
+            // Only accept votes and timeouts close to our current round.
+            core.aggregator.cleanup(&core.round);
+            core.synchronizer.cleanup(core.last_committed_round);
+            core.synchronizer.advance(core.committee.epoch, core.round);
+
+            // Recover the round of our latest checkpoint (and of the snapshot attached to it).
+            match Checkpoint::load(&mut core.store).await {
+                Ok(Some(x)) => {
+                    let block = &x.committed.block;
+                    if block.epoch == core.committee.epoch {
+                        core.checkpoint_round = block.round;
+                    }
+                    if x.snapshot.is_some() {
+                        core.last_snapshot = (block.epoch, block.round);
+                    }
+                }
+                Ok(None) => (),
+                Err(e) => error!("Failed to load checkpoint: {}", e),
+            }
+
+            // Remember the batches we recently committed.
+            if let Err(e) = core.mempool_driver.load(&core.last_committed).await {
+                error!("Failed to load the committed batches: {}", e);
+            }
+
+            // Track the blocks we may prune.
+            if let Err(e) = core.pruner.load().await {
+                error!("Failed to load the blocks to prune: {}", e);
+            }
+
+            // Feed the leader elector with the committed history it needs.
+            if let Err(e) = core.warm_up_leader_elector().await {
+                error!("Failed to load the leader elector's history: {}", e);
+            }
+
+            // Re-deliver the blocks committed after the application's cursor before resuming.
+            if let Err(e) = core.replay_committed(last_applied).await {
+                error!("Failed to replay committed blocks: {}", e);
+            }
+            core.run().await
         });
     }
 
@@ -90,55 +223,207 @@ impl Core {
         let key = block.digest().to_vec();
         let value = bincode::serialize(block).expect("Failed to serialize block");
         self.store.write(key, value).await;
+        self.pruner.insert(block).await;
+        self.leader_elector.add(block);
+    }
+
+    /// Return the leader of the specified round, extending the block certified by the QC.
+    fn leader(&self, round: Round, qc: &QC) -> Option<PublicKey> {
+        let parent = (!qc.is_genesis()).then(|| (qc.hash.clone(), qc.round));
+        self.leader_elector.get_leader(round, parent)
+    }
+
+    /// Return whether we lead the current round (which extends our highest QC).
+    fn is_leader(&self) -> bool {
+        self.leader(self.round, &self.high_qc) == Some(self.name)
+    }
+
+    /// Store the proof that a block is committed.
+    async fn store_committed(&mut self, committed: &CommittedBlock) {
+        let key = committed_key(&committed.block.digest());
+        let value = bincode::serialize(committed).expect("Failed to serialize committed block");
+        self.store.write(key, value).await;
     }
 
     fn increase_last_voted_round(&mut self, target: Round) {
         self.last_voted_round = max(self.last_voted_round, target);
     }
 
-    async fn make_vote(&mut self, block: &Block) -> Option<Vote> {
+    /// Durably write our state. This must be called before releasing any vote or timeout.
+    async fn persist_state(&mut self) -> ConsensusResult<()> {
+        let state = ConsensusState {
+            round: self.round,
+            last_voted_round: self.last_voted_round,
+            locked_round: self.locked_round,
+            last_committed_round: self.last_committed_round,
+            last_committed: self.last_committed.clone(),
+            high_qc: self.high_qc.clone(),
+            epoch_start: self.epoch_start.clone(),
+            reconfiguration: self.reconfiguration.clone(),
+            last_timeout: self.last_timeout.clone(),
+        };
+        state.persist(&mut self.store).await
+    }
+
+    async fn make_vote(&mut self, block: &Block) -> ConsensusResult<Option<Vote>> {
+        #[cfg(feature = "byzantine")]
+        if self.byzantine.withhold_votes {
+            return Ok(None);
+        }
+
         // Check if we can vote for this block.
         let safety_rule_1 = block.round > self.last_voted_round;
-        let mut safety_rule_2 = block.qc.round + 1 == block.round;
-        if let Some(ref tc) = block.tc {
-            let mut can_extend = tc.round + 1 == block.round;
-            can_extend &= block.qc.round >= *tc.high_qc_rounds().iter().max().expect("Empty TC");
-            safety_rule_2 |= can_extend;
+        let safety_rule_2 = match self.commit_rule {
+            CommitRule::TwoChain => {
+                let mut safety_rule_2 = block.qc.round + 1 == block.round;
+                if let Some(ref tc) = block.tc {
+                    let mut can_extend = tc.round + 1 == block.round;
+                    can_extend &=
+                        block.qc.round >= *tc.high_qc_rounds.iter().max().expect("Empty TC");
+                    safety_rule_2 |= can_extend;
+                }
+                safety_rule_2
+            }
+            // The block must extend the block we are locked on (or a more recent one).
+            CommitRule::ThreeChain => block.qc.round >= self.locked_round,
+        };
+        let safe = safety_rule_1 && safety_rule_2;
+        #[cfg(feature = "byzantine")]
+        let safe = safe || self.byzantine.vote_stale_blocks;
+        if !safe {
+            return Ok(None);
         }
-        if !(safety_rule_1 && safety_rule_2) {
-            return None;
+
+        // Let the application check the block's transactions.
+        if !self.validate(block).await? {
+            debug!("Cannot vote for {}: missing payload", block.digest());
+            return Ok(None);
         }
 
-        // Ensure we won't vote for contradicting blocks.
+        // Ensure we won't vote for contradicting blocks, even after a crash.
         self.increase_last_voted_round(block.round);
-        // TODO [issue #15]: Write to storage preferred_round and last_voted_round.
-        Some(Vote::new(block, self.name, self.signature_service.clone()).await)
+        self.persist_state().await?;
+        Ok(Some(
+            Vote::new(block, self.name, self.signature_service.clone()).await,
+        ))
+    }
+
+    /// Let the application check the block's transactions. Returns false if we miss some of them.
+    async fn validate(&mut self, block: &Block) -> ConsensusResult<bool> {
+        let batches = match self.mempool_driver.batches(block).await? {
+            Some(batches) => batches,
+            None => return Ok(false),
+        };
+        let result = match self.reproposed_batch(block).await? {
+            Some(digest) => Err(format!("Batch {} is already proposed", digest)),
+            None => self.validator.validate(block, &batches).await,
+        };
+        if let Err(reason) = result {
+            let rejected = self.rejected.entry(block.author).or_insert(0);
+            *rejected += 1;
+            bail!(ConsensusError::ProposalRejected {
+                digest: block.digest(),
+                author: block.author,
+                reason,
+                rejected: *rejected,
+            });
+        }
+        Ok(true)
+    }
+
+    /// Return a batch of the block that we recently committed, that one of its uncommitted
+    /// ancestors proposed, or that it proposes twice (if any).
+    async fn reproposed_batch(&mut self, block: &Block) -> ConsensusResult<Option<Digest>> {
+        let mut proposed = HashSet::new();
+        for x in &block.payload {
+            if self.mempool_driver.is_committed(x) || !proposed.insert(x) {
+                return Ok(Some(x.clone()));
+            }
+        }
+
+        let mut digest = block.parent().clone();
+        while digest != Digest::default() {
+            let ancestor: Block = match self.store.read(digest.to_vec()).await? {
+                Some(bytes) => bincode::deserialize(&bytes)?,
+                None => break,
+            };
+            if ancestor.epoch != block.epoch || ancestor.round <= self.last_committed_round {
+                break;
+            }
+            if let Some(x) = ancestor.payload.iter().find(|x| proposed.contains(x)) {
+                return Ok(Some(x.clone()));
+            }
+            digest = ancestor.parent().clone();
+        }
+        Ok(None)
     }
 
-    async fn commit(&mut self, block: Block) -> ConsensusResult<()> {
-        if self.last_committed_round >= block.round {
+    async fn commit(&mut self, head: CommittedBlock) -> ConsensusResult<()> {
+        if self.last_committed_round >= head.block.round {
             return Ok(());
         }
 
         // Ensure we commit the entire chain. This is needed after view-change.
-        let mut to_commit = VecDeque::new();
-        let mut parent = block.clone();
+        let mut chain = vec![head.block.clone()];
+        let mut parent = head.block.clone();
         while self.last_committed_round + 1 < parent.round {
             let ancestor = self
                 .synchronizer
                 .get_parent_block(&parent)
                 .await?
                 .expect("We should have all the ancestors by now");
-            to_commit.push_front(ancestor.clone());
+            // The head may extend a committed block after a timeout (or the genesis).
+            if ancestor.round <= self.last_committed_round {
+                break;
+            }
+            chain.push(ancestor.clone());
             parent = ancestor;
         }
-        to_commit.push_front(block.clone());
 
-        // Save the last committed block.
-        self.last_committed_round = block.round;
+        // Each ancestor is proven final by the chain leading to the head.
+        let mut to_commit = VecDeque::new();
+        let mut descendants = head.descendants.clone();
+        for block in chain {
+            to_commit.push_front(CommittedBlock {
+                block: block.clone(),
+                descendants: descendants.clone(),
+                qc: head.qc.clone(),
+            });
+            descendants.insert(0, block);
+        }
+
+        // The block carrying a reconfiguration ends the epoch: the (empty) blocks extending it
+        // are discarded.
+        if let Some(end) = to_commit
+            .iter()
+            .position(|x| x.block.reconfiguration.is_some())
+        {
+            to_commit.truncate(end + 1);
+        }
+        let last = to_commit
+            .back()
+            .cloned()
+            .expect("We should commit at least one block");
+
+        // Save the last committed block. We persist it before notifying the application so
+        // that a crash can only make us re-deliver blocks (see `replay_committed`), not skip them.
+        self.last_committed_round = last.block.round;
+        self.last_committed = last.block.digest();
+        self.synchronizer.cleanup(self.last_committed_round);
+        for committed in &to_commit {
+            self.store_committed(committed).await;
+        }
+        self.persist_state().await?;
+
+        // Make the checkpoint before notifying the application: it can then attach the snapshot
+        // of its state upon executing the checkpoint's block.
+        if last.block.reconfiguration.is_none() {
+            self.make_checkpoint(last.clone()).await;
+        }
 
         // Send all the newly committed blocks to the node's application layer.
-        while let Some(block) = to_commit.pop_back() {
+        while let Some(committed) = to_commit.pop_front() {
+            let block = &committed.block;
             if !block.payload.is_empty() {
                 info!("Committed {}", block);
 
@@ -149,7 +434,198 @@ impl Core {
                 }
             }
             debug!("Committed {:?}", block);
-            if let Err(e) = self.tx_commit.send(block).await {
+            self.leader_elector.update(block);
+            self.mempool_driver.commit(block).await;
+            if let Err(e) = self.tx_commit.send(committed).await {
+                warn!("Failed to send block through the commit channel: {}", e);
+            }
+        }
+
+        // Delete the history below our retention horizon.
+        let last_committed = (self.committee.epoch, self.last_committed_round);
+        self.pruner
+            .prune(last_committed, self.last_snapshot, &mut self.mempool_driver)
+            .await?;
+
+        // Switch to the next epoch (if the committed chain ends with a reconfiguration).
+        if let Some(reconfiguration) = last.block.reconfiguration.clone() {
+            self.end_epoch(&last.block, reconfiguration).await?;
+        }
+        Ok(())
+    }
+
+    /// Switch all modules to the committees of a new epoch.
+    async fn apply_reconfiguration(&mut self, reconfiguration: Reconfiguration) {
+        let committee = reconfiguration.consensus.clone();
+        self.leader_elector.reconfigure(committee.clone());
+        self.aggregator = Aggregator::new(committee.clone());
+        self.mempool_driver
+            .reconfigure(reconfiguration.mempool.clone())
+            .await;
+        let _ = self.tx_committee.send(committee.clone());
+        self.committee = committee;
+        self.reconfiguration = Some(reconfiguration);
+    }
+
+    /// End the current epoch with the specified (committed) block.
+    async fn end_epoch(
+        &mut self,
+        last: &Block,
+        reconfiguration: Reconfiguration,
+    ) -> ConsensusResult<()> {
+        info!("Epoch {} ended with {}", self.committee.epoch, last);
+        if self.pending_reconfiguration.as_ref() == Some(&reconfiguration) {
+            self.pending_reconfiguration = None;
+        }
+        self.apply_reconfiguration(reconfiguration).await;
+
+        // Rounds restart from 1, on top of the last block of the previous epoch.
+        self.round = 1;
+        self.last_voted_round = 0;
+        self.locked_round = 0;
+        self.last_committed_round = 0;
+        self.synchronizer.cleanup(0);
+        self.synchronizer.advance(self.committee.epoch, self.round);
+        self.last_proposed_round = 0;
+        self.checkpoint_round = 0;
+        self.checkpoint_request = 0;
+        self.proposals.clear();
+        self.high_qc = QC::epoch_genesis(last, self.committee.epoch);
+        self.high_tc = None;
+        self.epoch_start = last.digest();
+        self.persist_state().await?;
+        info!("Started epoch {}", self.committee.epoch);
+
+        // Start the first round of the new epoch.
+        self.timer.reset();
+        if self.is_leader() {
+            self.generate_proposal(None).await;
+        }
+        Ok(())
+    }
+
+    /// Return whether the block certified by the QC (or one of its uncommitted ancestors)
+    /// carries a reconfiguration.
+    async fn extends_reconfiguration(&mut self, qc: &QC) -> ConsensusResult<bool> {
+        let mut qc = qc.clone();
+        while !qc.is_genesis() && qc.round > self.last_committed_round {
+            let block = self.read_block(&qc.hash).await?;
+            if block.reconfiguration.is_some() {
+                return Ok(true);
+            }
+            qc = block.qc;
+        }
+        Ok(false)
+    }
+
+    /// Ensure the block complies with the reconfiguration rules before voting for it.
+    async fn check_reconfiguration(&mut self, block: &Block) -> ConsensusResult<()> {
+        // We only vote for the reconfigurations submitted by our operator.
+        if let Some(ref reconfiguration) = block.reconfiguration {
+            ensure!(
+                self.pending_reconfiguration.as_ref() == Some(reconfiguration),
+                ConsensusError::UnexpectedReconfiguration(block.digest())
+            );
+        }
+
+        // The blocks extending a reconfiguration are discarded when it commits: they must be empty.
+        if self.extends_reconfiguration(&block.qc).await? {
+            ensure!(
+                block.payload.is_empty() && block.reconfiguration.is_none(),
+                ConsensusError::ReconfigurationInProgress(block.digest())
+            );
+        }
+        Ok(())
+    }
+
+    /// Read a block from the store.
+    async fn read_block(&mut self, digest: &Digest) -> ConsensusResult<Block> {
+        match self.store.read(digest.to_vec()).await? {
+            Some(bytes) => Ok(bincode::deserialize(&bytes)?),
+            None => Err(ConsensusError::MissingBlock(digest.clone())),
+        }
+    }
+
+    /// Feed the leader elector with the recent blocks of the chain ending with our highest QC,
+    /// including the committed ones (upon booting).
+    async fn warm_up_leader_elector(&mut self) -> ConsensusResult<()> {
+        let history = self.leader_elector.history();
+        if history == 0 {
+            return Ok(());
+        }
+
+        let oldest = self.last_committed_round.saturating_sub(history);
+        let mut to_feed = Vec::new();
+        let mut truncated = false;
+        let mut qc = self.high_qc.clone();
+        while !qc.is_genesis() && qc.round >= oldest {
+            // We do not hold the history preceding the checkpoint we started from (if any).
+            let block: Block = match self.store.read(qc.hash.to_vec()).await? {
+                Some(bytes) => bincode::deserialize(&bytes)?,
+                None => {
+                    truncated = true;
+                    break;
+                }
+            };
+            if block.epoch != self.committee.epoch {
+                break;
+            }
+            qc = block.qc.clone();
+            to_feed.push(block);
+        }
+        if let Some(block) = to_feed.last().filter(|_| truncated) {
+            if block.round <= self.last_committed_round {
+                self.leader_elector.reset(block);
+            }
+        }
+        while let Some(block) = to_feed.pop() {
+            self.leader_elector.add(&block);
+            // The chain extends our last committed block.
+            if block.round <= self.last_committed_round {
+                self.leader_elector.update(&block);
+            }
+        }
+        Ok(())
+    }
+
+    /// Send to the application all the committed blocks following `last_applied` (or the entire
+    /// committed chain if `last_applied` is `None`), in order.
+    async fn replay_committed(&mut self, last_applied: Option<Digest>) -> ConsensusResult<()> {
+        let cursor = last_applied.unwrap_or_default();
+        if self.last_committed == cursor {
+            return Ok(());
+        }
+        if self.last_committed == Digest::default() {
+            warn!("Block {} is not committed, nothing to replay", cursor);
+            return Ok(());
+        }
+
+        // Walk the committed chain backwards (across epochs), from its tip to the cursor.
+        let mut to_replay = VecDeque::new();
+        let mut digest = self.last_committed.clone();
+        loop {
+            // We do not hold the history preceding the checkpoint we started from (if any).
+            let committed: CommittedBlock = match self.store.read(committed_key(&digest)).await? {
+                Some(bytes) => bincode::deserialize(&bytes)?,
+                None => break,
+            };
+            digest = committed.block.parent().clone();
+            to_replay.push_front(committed);
+            if digest == cursor || digest == Digest::default() {
+                break;
+            }
+        }
+        if digest != cursor {
+            warn!(
+                "Block {} is not committed, replaying the entire chain",
+                cursor
+            );
+        }
+
+        info!("Replaying {} committed blocks", to_replay.len());
+        while let Some(committed) = to_replay.pop_front() {
+            debug!("Replaying {:?}", committed.block);
+            if let Err(e) = self.tx_commit.send(committed).await {
                 warn!("Failed to send block through the commit channel: {}", e);
             }
         }
@@ -162,22 +638,43 @@ impl Core {
         }
     }
 
+    fn update_high_tc(&mut self, tc: &TC) {
+        if tc.round > self.high_tc.as_ref().map_or(0, |x| x.round) {
+            self.high_tc = Some(tc.clone());
+        }
+    }
+
     async fn local_timeout_round(&mut self) -> ConsensusResult<()> {
-        warn!("Timeout reached for round {}", self.round);
+        warn!(
+            "Timeout reached for round {} after {} ms",
+            self.round,
+            self.timer.delay()
+        );
 
         // Increase the last voted round.
         self.increase_last_voted_round(self.round);
 
-        // Make a timeout message.
-        let timeout = Timeout::new(
-            self.high_qc.clone(),
-            self.round,
-            self.name,
-            self.signature_service.clone(),
-        )
-        .await;
+        // Make a timeout message. If we already timed out at this round, we re-send the same
+        // timeout: signing another one (eg. with a newer high QC) would be an equivocation.
+        let timeout = match self.last_timeout.take() {
+            Some(x) if x.round == self.round && x.epoch == self.committee.epoch => x,
+            _ => {
+                Timeout::new(
+                    self.high_qc.clone(),
+                    self.round,
+                    self.committee.epoch,
+                    self.name,
+                    self.signature_service.clone(),
+                )
+                .await
+            }
+        };
+        self.last_timeout = Some(timeout.clone());
         debug!("Created {:?}", timeout);
 
+        // Persist our state (including the timeout) before releasing the timeout.
+        self.persist_state().await?;
+
         // Reset the timer.
         self.timer.reset();
 
@@ -195,10 +692,49 @@ impl Core {
             .broadcast(addresses, Bytes::from(message))
             .await;
 
+        #[cfg(feature = "byzantine")]
+        self.misbehave_on_timeout().await;
+
         // Process our message.
         self.handle_timeout(&timeout).await
     }
 
+    /// Enact the Byzantine behaviours triggered by our local timeouts.
+    #[cfg(feature = "byzantine")]
+    async fn misbehave_on_timeout(&mut self) {
+        let addresses: Vec<_> = self
+            .committee
+            .broadcast_addresses(&self.name)
+            .into_iter()
+            .map(|(_, x)| x)
+            .collect();
+
+        // Claim that every node holds a QC for the previous round. We cannot sign on behalf of
+        // the other nodes, so honest nodes should reject this TC.
+        if self.byzantine.inflate_tc {
+            let names = self.committee.sorted_names();
+            let tc = TC {
+                round: self.round,
+                epoch: self.committee.epoch,
+                signers: Signers::new(&names, &self.committee),
+                high_qc_rounds: vec![self.round.saturating_sub(1); names.len()],
+                signature: BlsSignature::default(),
+            };
+            debug!("Broadcasting inflated {:?}", tc);
+            let message = bincode::serialize(&ConsensusMessage::TC(tc))
+                .expect("Failed to serialize timeout certificate");
+            self.network
+                .broadcast(addresses.clone(), Bytes::from(message))
+                .await;
+        }
+
+        // Request our entire chain over and over again.
+        for _ in 0..self.byzantine.flood_sync_requests {
+            let request = Request::Sync(self.high_qc.hash.clone(), 0);
+            self.synchronizer.broadcast(request).await;
+        }
+    }
+
     #[async_recursion]
     async fn handle_vote(&mut self, vote: &Vote) -> ConsensusResult<()> {
         debug!("Processing {:?}", vote);
@@ -217,7 +753,7 @@ impl Core {
             self.process_qc(&qc).await;
 
             // Make a new block if we are the next leader.
-            if self.name == self.leader_elector.get_leader(self.round) {
+            if self.is_leader() {
                 self.generate_proposal(None).await;
             }
         }
@@ -226,22 +762,32 @@ impl Core {
 
     async fn handle_timeout(&mut self, timeout: &Timeout) -> ConsensusResult<()> {
         debug!("Processing {:?}", timeout);
+
+        // Ensure the timeout is well formed (before replying to its author).
+        timeout.verify(&self.committee)?;
+
         if timeout.round < self.round {
+            // The author missed the TC that ended its round: help it catch up.
+            if let Some(tc) = self.high_tc.as_ref().filter(|x| x.round >= timeout.round) {
+                if let Some(address) = self.committee.address(&timeout.author) {
+                    let message = bincode::serialize(&ConsensusMessage::TC(tc.clone()))
+                        .expect("Failed to serialize timeout certificate");
+                    self.network.send(address, Bytes::from(message)).await;
+                }
+            }
             return Ok(());
         }
 
-        // Ensure the timeout is well formed.
-        timeout.verify(&self.committee)?;
-
         // Process the QC embedded in the timeout.
         self.process_qc(&timeout.high_qc).await;
 
         // Add the new vote to our aggregator and see if we have a quorum.
         if let Some(tc) = self.aggregator.add_timeout(timeout.clone())? {
             debug!("Assembled {:?}", tc);
+            self.update_high_tc(&tc);
 
             // Try to advance the round.
-            self.advance_round(tc.round).await;
+            self.advance_round(tc.round, /* timed_out */ true).await;
 
             // Broadcast the TC.
             debug!("Broadcasting {:?}", tc);
@@ -258,7 +804,7 @@ impl Core {
                 .await;
 
             // Make a new block if we are the next leader.
-            if self.name == self.leader_elector.get_leader(self.round) {
+            if self.is_leader() {
                 self.generate_proposal(Some(tc)).await;
             }
         }
@@ -266,34 +812,72 @@ impl Core {
     }
 
     #[async_recursion]
-    async fn advance_round(&mut self, round: Round) {
+    async fn advance_round(&mut self, round: Round, timed_out: bool) {
         if round < self.round {
             return;
         }
+        // Back off if the round ended with a TC, otherwise restore the base timeout delay.
+        let delay = self.timer.delay();
+        if timed_out {
+            self.timer.back_off();
+        } else {
+            self.timer.restore();
+        }
+        if self.timer.delay() != delay {
+            info!("Pacemaker timeout set to {} ms", self.timer.delay());
+        }
+
         // Reset the timer and advance round.
         self.timer.reset();
         self.round = round + 1;
+        self.synchronizer.advance(self.committee.epoch, self.round);
         debug!("Moved to round {}", self.round);
 
-        // Cleanup the vote aggregator.
+        // Cleanup the vote aggregator and the proposals of past rounds.
         self.aggregator.cleanup(&self.round);
+        let current = self.round;
+        self.proposals.retain(|(r, _), _| r + 1 >= current);
     }
 
     #[async_recursion]
     async fn generate_proposal(&mut self, tc: Option<TC>) {
+        let (round, qc) = (self.round, self.high_qc.clone());
+
+        // We only propose once per round: proposing two blocks would be an equivocation.
+        if round <= self.last_proposed_round {
+            return;
+        }
+        self.last_proposed_round = round;
+
+        // We may not hold the block certified by our high QC (eg. if we only received its votes).
+        // Other nodes then reject our proposal if it does not comply with the reconfiguration rules.
+        let in_progress = self.extends_reconfiguration(&qc).await.unwrap_or_else(|e| {
+            debug!("{}", e);
+            false
+        });
+        let message = match &self.pending_reconfiguration {
+            // Wait for the reconfiguration to commit.
+            _ if in_progress => ProposerMessage::Reconfigure(round, qc, tc, None),
+            Some(x) if x.epoch() == self.committee.epoch + 1 => {
+                ProposerMessage::Reconfigure(round, qc, tc, Some(x.clone()))
+            }
+            _ => ProposerMessage::Make(round, qc, tc),
+        };
         self.tx_proposer
-            .send(ProposerMessage::Make(self.round, self.high_qc.clone(), tc))
+            .send(message)
             .await
             .expect("Failed to send message to proposer");
     }
 
     async fn cleanup_proposer(&mut self, b0: &Block, b1: &Block, block: &Block) {
-        let digests = b0
-            .payload
+        let digests = [b0, b1, block]
             .iter()
-            .cloned()
-            .chain(b1.payload.iter().cloned())
-            .chain(block.payload.iter().cloned())
+            .flat_map(|x| {
+                x.payload
+                    .iter()
+                    .cloned()
+                    .chain(x.evidence.iter().map(|e| e.digest()))
+            })
             .collect();
         self.tx_proposer
             .send(ProposerMessage::Cleanup(digests))
@@ -301,8 +885,48 @@ impl Core {
             .expect("Failed to send message to proposer");
     }
 
+    /// Return the block committed by the chain `b0 <- |qc0; b1| <- |qc1; block|` (if any),
+    /// according to our commit rule, along with the proof that it is committed. The 3-chain
+    /// rule also updates our lock.
+    async fn committable_head(
+        &mut self,
+        b0: Block,
+        b1: Block,
+        block: &Block,
+    ) -> ConsensusResult<Option<CommittedBlock>> {
+        if self.commit_rule == CommitRule::ThreeChain {
+            self.locked_round = max(self.locked_round, b0.round);
+        }
+        if b0.round + 1 != b1.round || b0.round <= self.last_committed_round {
+            return Ok(None);
+        }
+        let qc = block.qc.clone();
+        match self.commit_rule {
+            CommitRule::TwoChain => Ok(Some(CommittedBlock {
+                block: b0,
+                descendants: vec![b1],
+                qc,
+            })),
+            CommitRule::ThreeChain => {
+                let head = self
+                    .synchronizer
+                    .get_parent_block(&b0)
+                    .await?
+                    .expect("We should have all the ancestors by now");
+                if head.round + 1 != b0.round {
+                    return Ok(None);
+                }
+                Ok(Some(CommittedBlock {
+                    block: head,
+                    descendants: vec![b0, b1],
+                    qc,
+                }))
+            }
+        }
+    }
+
     async fn process_qc(&mut self, qc: &QC) {
-        self.advance_round(qc.round).await;
+        self.advance_round(qc.round, /* timed_out */ false).await;
         self.update_high_qc(qc);
     }
 
@@ -310,6 +934,22 @@ impl Core {
     async fn process_block(&mut self, block: &Block) -> ConsensusResult<()> {
         debug!("Processing {:?}", block);
 
+        // Blocks of previous epochs may still be delivered by the synchronizer or the mempool.
+        if block.epoch != self.committee.epoch {
+            return Ok(());
+        }
+
+        // Let's see if we have the block's data. If we don't, the mempool will get it and then
+        // make us resume processing this block. We check it here (rather than upon receiving
+        // the proposal) since the synchronizer may deliver ancestors we never saw proposed.
+        if !self.mempool_driver.verify(block.clone()).await? {
+            debug!(
+                "Processing of {} suspended: missing payload",
+                block.digest()
+            );
+            return Ok(());
+        }
+
         // Let's see if we have the last three ancestors of the block, that is:
         //      b0 <- |qc0; b1| <- |qc1; block|
         // If we don't, the synchronizer asks for them to other nodes. It will
@@ -328,24 +968,53 @@ impl Core {
 
         self.cleanup_proposer(&b0, &b1, block).await;
 
-        // Check if we can commit the head of the 2-chain.
+        // Check if we can commit the head of the 2-chain (or 3-chain).
         // Note that we commit blocks only if we have all its ancestors.
-        if b0.round + 1 == b1.round {
-            self.mempool_driver.cleanup(b0.round).await;
-            self.commit(b0).await?;
+        if let Some(head) = self.committable_head(b0, b1, block).await? {
+            self.mempool_driver.cleanup(head.block.round).await;
+            self.commit(head).await?;
         }
 
         // Ensure the block's round is as expected.
         // This check is important: it prevents bad leaders from producing blocks
         // far in the future that may cause overflow on the round number.
-        if block.round != self.round {
+        if block.epoch != self.committee.epoch || block.round != self.round {
             return Ok(());
         }
 
+        // Ensure the block proposer is the right leader for the round.
+        let leader = match self.leader(block.round, &block.qc) {
+            Some(leader) => leader,
+            None => {
+                debug!("Cannot elect the leader of round {}", block.round);
+                return Ok(());
+            }
+        };
+        ensure!(
+            block.author == leader,
+            ConsensusError::WrongLeader {
+                digest: block.digest(),
+                leader: block.author,
+                round: block.round
+            }
+        );
+
+        // Ensure the block complies with the reconfiguration rules.
+        self.check_reconfiguration(block).await?;
+
         // See if we can vote for this block.
-        if let Some(vote) = self.make_vote(block).await {
+        if let Some(vote) = self.make_vote(block).await? {
             debug!("Created {:?}", vote);
-            let next_leader = self.leader_elector.get_leader(self.round + 1);
+            let next_leader = match self
+                .leader_elector
+                .get_leader(self.round + 1, Some((block.digest(), block.round)))
+            {
+                Some(leader) => leader,
+                None => {
+                    debug!("Dropping {:?}: cannot elect the next leader", vote);
+                    return Ok(());
+                }
+            };
             if next_leader == self.name {
                 self.handle_vote(&vote).await?;
             } else {
@@ -365,32 +1034,46 @@ impl Core {
     async fn handle_proposal(&mut self, block: &Block) -> ConsensusResult<()> {
         let digest = block.digest();
 
-        // Ensure the block proposer is the right leader for the round.
-        ensure!(
-            block.author == self.leader_elector.get_leader(block.round),
-            ConsensusError::WrongLeader {
-                digest,
-                leader: block.author,
-                round: block.round
+        // Check the block is correctly formed. Note that we only check whether the author is the
+        // leader of the block's round if we are about to vote for it: leader electors may need the
+        // ancestors of the block to elect the leader of its round (see `process_block`).
+        block.verify(&self.committee)?;
+
+        // Ensure the author did not propose another block for this round. We only remember the
+        // proposals of the next few rounds, so that bad leaders cannot fill our memory.
+        if block.round <= self.round + MAX_ROUND_LOOKAHEAD {
+            let first = self
+                .proposals
+                .entry((block.round, block.author))
+                .or_insert_with(|| block.clone());
+            if first.digest() != digest {
+                let evidence = Evidence::DoubleProposal(first.header(), block.header());
+                bail!(ConsensusError::Equivocation(Box::new(evidence)));
             }
-        );
+        }
 
-        // Check the block is correctly formed.
-        block.verify(&self.committee)?;
+        // Download a checkpoint if we are lagging far behind.
+        if self.checkpoint_interval > 0
+            && block.round > self.last_committed_round + self.checkpoint_interval
+            && block.round >= self.checkpoint_request + self.checkpoint_interval
+        {
+            self.checkpoint_request = block.round;
+            self.request_checkpoint().await;
+        }
+
+        // The genesis QC of the epoch must point to the last block of the previous epoch.
+        ensure!(
+            !block.qc.is_genesis() || block.qc.hash == self.epoch_start,
+            ConsensusError::MalformedBlock(digest)
+        );
 
         // Process the QC. This may allow us to advance round.
         self.process_qc(&block.qc).await;
 
         // Process the TC (if any). This may also allow us to advance round.
         if let Some(ref tc) = block.tc {
-            self.advance_round(tc.round).await;
-        }
-
-        // Let's see if we have the block's data. If we don't, the mempool
-        // will get it and then make us resume processing this block.
-        if !self.mempool_driver.verify(block.clone()).await? {
-            debug!("Processing of {} suspended: missing payload", digest);
-            return Ok(());
+            self.update_high_tc(tc);
+            self.advance_round(tc.round, /* timed_out */ true).await;
         }
 
         // All check pass, we can process this block.
@@ -398,18 +1081,148 @@ impl Core {
     }
 
     async fn handle_tc(&mut self, tc: TC) -> ConsensusResult<()> {
-        self.advance_round(tc.round).await;
-        if self.name == self.leader_elector.get_leader(self.round) {
+        tc.verify(&self.committee)?;
+        self.update_high_tc(&tc);
+        self.advance_round(tc.round, /* timed_out */ true).await;
+        if self.is_leader() {
             self.generate_proposal(Some(tc)).await;
         }
         Ok(())
     }
 
+    async fn handle_evidence(&mut self, evidence: Evidence) -> ConsensusResult<()> {
+        // Only report each misbehaviour once.
+        let digest = evidence.digest();
+        if self.store.read(digest.to_vec()).await?.is_some() {
+            return Ok(());
+        }
+        warn!("Detected equivocation {:?}", evidence);
+
+        // Persist the evidence, include it in our next block, and notify the application.
+        let value = bincode::serialize(&evidence).expect("Failed to serialize evidence");
+        self.store.write(digest.to_vec(), value).await;
+        self.tx_proposer
+            .send(ProposerMessage::Evidence(evidence.clone()))
+            .await
+            .expect("Failed to send message to proposer");
+        if let Err(e) = self.tx_evidence.send(evidence).await {
+            warn!(
+                "Failed to send evidence through the evidence channel: {}",
+                e
+            );
+        }
+        Ok(())
+    }
+
+    /// Checkpoint the head of the committed chain every `checkpoint_interval` rounds.
+    async fn make_checkpoint(&mut self, head: CommittedBlock) {
+        if self.checkpoint_interval == 0
+            || head.block.epoch != self.committee.epoch
+            || head.block.round < self.checkpoint_round + self.checkpoint_interval
+        {
+            return;
+        }
+        let checkpoint = Checkpoint {
+            committed: head,
+            snapshot: None,
+            attestation: None,
+        };
+        debug!("Created {:?}", checkpoint);
+        self.checkpoint_round = checkpoint.committed.block.round;
+        checkpoint.persist(&mut self.store).await;
+    }
+
+    async fn request_checkpoint(&mut self) {
+        debug!("Requesting checkpoint");
+        self.synchronizer.broadcast(Request::Checkpoint).await;
+    }
+
+    /// Start from a checkpoint ahead of our committed chain, skipping the history it commits.
+    async fn handle_checkpoint(&mut self, checkpoint: Checkpoint) -> ConsensusResult<()> {
+        let committed = &checkpoint.committed;
+        if committed.block.epoch != self.committee.epoch
+            || committed.block.round <= self.last_committed_round
+        {
+            return Ok(());
+        }
+        checkpoint.verify(&self.committee, self.commit_rule)?;
+        info!("Installing checkpoint {}", committed.block);
+
+        // The leader elector misses the history we skip.
+        self.leader_elector.reset(&committed.block);
+
+        // Store the blocks of the checkpoint: the next blocks we receive extend them.
+        self.store_block(&committed.block).await;
+        for block in &committed.descendants {
+            self.store_block(block).await;
+        }
+        self.store_committed(committed).await;
+
+        // Resume from the committed block and its proof.
+        let round = committed.block.round;
+        self.last_committed_round = round;
+        self.last_committed = committed.block.digest();
+        if self.commit_rule == CommitRule::ThreeChain {
+            let lock = match committed.descendants.len() {
+                1 => round,
+                n => committed.descendants[n - 2].round,
+            };
+            self.locked_round = max(self.locked_round, lock);
+        }
+        let qc = committed.qc.clone();
+        self.synchronizer.cleanup(round);
+        self.mempool_driver.cleanup(round).await;
+        self.process_qc(&qc).await;
+        self.persist_state().await?;
+
+        // Serve the checkpoint to other nodes and notify the application: it needs to restore
+        // its state from the checkpoint's snapshot.
+        self.checkpoint_round = round;
+        checkpoint.persist(&mut self.store).await;
+        if let Err(e) = self.tx_checkpoint.send(checkpoint).await {
+            warn!(
+                "Failed to send checkpoint through the checkpoint channel: {}",
+                e
+            );
+        }
+        Ok(())
+    }
+
+    /// Attach the application's snapshot (and the committed block attesting it) to our latest
+    /// checkpoint (if it matches).
+    async fn handle_snapshot(
+        &mut self,
+        digest: Digest,
+        snapshot: Vec<u8>,
+        attestation: CommittedBlock,
+    ) -> ConsensusResult<()> {
+        if let Some(mut checkpoint) = Checkpoint::load(&mut self.store).await? {
+            let block = &checkpoint.committed.block;
+            if block.digest() == digest {
+                self.last_snapshot = (block.epoch, block.round);
+                checkpoint.snapshot = Some(snapshot);
+                checkpoint.attestation = Some(attestation);
+                checkpoint.persist(&mut self.store).await;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_reconfiguration(&mut self, reconfiguration: Reconfiguration) -> ConsensusResult<()> {
+        reconfiguration.verify(&self.committee)?;
+        info!(
+            "Scheduled reconfiguration to epoch {}",
+            reconfiguration.epoch()
+        );
+        self.pending_reconfiguration = Some(reconfiguration);
+        Ok(())
+    }
+
     pub async fn run(&mut self) {
         // Upon booting, generate the very first block (if we are the leader).
         // Also, schedule a timer in case we don't hear from the leader.
         self.timer.reset();
-        if self.name == self.leader_elector.get_leader(self.round) {
+        if self.is_leader() {
             self.generate_proposal(None).await;
         }
 
@@ -422,11 +1235,26 @@ impl Core {
                     ConsensusMessage::Vote(vote) => self.handle_vote(&vote).await,
                     ConsensusMessage::Timeout(timeout) => self.handle_timeout(&timeout).await,
                     ConsensusMessage::TC(tc) => self.handle_tc(tc).await,
+                    ConsensusMessage::CheckpointReply(checkpoint) => {
+                        self.handle_checkpoint(checkpoint).await
+                    },
                     _ => panic!("Unexpected protocol message")
                 },
                 Some(block) = self.rx_loopback.recv() => self.process_block(&block).await,
+                Some(reconfiguration) = self.rx_reconfiguration.recv() => {
+                    self.handle_reconfiguration(reconfiguration)
+                },
+                Some((digest, snapshot, attestation)) = self.rx_snapshot.recv() => {
+                    self.handle_snapshot(digest, snapshot, attestation).await
+                },
                 () = &mut self.timer => self.local_timeout_round().await,
             };
+            let result = match result {
+                Err(ConsensusError::Equivocation(evidence)) => {
+                    self.handle_evidence(*evidence).await
+                }
+                x => x,
+            };
             match result {
                 Ok(()) => (),
                 Err(ConsensusError::StoreError(e)) => error!("{}", e),
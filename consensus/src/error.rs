@@ -1,4 +1,6 @@
+use crate::config::EpochNumber;
 use crate::consensus::Round;
+use crate::evidence::Evidence;
 use crypto::{CryptoError, Digest, PublicKey};
 use store::StoreError;
 use thiserror::Error;
@@ -41,15 +43,35 @@ pub enum ConsensusError {
     #[error("Received more than one vote from {0}")]
     AuthorityReuse(PublicKey),
 
+    #[error("Rejected message from {author} for round {round} ({rejected} rejected so far)")]
+    MessageRejected {
+        author: PublicKey,
+        round: Round,
+        rejected: u64,
+    },
+
     #[error("Received vote from unknown authority {0}")]
     UnknownAuthority(PublicKey),
 
+    #[error("Received certificate with a malformed signers bitmap")]
+    MalformedSigners,
+
     #[error("Received QC without a quorum")]
     QCRequiresQuorum,
 
     #[error("Received TC without a quorum")]
     TCRequiresQuorum,
 
+    #[error("Block {0} is missing from the store")]
+    MissingBlock(Digest),
+
+    #[error("Block {digest} is below our retention horizon (round {round} of epoch {epoch})")]
+    BelowHorizon {
+        digest: Digest,
+        epoch: EpochNumber,
+        round: Round,
+    },
+
     #[error("Malformed block {0}")]
     MalformedBlock(Digest),
 
@@ -62,4 +84,42 @@ pub enum ConsensusError {
 
     #[error("Invalid payload")]
     InvalidPayload,
+
+    #[error("Rejected proposal {digest} from {author}: {reason} ({rejected} rejected so far)")]
+    ProposalRejected {
+        digest: Digest,
+        author: PublicKey,
+        reason: String,
+        rejected: u64,
+    },
+
+    #[error("Received message from epoch {received} while in epoch {expected}")]
+    WrongEpoch {
+        expected: EpochNumber,
+        received: EpochNumber,
+    },
+
+    #[error("Invalid reconfiguration to epoch {0}")]
+    InvalidReconfiguration(EpochNumber),
+
+    #[error("Block {0} carries a reconfiguration we did not authorize")]
+    UnexpectedReconfiguration(Digest),
+
+    #[error("Block {0} extends an uncommitted reconfiguration but is not empty")]
+    ReconfigurationInProgress(Digest),
+
+    #[error("Authority {} equivocated at round {}", .0.culprit(), .0.round())]
+    Equivocation(Box<Evidence>),
+
+    #[error("Received request addressed to {0}")]
+    MisaddressedRequest(PublicKey),
+
+    #[error("Received replayed request from {0}")]
+    ReplayedRequest(PublicKey),
+
+    #[error("Invalid equivocation evidence")]
+    InvalidEvidence,
+
+    #[error("Invalid proof that block {0} is committed")]
+    InvalidCommitProof(Digest),
 }
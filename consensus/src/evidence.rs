@@ -0,0 +1,129 @@
+use crate::config::{Committee, EpochNumber};
+use crate::consensus::Round;
+use crate::error::{ConsensusError, ConsensusResult};
+use crate::messages::{BlockHeader, Timeout, Vote};
+use crypto::{Digest, Hash, PublicKey};
+use ed25519_dalek::Digest as _;
+use ed25519_dalek::Sha512;
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::fmt;
+
+#[cfg(test)]
+#[path = "tests/evidence_tests.rs"]
+pub mod evidence_tests;
+
+/// Proof that an authority signed two conflicting messages. It holds both signed messages, so
+/// anyone can check it against the committee of its epoch.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Evidence {
+    /// An authority proposed two different blocks for the same round.
+    DoubleProposal(BlockHeader, BlockHeader),
+    /// An authority voted for two different blocks of the same round.
+    DoubleVote(Vote, Vote),
+    /// An authority signed two different timeouts for the same round.
+    DoubleTimeout(Timeout, Timeout),
+}
+
+impl Evidence {
+    /// The author, round, epoch, and digest of both conflicting messages.
+    fn headers(&self) -> [(PublicKey, Round, EpochNumber, Digest); 2] {
+        match self {
+            Self::DoubleProposal(x, y) => [
+                (x.block.author, x.block.round, x.block.epoch, x.digest()),
+                (y.block.author, y.block.round, y.block.epoch, y.digest()),
+            ],
+            Self::DoubleVote(x, y) => [
+                (x.author, x.round, x.epoch, x.digest()),
+                (y.author, y.round, y.epoch, y.digest()),
+            ],
+            Self::DoubleTimeout(x, y) => [
+                (x.author, x.round, x.epoch, x.digest()),
+                (y.author, y.round, y.epoch, y.digest()),
+            ],
+        }
+    }
+
+    /// The authority that signed both messages.
+    pub fn culprit(&self) -> PublicKey {
+        self.headers()[0].0
+    }
+
+    pub fn round(&self) -> Round {
+        self.headers()[0].1
+    }
+
+    pub fn epoch(&self) -> EpochNumber {
+        self.headers()[0].2
+    }
+
+    pub fn verify(&self, committee: &Committee) -> ConsensusResult<()> {
+        // Ensure both messages are signed by the same authority for the same round, and conflict.
+        let [(author_1, round_1, epoch_1, digest_1), (author_2, round_2, epoch_2, digest_2)] =
+            self.headers();
+        ensure!(
+            author_1 == author_2 && round_1 == round_2 && epoch_1 == epoch_2,
+            ConsensusError::InvalidEvidence
+        );
+        ensure!(digest_1 != digest_2, ConsensusError::InvalidEvidence);
+
+        // Check both messages.
+        match self {
+            Self::DoubleProposal(x, y) => {
+                x.verify(committee)?;
+                y.verify(committee)
+            }
+            Self::DoubleVote(x, y) => {
+                x.verify(committee)?;
+                y.verify(committee)
+            }
+            Self::DoubleTimeout(x, y) => {
+                x.verify(committee)?;
+                y.verify(committee)
+            }
+        }
+    }
+}
+
+impl Hash for Evidence {
+    fn digest(&self) -> Digest {
+        // Nodes may observe the conflicting messages in different orders: they should still
+        // agree on the digest of the evidence.
+        let [(author, round, epoch, digest_1), (_, _, _, digest_2)] = self.headers();
+        let mut digests = [digest_1, digest_2];
+        digests.sort();
+        let tag: u8 = match self {
+            Self::DoubleProposal(..) => 0,
+            Self::DoubleVote(..) => 1,
+            Self::DoubleTimeout(..) => 2,
+        };
+
+        let mut hasher = Sha512::new();
+        hasher.update([tag]);
+        hasher.update(author.0);
+        hasher.update(round.to_le_bytes());
+        hasher.update(epoch.to_le_bytes());
+        for x in &digests {
+            hasher.update(x);
+        }
+        Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+    }
+}
+
+impl fmt::Debug for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let kind = match self {
+            Self::DoubleProposal(..) => "DoubleProposal",
+            Self::DoubleVote(..) => "DoubleVote",
+            Self::DoubleTimeout(..) => "DoubleTimeout",
+        };
+        write!(
+            f,
+            "{}: {}({}, {})",
+            self.digest(),
+            kind,
+            self.culprit(),
+            self.round()
+        )
+    }
+}
@@ -1,66 +1,255 @@
+use crate::checkpoint::Checkpoint;
 use crate::config::Committee;
-use crate::consensus::ConsensusMessage;
+use crate::consensus::{ConsensusMessage, Round};
+use crate::error::{ConsensusError, ConsensusResult};
+use crate::messages::{Block, Nonce, Request, SignedRequest};
+use crate::pruner::load_horizon;
 use bytes::Bytes;
-use crypto::{Digest, PublicKey};
+use crypto::{Digest, Hash as _, PublicKey};
 use log::warn;
-use network::SimpleSender;
+use network::{RequestQuota, SimpleSender};
+use std::collections::{BTreeSet, HashMap};
+use store::keys::CHECKPOINT_KEY;
 use store::Store;
 use tokio::sync::mpsc::Receiver;
+use tokio::sync::watch;
 
 #[cfg(test)]
 #[path = "tests/helper_tests.rs"]
 pub mod helper_tests;
 
+/// The maximum number of blocks sent in a single sync reply.
+pub const SYNC_CHUNK_SIZE: usize = 100;
+
+/// The maximum number of blocks sent in reply to a single sync request.
+pub const MAX_SYNC_RANGE: usize = 1_000;
+
+/// We reject the requests made more than this number of rounds before the latest request of
+/// their origin.
+pub const REPLAY_WINDOW: Round = 100;
+
+/// The maximum number of requests we remember per authority to reject replays.
+pub const MAX_REPLAY_ENTRIES: usize = 10_000;
+
+/// The requests an authority recently made us, to reject replays.
+#[derive(Default)]
+struct Replays {
+    /// The digests of the requests, ordered by nonce.
+    seen: BTreeSet<(Nonce, Digest)>,
+    /// We forgot the requests up to this nonce, so we reject them all.
+    floor: Option<Nonce>,
+}
+
+impl Replays {
+    /// Record a request. Fails if we already received it, or if it is too old to tell.
+    fn insert(&mut self, nonce: Nonce, digest: Digest) -> bool {
+        if self.floor.is_some_and(|x| nonce <= x) || !self.seen.insert((nonce, digest.clone())) {
+            return false;
+        }
+
+        // Forget the requests outside the window of the latest one (including, possibly, the
+        // request we just recorded).
+        let latest = self.seen.last().map(|(x, _)| *x).unwrap_or_default();
+        while let Some((oldest, _)) = self.seen.first() {
+            let stale = oldest.epoch < latest.epoch
+                || oldest.round + REPLAY_WINDOW < latest.round
+                || self.seen.len() > MAX_REPLAY_ENTRIES;
+            if !stale {
+                break;
+            }
+            self.floor = self.seen.pop_first().map(|(x, _)| x);
+        }
+        self.seen.contains(&(nonce, digest))
+    }
+}
+
 /// A task dedicated to help other authorities by replying to their sync requests.
 pub struct Helper {
+    /// The public key of this authority.
+    name: PublicKey,
     /// The committee information.
-    committee: Committee,
+    rx_committee: watch::Receiver<Committee>,
     /// The persistent storage.
     store: Store,
-    /// Input channel to receive sync requests.
-    rx_requests: Receiver<(Digest, PublicKey)>,
+    /// Input channel to receive sync and checkpoint requests.
+    rx_requests: Receiver<SignedRequest>,
     /// A network sender to reply to the sync requests.
     network: SimpleSender,
+    /// Limits the resources each authority can make us spend.
+    quota: RequestQuota<PublicKey, Request>,
+    /// The requests each authority recently made us.
+    replays: HashMap<PublicKey, Replays>,
 }
 
 impl Helper {
-    pub fn spawn(committee: Committee, store: Store, rx_requests: Receiver<(Digest, PublicKey)>) {
+    pub fn spawn(
+        name: PublicKey,
+        rx_committee: watch::Receiver<Committee>,
+        store: Store,
+        rx_requests: Receiver<SignedRequest>,
+        max_requests: u64,
+        max_bytes: u64,
+    ) {
         tokio::spawn(async move {
             Self {
-                committee,
+                name,
+                rx_committee,
                 store,
                 rx_requests,
                 network: SimpleSender::new(),
+                quota: RequestQuota::new(max_requests, max_bytes),
+                replays: HashMap::new(),
             }
             .run()
             .await;
         });
     }
 
+    /// Read the chain of ancestors starting at the specified digest, down to (but excluding)
+    /// the known round. The blocks are ordered from the most recent one. Fails if the chain
+    /// crosses our retention horizon.
+    async fn ancestors(
+        &mut self,
+        digest: Digest,
+        known_round: Round,
+    ) -> ConsensusResult<Vec<Block>> {
+        let mut ancestors: Vec<Block> = Vec::new();
+        let mut next = Some(digest);
+        while let Some(digest) = next.take() {
+            if ancestors.len() >= MAX_SYNC_RANGE {
+                break;
+            }
+            let bytes = match self
+                .store
+                .read(digest.to_vec())
+                .await
+                .expect("Failed to read from storage")
+            {
+                Some(x) => x,
+                None => {
+                    if let Some(child) = ancestors.last().filter(|x| x.qc.round > known_round) {
+                        self.check_horizon(digest, child).await?;
+                    }
+                    break;
+                }
+            };
+            let block: Block = match bincode::deserialize(&bytes) {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("Failed to deserialize block {}: {}", digest, e);
+                    break;
+                }
+            };
+            if block.round <= known_round {
+                break;
+            }
+            if !block.qc.is_genesis() {
+                next = Some(block.parent().clone());
+            }
+            ancestors.push(block);
+        }
+        Ok(ancestors)
+    }
+
+    /// Ensure we did not prune the (missing) parent of the specified block.
+    async fn check_horizon(&mut self, digest: Digest, child: &Block) -> ConsensusResult<()> {
+        if let Some((epoch, round)) = load_horizon(&mut self.store).await? {
+            ensure!(
+                (child.epoch, child.qc.round) >= (epoch, round),
+                ConsensusError::BelowHorizon {
+                    digest,
+                    epoch,
+                    round
+                }
+            );
+        }
+        Ok(())
+    }
+
+    /// Read our latest checkpoint (if any).
+    async fn checkpoint(&mut self) -> Option<Checkpoint> {
+        let bytes = self
+            .store
+            .read(CHECKPOINT_KEY.to_vec())
+            .await
+            .expect("Failed to read from storage")?;
+        match bincode::deserialize(&bytes) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                warn!("Failed to deserialize checkpoint: {}", e);
+                None
+            }
+        }
+    }
+
     async fn run(&mut self) {
-        while let Some((digest, origin)) = self.rx_requests.recv().await {
-            // TODO [issue #58]: Do some accounting to prevent bad nodes from monopolizing our resources.
+        while let Some(signed) = self.rx_requests.recv().await {
+            // Ensure the request comes from its origin: we charge the origin for serving it.
+            let verified = signed.verify(&self.rx_committee.borrow(), &self.name);
+            if let Err(e) = verified {
+                warn!("Received invalid {:?}: {}", signed, e);
+                continue;
+            }
+
+            // Ensure the request is not replayed (by another authority) to spend its quota.
+            let origin = signed.origin;
+            let fresh = self
+                .replays
+                .entry(origin)
+                .or_default()
+                .insert(signed.nonce, signed.digest());
+            if !fresh {
+                let e = ConsensusError::ReplayedRequest(origin);
+                warn!("Dropped {:?}: {}", signed, e);
+                continue;
+            }
 
             // get the requestors address.
-            let address = match self.committee.address(&origin) {
+            let address = match self.rx_committee.borrow().address(&origin) {
                 Some(x) => x,
-                None => {
-                    warn!("Received sync request from unknown authority: {}", origin);
-                    continue;
+                None => continue,
+            };
+
+            // Prevent bad nodes from monopolizing our resources.
+            if let Err(e) = self.quota.admit(&origin, signed.request.clone()) {
+                warn!("Dropped {:?}: {}", signed, e);
+                continue;
+            }
+
+            // Reply to the request (if we can). We send ancestors in bounded chunks.
+            let messages = match signed.request {
+                Request::Sync(digest, known_round) => {
+                    match self.ancestors(digest, known_round).await {
+                        Ok(ancestors) => ancestors
+                            .chunks(SYNC_CHUNK_SIZE)
+                            .map(|x| ConsensusMessage::SyncReply(x.to_vec()))
+                            .collect(),
+                        // Our checkpoint lets the requestor skip the history we pruned.
+                        Err(e) => {
+                            warn!("Cannot serve sync request from {}: {}", origin, e);
+                            self.checkpoint()
+                                .await
+                                .map(ConsensusMessage::CheckpointReply)
+                                .into_iter()
+                                .collect()
+                        }
+                    }
                 }
+                Request::Checkpoint => self
+                    .checkpoint()
+                    .await
+                    .map(ConsensusMessage::CheckpointReply)
+                    .into_iter()
+                    .collect::<Vec<_>>(),
             };
+            for message in messages {
+                let message = bincode::serialize(&message).expect("Failed to serialize reply");
 
-            // Reply to the request (if we can).
-            if let Some(bytes) = self
-                .store
-                .read(digest.to_vec())
-                .await
-                .expect("Failed to read from storage")
-            {
-                let block =
-                    bincode::deserialize(&bytes).expect("Failed to deserialize our own block");
-                let message = bincode::serialize(&ConsensusMessage::Propose(block))
-                    .expect("Failed to serialize block");
+                // Stop replying as soon as the requestor spent its bytes budget.
+                if let Err(e) = self.quota.charge(&origin, message.len()) {
+                    warn!("Stopped replying to {}: {}", origin, e);
+                    break;
+                }
                 self.network.send(address, Bytes::from(message)).await;
             }
         }
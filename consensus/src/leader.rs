@@ -1,9 +1,89 @@
-use crate::config::Committee;
+use crate::config::{CommitRule, Committee, LeaderElection};
 use crate::consensus::Round;
-use crypto::PublicKey;
+use crate::messages::Block;
+use crypto::Hash as _;
+use crypto::{Digest, PublicKey};
+use ed25519_dalek::Digest as _;
+use ed25519_dalek::Sha512;
+use std::cmp::max;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
 
-pub type LeaderElector = RRLeaderElector;
+#[cfg(test)]
+#[path = "tests/leader_tests.rs"]
+pub mod leader_tests;
 
+/// Decides which authority is the leader of each round. All honest nodes must elect the same
+/// leader for a given round, so electors may only rely on deterministic data (eg. the committee,
+/// the committed chain, and the certified chain the round extends).
+pub trait LeaderElector: Send + 'static {
+    /// Return the leader of the specified round, extending the block with the specified digest
+    /// and round (`None` if the round extends the genesis of the epoch). Returns `None` if we do
+    /// not hold the history needed to elect the leader.
+    fn get_leader(&self, round: Round, parent: Option<(Digest, Round)>) -> Option<PublicKey>;
+
+    /// Notify the elector of a block we stored (we hold all its ancestors).
+    fn add(&mut self, _block: &Block) {}
+
+    /// Notify the elector of a newly committed block. Blocks are delivered in commit order.
+    fn update(&mut self, _block: &Block) {}
+
+    /// The number of rounds of history the elector needs to be fed (through `add` and `update`)
+    /// upon booting to take the same decisions as before a crash.
+    fn history(&self) -> Round {
+        0
+    }
+
+    /// Notify the elector that we skipped the committed chain up to the specified committed
+    /// block (eg. by installing a checkpoint): we do not hold the history preceding it.
+    fn reset(&mut self, _block: &Block) {}
+
+    /// Switch to the committee of a new epoch. Rounds restart from 1 at every epoch.
+    fn reconfigure(&mut self, committee: Committee);
+}
+
+impl LeaderElector for Box<dyn LeaderElector> {
+    fn get_leader(&self, round: Round, parent: Option<(Digest, Round)>) -> Option<PublicKey> {
+        (**self).get_leader(round, parent)
+    }
+
+    fn add(&mut self, block: &Block) {
+        (**self).add(block)
+    }
+
+    fn update(&mut self, block: &Block) {
+        (**self).update(block)
+    }
+
+    fn history(&self) -> Round {
+        (**self).history()
+    }
+
+    fn reset(&mut self, block: &Block) {
+        (**self).reset(block)
+    }
+
+    fn reconfigure(&mut self, committee: Committee) {
+        (**self).reconfigure(committee)
+    }
+}
+
+impl LeaderElection {
+    /// Make the leader elector selected by the parameters.
+    pub fn elector(&self, committee: Committee, commit_rule: CommitRule) -> Box<dyn LeaderElector> {
+        match *self {
+            Self::RoundRobin => Box::new(RRLeaderElector::new(committee)),
+            Self::Reputation { window, lag } => Box::new(ReputationLeaderElector::new(
+                committee,
+                window,
+                lag,
+                commit_rule,
+            )),
+        }
+    }
+}
+
+/// Rotates the leadership through the (sorted) public keys of the committee.
 pub struct RRLeaderElector {
     committee: Committee,
 }
@@ -13,9 +93,206 @@ impl RRLeaderElector {
         Self { committee }
     }
 
-    pub fn get_leader(&self, round: Round) -> PublicKey {
-        let mut keys: Vec<_> = self.committee.authorities.keys().cloned().collect();
-        keys.sort();
+    fn leader(&self, round: Round) -> PublicKey {
+        let keys = self.committee.sorted_names();
         keys[round as usize % self.committee.size()]
     }
 }
+
+impl LeaderElector for RRLeaderElector {
+    fn get_leader(&self, round: Round, _parent: Option<(Digest, Round)>) -> Option<PublicKey> {
+        Some(self.leader(round))
+    }
+
+    fn reconfigure(&mut self, committee: Committee) {
+        self.committee = committee;
+    }
+}
+
+/// The weight of authorities that authored a committed block within the reputation window.
+const ACTIVE_WEIGHT: u64 = 100;
+/// The weight of authorities that did not author any committed block within the reputation
+/// window. It is not zero to let them regain their reputation once they are back online.
+const INACTIVE_WEIGHT: u64 = 1;
+
+/// The position of a block's parent (`None` for the genesis of the epoch).
+type Parent = Option<(Digest, Round)>;
+
+/// Elects leaders proportionally to their stake, favouring the authorities that recently authored
+/// committed blocks. The leader of round `r` only depends on the blocks committed in the rounds
+/// `[r - lag - window, r - lag)`: it does not depend on the block that round `r` extends, so
+/// leaders cannot bias the election by choosing which QC to extend. Nodes that did not commit up
+/// to round `r - lag - 1` yet (or that no longer remember the window, or skipped it by installing
+/// a checkpoint) cannot elect the leader.
+///
+/// The lag lets honest nodes commit the window before they need it, but only while the chain
+/// grows at consecutive rounds. The rounds following a timeout are thus led by round-robin: the
+/// round-robin schedule leads round `r` unless the chain it extends grows at consecutive rounds
+/// over the `depth + 2` rounds preceding `r` (see `CommitRule::depth`), ie. unless the window
+/// is committed. This lets the committee commit again (and resume electing leaders by
+/// reputation) after any number of timeouts.
+pub struct ReputationLeaderElector {
+    committee: Committee,
+    /// The number of rounds of committed history considered to elect a leader.
+    window: Round,
+    /// The number of rounds between the end of the reputation window and the elected round.
+    lag: Round,
+    /// The number of descendants (at consecutive rounds) a block needs to be committed.
+    depth: Round,
+    /// The round and parent (unless it extends the genesis) of the recent blocks of the epoch,
+    /// by digest.
+    blocks: HashMap<Digest, (Round, Parent)>,
+    /// The round and author of the recently committed blocks of the epoch.
+    committed: VecDeque<(Round, PublicKey)>,
+    /// The round of the last committed block.
+    last_committed_round: Round,
+    /// The round from which we hold the committed history of the epoch (0 unless we skipped the
+    /// history preceding a checkpoint, see `reset`).
+    floor: Round,
+    /// The fallback elector (for the first rounds of each epoch and the rounds following a
+    /// timeout).
+    round_robin: RRLeaderElector,
+}
+
+impl ReputationLeaderElector {
+    pub fn new(committee: Committee, window: Round, lag: Round, commit_rule: CommitRule) -> Self {
+        let depth = commit_rule.depth() as Round;
+        Self {
+            round_robin: RRLeaderElector::new(committee.clone()),
+            committee,
+            window,
+            // Voters of round `r` committed up to round `r - depth - 1`.
+            lag: lag.max(depth + 1),
+            depth,
+            blocks: HashMap::new(),
+            committed: VecDeque::new(),
+            last_committed_round: 0,
+            floor: 0,
+        }
+    }
+
+    /// Pick an authority with probability proportional to its weight, using the round number as
+    /// source of (deterministic) randomness.
+    fn weighted_choice(round: Round, mut weights: Vec<(PublicKey, u64)>) -> PublicKey {
+        weights.sort();
+        let total: u64 = weights.iter().map(|(_, w)| w).sum();
+        let hash = Sha512::digest(&round.to_le_bytes());
+        let seed = u64::from_le_bytes(hash[..8].try_into().unwrap());
+        let mut target = seed % total;
+        for (name, weight) in &weights {
+            if target < *weight {
+                return *name;
+            }
+            target -= weight;
+        }
+        unreachable!("The target is always below the total weight")
+    }
+
+    /// Return whether the chain extending `parent` with a block of the specified round grows at
+    /// consecutive rounds over the `depth + 2` rounds preceding it, or `None` if we miss some of
+    /// its blocks.
+    fn consecutive(&self, round: Round, parent: Parent) -> Option<bool> {
+        let mut expected = round;
+        let mut next = parent;
+        for _ in 0..self.depth + 2 {
+            expected -= 1;
+            match next {
+                // The chain starts with the genesis of the epoch (round 0).
+                None => return Some(expected == 0),
+                Some((_, r)) if r != expected => return Some(false),
+                Some((digest, _)) => next = self.blocks.get(&digest)?.1.clone(),
+            }
+        }
+        Some(true)
+    }
+}
+
+impl LeaderElector for ReputationLeaderElector {
+    fn get_leader(&self, round: Round, parent: Option<(Digest, Round)>) -> Option<PublicKey> {
+        let end = round.saturating_sub(self.lag);
+        if end == 0 || !self.consecutive(round, parent)? {
+            return Some(self.round_robin.leader(round));
+        }
+
+        // Ensure we know all the blocks committed within the reputation window.
+        let start = end.saturating_sub(self.window);
+        let oldest = self.last_committed_round.saturating_sub(self.history());
+        if self.last_committed_round + 1 < end || start < max(oldest, self.floor) {
+            return None;
+        }
+
+        // Score the authorities based on the committed blocks they authored.
+        let active: HashSet<_> = self
+            .committed
+            .iter()
+            .filter(|(r, _)| *r >= start && *r < end)
+            .map(|(_, author)| *author)
+            .collect();
+        let weights = self
+            .committee
+            .authorities
+            .iter()
+            .map(|(name, authority)| {
+                let factor = if active.contains(name) {
+                    ACTIVE_WEIGHT
+                } else {
+                    INACTIVE_WEIGHT
+                };
+                (*name, authority.stake as u64 * factor)
+            })
+            .collect();
+        Some(Self::weighted_choice(round, weights))
+    }
+
+    fn add(&mut self, block: &Block) {
+        if block.epoch != self.committee.epoch
+            || block.round + self.history() < self.last_committed_round
+        {
+            return;
+        }
+        let parent = (!block.qc.is_genesis()).then(|| (block.qc.hash.clone(), block.qc.round));
+        self.blocks.insert(block.digest(), (block.round, parent));
+    }
+
+    fn update(&mut self, block: &Block) {
+        if block.round <= self.last_committed_round {
+            return;
+        }
+        self.last_committed_round = block.round;
+        self.committed.push_back((block.round, block.author));
+
+        // Forget the blocks that fell out of our history.
+        let oldest = self.last_committed_round.saturating_sub(self.history());
+        self.blocks.retain(|_, (r, _)| *r >= oldest);
+        while matches!(self.committed.front(), Some((r, _)) if *r < oldest) {
+            self.committed.pop_front();
+        }
+    }
+
+    fn history(&self) -> Round {
+        // Also remember one extra window to elect leaders of slightly older rounds.
+        2 * self.window + self.lag
+    }
+
+    fn reset(&mut self, block: &Block) {
+        if block.epoch != self.committee.epoch || block.round <= self.last_committed_round {
+            return;
+        }
+        self.blocks.clear();
+        self.committed.clear();
+        self.committed.push_back((block.round, block.author));
+        self.last_committed_round = block.round;
+        self.floor = block.round;
+        self.add(block);
+    }
+
+    fn reconfigure(&mut self, committee: Committee) {
+        // The reputation of the previous epoch is meaningless for the new committee.
+        self.round_robin.reconfigure(committee.clone());
+        self.committee = committee;
+        self.blocks.clear();
+        self.committed.clear();
+        self.last_committed_round = 0;
+        self.floor = 0;
+    }
+}
@@ -1,21 +1,37 @@
 #[macro_use]
 mod error;
 mod aggregator;
+mod builder;
+mod checkpoint;
 mod config;
 mod consensus;
 mod core;
+mod evidence;
 mod helper;
 mod leader;
 mod mempool;
 mod messages;
 mod proposer;
+mod pruner;
+mod state;
 mod synchronizer;
 mod timer;
+mod validator;
 
 #[cfg(test)]
 #[path = "tests/common.rs"]
 mod common;
 
-pub use crate::config::{Committee, Parameters};
+#[cfg(test)]
+#[path = "tests/simulation.rs"]
+mod simulation;
+
+pub use crate::builder::{Proposal, ProposalBuilder, ProposeAll};
+pub use crate::checkpoint::Checkpoint;
+pub use crate::config::{Byzantine, CommitRule, Committee, LeaderElection, Parameters};
 pub use crate::consensus::Consensus;
-pub use crate::messages::{Block, QC, TC};
+pub use crate::error::ConsensusError;
+pub use crate::evidence::Evidence;
+pub use crate::leader::{LeaderElector, RRLeaderElector, ReputationLeaderElector};
+pub use crate::messages::{Block, BlockHeader, CommittedBlock, Reconfiguration, Signers, QC, TC};
+pub use crate::validator::{AcceptAll, ProposalValidator};
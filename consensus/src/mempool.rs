@@ -1,21 +1,32 @@
 use crate::consensus::{Round, CHANNEL_CAPACITY};
 use crate::error::{ConsensusError, ConsensusResult};
 use crate::messages::Block;
+use crate::pruner::Position;
 use crypto::Digest;
 use crypto::Hash as _;
 use futures::future::try_join_all;
 use futures::stream::futures_unordered::FuturesUnordered;
 use futures::stream::StreamExt as _;
 use log::error;
-use mempool::ConsensusMempoolMessage;
+use mempool::{
+    pruned_key, Batch, Certificate, Committee as MempoolCommittee, ConsensusMempoolMessage,
+    MempoolMessage,
+};
 use std::collections::HashMap;
 use store::Store;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
+/// The number of rounds during which we remember the batches we committed, to reject the blocks
+/// proposing them again.
+const COMMITTED_BATCHES_DEPTH: Round = 1_000;
+
 pub struct MempoolDriver {
     store: Store,
     tx_mempool: Sender<ConsensusMempoolMessage>,
     tx_payload_waiter: Sender<PayloadWaiterMessage>,
+    /// The batches committed within the last `COMMITTED_BATCHES_DEPTH` rounds, and the round of
+    /// the block committing them.
+    committed: HashMap<Digest, Round>,
 }
 
 impl MempoolDriver {
@@ -34,9 +45,14 @@ impl MempoolDriver {
             store,
             tx_mempool,
             tx_payload_waiter,
+            committed: HashMap::new(),
         }
     }
 
+    /// Check whether the block's payload is available. If it isn't, we ask the mempool to sync
+    /// the batches we miss and the payload waiter re-delivers the block once we stored them: we
+    /// cannot vote for it before (see `batches`). Since the proposer only includes certified
+    /// batches, a quorum stored them and the sync cannot stall on a faulty proposer.
     pub async fn verify(&mut self, block: Block) -> ConsensusResult<bool> {
         let mut missing = Vec::new();
         for x in &block.payload {
@@ -63,7 +79,101 @@ impl MempoolDriver {
         Ok(false)
     }
 
+    /// Read the payload of a block (see `verify`). Returns `None` if we miss some of its batches
+    /// (e.g., they were pruned since we verified the block).
+    pub async fn batches(&mut self, block: &Block) -> ConsensusResult<Option<Vec<Batch>>> {
+        let mut batches = Vec::new();
+        for x in &block.payload {
+            let serialized = match self.store.read(x.to_vec()).await? {
+                Some(serialized) => serialized,
+                None => return Ok(None),
+            };
+            match bincode::deserialize(&serialized)? {
+                MempoolMessage::Batch(batch) => batches.push(batch),
+                _ => return Err(ConsensusError::InvalidPayload),
+            }
+        }
+        Ok(Some(batches))
+    }
+
+    /// Remember the batches of a committed block (see `COMMITTED_BATCHES_DEPTH`).
+    pub fn record(&mut self, block: &Block) {
+        for x in &block.payload {
+            self.committed.insert(x.clone(), block.round);
+        }
+    }
+
+    /// Remember the batches committed by the chain ending with the specified block (upon booting).
+    pub async fn load(&mut self, last_committed: &Digest) -> ConsensusResult<()> {
+        let mut digest = last_committed.clone();
+        let mut oldest = None;
+        while digest != Digest::default() {
+            let block: Block = match self.store.read(digest.to_vec()).await? {
+                Some(bytes) => bincode::deserialize(&bytes)?,
+                None => break,
+            };
+            let oldest = *oldest.get_or_insert((
+                block.epoch,
+                block.round.saturating_sub(COMMITTED_BATCHES_DEPTH),
+            ));
+            if (block.epoch, block.round) < oldest {
+                break;
+            }
+            self.record(&block);
+            digest = block.parent().clone();
+        }
+        Ok(())
+    }
+
+    /// Return whether we recently committed the batch.
+    pub fn is_committed(&self, digest: &Digest) -> bool {
+        self.committed.contains_key(digest)
+    }
+
+    /// Notify the mempool that the block's batches are committed (it sends receipts to clients).
+    pub async fn commit(&mut self, block: &Block) {
+        self.record(block);
+        self.tx_mempool
+            .send(ConsensusMempoolMessage::Commit(
+                block.payload.clone(),
+                block.round,
+            ))
+            .await
+            .expect("Failed to send commit message");
+    }
+
+    /// Delete the batches (and certificates) of a committed block. We keep a marker for each of
+    /// them: the mempool can then tell apart the batches we pruned from those we never stored.
+    pub async fn prune(&mut self, block: &Block) {
+        for x in &block.payload {
+            self.store.delete(x.to_vec()).await;
+            self.store.delete(Certificate::key(x)).await;
+            self.store.write(pruned_key(x), Vec::new()).await;
+        }
+    }
+
+    /// Delete the markers of batches we pruned long ago (see `prune`).
+    pub async fn unmark(&mut self, digests: &[Digest]) {
+        for x in digests {
+            self.store.delete(pruned_key(x)).await;
+        }
+    }
+
+    /// Let the mempool delete the batches (and certificates) it stored below our retention
+    /// horizon and that we did not commit.
+    pub async fn prune_stale(&mut self, horizon: Position) {
+        let (epoch, round) = horizon;
+        self.tx_mempool
+            .send(ConsensusMempoolMessage::Prune(epoch, round))
+            .await
+            .expect("Failed to send prune message");
+    }
+
     pub async fn cleanup(&mut self, round: Round) {
+        // Forget the batches committed long ago.
+        let gc_round = round.saturating_sub(COMMITTED_BATCHES_DEPTH);
+        self.committed.retain(|_, r| *r >= gc_round);
+
         // Cleanup the mempool.
         self.tx_mempool
             .send(ConsensusMempoolMessage::Cleanup(round))
@@ -76,6 +186,26 @@ impl MempoolDriver {
             .await
             .expect("Failed to send cleanup message");
     }
+
+    pub async fn reconfigure(&mut self, committee: MempoolCommittee) {
+        // Switch the mempool to the new committee.
+        self.tx_mempool
+            .send(ConsensusMempoolMessage::Reconfigure(committee))
+            .await
+            .expect("Failed to send reconfiguration message");
+
+        // Rounds restart at every epoch: we remember the batches committed in the previous epoch
+        // for another `COMMITTED_BATCHES_DEPTH` rounds.
+        for round in self.committed.values_mut() {
+            *round = Round::default();
+        }
+
+        // Drop all the blocks waiting for their payload.
+        self.tx_payload_waiter
+            .send(PayloadWaiterMessage::Cleanup(Round::MAX))
+            .await
+            .expect("Failed to send cleanup message");
+    }
 }
 
 #[derive(Debug)]
@@ -1,11 +1,12 @@
-use crate::config::Committee;
+use crate::config::{CommitRule, Committee, EpochNumber, Stake};
 use crate::consensus::Round;
 use crate::error::{ConsensusError, ConsensusResult};
-use crypto::{Digest, Hash, PublicKey, Signature, SignatureService};
+use crate::evidence::Evidence;
+use crypto::{BlsSignature, Digest, Hash, PublicKey, Signature, SignatureService};
 use ed25519_dalek::Digest as _;
 use ed25519_dalek::Sha512;
+use mempool::Committee as MempoolCommittee;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
 use std::convert::TryInto;
 use std::fmt;
 
@@ -13,23 +14,38 @@ use std::fmt;
 #[path = "tests/messages_tests.rs"]
 pub mod messages_tests;
 
+/// The maximum number of proofs of misbehaviour a block may carry. Proposers keep the remaining
+/// ones for their next blocks.
+pub const MAX_EVIDENCE: usize = 16;
+
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct Block {
     pub qc: QC,
     pub tc: Option<TC>,
     pub author: PublicKey,
     pub round: Round,
+    pub epoch: EpochNumber,
     pub payload: Vec<Digest>,
+    /// Application-defined data (see `ProposalBuilder`).
+    pub metadata: Option<Vec<u8>>,
+    pub reconfiguration: Option<Reconfiguration>,
+    /// Proofs of misbehaviour (they are committed along with the block).
+    pub evidence: Vec<Evidence>,
     pub signature: Signature,
 }
 
 impl Block {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         qc: QC,
         tc: Option<TC>,
         author: PublicKey,
         round: Round,
+        epoch: EpochNumber,
         payload: Vec<Digest>,
+        metadata: Option<Vec<u8>>,
+        reconfiguration: Option<Reconfiguration>,
+        evidence: Vec<Evidence>,
         mut signature_service: SignatureService,
     ) -> Self {
         let block = Self {
@@ -37,7 +53,11 @@ impl Block {
             tc,
             author,
             round,
+            epoch,
             payload,
+            metadata,
+            reconfiguration,
+            evidence,
             signature: Signature::default(),
         };
         let signature = signature_service.request_signature(block.digest()).await;
@@ -52,7 +72,43 @@ impl Block {
         &self.qc.hash
     }
 
+    /// The block without its evidence (see `BlockHeader`).
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            evidence: self.evidence.iter().map(|x| x.digest()).collect(),
+            block: Block {
+                evidence: Vec::new(),
+                ..self.clone()
+            },
+        }
+    }
+
     pub fn verify(&self, committee: &Committee) -> ConsensusResult<()> {
+        let digest = self.digest();
+        self.verify_header(committee, &digest)?;
+
+        // Check the evidence embedded in the block (if any).
+        ensure!(
+            self.evidence.len() <= MAX_EVIDENCE,
+            ConsensusError::MalformedBlock(digest)
+        );
+        for evidence in &self.evidence {
+            evidence.verify(committee)?;
+        }
+        Ok(())
+    }
+
+    /// Check everything but the evidence embedded in the block, whose digest is specified.
+    fn verify_header(&self, committee: &Committee, digest: &Digest) -> ConsensusResult<()> {
+        // Ensure the block belongs to the current epoch.
+        ensure!(
+            self.epoch == committee.epoch,
+            ConsensusError::WrongEpoch {
+                expected: committee.epoch,
+                received: self.epoch
+            }
+        );
+
         // Ensure the authority has voting rights.
         let voting_rights = committee.stake(&self.author);
         ensure!(
@@ -61,10 +117,10 @@ impl Block {
         );
 
         // Check the signature.
-        self.signature.verify(&self.digest(), &self.author)?;
+        self.signature.verify(digest, &self.author)?;
 
-        // Check the embedded QC.
-        if self.qc != QC::genesis() {
+        // Check the embedded QC (unless it is the genesis of the epoch).
+        if !self.qc.is_genesis() {
             self.qc.verify(committee)?;
         }
 
@@ -72,23 +128,57 @@ impl Block {
         if let Some(ref tc) = self.tc {
             tc.verify(committee)?;
         }
+
+        // Check the reconfiguration embedded in the block (if any).
+        if let Some(ref reconfiguration) = self.reconfiguration {
+            reconfiguration.verify(committee)?;
+        }
         Ok(())
     }
-}
 
-impl Hash for Block {
-    fn digest(&self) -> Digest {
+    /// The digest of the block, given the digests of its evidence.
+    fn digest_with(&self, evidence: &[Digest]) -> Digest {
         let mut hasher = Sha512::new();
         hasher.update(self.author.0);
         hasher.update(self.round.to_le_bytes());
+        hasher.update(self.epoch.to_le_bytes());
+        // Count the lists and tag the optional sections, so that no section can be mistaken for
+        // another one.
+        hasher.update((self.payload.len() as u64).to_le_bytes());
         for x in &self.payload {
             hasher.update(x);
         }
+        match self.metadata {
+            Some(ref metadata) => {
+                hasher.update([1u8]);
+                hasher.update((metadata.len() as u64).to_le_bytes());
+                hasher.update(metadata);
+            }
+            None => hasher.update([0u8]),
+        }
+        match self.reconfiguration {
+            Some(ref reconfiguration) => {
+                hasher.update([1u8]);
+                hasher.update(reconfiguration.digest());
+            }
+            None => hasher.update([0u8]),
+        }
+        hasher.update((evidence.len() as u64).to_le_bytes());
+        for x in evidence {
+            hasher.update(x);
+        }
         hasher.update(&self.qc.hash);
         Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
     }
 }
 
+impl Hash for Block {
+    fn digest(&self) -> Digest {
+        let evidence: Vec<_> = self.evidence.iter().map(|x| x.digest()).collect();
+        self.digest_with(&evidence)
+    }
+}
+
 impl fmt::Debug for Block {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(
@@ -109,12 +199,112 @@ impl fmt::Display for Block {
     }
 }
 
+/// A block stripped of its evidence, of which it only keeps the digests: it is enough to check the
+/// block's signature. Proofs of double proposals hold headers, so that evidence cannot nest.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BlockHeader {
+    /// The block, without evidence.
+    pub block: Block,
+    /// The digests of the block's evidence.
+    pub evidence: Vec<Digest>,
+}
+
+impl BlockHeader {
+    pub fn verify(&self, committee: &Committee) -> ConsensusResult<()> {
+        let digest = self.digest();
+        ensure!(
+            self.block.evidence.is_empty() && self.evidence.len() <= MAX_EVIDENCE,
+            ConsensusError::MalformedBlock(digest)
+        );
+        self.block.verify_header(committee, &digest)
+    }
+}
+
+impl Hash for BlockHeader {
+    fn digest(&self) -> Digest {
+        self.block.digest_with(&self.evidence)
+    }
+}
+
+/// A committed block along with the proof that it is final. Anyone can check the proof against
+/// the committee of the block's epoch, without trusting the node it came from.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CommittedBlock {
+    pub block: Block,
+    /// The descendants of the block, from its child to the chain satisfying the commit rule:
+    /// each block extends the previous one. Blocks committed along with one of their descendants
+    /// (eg. after a view-change) are proven final through the chain leading to it.
+    pub descendants: Vec<Block>,
+    /// The QC certifying the last descendant.
+    pub qc: QC,
+}
+
+impl CommittedBlock {
+    /// The QC certifying the committed block.
+    pub fn certificate(&self) -> &QC {
+        self.descendants.first().map_or(&self.qc, |x| &x.qc)
+    }
+
+    pub fn verify(&self, committee: &Committee, commit_rule: CommitRule) -> ConsensusResult<()> {
+        // Ensure the block belongs to the current epoch.
+        let digest = self.block.digest();
+        ensure!(
+            self.block.epoch == committee.epoch,
+            ConsensusError::WrongEpoch {
+                expected: committee.epoch,
+                received: self.block.epoch
+            }
+        );
+
+        // Ensure each descendant extends the previous one. The hash of the last descendant
+        // thus authenticates the entire chain.
+        let depth = commit_rule.depth();
+        ensure!(
+            self.descendants.len() >= depth,
+            ConsensusError::InvalidCommitProof(digest)
+        );
+        let mut parent = &self.block;
+        for (i, block) in self.descendants.iter().enumerate() {
+            ensure!(
+                block.qc.hash == parent.digest()
+                    && block.qc.round == parent.round
+                    && block.round > parent.round,
+                ConsensusError::InvalidCommitProof(digest)
+            );
+
+            // The last blocks must form a chain of certified blocks with consecutive rounds.
+            if i + depth >= self.descendants.len() {
+                ensure!(
+                    block.round == parent.round + 1,
+                    ConsensusError::InvalidCommitProof(digest)
+                );
+                block.qc.verify(committee)?;
+            }
+            parent = block;
+        }
+
+        // Ensure the last descendant is certified.
+        ensure!(
+            self.qc.hash == parent.digest() && self.qc.round == parent.round,
+            ConsensusError::InvalidCommitProof(digest)
+        );
+        self.qc.verify(committee)
+    }
+}
+
+impl fmt::Debug for CommittedBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "C({:?}, {:?})", self.block, self.qc)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Vote {
     pub hash: Digest,
     pub round: Round,
+    pub epoch: EpochNumber,
     pub author: PublicKey,
-    pub signature: Signature,
+    pub signature: BlsSignature,
 }
 
 impl Vote {
@@ -126,22 +316,31 @@ impl Vote {
         let vote = Self {
             hash: block.digest(),
             round: block.round,
+            epoch: block.epoch,
             author,
-            signature: Signature::default(),
+            signature: BlsSignature::default(),
         };
-        let signature = signature_service.request_signature(vote.digest()).await;
+        let signature = signature_service.request_bls_signature(vote.digest()).await;
         Self { signature, ..vote }
     }
 
     pub fn verify(&self, committee: &Committee) -> ConsensusResult<()> {
-        // Ensure the authority has voting rights.
+        // Ensure the vote belongs to the current epoch.
         ensure!(
-            committee.stake(&self.author) > 0,
-            ConsensusError::UnknownAuthority(self.author)
+            self.epoch == committee.epoch,
+            ConsensusError::WrongEpoch {
+                expected: committee.epoch,
+                received: self.epoch
+            }
         );
 
+        // Ensure the authority has voting rights.
+        let key = committee
+            .bls_key(&self.author)
+            .ok_or(ConsensusError::UnknownAuthority(self.author))?;
+
         // Check the signature.
-        self.signature.verify(&self.digest(), &self.author)?;
+        self.signature.verify(&self.digest(), key)?;
         Ok(())
     }
 }
@@ -151,6 +350,7 @@ impl Hash for Vote {
         let mut hasher = Sha512::new();
         hasher.update(&self.hash);
         hasher.update(self.round.to_le_bytes());
+        hasher.update(self.epoch.to_le_bytes());
         Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
     }
 }
@@ -161,11 +361,57 @@ impl fmt::Debug for Vote {
     }
 }
 
+/// The authorities that signed a certificate, as a bitmap indexed by the sorted names of the
+/// committee (see `Committee::sorted_names`).
+#[derive(Clone, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+pub struct Signers(Vec<u8>);
+
+impl Signers {
+    pub fn new<'a, I>(names: I, committee: &Committee) -> Self
+    where
+        I: IntoIterator<Item = &'a PublicKey>,
+    {
+        let sorted = committee.sorted_names();
+        let mut bitmap = vec![0; sorted.len().div_ceil(8)];
+        for name in names {
+            if let Ok(i) = sorted.binary_search(name) {
+                bitmap[i / 8] |= 1 << (i % 8);
+            }
+        }
+        Self(bitmap)
+    }
+
+    /// Return the names of the signers, sorted.
+    pub fn names(&self, committee: &Committee) -> ConsensusResult<Vec<PublicKey>> {
+        let sorted = committee.sorted_names();
+        ensure!(
+            self.0.len() == sorted.len().div_ceil(8),
+            ConsensusError::MalformedSigners
+        );
+        let names: Vec<_> = sorted
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| self.0[i / 8] & (1 << (i % 8)) != 0)
+            .map(|(_, name)| name)
+            .collect();
+
+        // Ensure no bit is set past the size of the committee.
+        let count: u32 = self.0.iter().map(|x| x.count_ones()).sum();
+        ensure!(
+            count as usize == names.len(),
+            ConsensusError::MalformedSigners
+        );
+        Ok(names)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Default)]
 pub struct QC {
     pub hash: Digest,
     pub round: Round,
-    pub votes: Vec<(PublicKey, Signature)>,
+    pub epoch: EpochNumber,
+    pub signers: Signers,
+    pub signature: BlsSignature,
 }
 
 impl QC {
@@ -173,28 +419,50 @@ impl QC {
         QC::default()
     }
 
+    /// The genesis QC of the epoch following the one ended by the specified block.
+    pub fn epoch_genesis(last_block: &Block, epoch: EpochNumber) -> Self {
+        Self {
+            hash: last_block.digest(),
+            round: 0,
+            epoch,
+            signers: Signers::default(),
+            signature: BlsSignature::default(),
+        }
+    }
+
+    /// Whether this is the genesis QC of an epoch. It carries no signatures: its hash must be
+    /// checked against the last block of the previous epoch (if any).
+    pub fn is_genesis(&self) -> bool {
+        self.round == 0
+    }
+
     pub fn timeout(&self) -> bool {
         self.hash == Digest::default() && self.round != 0
     }
 
     pub fn verify(&self, committee: &Committee) -> ConsensusResult<()> {
+        // Ensure the QC belongs to the current epoch.
+        ensure!(
+            self.epoch == committee.epoch,
+            ConsensusError::WrongEpoch {
+                expected: committee.epoch,
+                received: self.epoch
+            }
+        );
+
         // Ensure the QC has a quorum.
-        let mut weight = 0;
-        let mut used = HashSet::new();
-        for (name, _) in self.votes.iter() {
-            ensure!(!used.contains(name), ConsensusError::AuthorityReuse(*name));
-            let voting_rights = committee.stake(name);
-            ensure!(voting_rights > 0, ConsensusError::UnknownAuthority(*name));
-            used.insert(*name);
-            weight += voting_rights;
-        }
+        let names = self.signers.names(committee)?;
+        let weight: Stake = names.iter().map(|x| committee.stake(x)).sum();
         ensure!(
             weight >= committee.quorum_threshold(),
             ConsensusError::QCRequiresQuorum
         );
 
-        // Check the signatures.
-        Signature::verify_batch(&self.digest(), &self.votes).map_err(ConsensusError::from)
+        // Check the aggregate signature.
+        let keys = names.iter().filter_map(|x| committee.bls_key(x));
+        self.signature
+            .verify_aggregate(&self.digest(), keys)
+            .map_err(ConsensusError::from)
     }
 }
 
@@ -203,6 +471,7 @@ impl Hash for QC {
         let mut hasher = Sha512::new();
         hasher.update(&self.hash);
         hasher.update(self.round.to_le_bytes());
+        hasher.update(self.epoch.to_le_bytes());
         Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
     }
 }
@@ -223,24 +492,29 @@ impl PartialEq for QC {
 pub struct Timeout {
     pub high_qc: QC,
     pub round: Round,
+    pub epoch: EpochNumber,
     pub author: PublicKey,
-    pub signature: Signature,
+    pub signature: BlsSignature,
 }
 
 impl Timeout {
     pub async fn new(
         high_qc: QC,
         round: Round,
+        epoch: EpochNumber,
         author: PublicKey,
         mut signature_service: SignatureService,
     ) -> Self {
         let timeout = Self {
             high_qc,
             round,
+            epoch,
             author,
-            signature: Signature::default(),
+            signature: BlsSignature::default(),
         };
-        let signature = signature_service.request_signature(timeout.digest()).await;
+        let signature = signature_service
+            .request_bls_signature(timeout.digest())
+            .await;
         Self {
             signature,
             ..timeout
@@ -248,32 +522,48 @@ impl Timeout {
     }
 
     pub fn verify(&self, committee: &Committee) -> ConsensusResult<()> {
-        // Ensure the authority has voting rights.
+        // Ensure the timeout belongs to the current epoch.
         ensure!(
-            committee.stake(&self.author) > 0,
-            ConsensusError::UnknownAuthority(self.author)
+            self.epoch == committee.epoch,
+            ConsensusError::WrongEpoch {
+                expected: committee.epoch,
+                received: self.epoch
+            }
         );
 
+        // Ensure the authority has voting rights.
+        let key = committee
+            .bls_key(&self.author)
+            .ok_or(ConsensusError::UnknownAuthority(self.author))?;
+
         // Check the signature.
-        self.signature.verify(&self.digest(), &self.author)?;
+        self.signature.verify(&self.digest(), key)?;
 
         // Check the embedded QC.
-        if self.high_qc != QC::genesis() {
+        if !self.high_qc.is_genesis() {
             self.high_qc.verify(committee)?;
         }
         Ok(())
     }
 }
 
-impl Hash for Timeout {
-    fn digest(&self) -> Digest {
+impl Timeout {
+    /// The digest signed by the authors of timeouts for the specified round.
+    pub fn signed_digest(round: Round, epoch: EpochNumber, high_qc_round: Round) -> Digest {
         let mut hasher = Sha512::new();
-        hasher.update(self.round.to_le_bytes());
-        hasher.update(self.high_qc.round.to_le_bytes());
+        hasher.update(round.to_le_bytes());
+        hasher.update(epoch.to_le_bytes());
+        hasher.update(high_qc_round.to_le_bytes());
         Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
     }
 }
 
+impl Hash for Timeout {
+    fn digest(&self) -> Digest {
+        Self::signed_digest(self.round, self.epoch, self.high_qc.round)
+    }
+}
+
 impl fmt::Debug for Timeout {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "TV({}, {}, {:?})", self.author, self.round, self.high_qc)
@@ -283,44 +573,223 @@ impl fmt::Debug for Timeout {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TC {
     pub round: Round,
-    pub votes: Vec<(PublicKey, Signature, Round)>,
+    pub epoch: EpochNumber,
+    pub signers: Signers,
+    /// The round of the high QC of each signer (in the order of the signers).
+    pub high_qc_rounds: Vec<Round>,
+    pub signature: BlsSignature,
 }
 
 impl TC {
     pub fn verify(&self, committee: &Committee) -> ConsensusResult<()> {
-        // Ensure the QC has a quorum.
-        let mut weight = 0;
-        let mut used = HashSet::new();
-        for (name, _, _) in self.votes.iter() {
-            ensure!(!used.contains(name), ConsensusError::AuthorityReuse(*name));
-            let voting_rights = committee.stake(name);
-            ensure!(voting_rights > 0, ConsensusError::UnknownAuthority(*name));
-            used.insert(*name);
-            weight += voting_rights;
-        }
+        // Ensure the TC belongs to the current epoch.
+        ensure!(
+            self.epoch == committee.epoch,
+            ConsensusError::WrongEpoch {
+                expected: committee.epoch,
+                received: self.epoch
+            }
+        );
+
+        // Ensure the TC has a quorum.
+        let names = self.signers.names(committee)?;
+        ensure!(
+            names.len() == self.high_qc_rounds.len(),
+            ConsensusError::MalformedSigners
+        );
+        let weight: Stake = names.iter().map(|x| committee.stake(x)).sum();
         ensure!(
             weight >= committee.quorum_threshold(),
             ConsensusError::TCRequiresQuorum
         );
 
-        // Check the signatures.
-        for (author, signature, high_qc_round) in &self.votes {
-            let mut hasher = Sha512::new();
-            hasher.update(self.round.to_le_bytes());
-            hasher.update(high_qc_round.to_le_bytes());
-            let digest = Digest(hasher.finalize().as_slice()[..32].try_into().unwrap());
-            signature.verify(&digest, author)?;
+        // Check the aggregate signature. Signers with the same high QC signed the same digest.
+        let mut groups: Vec<(Round, Vec<_>)> = Vec::new();
+        for (name, high_qc_round) in names.iter().zip(self.high_qc_rounds.iter()) {
+            let key = committee
+                .bls_key(name)
+                .ok_or(ConsensusError::UnknownAuthority(*name))?;
+            match groups.iter_mut().find(|(r, _)| r == high_qc_round) {
+                Some((_, keys)) => keys.push(key),
+                None => groups.push((*high_qc_round, vec![key])),
+            }
+        }
+        let groups: Vec<_> = groups
+            .into_iter()
+            .map(|(r, keys)| (Timeout::signed_digest(self.round, self.epoch, r), keys))
+            .collect();
+        self.signature
+            .verify_aggregate_multi(&groups)
+            .map_err(ConsensusError::from)
+    }
+}
+
+impl fmt::Debug for TC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "TC({}, {:?})", self.round, self.high_qc_rounds)
+    }
+}
+
+/// Announces the committees of the next epoch. The current epoch ends with the block carrying
+/// the reconfiguration: once it is committed, all modules switch to the new committees.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Reconfiguration {
+    pub consensus: Committee,
+    pub mempool: MempoolCommittee,
+}
+
+impl Reconfiguration {
+    pub fn epoch(&self) -> EpochNumber {
+        self.consensus.epoch
+    }
+
+    pub fn verify(&self, committee: &Committee) -> ConsensusResult<()> {
+        // Ensure the reconfiguration announces the next epoch.
+        let epoch = self.epoch();
+        ensure!(
+            epoch == committee.epoch + 1 && self.mempool.epoch == epoch,
+            ConsensusError::InvalidReconfiguration(epoch)
+        );
+
+        // Ensure both committees are made of the same authorities, with the same stake.
+        ensure!(
+            self.consensus.size() == self.mempool.authorities.len()
+                && self
+                    .consensus
+                    .authorities
+                    .iter()
+                    .all(|(name, x)| x.stake > 0 && self.mempool.stake(name) == x.stake),
+            ConsensusError::InvalidReconfiguration(epoch)
+        );
+        Ok(())
+    }
+}
+
+impl Hash for Reconfiguration {
+    fn digest(&self) -> Digest {
+        // Hash the authorities in a deterministic order.
+        let mut hasher = Sha512::new();
+        hasher.update(self.epoch().to_le_bytes());
+        let mut names: Vec<_> = self.consensus.authorities.keys().collect();
+        names.sort();
+        for name in names {
+            hasher.update(name);
+            hasher.update(self.consensus.stake(name).to_le_bytes());
+            if let Some(x) = self.consensus.authorities.get(name) {
+                hasher.update(x.bls_key.encode_base64());
+                hasher.update(x.address.to_string());
+            }
+            if let Some(x) = self.mempool.authorities.get(name) {
+                hasher.update(x.transactions_address.to_string());
+                hasher.update(x.mempool_address.to_string());
+            }
+        }
+        Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+    }
+}
+
+impl PartialEq for Reconfiguration {
+    fn eq(&self, other: &Self) -> bool {
+        self.digest() == other.digest()
+    }
+}
+
+/// The requests served by the helpers of other authorities.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+pub enum Request {
+    /// The ancestors of a block (inclusive), down to a known round.
+    Sync(Digest, Round),
+    /// The latest checkpoint.
+    Checkpoint,
+}
+
+/// Distinguishes the requests of an authority: its epoch and round when it made the request, and
+/// a counter that never repeats (see `Nonces`). Helpers reject requests they already served, or
+/// that are too old (see `Helper`).
+#[derive(
+    Clone, Copy, Serialize, Deserialize, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub struct Nonce {
+    pub epoch: EpochNumber,
+    pub round: Round,
+    pub counter: u64,
+}
+
+/// A request signed by its origin and addressed to a single authority. Helpers charge the
+/// (authenticated) origin for serving it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SignedRequest {
+    pub request: Request,
+    pub origin: PublicKey,
+    pub recipient: PublicKey,
+    pub nonce: Nonce,
+    pub signature: Signature,
+}
+
+impl SignedRequest {
+    pub async fn new(
+        request: Request,
+        origin: PublicKey,
+        recipient: PublicKey,
+        nonce: Nonce,
+        mut signature_service: SignatureService,
+    ) -> Self {
+        let signed = Self {
+            request,
+            origin,
+            recipient,
+            nonce,
+            signature: Signature::default(),
+        };
+        let signature = signature_service.request_signature(signed.digest()).await;
+        Self {
+            signature,
+            ..signed
         }
+    }
+
+    pub fn verify(&self, committee: &Committee, name: &PublicKey) -> ConsensusResult<()> {
+        // Ensure the origin is in the committee.
+        ensure!(
+            committee.address(&self.origin).is_some(),
+            ConsensusError::UnknownAuthority(self.origin)
+        );
+
+        // Ensure the request is meant for us, so that it cannot be replayed to other authorities.
+        ensure!(
+            &self.recipient == name,
+            ConsensusError::MisaddressedRequest(self.recipient)
+        );
+
+        // Check the signature.
+        self.signature.verify(&self.digest(), &self.origin)?;
         Ok(())
     }
+}
 
-    pub fn high_qc_rounds(&self) -> Vec<Round> {
-        self.votes.iter().map(|(_, _, r)| r).cloned().collect()
+impl Hash for SignedRequest {
+    fn digest(&self) -> Digest {
+        let mut hasher = Sha512::new();
+        hasher.update(b"request");
+        match &self.request {
+            Request::Sync(digest, known_round) => {
+                hasher.update([0u8]);
+                hasher.update(digest);
+                hasher.update(known_round.to_le_bytes());
+            }
+            Request::Checkpoint => hasher.update([1u8]),
+        }
+        hasher.update(self.origin);
+        hasher.update(self.recipient);
+        hasher.update(self.nonce.epoch.to_le_bytes());
+        hasher.update(self.nonce.round.to_le_bytes());
+        hasher.update(self.nonce.counter.to_le_bytes());
+        Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
     }
 }
 
-impl fmt::Debug for TC {
+impl fmt::Debug for SignedRequest {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "TC({}, {:?})", self.round, self.high_qc_rounds())
+        write!(f, "{:?} from {}", self.request, self.origin)
     }
 }
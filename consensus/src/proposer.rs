@@ -1,51 +1,84 @@
-use crate::config::{Committee, Stake};
+use crate::builder::{Proposal, ProposalBuilder};
+use crate::config::{Byzantine, Committee, Stake};
 use crate::consensus::{ConsensusMessage, Round};
-use crate::messages::{Block, QC, TC};
+use crate::evidence::Evidence;
+use crate::messages::{Block, Reconfiguration, MAX_EVIDENCE, QC, TC};
 use bytes::Bytes;
-use crypto::{Digest, PublicKey, SignatureService};
+use crypto::{Digest, Hash as _, PublicKey, SignatureService};
 use futures::stream::futures_unordered::FuturesUnordered;
 use futures::stream::StreamExt as _;
 use log::{debug, info};
 use network::{CancelHandler, ReliableSender};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::watch;
+
+#[cfg(test)]
+#[path = "tests/proposer_tests.rs"]
+pub mod proposer_tests;
+
+/// The number of cleaned up payload digests we remember: the mempool may deliver a batch after the
+/// block proposing it, and a block proposing it again would be rejected (see `Core::validate`).
+pub const MAX_CLEANED_DIGESTS: usize = 10_000;
 
 #[derive(Debug)]
 pub enum ProposerMessage {
     Make(Round, QC, Option<TC>),
+    /// Make an empty block carrying the specified reconfiguration (if any). Blocks extending an
+    /// uncommitted reconfiguration must be empty, since they are discarded when the epoch ends.
+    Reconfigure(Round, QC, Option<TC>, Option<Reconfiguration>),
+    /// Include the evidence in our next block.
+    Evidence(Evidence),
+    /// Remove the specified payload digests and evidence (by digest) from our buffers.
     Cleanup(Vec<Digest>),
 }
 
-pub struct Proposer {
+pub struct Proposer<B: ProposalBuilder> {
     name: PublicKey,
-    committee: Committee,
+    rx_committee: watch::Receiver<Committee>,
     signature_service: SignatureService,
+    builder: B,
+    /// Receives the digests of the certified batches (the only ones we propose).
     rx_mempool: Receiver<Digest>,
     rx_message: Receiver<ProposerMessage>,
     tx_loopback: Sender<Block>,
-    buffer: HashSet<Digest>,
+    buffer: BTreeSet<Digest>,
+    evidence: BTreeMap<Digest, Evidence>,
+    /// The digests we recently cleaned up, and the order in which we did (oldest first).
+    cleaned: HashSet<Digest>,
+    cleaned_order: VecDeque<Digest>,
     network: ReliableSender,
+    #[cfg_attr(not(feature = "byzantine"), allow(dead_code))]
+    byzantine: Byzantine,
 }
 
-impl Proposer {
+impl<B: ProposalBuilder> Proposer<B> {
+    #[allow(clippy::too_many_arguments)]
     pub fn spawn(
         name: PublicKey,
-        committee: Committee,
+        rx_committee: watch::Receiver<Committee>,
         signature_service: SignatureService,
+        builder: B,
         rx_mempool: Receiver<Digest>,
         rx_message: Receiver<ProposerMessage>,
         tx_loopback: Sender<Block>,
+        byzantine: Byzantine,
     ) {
         tokio::spawn(async move {
             Self {
                 name,
-                committee,
+                rx_committee,
                 signature_service,
+                builder,
                 rx_mempool,
                 rx_message,
                 tx_loopback,
-                buffer: HashSet::new(),
+                buffer: BTreeSet::new(),
+                evidence: BTreeMap::new(),
+                cleaned: HashSet::new(),
+                cleaned_order: VecDeque::new(),
                 network: ReliableSender::new(),
+                byzantine,
             }
             .run()
             .await;
@@ -58,14 +91,28 @@ impl Proposer {
         deliver
     }
 
-    async fn make_block(&mut self, round: Round, qc: QC, tc: Option<TC>) {
+    async fn make_block(
+        &mut self,
+        round: Round,
+        qc: QC,
+        tc: Option<TC>,
+        proposal: Proposal,
+        reconfiguration: Option<Reconfiguration>,
+        evidence: Vec<Evidence>,
+    ) {
+        let committee = self.rx_committee.borrow().clone();
+
         // Generate a new block.
         let block = Block::new(
             qc,
             tc,
             self.name,
             round,
-            /* payload */ self.buffer.drain().collect(),
+            committee.epoch,
+            proposal.payload,
+            proposal.metadata,
+            reconfiguration,
+            evidence,
             self.signature_service.clone(),
         )
         .await;
@@ -83,18 +130,40 @@ impl Proposer {
 
         // Broadcast our new block.
         debug!("Broadcasting {:?}", block);
-        let (names, addresses): (Vec<_>, _) = self
-            .committee
+        let (names, addresses): (Vec<_>, _) = committee
             .broadcast_addresses(&self.name)
             .iter()
             .cloned()
             .unzip();
         let message = bincode::serialize(&ConsensusMessage::Propose(block.clone()))
             .expect("Failed to serialize block");
+        #[cfg(not(feature = "byzantine"))]
         let handles = self
             .network
             .broadcast(addresses, Bytes::from(message))
             .await;
+        #[cfg(feature = "byzantine")]
+        let handles = match self.make_twin(&block).await {
+            // Send our block to half of the committee and its twin to the other half.
+            Some(twin) => {
+                debug!("Equivocating with {:?}", twin);
+                let twin = bincode::serialize(&ConsensusMessage::Propose(twin))
+                    .expect("Failed to serialize block");
+                let mut addresses: Vec<_> = addresses;
+                let others = addresses.split_off(addresses.len() / 2);
+                let mut handles = self
+                    .network
+                    .broadcast(addresses, Bytes::from(message))
+                    .await;
+                handles.extend(self.network.broadcast(others, Bytes::from(twin)).await);
+                handles
+            }
+            None => {
+                self.network
+                    .broadcast(addresses, Bytes::from(message))
+                    .await
+            }
+        };
 
         // Send our block to the core for processing.
         self.tx_loopback
@@ -107,33 +176,110 @@ impl Proposer {
             .into_iter()
             .zip(handles.into_iter())
             .map(|(name, handler)| {
-                let stake = self.committee.stake(&name);
+                let stake = committee.stake(&name);
                 Self::waiter(handler, stake)
             })
             .collect();
 
-        let mut total_stake = self.committee.stake(&self.name);
+        let mut total_stake = committee.stake(&self.name);
         while let Some(stake) = wait_for_quorum.next().await {
             total_stake += stake;
-            if total_stake >= self.committee.quorum_threshold() {
+            if total_stake >= committee.quorum_threshold() {
                 break;
             }
         }
     }
 
+    /// Make a block conflicting with the specified one (at the same round), if we are set to
+    /// equivocate. The twin drops the last payload digest or, for empty blocks, toggles the
+    /// presence of the application metadata (both are part of the block's digest). The
+    /// application may reject such a twin, but it remains a valid equivocation.
+    #[cfg(feature = "byzantine")]
+    async fn make_twin(&mut self, block: &Block) -> Option<Block> {
+        if !self.byzantine.equivocate {
+            return None;
+        }
+        let mut twin = block.clone();
+        if twin.payload.pop().is_none() {
+            twin.metadata = match twin.metadata {
+                Some(_) => None,
+                None => Some(Vec::new()),
+            };
+        }
+        let twin = Block::new(
+            twin.qc,
+            twin.tc,
+            twin.author,
+            twin.round,
+            twin.epoch,
+            twin.payload,
+            twin.metadata,
+            twin.reconfiguration,
+            twin.evidence,
+            self.signature_service.clone(),
+        )
+        .await;
+        Some(twin)
+    }
+
+    /// Drain (up to `MAX_EVIDENCE` items of) the evidence buffer. We only keep the evidence
+    /// gathered during the current epoch since older evidence cannot be verified against the
+    /// current committee.
+    fn drain_evidence(&mut self) -> Vec<Evidence> {
+        let epoch = self.rx_committee.borrow().epoch;
+        self.evidence.retain(|_, x| x.epoch() == epoch);
+        let digests: Vec<_> = self.evidence.keys().take(MAX_EVIDENCE).cloned().collect();
+        digests
+            .iter()
+            .filter_map(|x| self.evidence.remove(x))
+            .collect()
+    }
+
+    /// Remember a cleaned up digest, forgetting the oldest one if needed.
+    fn record_cleaned(&mut self, digest: &Digest) {
+        if !self.cleaned.insert(digest.clone()) {
+            return;
+        }
+        self.cleaned_order.push_back(digest.clone());
+        if self.cleaned_order.len() > MAX_CLEANED_DIGESTS {
+            if let Some(oldest) = self.cleaned_order.pop_front() {
+                self.cleaned.remove(&oldest);
+            }
+        }
+    }
+
     async fn run(&mut self) {
         loop {
             tokio::select! {
                 Some(digest) = self.rx_mempool.recv() => {
+                    // Skip the batches already proposed by the blocks we processed.
+                    if self.cleaned.contains(&digest) {
+                        continue;
+                    }
                     //if self.buffer.len() < 155 {
                         self.buffer.insert(digest);
                     //}
                 },
                 Some(message) = self.rx_message.recv() => match message {
-                    ProposerMessage::Make(round, qc, tc) => self.make_block(round, qc, tc).await,
+                    ProposerMessage::Make(round, qc, tc) => {
+                        let candidates = self.buffer.iter().cloned().collect();
+                        let mut proposal = self.builder.build(round, candidates).await;
+                        proposal.payload.retain(|x| self.buffer.remove(x));
+                        let evidence = self.drain_evidence();
+                        self.make_block(round, qc, tc, proposal, None, evidence).await
+                    },
+                    ProposerMessage::Reconfigure(round, qc, tc, reconfiguration) => {
+                        let proposal = Proposal::default();
+                        self.make_block(round, qc, tc, proposal, reconfiguration, Vec::new()).await
+                    },
+                    ProposerMessage::Evidence(evidence) => {
+                        self.evidence.insert(evidence.digest(), evidence);
+                    },
                     ProposerMessage::Cleanup(digests) => {
                         for x in &digests {
                             self.buffer.remove(x);
+                            self.evidence.remove(x);
+                            self.record_cleaned(x);
                         }
                     }
                 }
@@ -0,0 +1,220 @@
+use crate::config::EpochNumber;
+use crate::consensus::Round;
+use crate::error::ConsensusResult;
+use crate::mempool::MempoolDriver;
+use crate::messages::Block;
+use crate::state::committed_key;
+use crypto::Digest;
+use crypto::Hash as _;
+use log::debug;
+use std::cmp::min;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryInto as _;
+use store::keys::HORIZON_KEY;
+use store::Store;
+
+#[cfg(test)]
+#[path = "tests/pruner_tests.rs"]
+pub mod pruner_tests;
+
+/// The prefix of the storage keys indexing the blocks we hold by position (see `index_key`).
+const BLOCKS_INDEX_PREFIX: &[u8] = b"consensus_blocks_index";
+
+/// The prefix of the storage keys indexing the markers of the batches we pruned, by the position
+/// of their block (see `MempoolDriver::prune`).
+const MARKERS_INDEX_PREFIX: &[u8] = b"consensus_markers_index";
+
+/// The position of a block in the chain (rounds restart with every epoch).
+pub type Position = (EpochNumber, Round);
+
+/// The storage key indexing a digest by position. Keys are ordered by position.
+fn index_key(prefix: &[u8], position: Position, digest: &Digest) -> Vec<u8> {
+    let (epoch, round) = position;
+    [
+        prefix,
+        &epoch.to_be_bytes(),
+        &round.to_be_bytes(),
+        &digest.0,
+    ]
+    .concat()
+}
+
+/// Parse a key made by `index_key`.
+fn parse_index_key(prefix: &[u8], key: &[u8]) -> Option<(Position, Digest)> {
+    let key = key.strip_prefix(prefix)?;
+    if key.len() != 16 + 8 + 32 {
+        return None;
+    }
+    let epoch = EpochNumber::from_be_bytes(key[..16].try_into().ok()?);
+    let round = Round::from_be_bytes(key[16..24].try_into().ok()?);
+    let digest = Digest(key[24..].try_into().ok()?);
+    Some(((epoch, round), digest))
+}
+
+/// Load our retention horizon from storage: we pruned the blocks preceding it (if any).
+pub async fn load_horizon(store: &mut Store) -> ConsensusResult<Option<Position>> {
+    match store.read(HORIZON_KEY.to_vec()).await? {
+        Some(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Deletes the blocks below the retention horizon from storage (forks included), along with the
+/// payload of the committed ones. It indexes the blocks we store by position, so that it also
+/// prunes the blocks stored before a restart. The markers of the pruned batches are deleted
+/// `retention` rounds later (or once the horizon reaches the next epoch).
+pub struct Pruner {
+    store: Store,
+    /// The number of committed rounds we keep (zero keeps the entire history).
+    retention: Round,
+    /// The blocks we hold, by position (mirrored in storage).
+    blocks: BTreeMap<Position, HashSet<Digest>>,
+    /// The batches we marked as pruned, by position of their block (mirrored in storage).
+    markers: BTreeMap<Position, Vec<Digest>>,
+    /// We pruned all the blocks below this position.
+    horizon: Position,
+}
+
+impl Pruner {
+    pub fn new(store: Store, retention: Round) -> Self {
+        Self {
+            store,
+            retention,
+            blocks: BTreeMap::new(),
+            markers: BTreeMap::new(),
+            horizon: Position::default(),
+        }
+    }
+
+    /// Track a block we stored.
+    pub async fn insert(&mut self, block: &Block) {
+        if self.retention == 0 {
+            return;
+        }
+        let position = (block.epoch, block.round);
+        let digest = block.digest();
+        let inserted = self
+            .blocks
+            .entry(position)
+            .or_insert_with(HashSet::new)
+            .insert(digest.clone());
+        if inserted {
+            let key = index_key(BLOCKS_INDEX_PREFIX, position, &digest);
+            self.store.write(key, Vec::new()).await;
+        }
+    }
+
+    /// Load our horizon and the blocks and markers we track (upon booting).
+    pub async fn load(&mut self) -> ConsensusResult<()> {
+        if self.retention == 0 {
+            return Ok(());
+        }
+        if let Some(horizon) = load_horizon(&mut self.store).await? {
+            self.horizon = horizon;
+        }
+        for (key, _) in self.store.scan(BLOCKS_INDEX_PREFIX.to_vec()).await? {
+            if let Some((position, digest)) = parse_index_key(BLOCKS_INDEX_PREFIX, &key) {
+                self.blocks
+                    .entry(position)
+                    .or_insert_with(HashSet::new)
+                    .insert(digest);
+            }
+        }
+        for (key, _) in self.store.scan(MARKERS_INDEX_PREFIX.to_vec()).await? {
+            if let Some((position, digest)) = parse_index_key(MARKERS_INDEX_PREFIX, &key) {
+                self.markers
+                    .entry(position)
+                    .or_insert_with(Vec::new)
+                    .push(digest);
+            }
+        }
+        Ok(())
+    }
+
+    /// Prune the blocks older than `retention` committed rounds (of the current epoch). We never
+    /// prune the blocks following the application's latest snapshot: it replays them upon
+    /// restart. Pruning thus requires checkpoints, and the blocks of the previous epochs are
+    /// pruned once the application snapshots the current one. Finally, let the mempool delete
+    /// the batches it stored below the horizon and that we did not commit.
+    pub async fn prune(
+        &mut self,
+        last_committed: Position,
+        last_snapshot: Position,
+        mempool_driver: &mut MempoolDriver,
+    ) -> ConsensusResult<()> {
+        if self.retention == 0 {
+            return Ok(());
+        }
+        let (epoch, round) = last_committed;
+        let horizon = min((epoch, round.saturating_sub(self.retention)), last_snapshot);
+        if horizon <= self.horizon {
+            return Ok(());
+        }
+
+        let kept = self.blocks.split_off(&horizon);
+        let pruned = std::mem::replace(&mut self.blocks, kept);
+        for (position, digests) in pruned {
+            for digest in digests {
+                self.prune_block(position, digest, mempool_driver).await?;
+            }
+        }
+
+        // Delete the markers of the batches pruned `retention` rounds ago.
+        let (epoch, round) = horizon;
+        let kept = self
+            .markers
+            .split_off(&(epoch, round.saturating_sub(self.retention)));
+        let expired = std::mem::replace(&mut self.markers, kept);
+        for (position, digests) in expired {
+            for digest in &digests {
+                let key = index_key(MARKERS_INDEX_PREFIX, position, digest);
+                self.store.delete(key).await;
+            }
+            mempool_driver.unmark(&digests).await;
+        }
+        mempool_driver.prune_stale(horizon).await;
+
+        // Persist the horizon: it lets us tell apart the blocks we pruned from those we never
+        // stored (see `Helper`).
+        debug!(
+            "Pruned blocks below round {} of epoch {}",
+            horizon.1, horizon.0
+        );
+        self.horizon = horizon;
+        let value = bincode::serialize(&horizon).expect("Failed to serialize horizon");
+        self.store.write(HORIZON_KEY.to_vec(), value).await;
+        Ok(())
+    }
+
+    async fn prune_block(
+        &mut self,
+        position: Position,
+        digest: Digest,
+        mempool_driver: &mut MempoolDriver,
+    ) -> ConsensusResult<()> {
+        let index = index_key(BLOCKS_INDEX_PREFIX, position, &digest);
+        self.store.delete(index).await;
+        let block: Block = match self.store.read(digest.to_vec()).await? {
+            Some(bytes) => bincode::deserialize(&bytes)?,
+            None => return Ok(()),
+        };
+
+        // Only the batches of committed blocks are final: the others may be proposed again (the
+        // mempool deletes them if they are not committed, see `MempoolDriver::prune_stale`).
+        let key = committed_key(&digest);
+        if self.store.read(key.clone()).await?.is_some() {
+            mempool_driver.prune(&block).await;
+            for batch in &block.payload {
+                let key = index_key(MARKERS_INDEX_PREFIX, position, batch);
+                self.store.write(key, Vec::new()).await;
+            }
+            self.markers
+                .entry(position)
+                .or_insert_with(Vec::new)
+                .extend(block.payload.iter().cloned());
+            self.store.delete(key).await;
+        }
+        self.store.delete(digest.to_vec()).await;
+        Ok(())
+    }
+}
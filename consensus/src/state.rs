@@ -0,0 +1,72 @@
+use crate::consensus::Round;
+use crate::error::ConsensusResult;
+use crate::messages::{Reconfiguration, Timeout, QC};
+use crypto::Digest;
+use serde::{Deserialize, Serialize};
+use store::keys::STATE_KEY;
+use store::Store;
+
+#[cfg(test)]
+#[path = "tests/state_tests.rs"]
+pub mod state_tests;
+
+/// The storage key of the proof that the specified committed block is final (see
+/// `CommittedBlock`). It lets us re-deliver committed blocks along with their proofs.
+pub fn committed_key(digest: &Digest) -> Vec<u8> {
+    [b"committed".to_vec(), digest.to_vec()].concat()
+}
+
+/// The part of the core's state that must survive a crash. A node reloading this state upon
+/// restart cannot sign votes or timeouts contradicting the ones it released before crashing.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ConsensusState {
+    pub round: Round,
+    pub last_voted_round: Round,
+    /// The round of the block we are locked on (only used by the 3-chain commit rule).
+    pub locked_round: Round,
+    pub last_committed_round: Round,
+    /// The digest of the last committed block (the default digest if we did not commit yet).
+    pub last_committed: Digest,
+    pub high_qc: QC,
+    /// The digest of the block that ended the previous epoch (the default digest in the first
+    /// epoch). The genesis QC of the current epoch must point to it.
+    pub epoch_start: Digest,
+    /// The last committed reconfiguration (if any). It is re-applied upon restart since the
+    /// committees of the configuration files are those of the first epoch.
+    pub reconfiguration: Option<Reconfiguration>,
+    /// The last timeout we signed (if any). We re-send it if we time out again at its round.
+    pub last_timeout: Option<Timeout>,
+}
+
+impl Default for ConsensusState {
+    fn default() -> Self {
+        Self {
+            round: 1,
+            last_voted_round: 0,
+            locked_round: 0,
+            last_committed_round: 0,
+            last_committed: Digest::default(),
+            high_qc: QC::genesis(),
+            epoch_start: Digest::default(),
+            reconfiguration: None,
+            last_timeout: None,
+        }
+    }
+}
+
+impl ConsensusState {
+    /// Load the state from storage (if any).
+    pub async fn load(store: &mut Store) -> ConsensusResult<Option<Self>> {
+        match store.read(STATE_KEY.to_vec()).await? {
+            Some(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Write the state to storage. This function only returns once the state is on disk.
+    pub async fn persist(&self, store: &mut Store) -> ConsensusResult<()> {
+        let value = bincode::serialize(self).expect("Failed to serialize consensus state");
+        store.sync_write(STATE_KEY.to_vec(), value).await?;
+        Ok(())
+    }
+}
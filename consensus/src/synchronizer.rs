@@ -1,18 +1,21 @@
-use crate::config::Committee;
-use crate::consensus::{ConsensusMessage, CHANNEL_CAPACITY};
+use crate::config::{Committee, EpochNumber};
+use crate::consensus::{ConsensusMessage, Round, CHANNEL_CAPACITY};
 use crate::error::ConsensusResult;
-use crate::messages::{Block, QC};
+use crate::messages::{Block, Nonce, Request, SignedRequest};
+use crate::pruner::Position;
 use bytes::Bytes;
 use crypto::Hash as _;
-use crypto::{Digest, PublicKey};
+use crypto::{Digest, PublicKey, SignatureService};
 use futures::stream::futures_unordered::FuturesUnordered;
 use futures::stream::StreamExt as _;
-use log::{debug, error};
+use log::{debug, error, warn};
 use network::SimpleSender;
-use std::collections::{HashMap, HashSet};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+use store::keys::NONCE_KEY;
 use store::Store;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::watch;
 use tokio::time::{sleep, Duration, Instant};
 
 #[cfg(test)]
@@ -21,53 +24,170 @@ pub mod synchronizer_tests;
 
 const TIMER_ACCURACY: u64 = 5_000;
 
+/// The number of nonces we reserve at once (see `Nonces`).
+const NONCES_RESERVATION: u64 = 1_000;
+
+/// Tags our requests (see `Nonce`). All our requests go through the synchronizer, so that their
+/// counters never repeat within a round. We persist a bound on the counters we used (reserving
+/// `NONCES_RESERVATION` of them at a time): after a crash, we resume above it rather than reusing
+/// nonces that the helpers would reject as replays.
+pub struct Nonces {
+    store: Store,
+    nonce: Nonce,
+    /// The counters up to this one are reserved.
+    reserved: u64,
+}
+
+impl Nonces {
+    pub async fn load(mut store: Store) -> ConsensusResult<Self> {
+        let reserved = match store.read(NONCE_KEY.to_vec()).await? {
+            Some(bytes) => bincode::deserialize(&bytes)?,
+            None => 0,
+        };
+        let nonce = Nonce {
+            counter: reserved,
+            ..Nonce::default()
+        };
+        Ok(Self {
+            store,
+            nonce,
+            reserved,
+        })
+    }
+
+    /// Tag our next requests with our latest position.
+    pub fn advance(&mut self, (epoch, round): Position) {
+        self.nonce.epoch = epoch;
+        self.nonce.round = round;
+    }
+
+    pub async fn next(&mut self) -> Nonce {
+        self.nonce.counter += 1;
+        if self.nonce.counter > self.reserved {
+            self.reserved += NONCES_RESERVATION;
+            let bytes = bincode::serialize(&self.reserved).expect("Failed to serialize nonces");
+            self.store.write(NONCE_KEY.to_vec(), bytes).await;
+        }
+        self.nonce
+    }
+}
+
+#[derive(Debug)]
+enum SynchronizerMessage {
+    /// Ask the author of the block for its missing ancestors, down to the specified round.
+    Sync(Block, Round, Position),
+    /// Send the request to all the other authorities.
+    Broadcast(Request, Position),
+}
+
 pub struct Synchronizer {
     store: Store,
-    inner_channel: Sender<Block>,
+    inner_channel: Sender<SynchronizerMessage>,
+    /// The round of our last committed block: we hold all the blocks up to this round.
+    committed_round: Round,
+    /// Our current epoch and round, tagging our requests (see `Nonce`).
+    position: Position,
 }
 
 impl Synchronizer {
     pub fn new(
         name: PublicKey,
-        committee: Committee,
+        signature_service: SignatureService,
+        rx_committee: watch::Receiver<Committee>,
         store: Store,
+        rx_reply: Receiver<Vec<Block>>,
         tx_loopback: Sender<Block>,
         sync_retry_delay: u64,
     ) -> Self {
         let mut network = SimpleSender::new();
-        let (tx_inner, mut rx_inner): (_, Receiver<Block>) = channel(CHANNEL_CAPACITY);
+        let (tx_inner, mut rx_inner): (_, Receiver<SynchronizerMessage>) =
+            channel(CHANNEL_CAPACITY);
+        let mut rx_reply = rx_reply;
 
-        let store_copy = store.clone();
+        let mut store_copy = store.clone();
         tokio::spawn(async move {
             let mut waiting = FuturesUnordered::new();
             let mut pending = HashSet::new();
-            let mut requests = HashMap::new();
+            let mut requests = BTreeMap::new();
+            let mut nonces = Nonces::load(store_copy.clone())
+                .await
+                .expect("Failed to load nonces");
 
             let timer = sleep(Duration::from_millis(TIMER_ACCURACY));
             tokio::pin!(timer);
             loop {
                 tokio::select! {
-                    Some(block) = rx_inner.recv() => {
-                        if pending.insert(block.digest()) {
-                            let parent = block.parent().clone();
-                            let author = block.author;
-                            let fut = Self::waiter(store_copy.clone(), parent.clone(), block);
-                            waiting.push(fut);
-
-                            if !requests.contains_key(&parent){
-                                debug!("Requesting sync for block {}", parent);
-                                let now = SystemTime::now()
-                                    .duration_since(UNIX_EPOCH)
-                                    .expect("Failed to measure time")
-                                    .as_millis();
-                                requests.insert(parent.clone(), now);
-                                let address = committee
-                                    .address(&author)
-                                    .expect("Author of valid block is not in the committee");
-                                let message = ConsensusMessage::SyncRequest(parent, name);
-                                let message = bincode::serialize(&message)
-                                    .expect("Failed to serialize sync request");
-                                network.send(address, Bytes::from(message)).await;
+                    Some(message) = rx_inner.recv() => match message {
+                        SynchronizerMessage::Sync(block, known_round, position) => {
+                            nonces.advance(position);
+                            if pending.insert(block.digest()) {
+                                let parent = block.parent().clone();
+                                let author = block.author;
+                                let fut = Self::waiter(store_copy.clone(), parent.clone(), block);
+                                waiting.push(fut);
+
+                                if !requests.contains_key(&parent){
+                                    // Ask for all the missing ancestors at once.
+                                    // The committee may have changed since we verified the block.
+                                    let Some(address) = rx_committee.borrow().address(&author) else {
+                                        warn!("Cannot sync with {}: not in the committee", author);
+                                        continue;
+                                    };
+                                    debug!("Requesting sync for block {} down to round {}", parent, known_round);
+                                    requests.insert(parent.clone(), (Instant::now(), known_round));
+                                    let request = Request::Sync(parent, known_round);
+                                    Self::send_request(
+                                        &mut network,
+                                        &signature_service,
+                                        name,
+                                        &mut nonces,
+                                        request,
+                                        vec![(author, address)],
+                                    ).await;
+                                }
+                            }
+                        },
+                        SynchronizerMessage::Broadcast(request, position) => {
+                            nonces.advance(position);
+                            let recipients = rx_committee.borrow().broadcast_addresses(&name);
+                            Self::send_request(
+                                &mut network,
+                                &signature_service,
+                                name,
+                                &mut nonces,
+                                request,
+                                recipients,
+                            ).await;
+                        }
+                    },
+                    Some(chunk) = rx_reply.recv() => {
+                        let committee = rx_committee.borrow().clone();
+                        let ancestors = match Self::check_chunk(chunk, &requests, &committee) {
+                            Ok(x) => x,
+                            Err(e) => {
+                                warn!("Received invalid sync reply: {}", e);
+                                continue;
+                            }
+                        };
+
+                        // We are now waiting for the parent of the oldest ancestor of the chunk
+                        // (unless we already have it).
+                        if let (Some(first), Some(last)) = (ancestors.first(), ancestors.last()) {
+                            if let Some((_, known_round)) = requests.remove(&first.digest()) {
+                                let parent = last.parent().clone();
+                                let missing = !last.qc.is_genesis()
+                                    && last.round > known_round + 1
+                                    && matches!(store_copy.read(parent.to_vec()).await, Ok(None));
+                                if missing {
+                                    requests.insert(parent, (Instant::now(), known_round));
+                                }
+                            }
+                        }
+
+                        // Deliver the ancestors to the core, starting from the oldest one.
+                        for block in ancestors.into_iter().rev() {
+                            if let Err(e) = tx_loopback.send(block).await {
+                                panic!("Failed to send message through core channel: {}", e);
                             }
                         }
                     },
@@ -83,22 +203,19 @@ impl Synchronizer {
                     },
                     () = &mut timer => {
                         // This implements the 'perfect point to point link' abstraction.
-                        for (digest, timestamp) in &requests {
-                            let now = SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .expect("Failed to measure time")
-                                .as_millis();
-                            if timestamp + (sync_retry_delay as u128) < now {
+                        for (digest, (timestamp, known_round)) in &requests {
+                            if timestamp.elapsed() > Duration::from_millis(sync_retry_delay) {
                                 debug!("Requesting sync for block {} (retry)", digest);
-                                let addresses = committee
-                                    .broadcast_addresses(&name)
-                                    .into_iter()
-                                    .map(|(_, x)| x)
-                                    .collect();
-                                let message = ConsensusMessage::SyncRequest(digest.clone(), name);
-                                let message = bincode::serialize(&message)
-                                    .expect("Failed to serialize sync request");
-                                network.broadcast(addresses, Bytes::from(message)).await;
+                                let recipients = rx_committee.borrow().broadcast_addresses(&name);
+                                let request = Request::Sync(digest.clone(), *known_round);
+                                Self::send_request(
+                                    &mut network,
+                                    &signature_service,
+                                    name,
+                                    &mut nonces,
+                                    request,
+                                    recipients,
+                                ).await;
                             }
                         }
                         timer.as_mut().reset(Instant::now() + Duration::from_millis(TIMER_ACCURACY));
@@ -109,23 +226,95 @@ impl Synchronizer {
         Self {
             store,
             inner_channel: tx_inner,
+            committed_round: 0,
+            position: (0, 0),
+        }
+    }
+
+    /// Send a request to each of the specified authorities. We sign a distinct request for each
+    /// of them, so that they cannot replay it to one another.
+    async fn send_request(
+        network: &mut SimpleSender,
+        signature_service: &SignatureService,
+        name: PublicKey,
+        nonces: &mut Nonces,
+        request: Request,
+        recipients: Vec<(PublicKey, SocketAddr)>,
+    ) {
+        for (recipient, address) in recipients {
+            let signed = SignedRequest::new(
+                request.clone(),
+                name,
+                recipient,
+                nonces.next().await,
+                signature_service.clone(),
+            )
+            .await;
+            let message = bincode::serialize(&ConsensusMessage::Request(signed))
+                .expect("Failed to serialize sync request");
+            network.send(address, Bytes::from(message)).await;
         }
     }
 
+    /// Check a chunk of ancestors (ordered from the most recent one). It must start with a block
+    /// we requested, and each block must be the parent of the one before it. We keep the longest
+    /// valid prefix of the chunk.
+    fn check_chunk(
+        chunk: Vec<Block>,
+        requests: &BTreeMap<Digest, (Instant, Round)>,
+        committee: &Committee,
+    ) -> ConsensusResult<Vec<Block>> {
+        let mut ancestors: Vec<Block> = Vec::new();
+        for block in chunk {
+            let digest = block.digest();
+            let linked = match ancestors.last() {
+                Some(child) => child.parent() == &digest,
+                None => requests.contains_key(&digest),
+            };
+            if !linked {
+                break;
+            }
+            block.verify(committee)?;
+            ancestors.push(block);
+        }
+        Ok(ancestors)
+    }
+
     async fn waiter(mut store: Store, wait_on: Digest, deliver: Block) -> ConsensusResult<Block> {
         let _ = store.notify_read(wait_on.to_vec()).await?;
         Ok(deliver)
     }
 
+    /// Notify the synchronizer of our last committed round: when we miss a block, we ask for
+    /// all its ancestors down to this round.
+    pub fn cleanup(&mut self, committed_round: Round) {
+        self.committed_round = committed_round;
+    }
+
+    /// Notify the synchronizer of our current epoch and round: they tag our requests.
+    pub fn advance(&mut self, epoch: EpochNumber, round: Round) {
+        self.position = (epoch, round);
+    }
+
+    /// Send a request to all the other authorities.
+    pub async fn broadcast(&mut self, request: Request) {
+        let message = SynchronizerMessage::Broadcast(request, self.position);
+        if let Err(e) = self.inner_channel.send(message).await {
+            panic!("Failed to send request to synchronizer: {}", e);
+        }
+    }
+
     pub async fn get_parent_block(&mut self, block: &Block) -> ConsensusResult<Option<Block>> {
-        if block.qc == QC::genesis() {
+        if block.qc.is_genesis() {
             return Ok(Some(Block::genesis()));
         }
         let parent = block.parent();
         match self.store.read(parent.to_vec()).await? {
             Some(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
             None => {
-                if let Err(e) = self.inner_channel.send(block.clone()).await {
+                let message =
+                    SynchronizerMessage::Sync(block.clone(), self.committed_round, self.position);
+                if let Err(e) = self.inner_channel.send(message).await {
                     panic!("Failed to send request to synchronizer: {}", e);
                 }
                 Ok(None)
@@ -141,10 +330,11 @@ impl Synchronizer {
             Some(b) => b,
             None => return Ok(None),
         };
-        let b0 = self
-            .get_parent_block(&b1)
-            .await?
-            .expect("We should have all ancestors of delivered blocks");
+        // We may miss the parent of the block we started from (see `Checkpoint`).
+        let b0 = match self.get_parent_block(&b1).await? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
         Ok(Some((b0, b1)))
     }
 }
@@ -1,5 +1,6 @@
 use super::*;
 use crate::common::{committee, keys, qc, vote};
+use crypto::Digest;
 
 #[test]
 fn add_vote() {
@@ -39,6 +40,41 @@ fn make_qc() {
     }
 }
 
+#[test]
+fn detect_double_vote() {
+    let mut aggregator = Aggregator::new(committee());
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let result = aggregator.add_vote(vote());
+    assert!(result.is_ok());
+
+    // Vote for another block at the same round.
+    let vote = Vote::new_from_key(Digest::default(), 1, public_key, &secret_key);
+    match aggregator.add_vote(vote) {
+        Err(ConsensusError::Equivocation(evidence)) => {
+            assert!(evidence.verify(&committee()).is_ok())
+        }
+        _ => panic!("Failed to detect double vote"),
+    }
+}
+
+#[test]
+fn detect_double_timeout() {
+    let mut aggregator = Aggregator::new(committee());
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let timeout = Timeout::new_from_key(QC::genesis(), 2, public_key, &secret_key);
+    let result = aggregator.add_timeout(timeout);
+    assert!(result.is_ok());
+
+    // Time out the same round with another high QC.
+    let timeout = Timeout::new_from_key(qc(), 2, public_key, &secret_key);
+    match aggregator.add_timeout(timeout) {
+        Err(ConsensusError::Equivocation(evidence)) => {
+            assert!(evidence.verify(&committee()).is_ok())
+        }
+        _ => panic!("Failed to detect double timeout"),
+    }
+}
+
 #[test]
 fn cleanup() {
     let mut aggregator = Aggregator::new(committee());
@@ -54,3 +90,43 @@ fn cleanup() {
     assert!(aggregator.votes_aggregators.is_empty());
     assert!(aggregator.timeouts_aggregators.is_empty());
 }
+
+#[test]
+fn reject_far_future_vote() {
+    let mut aggregator = Aggregator::new(committee());
+    let (public_key, secret_key) = keys().pop().unwrap();
+
+    // Add a vote for a round too far in the future.
+    let round = MAX_ROUND_LOOKAHEAD + 1;
+    let vote = Vote::new_from_key(Digest::default(), round, public_key, &secret_key);
+    match aggregator.add_vote(vote) {
+        Err(ConsensusError::MessageRejected { rejected, .. }) => assert_eq!(rejected, 1),
+        _ => panic!("Failed to reject vote"),
+    }
+    assert!(aggregator.votes_aggregators.is_empty());
+}
+
+#[test]
+fn bound_pending_per_authority() {
+    let mut aggregator = Aggregator::new(committee());
+    let (public_key, secret_key) = keys().pop().unwrap();
+
+    // Fill the quota of the authority.
+    for round in 1..=MAX_PENDING_PER_AUTHORITY as Round {
+        let vote = Vote::new_from_key(Digest::default(), round, public_key, &secret_key);
+        assert!(aggregator.add_vote(vote).is_ok());
+    }
+
+    // Ensure the next timeout is rejected.
+    let round = MAX_PENDING_PER_AUTHORITY as Round + 1;
+    let timeout = Timeout::new_from_key(QC::genesis(), round, public_key, &secret_key);
+    match aggregator.add_timeout(timeout.clone()) {
+        Err(ConsensusError::MessageRejected { .. }) => (),
+        _ => panic!("Failed to reject timeout"),
+    }
+
+    // Cleaning up past rounds releases the quota.
+    aggregator.cleanup(&round);
+    assert!(aggregator.add_timeout(timeout).is_ok());
+    assert_eq!(aggregator.pending.get(&public_key), Some(&1));
+}
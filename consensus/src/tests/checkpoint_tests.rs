@@ -0,0 +1,48 @@
+use super::*;
+use crate::common::{chain, committee, keys};
+use crate::messages::QC;
+use crypto::Hash as _;
+
+#[test]
+fn verify_valid_checkpoint() {
+    // Checkpoint the first block of a chain, and attest its snapshot with the second block.
+    let chain = chain(keys());
+    let checkpoint = Checkpoint {
+        committed: CommittedBlock {
+            block: chain[0].clone(),
+            descendants: vec![chain[1].clone(), chain[2].clone()],
+            qc: chain[3].qc.clone(),
+        },
+        snapshot: Some(b"state".to_vec()),
+        attestation: Some(CommittedBlock {
+            block: chain[1].clone(),
+            descendants: vec![chain[2].clone(), chain[3].clone()],
+            qc: QC::new_from_keys(chain[3].digest(), chain[3].round, &keys()),
+        }),
+    };
+    assert!(checkpoint
+        .verify(&committee(), CommitRule::ThreeChain)
+        .is_ok());
+}
+
+#[test]
+fn verify_checkpoint_uncertified_attestation() {
+    // Attest the snapshot with a block that is not committed.
+    let chain = chain(keys());
+    let checkpoint = Checkpoint {
+        committed: CommittedBlock {
+            block: chain[0].clone(),
+            descendants: vec![chain[1].clone(), chain[2].clone()],
+            qc: chain[3].qc.clone(),
+        },
+        snapshot: Some(b"state".to_vec()),
+        attestation: Some(CommittedBlock {
+            block: chain[3].clone(),
+            descendants: Vec::new(),
+            qc: QC::genesis(),
+        }),
+    };
+    assert!(checkpoint
+        .verify(&committee(), CommitRule::ThreeChain)
+        .is_err());
+}
@@ -1,11 +1,14 @@
 use crate::config::Committee;
 use crate::consensus::Round;
-use crate::messages::{Block, Timeout, Vote, QC};
+use crate::messages::{Block, Reconfiguration, Signers, Timeout, Vote, QC};
 use bytes::Bytes;
 use crypto::Hash as _;
-use crypto::{generate_keypair, Digest, PublicKey, SecretKey, Signature};
+use crypto::{
+    derive_bls_keypair, generate_keypair, BlsSignature, Digest, PublicKey, SecretKey, Signature,
+};
 use futures::sink::SinkExt as _;
 use futures::stream::StreamExt as _;
+use mempool::Committee as MempoolCommittee;
 use rand::rngs::StdRng;
 use rand::SeedableRng as _;
 use std::net::SocketAddr;
@@ -25,10 +28,11 @@ pub fn committee() -> Committee {
         keys()
             .into_iter()
             .enumerate()
-            .map(|(i, (name, _))| {
+            .map(|(i, (name, secret))| {
                 let address = format!("127.0.0.1:{}", i).parse().unwrap();
                 let stake = 1;
-                (name, stake, address)
+                let (bls_key, _) = derive_bls_keypair(&secret);
+                (name, stake, bls_key, address)
             })
             .collect(),
         /* epoch */ 100,
@@ -45,6 +49,21 @@ pub fn committee_with_base_port(base_port: u16) -> Committee {
     committee
 }
 
+// Fixture.
+pub fn reconfiguration() -> Reconfiguration {
+    let mut consensus = committee();
+    consensus.epoch += 1;
+    let mempool = MempoolCommittee::new(
+        consensus
+            .authorities
+            .iter()
+            .map(|(name, x)| (*name, x.stake, x.address, x.address))
+            .collect(),
+        consensus.epoch,
+    );
+    Reconfiguration { consensus, mempool }
+}
+
 impl Block {
     pub fn new_from_key(
         qc: QC,
@@ -58,7 +77,11 @@ impl Block {
             tc: None,
             author,
             round,
+            epoch: committee().epoch,
             payload,
+            metadata: None,
+            reconfiguration: None,
+            evidence: Vec::new(),
             signature: Signature::default(),
         };
         let signature = Signature::new(&block.digest(), secret);
@@ -77,10 +100,12 @@ impl Vote {
         let vote = Self {
             hash,
             round,
+            epoch: committee().epoch,
             author,
-            signature: Signature::default(),
+            signature: BlsSignature::default(),
         };
-        let signature = Signature::new(&vote.digest(), &secret);
+        let (_, bls_secret) = derive_bls_keypair(secret);
+        let signature = BlsSignature::new(&vote.digest(), &bls_secret);
         Self { signature, ..vote }
     }
 }
@@ -96,10 +121,12 @@ impl Timeout {
         let timeout = Self {
             high_qc,
             round,
+            epoch: committee().epoch,
             author,
-            signature: Signature::default(),
+            signature: BlsSignature::default(),
         };
-        let signature = Signature::new(&timeout.digest(), &secret);
+        let (_, bls_secret) = derive_bls_keypair(secret);
+        let signature = BlsSignature::new(&timeout.digest(), &bls_secret);
         Self {
             signature,
             ..timeout
@@ -113,6 +140,27 @@ impl PartialEq for Timeout {
     }
 }
 
+impl QC {
+    pub fn new_from_keys(hash: Digest, round: Round, keys: &[(PublicKey, SecretKey)]) -> Self {
+        let qc = QC {
+            hash,
+            round,
+            epoch: committee().epoch,
+            ..QC::default()
+        };
+        let digest = qc.digest();
+        let signatures: Vec<_> = keys
+            .iter()
+            .map(|(_, secret)| BlsSignature::new(&digest, &derive_bls_keypair(secret).1))
+            .collect();
+        QC {
+            signers: Signers::new(keys.iter().map(|(name, _)| name), &committee()),
+            signature: BlsSignature::aggregate(&signatures).unwrap(),
+            ..qc
+        }
+    }
+}
+
 // Fixture.
 pub fn block() -> Block {
     let (public_key, secret_key) = keys().pop().unwrap();
@@ -127,20 +175,8 @@ pub fn vote() -> Vote {
 
 // Fixture.
 pub fn qc() -> QC {
-    let qc = QC {
-        hash: Digest::default(),
-        round: 1,
-        votes: Vec::new(),
-    };
-    let digest = qc.digest();
-    let mut keys = keys();
-    let votes: Vec<_> = (0..3)
-        .map(|_| {
-            let (public_key, secret_key) = keys.pop().unwrap();
-            (public_key, Signature::new(&digest, &secret_key))
-        })
-        .collect();
-    QC { votes, ..qc }
+    let keys: Vec<_> = keys().into_iter().skip(1).collect();
+    QC::new_from_keys(Digest::default(), 1, &keys)
 }
 
 // Fixture.
@@ -160,17 +196,7 @@ pub fn chain(keys: Vec<(PublicKey, SecretKey)>) -> Vec<Block> {
             );
 
             // Make a qc for that block (it will be used for the next block).
-            let qc = QC {
-                hash: block.digest(),
-                round: block.round,
-                votes: Vec::new(),
-            };
-            let digest = qc.digest();
-            let votes: Vec<_> = keys
-                .iter()
-                .map(|(public_key, secret_key)| (*public_key, Signature::new(&digest, secret_key)))
-                .collect();
-            latest_qc = QC { votes, ..qc };
+            latest_qc = QC::new_from_keys(block.digest(), block.round, &keys);
 
             // Return the block.
             block
@@ -1,6 +1,9 @@
 use super::*;
+use crate::builder::ProposeAll;
 use crate::common::{committee_with_base_port, keys};
 use crate::config::Parameters;
+use crate::leader::RRLeaderElector;
+use crate::validator::AcceptAll;
 use crypto::SecretKey;
 use futures::future::try_join_all;
 use std::fs;
@@ -18,15 +21,21 @@ fn spawn_nodes(
             let committee = committee.clone();
             let parameters = Parameters {
                 timeout_delay: 100,
+                max_timeout_delay: 1_000,
                 ..Parameters::default()
             };
             let store_path = format!("{}_{}", store_path, i);
             let _ = fs::remove_dir_all(&store_path);
             let store = Store::new(&store_path).unwrap();
             let signature_service = SignatureService::new(secret);
+            let leader_elector = RRLeaderElector::new(committee.clone());
             let (tx_consensus_to_mempool, mut rx_consensus_to_mempool) = channel(10);
             let (_tx_mempool_to_consensus, rx_mempool_to_consensus) = channel(1);
+            let (_tx_reconfiguration, rx_reconfiguration) = channel(1);
+            let (_tx_snapshot, rx_snapshot) = channel(1);
             let (tx_commit, mut rx_commit) = channel(1);
+            let (tx_evidence, _rx_evidence) = channel(1);
+            let (tx_checkpoint, _rx_checkpoint) = channel(1);
 
             // Sink the mempool channel.
             tokio::spawn(async move {
@@ -43,12 +52,20 @@ fn spawn_nodes(
                     parameters,
                     signature_service,
                     store,
+                    leader_elector,
+                    AcceptAll,
+                    ProposeAll,
                     rx_mempool_to_consensus,
                     tx_consensus_to_mempool,
+                    rx_reconfiguration,
+                    rx_snapshot,
                     tx_commit,
+                    tx_evidence,
+                    tx_checkpoint,
+                    /* last_applied */ None,
                 );
 
-                rx_commit.recv().await.unwrap()
+                rx_commit.recv().await.unwrap().block
             })
         })
         .collect()
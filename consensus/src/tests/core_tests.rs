@@ -1,9 +1,14 @@
 use super::*;
-use crate::common::{chain, committee, committee_with_base_port, keys, listener};
-use crypto::SecretKey;
+use crate::common::{chain, committee, committee_with_base_port, keys, listener, reconfiguration};
+use crate::leader::RRLeaderElector;
+use crate::validator::AcceptAll;
+use async_trait::async_trait;
+use crypto::{SecretKey, Signature};
 use futures::future::try_join_all;
+use mempool::{Batch, Certificate, MempoolMessage};
 use std::fs;
 use tokio::sync::mpsc::channel;
+use tokio::time::{timeout, Duration};
 
 fn core(
     name: PublicKey,
@@ -13,23 +18,70 @@ fn core(
 ) -> (
     Sender<ConsensusMessage>,
     Receiver<ProposerMessage>,
-    Receiver<Block>,
+    Receiver<CommittedBlock>,
+) {
+    core_with_commit_rule(name, secret, committee, store_path, CommitRule::TwoChain)
+}
+
+fn core_with_commit_rule(
+    name: PublicKey,
+    secret: SecretKey,
+    committee: Committee,
+    store_path: &str,
+    commit_rule: CommitRule,
+) -> (
+    Sender<ConsensusMessage>,
+    Receiver<ProposerMessage>,
+    Receiver<CommittedBlock>,
+) {
+    let _ = fs::remove_dir_all(store_path);
+    let store = Store::new(store_path).unwrap();
+    let (_, rx_reconfiguration) = channel(1);
+    core_with_store(
+        name,
+        secret,
+        committee,
+        store,
+        commit_rule,
+        rx_reconfiguration,
+        None,
+        AcceptAll,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn core_with_store(
+    name: PublicKey,
+    secret: SecretKey,
+    committee: Committee,
+    store: Store,
+    commit_rule: CommitRule,
+    rx_reconfiguration: Receiver<Reconfiguration>,
+    last_applied: Option<Digest>,
+    validator: impl ProposalValidator,
+) -> (
+    Sender<ConsensusMessage>,
+    Receiver<ProposerMessage>,
+    Receiver<CommittedBlock>,
 ) {
     let (tx_core, rx_core) = channel(1);
     let (tx_loopback, rx_loopback) = channel(1);
     let (tx_proposer, rx_proposer) = channel(1);
     let (tx_mempool, mut rx_mempool) = channel(1);
     let (tx_commit, rx_commit) = channel(1);
+    let (tx_evidence, _) = channel(1);
+    let (tx_checkpoint, _) = channel(1);
+    let (tx_committee, _) = watch::channel(committee.clone());
 
     let signature_service = SignatureService::new(secret);
-    let _ = fs::remove_dir_all(store_path);
-    let store = Store::new(store_path).unwrap();
-    let leader_elector = LeaderElector::new(committee.clone());
+    let leader_elector = RRLeaderElector::new(committee.clone());
     let mempool_driver = MempoolDriver::new(store.clone(), tx_mempool, tx_loopback.clone());
     let synchronizer = Synchronizer::new(
         name,
-        committee.clone(),
+        signature_service.clone(),
+        tx_committee.subscribe(),
         store.clone(),
+        /* rx_reply */ channel(1).1,
         tx_loopback,
         /* sync_retry_delay */ 100_000,
     );
@@ -46,21 +98,33 @@ fn core(
         signature_service,
         store,
         leader_elector,
+        validator,
         mempool_driver,
         synchronizer,
         /* timeout_delay */ 100,
+        /* max_timeout_delay */ 1_000,
+        commit_rule,
+        /* checkpoint_interval */ 1_000,
+        /* retention */ 0,
+        Byzantine::default(),
         /* rx_message */ rx_core,
         rx_loopback,
+        rx_reconfiguration,
+        /* rx_snapshot */ channel(1).1,
         tx_proposer,
         tx_commit,
+        tx_evidence,
+        tx_checkpoint,
+        tx_committee,
+        last_applied,
     );
 
     (tx_core, rx_proposer, rx_commit)
 }
 
 fn leader_keys(round: Round) -> (PublicKey, SecretKey) {
-    let leader_elector = LeaderElector::new(committee());
-    let leader = leader_elector.get_leader(round);
+    let leader_elector = RRLeaderElector::new(committee());
+    let leader = leader_elector.get_leader(round, None).unwrap();
     keys()
         .into_iter()
         .find(|(public_key, _)| *public_key == leader)
@@ -93,6 +157,274 @@ async fn handle_proposal() {
     assert!(handle.await.is_ok());
 }
 
+/// Rejects every block, after reporting the block and its payload.
+struct RejectAll(Sender<(Digest, Vec<Batch>)>);
+
+#[async_trait]
+impl ProposalValidator for RejectAll {
+    async fn validate(&self, block: &Block, batches: &[Batch]) -> Result<(), String> {
+        self.0
+            .send((block.digest(), batches.to_vec()))
+            .await
+            .unwrap();
+        Err("Invalid transactions".to_string())
+    }
+}
+
+#[tokio::test]
+async fn reject_invalid_proposal() {
+    let committee = committee_with_base_port(16_500);
+
+    // Make a block whose payload is in the store.
+    let store_path = ".db_test_reject_invalid_proposal";
+    let _ = fs::remove_dir_all(store_path);
+    let mut store = Store::new(store_path).unwrap();
+    let batch = vec![vec![1u8; 10], vec![2u8; 10]];
+    let serialized = bincode::serialize(&MempoolMessage::Batch(batch.clone())).unwrap();
+    let digest = Digest([1u8; 32]);
+    store.write(digest.to_vec(), serialized).await;
+    let (leader, leader_key) = leader_keys(1);
+    let block = Block::new_from_key(QC::genesis(), leader, 1, vec![digest], &leader_key);
+
+    // Run a core instance whose application rejects every block.
+    let (public_key, _) = keys().pop().unwrap();
+    let (tx_validator, mut rx_validator) = channel(1);
+    let (_, rx_reconfiguration) = channel(1);
+    let (tx_core, _rx_proposer, _rx_commit) = core_with_store(
+        public_key,
+        keys().pop().unwrap().1,
+        committee.clone(),
+        store,
+        CommitRule::TwoChain,
+        rx_reconfiguration,
+        None,
+        RejectAll(tx_validator),
+    );
+
+    // Send the block to the core: the application receives its payload.
+    tx_core
+        .send(ConsensusMessage::Propose(block.clone()))
+        .await
+        .unwrap();
+    let (received, batches) = rx_validator.recv().await.unwrap();
+    assert_eq!(received, block.digest());
+    assert_eq!(batches, vec![batch]);
+
+    // Ensure the next leader gets our timeout rather than a vote.
+    let (_, secret_key) = keys().pop().unwrap();
+    let timeout = Timeout::new_from_key(QC::genesis(), 1, public_key, &secret_key);
+    let expected = bincode::serialize(&ConsensusMessage::Timeout(timeout)).unwrap();
+    let (next_leader, _) = leader_keys(2);
+    let address = committee.address(&next_leader).unwrap();
+    let handle = listener(address, Some(Bytes::from(expected)));
+    assert!(handle.await.is_ok());
+}
+
+#[tokio::test]
+async fn reject_reproposed_batch() {
+    let committee = committee_with_base_port(16_900);
+
+    // Make a block whose payload is in the store, and a child proposing the same batch again.
+    let store_path = ".db_test_reject_reproposed_batch";
+    let _ = fs::remove_dir_all(store_path);
+    let mut store = Store::new(store_path).unwrap();
+    let serialized = bincode::serialize(&MempoolMessage::Batch(vec![vec![1u8; 10]])).unwrap();
+    let digest = Digest([1u8; 32]);
+    store.write(digest.to_vec(), serialized).await;
+    let (leader, leader_key) = leader_keys(1);
+    let block = Block::new_from_key(QC::genesis(), leader, 1, vec![digest.clone()], &leader_key);
+    let qc = QC::new_from_keys(block.digest(), block.round, &keys());
+    let (leader, leader_key) = leader_keys(2);
+    let child = Block::new_from_key(qc, leader, 2, vec![digest], &leader_key);
+
+    // Run a core instance reporting the blocks it lets the application check.
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let (tx_validator, mut rx_validator) = channel(1);
+    let (_, rx_reconfiguration) = channel(1);
+    let (tx_core, _rx_proposer, _rx_commit) = core_with_store(
+        public_key,
+        secret_key,
+        committee.clone(),
+        store,
+        CommitRule::TwoChain,
+        rx_reconfiguration,
+        None,
+        RejectAll(tx_validator),
+    );
+
+    // Send both blocks to the core.
+    for x in [&block, &child] {
+        tx_core
+            .send(ConsensusMessage::Propose(x.clone()))
+            .await
+            .unwrap();
+    }
+
+    // Ensure the core rejects the child before the application gets to check it.
+    let (received, _) = rx_validator.recv().await.unwrap();
+    assert_eq!(received, block.digest());
+    let received = timeout(Duration::from_millis(500), rx_validator.recv()).await;
+    assert!(received.is_err(), "The application checked the child");
+}
+
+#[tokio::test]
+async fn process_certified_payload() {
+    let committee = committee_with_base_port(16_700);
+
+    // Make a block whose payload is certified but not in the store.
+    let store_path = ".db_test_process_certified_payload";
+    let _ = fs::remove_dir_all(store_path);
+    let mut store = Store::new(store_path).unwrap();
+    let digest = Digest([1u8; 32]);
+    let (leader, leader_key) = leader_keys(1);
+    let certificate = Certificate {
+        digest: digest.clone(),
+        author: leader,
+        signature: Signature::default(),
+        votes: Vec::new(),
+    };
+    let serialized = bincode::serialize(&certificate).unwrap();
+    store.write(Certificate::key(&digest), serialized).await;
+    let block = Block::new_from_key(QC::genesis(), leader, 1, vec![digest.clone()], &leader_key);
+
+    // Make the vote we expect to receive once the payload is synced.
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let vote = Vote::new_from_key(block.digest(), block.round, public_key, &secret_key);
+    let expected = bincode::serialize(&ConsensusMessage::Vote(vote)).unwrap();
+
+    // Run a core instance.
+    let (_, rx_reconfiguration) = channel(1);
+    let (tx_core, mut rx_proposer, _rx_commit) = core_with_store(
+        public_key,
+        secret_key,
+        committee.clone(),
+        store.clone(),
+        CommitRule::TwoChain,
+        rx_reconfiguration,
+        None,
+        AcceptAll,
+    );
+
+    // Send the block to the core: it suspends processing the block until it holds its payload
+    // (which we deliver before the core times out).
+    tx_core
+        .send(ConsensusMessage::Propose(block.clone()))
+        .await
+        .unwrap();
+    let stored = timeout(
+        Duration::from_millis(20),
+        store.notify_read(block.digest().to_vec()),
+    )
+    .await;
+    assert!(
+        stored.is_err(),
+        "The block is processed without its payload"
+    );
+
+    // Ensure we process the block once the mempool synced its payload, and vote for it.
+    let (next_leader, _) = leader_keys(2);
+    let address = committee.address(&next_leader).unwrap();
+    let handle = listener(address, Some(Bytes::from(expected)));
+    let batch = bincode::serialize(&MempoolMessage::Batch(Vec::new())).unwrap();
+    store.write(digest.to_vec(), batch).await;
+    assert!(handle.await.is_ok());
+    match rx_proposer.recv().await {
+        Some(ProposerMessage::Cleanup(digests)) => assert_eq!(digests, vec![digest]),
+        _ => panic!("Unexpected proposer message"),
+    }
+
+    // Ensure we process the block only once.
+    let processed = timeout(Duration::from_millis(200), rx_proposer.recv()).await;
+    assert!(processed.is_err(), "The block is processed twice");
+}
+
+#[tokio::test]
+async fn detect_double_proposal() {
+    let committee = committee_with_base_port(16_100);
+
+    // Make two different blocks for the same round.
+    let (leader, leader_key) = leader_keys(1);
+    let block_1 = Block::new_from_key(QC::genesis(), leader, 1, Vec::new(), &leader_key);
+    let block_2 = Block::new_from_key(
+        QC::genesis(),
+        leader,
+        1,
+        vec![Digest::default()],
+        &leader_key,
+    );
+
+    // Run a core instance.
+    let store_path = ".db_test_detect_double_proposal";
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let (tx_core, mut rx_proposer, _rx_commit) =
+        core(public_key, secret_key, committee.clone(), store_path);
+
+    // Send both blocks to the core.
+    tx_core
+        .send(ConsensusMessage::Propose(block_1))
+        .await
+        .unwrap();
+    tx_core
+        .send(ConsensusMessage::Propose(block_2))
+        .await
+        .unwrap();
+
+    // Ensure the core includes the evidence in its next proposal.
+    loop {
+        match rx_proposer.recv().await.unwrap() {
+            ProposerMessage::Evidence(evidence) => {
+                assert_eq!(evidence.culprit(), leader);
+                assert!(evidence.verify(&committee).is_ok());
+                break;
+            }
+            ProposerMessage::Cleanup(_) => (),
+            _ => panic!("Unexpected protocol message"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn install_checkpoint() {
+    let committee = committee_with_base_port(16_200);
+
+    // Make a checkpoint of the block of round 2.
+    let chain = chain(keys());
+    let checkpoint = Checkpoint {
+        committed: CommittedBlock {
+            block: chain[1].clone(),
+            descendants: vec![chain[2].clone()],
+            qc: chain[3].qc.clone(),
+        },
+        snapshot: None,
+        attestation: None,
+    };
+
+    // Make a block extending the checkpoint and the vote we expect to receive.
+    let (leader, leader_key) = leader_keys(4);
+    let qc = checkpoint.committed.qc.clone();
+    let block = Block::new_from_key(qc, leader, 4, Vec::new(), &leader_key);
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let vote = Vote::new_from_key(block.digest(), block.round, public_key, &secret_key);
+    let expected = bincode::serialize(&ConsensusMessage::Vote(vote)).unwrap();
+
+    // Run a core instance without any history.
+    let store_path = ".db_test_install_checkpoint";
+    let (tx_core, _rx_proposer, _rx_commit) =
+        core(public_key, secret_key, committee.clone(), store_path);
+
+    // Send the checkpoint and then the block to the core.
+    let message = ConsensusMessage::CheckpointReply(checkpoint);
+    tx_core.send(message).await.unwrap();
+    let message = ConsensusMessage::Propose(block);
+    tx_core.send(message).await.unwrap();
+
+    // Ensure the core votes for the block (it thus has all its ancestors).
+    let (next_leader, _) = leader_keys(5);
+    let address = committee.address(&next_leader).unwrap();
+    let handle = listener(address, Some(Bytes::from(expected)));
+    assert!(handle.await.is_ok());
+}
+
 #[tokio::test]
 async fn generate_proposal() {
     // Get the keys of the leaders of this round and the next.
@@ -108,15 +440,7 @@ async fn generate_proposal() {
             Vote::new_from_key(hash.clone(), block.round, *public_key, &secret_key)
         })
         .collect();
-    let hight_qc = QC {
-        hash,
-        round: block.round,
-        votes: votes
-            .iter()
-            .cloned()
-            .map(|x| (x.author, x.signature))
-            .collect(),
-    };
+    let hight_qc = QC::new_from_keys(hash, block.round, &keys());
 
     // Run a core instance.
     let store_path = ".db_test_generate_proposal";
@@ -161,13 +485,63 @@ async fn commit_block() {
         let _ = rx_proposer.recv().await.unwrap();
     }
 
-    // Ensure the core commits the head.
+    // Ensure the core commits the head, along with the proof that it is committed.
     match rx_commit.recv().await {
-        Some(b) => assert_eq!(b, committed),
+        Some(b) => {
+            assert_eq!(b.block, committed);
+            assert!(b.verify(&committee(), CommitRule::TwoChain).is_ok());
+        }
         _ => assert!(false),
     }
 }
 
+#[tokio::test]
+async fn commit_block_three_chain() {
+    // Get enough distinct leaders to form a quorum.
+    let leaders = vec![
+        leader_keys(1),
+        leader_keys(2),
+        leader_keys(3),
+        leader_keys(4),
+    ];
+    let mut chain = chain(leaders);
+    let last = chain.pop().unwrap();
+
+    // Run a core instance.
+    let store_path = ".db_test_commit_block_three_chain";
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let (tx_core, mut rx_proposer, mut rx_commit) = core_with_commit_rule(
+        public_key,
+        secret_key,
+        committee(),
+        store_path,
+        CommitRule::ThreeChain,
+    );
+
+    // Send a the blocks to the core. A 2-chain is not enough to commit its head.
+    let committed = chain[0].clone();
+    for block in chain {
+        let message = ConsensusMessage::Propose(block);
+        tx_core.send(message).await.unwrap();
+
+        let _ = rx_proposer.recv().await.unwrap();
+    }
+    let result = timeout(Duration::from_millis(200), rx_commit.recv()).await;
+    assert!(result.is_err());
+
+    // Ensure the core commits the head of the 3-chain.
+    let message = ConsensusMessage::Propose(last);
+    tx_core.send(message).await.unwrap();
+    let _ = rx_proposer.recv().await.unwrap();
+    match rx_commit.recv().await {
+        Some(b) => {
+            assert_eq!(b.block, committed);
+            assert!(b.verify(&committee(), CommitRule::ThreeChain).is_ok());
+        }
+        _ => panic!("Failed to commit block"),
+    }
+}
+
 #[tokio::test]
 async fn local_timeout_round() {
     let committee = committee_with_base_port(16_100);
@@ -190,3 +564,167 @@ async fn local_timeout_round() {
         .collect();
     assert!(try_join_all(handles).await.is_ok());
 }
+
+#[tokio::test]
+async fn local_timeout_round_after_restart() {
+    let committee = committee_with_base_port(16_200);
+    let (public_key, secret_key) = leader_keys(3);
+
+    // Persist the state of a node that crashed at round 3 with a high QC of round 2.
+    let store_path = ".db_test_local_timeout_round_after_restart";
+    let _ = fs::remove_dir_all(store_path);
+    let mut store = Store::new(store_path).unwrap();
+    let high_qc = chain(keys()).pop().unwrap().qc;
+    let state = ConsensusState {
+        round: 3,
+        last_voted_round: 2,
+        last_committed_round: 0,
+        last_committed: Digest::default(),
+        high_qc: high_qc.clone(),
+        ..ConsensusState::default()
+    };
+    state.persist(&mut store).await.unwrap();
+
+    // Make the timeout vote we expect to send: it must carry the recovered state.
+    let timeout = Timeout::new_from_key(high_qc, 3, public_key, &secret_key);
+    let expected = bincode::serialize(&ConsensusMessage::Timeout(timeout)).unwrap();
+
+    // Run a core instance on top of the existing store.
+    let (_, rx_reconfiguration) = channel(1);
+    let (_tx_core, _rx_proposer, _rx_commit) = core_with_store(
+        public_key,
+        secret_key,
+        committee.clone(),
+        store,
+        CommitRule::TwoChain,
+        rx_reconfiguration,
+        None,
+        AcceptAll,
+    );
+
+    // Ensure the node broadcasts a timeout vote for the recovered round.
+    let handles: Vec<_> = committee
+        .broadcast_addresses(&public_key)
+        .into_iter()
+        .map(|(_, address)| listener(address, Some(Bytes::from(expected.clone()))))
+        .collect();
+    assert!(try_join_all(handles).await.is_ok());
+}
+
+#[tokio::test]
+async fn replay_committed_blocks() {
+    let chain = chain(keys());
+
+    // Store a chain of blocks, and persist the state of a node that committed the first three.
+    let store_path = ".db_test_replay_committed_blocks";
+    let _ = fs::remove_dir_all(store_path);
+    let mut store = Store::new(store_path).unwrap();
+    for block in &chain {
+        let value = bincode::serialize(block).unwrap();
+        store.write(block.digest().to_vec(), value).await;
+    }
+    for i in 0..3 {
+        let committed = CommittedBlock {
+            block: chain[i].clone(),
+            descendants: chain[i + 1..].to_vec(),
+            qc: QC::new_from_keys(chain[3].digest(), chain[3].round, &keys()),
+        };
+        let value = bincode::serialize(&committed).unwrap();
+        store.write(committed_key(&chain[i].digest()), value).await;
+    }
+    let state = ConsensusState {
+        round: 4,
+        last_voted_round: 4,
+        last_committed_round: 3,
+        last_committed: chain[2].digest(),
+        high_qc: chain[3].qc.clone(),
+        ..ConsensusState::default()
+    };
+    state.persist(&mut store).await.unwrap();
+
+    // Run a core instance, telling it the application already applied the first block.
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let last_applied = Some(chain[0].digest());
+    let (_, rx_reconfiguration) = channel(1);
+    let (_tx_core, _rx_proposer, mut rx_commit) = core_with_store(
+        public_key,
+        secret_key,
+        committee(),
+        store,
+        CommitRule::TwoChain,
+        rx_reconfiguration,
+        last_applied,
+        AcceptAll,
+    );
+
+    // Ensure the core replays exactly the blocks committed after the cursor, in order.
+    assert_eq!(rx_commit.recv().await.unwrap().block, chain[1]);
+    assert_eq!(rx_commit.recv().await.unwrap().block, chain[2]);
+}
+
+#[tokio::test]
+async fn commit_reconfiguration() {
+    let committee = committee_with_base_port(16_400);
+    let (public_key, secret_key) = leader_keys(1);
+
+    // Make a chain b1 <- b2 <- b3 where b1 carries a reconfiguration and b2 extends it.
+    let reconfiguration = reconfiguration();
+    let (author, secret) = leader_keys(1);
+    let b1 = Block {
+        reconfiguration: Some(reconfiguration.clone()),
+        ..Block::new_from_key(QC::genesis(), author, 1, Vec::new(), &secret)
+    };
+    let b1 = Block {
+        signature: Signature::new(&b1.digest(), &secret),
+        ..b1
+    };
+    let make_qc = |block: &Block| QC::new_from_keys(block.digest(), block.round, &keys());
+    let (author, secret) = leader_keys(2);
+    let b2 = Block::new_from_key(make_qc(&b1), author, 2, Vec::new(), &secret);
+    let (author, secret) = leader_keys(3);
+    let b3 = Block::new_from_key(make_qc(&b2), author, 3, Vec::new(), &secret);
+
+    // Run a core instance and authorize the reconfiguration.
+    let store_path = ".db_test_commit_reconfiguration";
+    let _ = fs::remove_dir_all(store_path);
+    let store = Store::new(store_path).unwrap();
+    let (tx_reconfiguration, rx_reconfiguration) = channel(1);
+    let (tx_core, mut rx_proposer, mut rx_commit) = core_with_store(
+        public_key,
+        secret_key,
+        committee,
+        store,
+        CommitRule::TwoChain,
+        rx_reconfiguration,
+        None,
+        AcceptAll,
+    );
+    tx_reconfiguration.send(reconfiguration).await.unwrap();
+
+    // Collect the proposer messages until the core starts the new epoch.
+    let epoch = b1.epoch + 1;
+    let handle = tokio::spawn(async move {
+        loop {
+            match rx_proposer.recv().await.unwrap() {
+                ProposerMessage::Make(round, qc, tc) if qc.epoch == epoch => break (round, qc, tc),
+                _ => (),
+            }
+        }
+    });
+
+    // Send the chain to the core: b3 commits b1.
+    for block in [b1.clone(), b2, b3] {
+        tx_core
+            .send(ConsensusMessage::Propose(block))
+            .await
+            .unwrap();
+    }
+    assert_eq!(rx_commit.recv().await.unwrap().block, b1);
+
+    // Ensure the core starts the new epoch on top of b1 (it is the leader of the first round).
+    let (round, qc, tc) = handle.await.unwrap();
+    assert_eq!(round, 1);
+    assert!(qc.is_genesis());
+    assert_eq!(qc.hash, b1.digest());
+    assert!(tc.is_none());
+}
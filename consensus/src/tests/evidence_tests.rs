@@ -0,0 +1,86 @@
+use super::*;
+use crate::common::{block, committee, keys};
+use crate::messages::QC;
+use crypto::{Digest, Signature};
+
+#[test]
+fn verify_valid_double_vote() {
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let vote_1 = Vote::new_from_key(block().digest(), 1, public_key, &secret_key);
+    let vote_2 = Vote::new_from_key(Digest::default(), 1, public_key, &secret_key);
+    let evidence = Evidence::DoubleVote(vote_1, vote_2);
+    assert!(evidence.verify(&committee()).is_ok());
+    assert_eq!(evidence.culprit(), public_key);
+}
+
+#[test]
+fn verify_valid_double_proposal() {
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let block_1 = Block::new_from_key(QC::genesis(), public_key, 1, Vec::new(), &secret_key);
+    let block_2 = Block::new_from_key(
+        QC::genesis(),
+        public_key,
+        1,
+        vec![Digest::default()],
+        &secret_key,
+    );
+    let evidence = Evidence::DoubleProposal(block_1.header(), block_2.header());
+    assert!(evidence.verify(&committee()).is_ok());
+}
+
+#[test]
+fn double_proposal_strips_evidence() {
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let vote_1 = Vote::new_from_key(block().digest(), 1, public_key, &secret_key);
+    let vote_2 = Vote::new_from_key(Digest::default(), 1, public_key, &secret_key);
+    let double_vote = Evidence::DoubleVote(vote_1, vote_2);
+
+    // A block carrying evidence.
+    let mut block_1 = Block::new_from_key(QC::genesis(), public_key, 1, Vec::new(), &secret_key);
+    block_1.evidence = vec![double_vote.clone()];
+    block_1.signature = Signature::new(&block_1.digest(), &secret_key);
+    let block_2 = Block::new_from_key(QC::genesis(), public_key, 1, Vec::new(), &secret_key);
+
+    // The proof of the double proposal only holds the digest of that evidence.
+    let header = block_1.header();
+    assert!(header.block.evidence.is_empty());
+    assert_eq!(header.evidence, vec![double_vote.digest()]);
+    assert_eq!(header.digest(), block_1.digest());
+    let evidence = Evidence::DoubleProposal(header, block_2.header());
+    assert!(evidence.verify(&committee()).is_ok());
+}
+
+#[test]
+fn verify_identical_votes() {
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let vote = Vote::new_from_key(block().digest(), 1, public_key, &secret_key);
+    let evidence = Evidence::DoubleVote(vote.clone(), vote);
+    match evidence.verify(&committee()) {
+        Err(ConsensusError::InvalidEvidence) => (),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn verify_votes_different_authors() {
+    let mut keys = keys();
+    let (public_key_1, secret_key_1) = keys.pop().unwrap();
+    let (public_key_2, secret_key_2) = keys.pop().unwrap();
+    let vote_1 = Vote::new_from_key(block().digest(), 1, public_key_1, &secret_key_1);
+    let vote_2 = Vote::new_from_key(Digest::default(), 1, public_key_2, &secret_key_2);
+    let evidence = Evidence::DoubleVote(vote_1, vote_2);
+    match evidence.verify(&committee()) {
+        Err(ConsensusError::InvalidEvidence) => (),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn digest_ignores_order() {
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let vote_1 = Vote::new_from_key(block().digest(), 1, public_key, &secret_key);
+    let vote_2 = Vote::new_from_key(Digest::default(), 1, public_key, &secret_key);
+    let evidence_1 = Evidence::DoubleVote(vote_1.clone(), vote_2.clone());
+    let evidence_2 = Evidence::DoubleVote(vote_2, vote_1);
+    assert_eq!(evidence_1.digest(), evidence_2.digest());
+}
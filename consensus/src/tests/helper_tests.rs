@@ -1,13 +1,17 @@
 use super::*;
-use crate::common::{block, committee_with_base_port, keys, listener};
-use crypto::Hash as _;
+use crate::common::{block, chain, committee_with_base_port, keys, listener};
+use crate::messages::{CommittedBlock, Nonce, QC};
+use crate::synchronizer::Nonces;
+use crypto::SignatureService;
 use std::fs;
+use store::keys::HORIZON_KEY;
 use tokio::sync::mpsc::channel;
 
 #[tokio::test]
 async fn sync_reply() {
     let (tx_request, rx_request) = channel(1);
-    let (requestor, _) = keys().pop().unwrap();
+    let (requestor, secret) = keys().pop().unwrap();
+    let (name, _) = keys().remove(0);
     let committee = committee_with_base_port(13_000);
 
     // Create a new test store.
@@ -21,17 +25,208 @@ async fn sync_reply() {
     store.write(digest.to_vec(), serialized.clone()).await;
 
     // Spawn an `Helper` instance.
-    Helper::spawn(committee.clone(), store, rx_request);
+    let (_tx_committee, rx_committee) = watch::channel(committee.clone());
+    Helper::spawn(
+        name,
+        rx_committee,
+        store,
+        rx_request,
+        /* max_requests */ 10,
+        /* max_bytes */ 1_000_000,
+    );
 
     // Spawn a listener to receive the sync reply.
     let address = committee.address(&requestor).unwrap();
-    let message = ConsensusMessage::Propose(block());
+    let message = ConsensusMessage::SyncReply(vec![block()]);
     let expected = Bytes::from(bincode::serialize(&message).unwrap());
     let handle = listener(address, Some(expected));
 
     // Send a sync request.
-    tx_request.send((digest, requestor)).await.unwrap();
+    let request = Request::Sync(digest, 0);
+    let signature_service = SignatureService::new(secret);
+    let request = SignedRequest::new(
+        request,
+        requestor,
+        name,
+        Nonce::default(),
+        signature_service,
+    )
+    .await;
+    tx_request.send(request).await.unwrap();
 
     // Ensure the requestor received the batch (ie. it did not panic).
     assert!(handle.await.is_ok());
 }
+
+#[tokio::test]
+async fn sync_reply_range() {
+    let (tx_request, rx_request) = channel(1);
+    let (requestor, secret) = keys().pop().unwrap();
+    let (name, _) = keys().remove(0);
+    let committee = committee_with_base_port(13_100);
+
+    // Create a new test store.
+    let path = ".db_test_sync_reply_range";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Add a chain of blocks to the store.
+    let chain = chain(keys());
+    for block in &chain {
+        let serialized = bincode::serialize(block).unwrap();
+        store.write(block.digest().to_vec(), serialized).await;
+    }
+
+    // Spawn an `Helper` instance.
+    let (_tx_committee, rx_committee) = watch::channel(committee.clone());
+    Helper::spawn(
+        name,
+        rx_committee,
+        store,
+        rx_request,
+        /* max_requests */ 10,
+        /* max_bytes */ 1_000_000,
+    );
+
+    // Spawn a listener to receive the sync reply. It should contain all the ancestors of the
+    // last block (from the most recent one) down to the known round.
+    let address = committee.address(&requestor).unwrap();
+    let ancestors = chain.iter().skip(1).rev().cloned().collect();
+    let message = ConsensusMessage::SyncReply(ancestors);
+    let expected = Bytes::from(bincode::serialize(&message).unwrap());
+    let handle = listener(address, Some(expected));
+
+    // Request the ancestors of the last block, knowing the first one.
+    let digest = chain.last().unwrap().digest();
+    let request = Request::Sync(digest, 1);
+    let signature_service = SignatureService::new(secret);
+    let request = SignedRequest::new(
+        request,
+        requestor,
+        name,
+        Nonce::default(),
+        signature_service,
+    )
+    .await;
+    tx_request.send(request).await.unwrap();
+
+    // Ensure the requestor received the ancestors (ie. it did not panic).
+    assert!(handle.await.is_ok());
+}
+
+#[tokio::test]
+async fn sync_reply_below_horizon() {
+    let (tx_request, rx_request) = channel(1);
+    let (requestor, secret) = keys().pop().unwrap();
+    let (name, _) = keys().remove(0);
+    let committee = committee_with_base_port(13_200);
+
+    // Create a new test store.
+    let path = ".db_test_sync_reply_below_horizon";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Add a chain of blocks to the store, but prune its first block.
+    let chain = chain(keys());
+    for block in chain.iter().skip(1) {
+        let serialized = bincode::serialize(block).unwrap();
+        store.write(block.digest().to_vec(), serialized).await;
+    }
+    let horizon = bincode::serialize(&(committee.epoch, 2 as Round)).unwrap();
+    store.write(HORIZON_KEY.to_vec(), horizon).await;
+
+    // Make a checkpoint of the second block.
+    let checkpoint = Checkpoint {
+        committed: CommittedBlock {
+            block: chain[1].clone(),
+            descendants: chain[2..].to_vec(),
+            qc: QC::new_from_keys(chain[3].digest(), chain[3].round, &keys()),
+        },
+        snapshot: None,
+        attestation: None,
+    };
+    checkpoint.persist(&mut store).await;
+
+    // Spawn an `Helper` instance.
+    let (_tx_committee, rx_committee) = watch::channel(committee.clone());
+    Helper::spawn(
+        name,
+        rx_committee,
+        store,
+        rx_request,
+        /* max_requests */ 10,
+        /* max_bytes */ 1_000_000,
+    );
+
+    // Spawn a listener to receive the reply. We cannot serve the pruned history: we should reply
+    // with our checkpoint instead.
+    let address = committee.address(&requestor).unwrap();
+    let message = ConsensusMessage::CheckpointReply(checkpoint);
+    let expected = Bytes::from(bincode::serialize(&message).unwrap());
+    let handle = listener(address, Some(expected));
+
+    // Request all the ancestors of the last block.
+    let digest = chain.last().unwrap().digest();
+    let request = Request::Sync(digest, 0);
+    let signature_service = SignatureService::new(secret);
+    let request = SignedRequest::new(
+        request,
+        requestor,
+        name,
+        Nonce::default(),
+        signature_service,
+    )
+    .await;
+    tx_request.send(request).await.unwrap();
+
+    // Ensure the requestor received the checkpoint (ie. it did not panic).
+    assert!(handle.await.is_ok());
+}
+
+#[test]
+fn reject_replays() {
+    let mut replays = Replays::default();
+    let nonce = |epoch, round, counter| Nonce {
+        epoch,
+        round,
+        counter,
+    };
+
+    // We serve each request once.
+    assert!(replays.insert(nonce(1, 10, 1), Digest([1u8; 32])));
+    assert!(!replays.insert(nonce(1, 10, 1), Digest([1u8; 32])));
+    assert!(replays.insert(nonce(1, 10, 2), Digest([2u8; 32])));
+
+    // We forget the requests outside the window of the latest one, and reject them thereafter.
+    assert!(replays.insert(nonce(1, 10 + REPLAY_WINDOW + 1, 3), Digest([3u8; 32])));
+    assert!(!replays.insert(nonce(1, 10, 1), Digest([1u8; 32])));
+    assert!(!replays.insert(nonce(1, 5, 4), Digest([4u8; 32])));
+
+    // Requests within the window are still welcome, but not those of previous epochs.
+    assert!(replays.insert(nonce(1, 11 + REPLAY_WINDOW, 5), Digest([5u8; 32])));
+    assert!(replays.insert(nonce(2, 1, 6), Digest([6u8; 32])));
+    assert!(!replays.insert(nonce(1, 20 + REPLAY_WINDOW, 7), Digest([7u8; 32])));
+}
+
+#[tokio::test]
+async fn serve_requests_after_restart() {
+    let path = ".db_test_serve_requests_after_restart";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+    let mut replays = Replays::default();
+    let digest = Digest([1u8; 32]);
+
+    // An authority makes a request.
+    let mut nonces = Nonces::load(store.clone()).await.unwrap();
+    nonces.advance((1, 10));
+    assert!(replays.insert(nonces.next().await, digest.clone()));
+
+    // It restarts within the same round and makes the same request again: we serve it.
+    let mut nonces = Nonces::load(store).await.unwrap();
+    nonces.advance((1, 10));
+    let nonce = nonces.next().await;
+    assert!(replays.insert(nonce, digest.clone()));
+
+    // Its counter keeps increasing.
+    assert!(nonces.next().await > nonce);
+}
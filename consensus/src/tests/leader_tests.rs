@@ -0,0 +1,192 @@
+use super::*;
+use crate::common::{committee, keys};
+use crate::messages::QC;
+use crypto::SecretKey;
+
+fn qc(block: &Block) -> QC {
+    QC {
+        hash: block.digest(),
+        round: block.round,
+        ..QC::default()
+    }
+}
+
+// Make a chain of blocks (one per round, starting at round 1) authored by the specified keys.
+fn make_chain(authors: Vec<&(PublicKey, SecretKey)>) -> Vec<Block> {
+    let mut parent = QC::genesis();
+    authors
+        .into_iter()
+        .enumerate()
+        .map(|(i, (public_key, secret_key))| {
+            let block = Block::new_from_key(
+                parent.clone(),
+                *public_key,
+                1 + i as Round,
+                Vec::new(),
+                secret_key,
+            );
+            parent = qc(&block);
+            block
+        })
+        .collect()
+}
+
+fn reputation(window: Round, lag: Round) -> ReputationLeaderElector {
+    ReputationLeaderElector::new(committee(), window, lag, CommitRule::TwoChain)
+}
+
+fn tip(chain: &[Block]) -> Option<(Digest, Round)> {
+    chain.last().map(|block| (block.digest(), block.round))
+}
+
+#[test]
+fn round_robin() {
+    let elector = RRLeaderElector::new(committee());
+    let mut keys: Vec<_> = keys().into_iter().map(|(x, _)| x).collect();
+    keys.sort();
+    for round in 0..8 {
+        assert_eq!(
+            elector.get_leader(round, None),
+            Some(keys[round as usize % keys.len()])
+        );
+    }
+}
+
+#[test]
+fn reputation_fallback() {
+    // The elector falls back to round-robin for the rounds preceding the lag.
+    let elector = reputation(/* window */ 10, /* lag */ 2);
+    let round_robin = RRLeaderElector::new(committee());
+    for round in 0..=2 {
+        assert_eq!(
+            elector.get_leader(round, None),
+            round_robin.get_leader(round, None)
+        );
+    }
+}
+
+#[test]
+fn reputation_favours_active_authorities() {
+    let keys = keys();
+    let (offline, _) = keys[0];
+
+    // Commit a window of blocks, none of them authored by the first authority.
+    let mut elector = reputation(/* window */ 10, /* lag */ 2);
+    let authors = (1..=30).map(|round| &keys[1 + round % 3]).collect();
+    let chain = make_chain(authors);
+    chain.iter().for_each(|block| elector.add(block));
+    chain[..28].iter().for_each(|block| elector.update(block));
+
+    // The offline authority is not elected in the following rounds.
+    let elected = (13..=30)
+        .filter(|round| {
+            elector.get_leader(*round, tip(&chain[..*round as usize - 1])) == Some(offline)
+        })
+        .count();
+    assert_eq!(elected, 0);
+}
+
+#[test]
+fn reputation_requires_committed_history() {
+    let keys = keys();
+    let chain = make_chain(keys.iter().cycle().take(20).collect());
+    let mut elector = reputation(/* window */ 10, /* lag */ 2);
+    chain.iter().for_each(|block| elector.add(block));
+
+    // We cannot elect leaders before committing the reputation window.
+    chain[..15].iter().for_each(|block| elector.update(block));
+    assert!(elector.get_leader(21, tip(&chain)).is_none());
+
+    // The blocks committed after the window are not needed.
+    chain[15..18].iter().for_each(|block| elector.update(block));
+    assert!(elector.get_leader(21, tip(&chain)).is_some());
+}
+
+#[test]
+fn reputation_ignores_parent() {
+    let keys = keys();
+    let chain = make_chain(keys.iter().cycle().take(20).collect());
+    let mut elector = reputation(/* window */ 10, /* lag */ 2);
+    chain.iter().for_each(|block| elector.add(block));
+    chain[..18].iter().for_each(|block| elector.update(block));
+
+    // Make a fork of the last block, authored by someone else.
+    let (author, secret) = &keys[0];
+    let fork = Block::new_from_key(qc(&chain[18]), *author, 20, Vec::new(), secret);
+    assert_ne!(fork.digest(), chain[19].digest());
+    elector.add(&fork);
+
+    // The leader does not depend on the block the round extends.
+    let leader = elector.get_leader(21, tip(&chain));
+    assert!(leader.is_some());
+    assert_eq!(elector.get_leader(21, Some((fork.digest(), 20))), leader);
+}
+
+#[test]
+fn reputation_after_timeout() {
+    let keys = keys();
+    let mut chain = make_chain(keys.iter().cycle().take(10).collect());
+
+    // Round 11 times out: round 12 extends round 10.
+    let mut parent = qc(chain.last().unwrap());
+    for round in 12..=14 {
+        let (author, secret) = &keys[round as usize % keys.len()];
+        let block = Block::new_from_key(parent, *author, round, Vec::new(), secret);
+        parent = qc(&block);
+        chain.push(block);
+    }
+    let mut elector = reputation(/* window */ 5, /* lag */ 2);
+    chain.iter().for_each(|block| elector.add(block));
+    chain[..10].iter().for_each(|block| elector.update(block));
+
+    // The rounds following the timeout are led by round-robin (we do not need the committed
+    // history to elect their leaders).
+    let round_robin = RRLeaderElector::new(committee());
+    for round in 12..=14 {
+        let parent = tip(&chain[..round as usize - 2]);
+        assert_eq!(
+            elector.get_leader(round, parent.clone()),
+            round_robin.get_leader(round, parent)
+        );
+    }
+
+    // Once the chain grows at consecutive rounds again, we use the committed history.
+    assert!(elector.get_leader(15, tip(&chain)).is_none());
+    elector.update(&chain[10]);
+    assert!(elector.get_leader(15, tip(&chain)).is_some());
+}
+
+#[test]
+fn reputation_after_checkpoint() {
+    let keys = keys();
+    let authors = (1..=40)
+        .map(|round| match round {
+            1..=25 => &keys[round % keys.len()],
+            _ => &keys[1 + round % 3],
+        })
+        .collect();
+    let chain = make_chain(authors);
+
+    // A node that never lagged commits the whole chain.
+    let mut elector = reputation(/* window */ 10, /* lag */ 2);
+    chain.iter().for_each(|block| elector.add(block));
+    chain[..38].iter().for_each(|block| elector.update(block));
+
+    // Another node installs a checkpoint of round 20, and then commits the following blocks.
+    let mut lagging = reputation(/* window */ 10, /* lag */ 2);
+    lagging.reset(&chain[19]);
+    chain[19..].iter().for_each(|block| lagging.add(block));
+    chain[20..38].iter().for_each(|block| lagging.update(block));
+
+    // It cannot elect the leaders whose reputation window precedes the checkpoint, and then
+    // elects the same leaders as the other node.
+    for round in 28..=40 {
+        let parent = tip(&chain[..round as usize - 1]);
+        let leader = elector.get_leader(round, parent.clone());
+        assert!(leader.is_some());
+        match round {
+            28..=31 => assert!(lagging.get_leader(round, parent).is_none()),
+            _ => assert_eq!(lagging.get_leader(round, parent), leader),
+        }
+    }
+}
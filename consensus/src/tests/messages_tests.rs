@@ -1,8 +1,5 @@
 use super::*;
-use crate::common::{committee, qc};
-use crypto::generate_keypair;
-use rand::rngs::StdRng;
-use rand::SeedableRng as _;
+use crate::common::{block, chain, committee, keys, qc, reconfiguration};
 
 #[test]
 fn verify_valid_qc() {
@@ -10,34 +7,28 @@ fn verify_valid_qc() {
 }
 
 #[test]
-fn verify_qc_authority_reuse() {
-    // Modify QC to reuse one authority.
+fn verify_qc_malformed_signers() {
+    // Modify QC to add a signer outside the committee.
     let mut qc = qc();
-    let _ = qc.votes.pop();
-    let vote = qc.votes[0].clone();
-    qc.votes.push(vote.clone());
+    qc.signers.0[0] |= 1 << 5;
 
     // Verify the QC.
     match qc.verify(&committee()) {
-        Err(ConsensusError::AuthorityReuse(name)) => assert_eq!(name, vote.0),
-        _ => assert!(false),
+        Err(ConsensusError::MalformedSigners) => (),
+        _ => panic!("Unexpected verification result"),
     }
 }
 
 #[test]
-fn verify_qc_unknown_authority() {
+fn verify_qc_wrong_signers() {
+    // Modify QC to swap one signer for another authority.
     let mut qc = qc();
-
-    // Modify QC to add one unknown authority.
-    let mut rng = StdRng::from_seed([1; 32]);
-    let (unknown, _) = generate_keypair(&mut rng);
-    let (_, sig) = qc.votes.pop().unwrap();
-    qc.votes.push((unknown, sig));
+    qc.signers.0[0] ^= 0b11;
 
     // Verify the QC.
     match qc.verify(&committee()) {
-        Err(ConsensusError::UnknownAuthority(name)) => assert_eq!(name, unknown),
-        _ => assert!(false),
+        Err(ConsensusError::InvalidSignature(_)) => (),
+        _ => panic!("Unexpected verification result"),
     }
 }
 
@@ -45,7 +36,8 @@ fn verify_qc_unknown_authority() {
 fn verify_qc_insufficient_stake() {
     // Modify QC to remove one authority.
     let mut qc = qc();
-    let _ = qc.votes.pop();
+    let names = qc.signers.names(&committee()).unwrap();
+    qc.signers = Signers::new(names.iter().skip(1), &committee());
 
     // Verify the QC.
     match qc.verify(&committee()) {
@@ -53,3 +45,203 @@ fn verify_qc_insufficient_stake() {
         _ => assert!(false),
     }
 }
+
+#[test]
+fn verify_qc_wrong_epoch() {
+    // Modify the QC to belong to the previous epoch.
+    let mut qc = qc();
+    qc.epoch -= 1;
+
+    // Verify the QC.
+    match qc.verify(&committee()) {
+        Err(ConsensusError::WrongEpoch { expected, received }) => {
+            assert_eq!(expected, committee().epoch);
+            assert_eq!(received, qc.epoch);
+        }
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn verify_valid_reconfiguration() {
+    assert!(reconfiguration().verify(&committee()).is_ok());
+}
+
+#[test]
+fn verify_reconfiguration_wrong_epoch() {
+    // Make a reconfiguration that skips an epoch.
+    let mut reconfiguration = reconfiguration();
+    reconfiguration.consensus.epoch += 1;
+
+    // Verify the reconfiguration.
+    match reconfiguration.verify(&committee()) {
+        Err(ConsensusError::InvalidReconfiguration(epoch)) => {
+            assert_eq!(epoch, reconfiguration.epoch())
+        }
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn verify_block_too_much_evidence() {
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let vote_1 = Vote::new_from_key(Digest([1u8; 32]), 1, public_key, &secret_key);
+    let vote_2 = Vote::new_from_key(Digest([2u8; 32]), 1, public_key, &secret_key);
+    let evidence = Evidence::DoubleVote(vote_1, vote_2);
+
+    let mut block = Block::new_from_key(QC::genesis(), public_key, 1, Vec::new(), &secret_key);
+    block.evidence = vec![evidence; MAX_EVIDENCE + 1];
+    block.signature = Signature::new(&block.digest(), &secret_key);
+    match block.verify(&committee()) {
+        Err(ConsensusError::MalformedBlock(x)) => assert_eq!(x, block.digest()),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn verify_valid_committed_block() {
+    // Commit the first block of a chain along with the second one (eg. after a view-change).
+    let chain = chain(keys());
+    let committed = CommittedBlock {
+        block: chain[0].clone(),
+        descendants: chain[1..].to_vec(),
+        qc: QC::new_from_keys(chain[3].digest(), chain[3].round, &keys()),
+    };
+    assert!(committed.verify(&committee(), CommitRule::TwoChain).is_ok());
+    assert!(committed
+        .verify(&committee(), CommitRule::ThreeChain)
+        .is_ok());
+}
+
+#[test]
+fn verify_committed_block_broken_chain() {
+    // Remove a block from the middle of the chain.
+    let chain = chain(keys());
+    let committed = CommittedBlock {
+        block: chain[0].clone(),
+        descendants: vec![chain[2].clone()],
+        qc: chain[3].qc.clone(),
+    };
+    match committed.verify(&committee(), CommitRule::TwoChain) {
+        Err(ConsensusError::InvalidCommitProof(digest)) => assert_eq!(digest, chain[0].digest()),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn verify_committed_block_short_chain() {
+    // A 2-chain does not commit its head under the 3-chain rule.
+    let chain = chain(keys());
+    let committed = CommittedBlock {
+        block: chain[0].clone(),
+        descendants: vec![chain[1].clone()],
+        qc: chain[2].qc.clone(),
+    };
+    assert!(committed.verify(&committee(), CommitRule::TwoChain).is_ok());
+    assert!(committed
+        .verify(&committee(), CommitRule::ThreeChain)
+        .is_err());
+}
+
+#[tokio::test]
+async fn verify_valid_signed_request() {
+    let (name, secret) = keys().pop().unwrap();
+    let signature_service = SignatureService::new(secret);
+    let (recipient, _) = keys().remove(0);
+    let nonce = Nonce::default();
+    let request = SignedRequest::new(
+        Request::Checkpoint,
+        name,
+        recipient,
+        nonce,
+        signature_service,
+    )
+    .await;
+    assert!(request.verify(&committee(), &recipient).is_ok());
+}
+
+#[tokio::test]
+async fn verify_signed_request_forged_origin() {
+    // Sign a request on behalf of another authority.
+    let (_, secret) = keys().pop().unwrap();
+    let (victim, _) = keys().remove(0);
+    let signature_service = SignatureService::new(secret);
+    let (recipient, _) = keys().remove(1);
+    let nonce = Nonce::default();
+    let request = SignedRequest::new(
+        Request::Checkpoint,
+        victim,
+        recipient,
+        nonce,
+        signature_service,
+    )
+    .await;
+
+    // Verify the request.
+    match request.verify(&committee(), &recipient) {
+        Err(ConsensusError::InvalidSignature(_)) => (),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[tokio::test]
+async fn verify_signed_request_wrong_recipient() {
+    // Replay to another authority a request addressed to the first one.
+    let (name, secret) = keys().pop().unwrap();
+    let (recipient, _) = keys().remove(0);
+    let (other, _) = keys().remove(1);
+    let signature_service = SignatureService::new(secret);
+    let nonce = Nonce::default();
+    let request = SignedRequest::new(
+        Request::Checkpoint,
+        name,
+        recipient,
+        nonce,
+        signature_service,
+    )
+    .await;
+
+    // Verify the request.
+    match request.verify(&committee(), &other) {
+        Err(ConsensusError::MisaddressedRequest(x)) => assert_eq!(x, recipient),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn block_digest_payload_and_evidence() {
+    let (public_key, secret_key) = keys().pop().unwrap();
+    let vote_1 = Vote::new_from_key(Digest([1u8; 32]), 1, public_key, &secret_key);
+    let vote_2 = Vote::new_from_key(Digest([2u8; 32]), 1, public_key, &secret_key);
+    let evidence = Evidence::DoubleVote(vote_1, vote_2);
+
+    // Moving the digest of the evidence into the payload must change the block's digest.
+    let x = Digest([3u8; 32]);
+    let mut block_1 = block();
+    block_1.payload = vec![x.clone(), evidence.digest()];
+    let mut block_2 = block();
+    block_2.payload = vec![x];
+    block_2.evidence = vec![evidence];
+    assert_ne!(block_1.digest(), block_2.digest());
+}
+
+#[test]
+fn block_digest_payload_and_metadata() {
+    // A payload entry cannot stand in for the (length-prefixed) metadata.
+    let metadata = vec![4u8; 24];
+    let entry = [
+        (metadata.len() as u64).to_le_bytes().to_vec(),
+        metadata.clone(),
+    ]
+    .concat();
+    let mut block_1 = block();
+    block_1.payload = vec![Digest(entry.try_into().unwrap())];
+    let mut block_2 = block();
+    block_2.metadata = Some(metadata);
+    assert_ne!(block_1.digest(), block_2.digest());
+
+    // Empty metadata differs from no metadata.
+    let mut block_3 = block();
+    block_3.metadata = Some(Vec::new());
+    assert_ne!(block().digest(), block_3.digest());
+}
@@ -0,0 +1,159 @@
+use super::*;
+use crate::builder::ProposeAll;
+use crate::common::{committee_with_base_port, keys};
+use async_trait::async_trait;
+use tokio::sync::mpsc::channel;
+use tokio::time::{sleep, Duration};
+#[cfg(feature = "byzantine")]
+use {
+    futures::sink::SinkExt as _,
+    std::net::SocketAddr,
+    tokio::net::TcpListener,
+    tokio::task::JoinHandle,
+    tokio_util::codec::{Framed, LengthDelimitedCodec},
+};
+
+/// Only proposes the smallest candidate, tagged with the round.
+struct ProposeSmallest;
+
+#[async_trait]
+impl ProposalBuilder for ProposeSmallest {
+    async fn build(&mut self, round: Round, candidates: Vec<Digest>) -> Proposal {
+        Proposal {
+            payload: candidates.into_iter().min().into_iter().collect(),
+            metadata: Some(round.to_le_bytes().to_vec()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn build_proposal() {
+    let committee = committee_with_base_port(16_600);
+    let (name, secret) = keys().pop().unwrap();
+    let (tx_mempool, rx_mempool) = channel(10);
+    let (tx_message, rx_message) = channel(10);
+    let (tx_loopback, mut rx_loopback) = channel(10);
+    let (_tx_committee, rx_committee) = watch::channel(committee);
+    Proposer::spawn(
+        name,
+        rx_committee,
+        SignatureService::new(secret),
+        ProposeSmallest,
+        rx_mempool,
+        rx_message,
+        tx_loopback,
+        Byzantine::default(),
+    );
+
+    // Make the mempool deliver two batches.
+    tx_mempool.send(Digest([2u8; 32])).await.unwrap();
+    tx_mempool.send(Digest([1u8; 32])).await.unwrap();
+    sleep(Duration::from_millis(50)).await;
+
+    // Ensure our block holds the payload and metadata chosen by the builder.
+    let message = ProposerMessage::Make(1, QC::genesis(), None);
+    tx_message.send(message).await.unwrap();
+    let block = rx_loopback.recv().await.unwrap();
+    assert_eq!(block.payload, vec![Digest([1u8; 32])]);
+    assert_eq!(block.metadata, Some(1u64.to_le_bytes().to_vec()));
+}
+
+#[tokio::test]
+async fn skip_cleaned_up_batch() {
+    let committee = committee_with_base_port(17_000);
+    let (name, secret) = keys().pop().unwrap();
+    let (tx_mempool, rx_mempool) = channel(10);
+    let (tx_message, rx_message) = channel(10);
+    let (tx_loopback, mut rx_loopback) = channel(10);
+    let (_tx_committee, rx_committee) = watch::channel(committee);
+    Proposer::spawn(
+        name,
+        rx_committee,
+        SignatureService::new(secret),
+        ProposeAll,
+        rx_mempool,
+        rx_message,
+        tx_loopback,
+        Byzantine::default(),
+    );
+
+    // Process a block proposing a batch before the mempool delivers it.
+    let message = ProposerMessage::Cleanup(vec![Digest([1u8; 32])]);
+    tx_message.send(message).await.unwrap();
+    sleep(Duration::from_millis(50)).await;
+    tx_mempool.send(Digest([1u8; 32])).await.unwrap();
+    tx_mempool.send(Digest([2u8; 32])).await.unwrap();
+    sleep(Duration::from_millis(50)).await;
+
+    // Ensure we do not propose the batch again.
+    let message = ProposerMessage::Make(1, QC::genesis(), None);
+    tx_message.send(message).await.unwrap();
+    let block = rx_loopback.recv().await.unwrap();
+    assert_eq!(block.payload, vec![Digest([2u8; 32])]);
+}
+
+// Receive the first message sent to the specified address.
+#[cfg(feature = "byzantine")]
+fn capture(address: SocketAddr) -> JoinHandle<Bytes> {
+    tokio::spawn(async move {
+        let listener = TcpListener::bind(&address).await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
+        let (mut writer, mut reader) = Framed::new(socket, LengthDelimitedCodec::new()).split();
+        let received = reader.next().await.unwrap().unwrap();
+        writer.send(Bytes::from("Ack")).await.unwrap();
+        received.freeze()
+    })
+}
+
+#[cfg(feature = "byzantine")]
+#[tokio::test]
+async fn equivocate_empty_block() {
+    let committee = committee_with_base_port(16_800);
+    let (name, secret) = keys().pop().unwrap();
+    let (_tx_mempool, rx_mempool) = channel(10);
+    let (tx_message, rx_message) = channel(10);
+    let (tx_loopback, mut rx_loopback) = channel(10);
+    let (_tx_committee, rx_committee) = watch::channel(committee.clone());
+    let byzantine = Byzantine {
+        equivocate: true,
+        ..Byzantine::default()
+    };
+    Proposer::spawn(
+        name,
+        rx_committee,
+        SignatureService::new(secret),
+        ProposeSmallest,
+        rx_mempool,
+        rx_message,
+        tx_loopback,
+        byzantine,
+    );
+
+    // Capture the proposals sent to the other nodes.
+    let handles: Vec<_> = committee
+        .broadcast_addresses(&name)
+        .into_iter()
+        .map(|(_, address)| capture(address))
+        .collect();
+
+    // Make an empty block.
+    let message = ProposerMessage::Make(1, QC::genesis(), None);
+    tx_message.send(message).await.unwrap();
+    let block = rx_loopback.recv().await.unwrap();
+    assert!(block.payload.is_empty());
+
+    // Ensure some nodes received a (valid) twin of our block.
+    let mut twins = 0;
+    for handle in handles {
+        match bincode::deserialize(&handle.await.unwrap()).unwrap() {
+            ConsensusMessage::Propose(x) if x.digest() != block.digest() => {
+                assert_eq!((x.author, x.round), (block.author, block.round));
+                assert!(x.verify(&committee).is_ok());
+                twins += 1;
+            }
+            ConsensusMessage::Propose(_) => (),
+            _ => panic!("Unexpected message"),
+        }
+    }
+    assert!(twins > 0);
+}
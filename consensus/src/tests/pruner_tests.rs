@@ -0,0 +1,107 @@
+use super::*;
+use crate::common::{committee, keys};
+use crate::messages::QC;
+use mempool::{pruned_key, Certificate, ConsensusMempoolMessage};
+use std::fs;
+use tokio::sync::mpsc::channel;
+
+#[tokio::test]
+async fn prune_blocks_below_horizon() {
+    let (name, secret) = keys().pop().unwrap();
+    let epoch = committee().epoch;
+    let batch_1 = Digest([1u8; 32]);
+    let batch_2 = Digest([2u8; 32]);
+
+    // Create a new test store.
+    let path = ".db_test_prune_blocks_below_horizon";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+    let (tx_mempool, mut rx_mempool) = channel(10);
+    let (tx_loopback, _rx_loopback) = channel(1);
+    let mut mempool_driver = MempoolDriver::new(store.clone(), tx_mempool, tx_loopback);
+
+    // Store a committed block, a fork (that we did not commit), and a recent committed block.
+    let committed = Block::new_from_key(QC::genesis(), name, 1, vec![batch_1.clone()], &secret);
+    let fork = Block::new_from_key(QC::genesis(), name, 2, vec![batch_2.clone()], &secret);
+    let recent = Block::new_from_key(QC::genesis(), name, 3, Vec::new(), &secret);
+    let mut pruner = Pruner::new(store.clone(), /* retention */ 1);
+    for block in [&committed, &fork, &recent] {
+        let value = bincode::serialize(block).unwrap();
+        store.write(block.digest().to_vec(), value).await;
+        pruner.insert(block).await;
+    }
+    for block in [&committed, &recent] {
+        store
+            .write(committed_key(&block.digest()), Vec::new())
+            .await;
+    }
+    for batch in [&batch_1, &batch_2] {
+        store.write(batch.to_vec(), Vec::new()).await;
+        store.write(Certificate::key(batch), Vec::new()).await;
+    }
+
+    // Ensure we keep track of the blocks across restarts.
+    let mut pruner = Pruner::new(store.clone(), /* retention */ 1);
+    pruner.load().await.unwrap();
+
+    // We cannot prune the blocks following the application's latest snapshot.
+    pruner
+        .prune((epoch, 4), (epoch, 1), &mut mempool_driver)
+        .await
+        .unwrap();
+    assert!(store
+        .read(committed.digest().to_vec())
+        .await
+        .unwrap()
+        .is_some());
+    assert_eq!(load_horizon(&mut store).await.unwrap(), Some((epoch, 1)));
+    match rx_mempool.recv().await {
+        Some(ConsensusMempoolMessage::Prune(e, r)) => assert_eq!((e, r), (epoch, 1)),
+        x => panic!("Unexpected mempool message: {:?}", x),
+    }
+
+    // Prune the committed block.
+    pruner
+        .prune((epoch, 3), (epoch, 4), &mut mempool_driver)
+        .await
+        .unwrap();
+    assert_eq!(load_horizon(&mut store).await.unwrap(), Some((epoch, 2)));
+
+    // Ensure we pruned the committed block along with its payload.
+    assert!(store
+        .read(committed.digest().to_vec())
+        .await
+        .unwrap()
+        .is_none());
+    let key = committed_key(&committed.digest());
+    assert!(store.read(key).await.unwrap().is_none());
+    assert!(store.read(batch_1.to_vec()).await.unwrap().is_none());
+    assert!(store
+        .read(Certificate::key(&batch_1))
+        .await
+        .unwrap()
+        .is_none());
+    assert!(store.read(pruned_key(&batch_1)).await.unwrap().is_some());
+
+    // Keep the last committed round only.
+    pruner
+        .prune((epoch, 4), (epoch, 4), &mut mempool_driver)
+        .await
+        .unwrap();
+    assert_eq!(load_horizon(&mut store).await.unwrap(), Some((epoch, 3)));
+
+    // Ensure we pruned the fork, but kept its payload (the mempool deletes it if it is not
+    // committed by then).
+    assert!(store.read(fork.digest().to_vec()).await.unwrap().is_none());
+    assert!(store.read(batch_2.to_vec()).await.unwrap().is_some());
+
+    // Ensure the marker of the committed batch expired `retention` rounds after we pruned it.
+    assert!(store.read(pruned_key(&batch_1)).await.unwrap().is_none());
+
+    // Ensure we kept the recent block.
+    assert!(store
+        .read(recent.digest().to_vec())
+        .await
+        .unwrap()
+        .is_some());
+}
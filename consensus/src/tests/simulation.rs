@@ -0,0 +1,493 @@
+use crate::builder::ProposeAll;
+use crate::common::keys;
+use crate::config::{Byzantine, Committee, LeaderElection, Parameters};
+use crate::consensus::{Consensus, Round, CHANNEL_CAPACITY};
+use crate::messages::CommittedBlock;
+use crate::pruner::Position;
+use crate::validator::AcceptAll;
+use crate::{Checkpoint, Evidence, Reconfiguration};
+use bytes::Bytes;
+use crypto::Hash as _;
+use crypto::{derive_bls_keypair, Digest, PublicKey, SignatureService};
+use mempool::{AdmitAll, Committee as MempoolCommittee, Mempool, Parameters as MempoolParameters};
+use network::simulation::{Host, LinkConfig, SimulatedNetwork};
+use network::SimpleSender;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom as _;
+use rand::{Rng as _, SeedableRng as _};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use store::{MemoryDb, Store};
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::time::{sleep, Duration};
+
+#[path = "simulation_tests.rs"]
+pub mod simulation_tests;
+
+/// The virtual time of the simulation (in ms).
+pub type Time = u64;
+
+/// The number of times each node may yield to its tasks at every tick of the virtual clock.
+const YIELDS_PER_TICK: usize = 10;
+
+/// The delay between two transactions of each client (in ms).
+const TRANSACTION_INTERVAL: u64 = 10;
+
+/// Make the runtime of a node pick among the ready branches of `select!` with a generator seeded
+/// from the simulation. This requires tokio's unstable API (see the `simulation` feature).
+#[cfg(all(feature = "simulation", tokio_unstable))]
+fn seed_scheduler(builder: &mut Builder, seed: u64, host: Host) {
+    let seed = [seed, host as u64].map(u64::to_le_bytes).concat();
+    builder.rng_seed(tokio::runtime::RngSeed::from_bytes(&seed));
+}
+
+/// Without tokio's unstable API, the schedulers are not seeded: replaying a seed may then yield a
+/// different execution.
+#[cfg(not(all(feature = "simulation", tokio_unstable)))]
+fn seed_scheduler(_builder: &mut Builder, _seed: u64, _host: Host) {
+    static UNSEEDED: std::sync::Once = std::sync::Once::new();
+    UNSEEDED.call_once(|| {
+        log::warn!(
+            "The schedulers of the simulated nodes are not seeded: enable the `simulation` \
+            feature and build with `--cfg tokio_unstable` to replay executions"
+        )
+    });
+}
+
+/// A fault injected in the simulation.
+#[derive(Clone, Debug)]
+pub enum Fault {
+    Crash(Host),
+    Recover(Host),
+    Partition(Vec<Vec<Host>>),
+    Heal,
+    Configure(LinkConfig),
+}
+
+/// A committed block, as delivered to a node.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Commit {
+    pub time: Time,
+    pub round: Round,
+    pub digest: Digest,
+}
+
+/// A simulated node, along with the channels connecting it to the simulation.
+struct Node {
+    name: PublicKey,
+    /// The storage of the node: it survives crashes.
+    db: MemoryDb,
+    /// The runtime running all the tasks of the node (if it is up).
+    runtime: Option<Runtime>,
+    rx_commit: Option<Receiver<CommittedBlock>>,
+    rx_evidence: Option<Receiver<Evidence>>,
+    rx_checkpoint: Option<Receiver<Checkpoint>>,
+    tx_reconfiguration: Option<Sender<Reconfiguration>>,
+    tx_snapshot: Option<Sender<(Digest, Vec<u8>, CommittedBlock)>>,
+    /// The last block delivered to the node.
+    last_applied: Option<Digest>,
+    commits: Vec<Commit>,
+    /// The adversarial behaviours of the node (if any).
+    byzantine: Byzantine,
+}
+
+/// Runs a whole committee in the current thread: each node runs its mempool and consensus (with
+/// an in-memory store) in its own tokio runtime, and connects to the others through a simulated
+/// network. The runtimes' clocks are paused and advanced in lockstep by the simulation, which
+/// runs the nodes of every tick in an order drawn from its seed. The network delays, the faults,
+/// the order of the nodes and the scheduling of their tasks (see `seed_scheduler`) are thus a function of the seed, and replaying a (failing) seed yields the
+/// same execution (see `simulation_tests`).
+pub struct Simulation {
+    seed: u64,
+    rng: StdRng,
+    network: SimulatedNetwork,
+    /// The link configuration after the global stabilization time.
+    synchronous: LinkConfig,
+    committee: Committee,
+    mempool_committee: MempoolCommittee,
+    nodes: Vec<Node>,
+    now: Time,
+    /// The faults to inject, with the time at which to inject them.
+    faults: Vec<(Time, Fault)>,
+    /// The digest and the parent of the blocks committed by the honest nodes, by position. They
+    /// must all form a single chain.
+    chain: BTreeMap<Position, (Digest, Digest)>,
+    /// The checkpoint interval and the retention of the nodes (see `Parameters`).
+    checkpoint_interval: u64,
+    retention: u64,
+    /// The leader election rule of the nodes.
+    leader_election: LeaderElection,
+    /// The runtimes of the crashed nodes. They are never run again, and are only dropped along
+    /// with the simulation: dropping a runtime shuts its tasks down in an arbitrary order.
+    graveyard: Vec<Runtime>,
+}
+
+impl Simulation {
+    pub fn new(seed: u64, link: LinkConfig) -> Self {
+        let network = SimulatedNetwork::new(seed, link);
+        let keys = keys();
+        let address = |port: usize| format!("127.0.0.1:{}", port).parse::<SocketAddr>().unwrap();
+        let committee = Committee::new(
+            keys.iter()
+                .enumerate()
+                .map(|(i, (name, secret))| {
+                    let (bls_key, _) = derive_bls_keypair(secret);
+                    (*name, /* stake */ 1, bls_key, address(1_000 + i))
+                })
+                .collect(),
+            /* epoch */ 1,
+        );
+        let mempool_committee = MempoolCommittee::new(
+            keys.iter()
+                .enumerate()
+                .map(|(i, (name, _))| (*name, 1, address(2_000 + i), address(3_000 + i)))
+                .collect(),
+            /* epoch */ 1,
+        );
+        let nodes = keys
+            .into_iter()
+            .map(|(name, _)| Node {
+                name,
+                db: MemoryDb::default(),
+                runtime: None,
+                rx_commit: None,
+                rx_evidence: None,
+                rx_checkpoint: None,
+                tx_reconfiguration: None,
+                tx_snapshot: None,
+                last_applied: None,
+                commits: Vec::new(),
+                byzantine: Byzantine::default(),
+            })
+            .collect();
+
+        let mut simulation = Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            network,
+            synchronous: LinkConfig {
+                drop_probability: 0.0,
+                ..link
+            },
+            committee,
+            mempool_committee,
+            nodes,
+            now: 0,
+            faults: Vec::new(),
+            chain: BTreeMap::new(),
+            checkpoint_interval: Parameters::default().checkpoint_interval,
+            retention: Parameters::default().retention,
+            leader_election: LeaderElection::default(),
+            graveyard: Vec::new(),
+        };
+        for host in 0..simulation.nodes.len() {
+            simulation.boot(host);
+        }
+        simulation
+    }
+
+    /// Restart a node with the specified adversarial behaviours. Byzantine nodes are exempt
+    /// from the safety and liveness checks.
+    #[cfg(feature = "byzantine")]
+    pub fn corrupt(&mut self, host: Host, byzantine: Byzantine) {
+        self.nodes[host].byzantine = byzantine;
+        if self.nodes[host].runtime.is_some() {
+            self.apply(Fault::Crash(host));
+            self.boot(host);
+        }
+    }
+
+    /// Restart all nodes, making them checkpoint every `checkpoint_interval` rounds and prune
+    /// their history beyond `retention` committed rounds.
+    pub fn enable_pruning(&mut self, checkpoint_interval: u64, retention: u64) {
+        self.checkpoint_interval = checkpoint_interval;
+        self.retention = retention;
+        self.restart();
+    }
+
+    /// Restart all nodes, making them elect leaders with the specified rule.
+    pub fn set_leader_election(&mut self, leader_election: LeaderElection) {
+        self.leader_election = leader_election;
+        self.restart();
+    }
+
+    /// Restart all the nodes that are up.
+    fn restart(&mut self) {
+        for host in 0..self.nodes.len() {
+            if self.nodes[host].runtime.is_some() {
+                self.apply(Fault::Crash(host));
+                self.boot(host);
+            }
+        }
+    }
+
+    /// Schedule a fault.
+    pub fn inject(&mut self, time: Time, fault: Fault) {
+        self.faults.push((time, fault));
+        self.faults.sort_by_key(|(time, _)| *time);
+    }
+
+    /// Schedule random faults (crashes and partitions, each affecting at most f nodes) until the
+    /// global stabilization time. After that time, no messages are lost, all nodes are up and
+    /// connected.
+    pub fn inject_random_faults(&mut self, gst: Time) {
+        const PERIODS: u64 = 4;
+        let size = self.nodes.len();
+        let f = (size - 1) / 3;
+        for i in 0..PERIODS {
+            let (start, end) = (i * gst / PERIODS, (i + 1) * gst / PERIODS);
+            let mut hosts: Vec<Host> = (0..size).collect();
+            hosts.shuffle(&mut self.rng);
+            let faulty: Vec<_> = hosts.drain(..self.rng.gen_range(0, f + 1)).collect();
+            match self.rng.gen_range(0, 3) {
+                0 => {
+                    for host in faulty {
+                        self.inject(start, Fault::Crash(host));
+                        self.inject(end, Fault::Recover(host));
+                    }
+                }
+                1 => {
+                    self.inject(start, Fault::Partition(vec![faulty, hosts]));
+                    self.inject(end, Fault::Heal);
+                }
+                _ => (),
+            }
+        }
+        self.inject(gst, Fault::Configure(self.synchronous));
+    }
+
+    /// Run the simulation until the specified time, checking safety along the way.
+    pub fn run(&mut self, until: Time) {
+        while self.now < until {
+            // Inject the faults that are due.
+            while !self.faults.is_empty() && self.faults[0].0 <= self.now {
+                let (_, fault) = self.faults.remove(0);
+                self.apply(fault);
+            }
+            self.now += 1;
+
+            // Let every node run for one tick, in random order.
+            let mut hosts: Vec<_> = (0..self.nodes.len())
+                .filter(|host| self.nodes[*host].runtime.is_some())
+                .collect();
+            hosts.shuffle(&mut self.rng);
+            for host in hosts {
+                self.network.enter(host);
+                let runtime = self.nodes[host].runtime.as_ref().unwrap();
+                runtime.block_on(async {
+                    tokio::time::advance(Duration::from_millis(1)).await;
+                    for _ in 0..YIELDS_PER_TICK {
+                        tokio::task::yield_now().await;
+                    }
+                });
+                self.collect(host);
+            }
+        }
+    }
+
+    /// Ensure every honest node that is up committed a block after the specified time.
+    pub fn check_liveness(&self, since: Time) {
+        for (host, node) in self.nodes.iter().enumerate() {
+            if !node.byzantine.is_honest() || node.runtime.is_none() {
+                continue;
+            }
+            if !node.commits.iter().any(|x| x.time > since) {
+                panic!(
+                    "Seed {}: node {} did not commit any block after {} ms",
+                    self.seed, host, since
+                );
+            }
+        }
+    }
+
+    /// The blocks committed by each node, in the order they were delivered to it.
+    pub fn trace(&self) -> Vec<Vec<Commit>> {
+        self.nodes.iter().map(|x| x.commits.clone()).collect()
+    }
+
+    /// The highest round committed by the specified node.
+    pub fn committed_round(&self, host: Host) -> Round {
+        self.nodes[host].commits.last().map_or(0, |x| x.round)
+    }
+
+    fn apply(&mut self, fault: Fault) {
+        match fault {
+            Fault::Crash(host) => {
+                self.network.crash(host);
+                let node = &mut self.nodes[host];
+                self.graveyard.extend(node.runtime.take());
+                node.rx_commit = None;
+                node.rx_evidence = None;
+                node.rx_checkpoint = None;
+                node.tx_reconfiguration = None;
+                node.tx_snapshot = None;
+            }
+            Fault::Recover(host) if self.nodes[host].runtime.is_none() => self.boot(host),
+            Fault::Recover(_) => (),
+            Fault::Partition(groups) => self.network.partition(&groups),
+            Fault::Heal => self.network.heal(),
+            Fault::Configure(link) => self.network.configure(link),
+        }
+    }
+
+    /// Start (or restart) a node from its storage.
+    fn boot(&mut self, host: Host) {
+        let mut builder = Builder::new_current_thread();
+        builder.enable_all().start_paused(true);
+        seed_scheduler(&mut builder, self.seed, host);
+        let runtime = builder.build().expect("Failed to build runtime");
+
+        let (tx_commit, rx_commit) = channel(CHANNEL_CAPACITY);
+        let (tx_evidence, rx_evidence) = channel(CHANNEL_CAPACITY);
+        let (tx_consensus_to_mempool, rx_consensus_to_mempool) = channel(CHANNEL_CAPACITY);
+        let (tx_mempool_to_consensus, rx_mempool_to_consensus) = channel(CHANNEL_CAPACITY);
+        let (tx_reconfiguration, rx_reconfiguration) = channel(CHANNEL_CAPACITY);
+        let (tx_snapshot, rx_snapshot) = channel(CHANNEL_CAPACITY);
+        let (tx_checkpoint, rx_checkpoint) = channel(CHANNEL_CAPACITY);
+
+        // Nodes re-derive their secret key from the fixture (secret keys cannot be copied).
+        let (_, secret) = keys().swap_remove(host);
+        self.network.enter(host);
+        let guard = runtime.enter();
+        let node = &self.nodes[host];
+        let store = Store::new_in_memory(node.db.clone());
+        let signature_service = SignatureService::new(secret);
+        let parameters = Parameters {
+            timeout_delay: 500,
+            max_timeout_delay: 2_000,
+            sync_retry_delay: 1_000,
+            checkpoint_interval: self.checkpoint_interval,
+            retention: self.retention,
+            leader_election: self.leader_election,
+            byzantine: node.byzantine.clone(),
+            ..Parameters::default()
+        };
+        let leader_elector = parameters
+            .leader_election
+            .elector(self.committee.clone(), parameters.commit_rule);
+        Mempool::spawn(
+            node.name,
+            signature_service.clone(),
+            self.mempool_committee.clone(),
+            MempoolParameters {
+                sync_retry_delay: 1_000,
+                batch_size: 1_000,
+                ..MempoolParameters::default()
+            },
+            store.clone(),
+            rx_consensus_to_mempool,
+            tx_mempool_to_consensus,
+            AdmitAll,
+        );
+        Consensus::spawn(
+            node.name,
+            self.committee.clone(),
+            parameters,
+            signature_service,
+            store,
+            leader_elector,
+            AcceptAll,
+            ProposeAll,
+            rx_mempool_to_consensus,
+            tx_consensus_to_mempool,
+            rx_reconfiguration,
+            rx_snapshot,
+            tx_commit,
+            tx_evidence,
+            tx_checkpoint,
+            node.last_applied.clone(),
+        );
+
+        // Spawn a client submitting transactions to the node.
+        let address = self.mempool_committee.transactions_address(&node.name);
+        let address = address.expect("Our public key is not in the committee");
+        tokio::spawn(async move {
+            let mut sender = SimpleSender::new();
+            for counter in 0u64.. {
+                sleep(Duration::from_millis(TRANSACTION_INTERVAL)).await;
+                let mut transaction = vec![0u8; 100];
+                transaction[..8].copy_from_slice(&counter.to_be_bytes());
+                transaction[8] = host as u8;
+                sender.send(address, Bytes::from(transaction)).await;
+            }
+        });
+        drop(guard);
+
+        let node = &mut self.nodes[host];
+        node.runtime = Some(runtime);
+        node.rx_commit = Some(rx_commit);
+        node.rx_evidence = Some(rx_evidence);
+        node.rx_checkpoint = Some(rx_checkpoint);
+        node.tx_reconfiguration = Some(tx_reconfiguration);
+        node.tx_snapshot = Some(tx_snapshot);
+    }
+
+    /// Record the blocks committed by an honest node, and ensure they do not conflict with the
+    /// blocks committed by the other honest nodes.
+    fn collect(&mut self, host: Host) {
+        let node = &mut self.nodes[host];
+        while let Some(Ok(_)) = node.rx_evidence.as_mut().map(|x| x.try_recv()) {}
+        while let Some(Ok(_)) = node.rx_checkpoint.as_mut().map(|x| x.try_recv()) {}
+        while let Some(Ok(committed)) = node.rx_commit.as_mut().map(|x| x.try_recv()) {
+            if !node.byzantine.is_honest() {
+                continue;
+            }
+            let block = committed.block.clone();
+            let digest = block.digest();
+            if let Some(last) = node.commits.last() {
+                assert!(
+                    block.round > last.round,
+                    "Seed {}: node {} committed round {} after round {}",
+                    self.seed,
+                    host,
+                    block.round,
+                    last.round
+                );
+            }
+            // Ensure the block is the one of the chain at its position, or that it links to the
+            // blocks of the chain around it. Nodes recovering from a checkpoint may skip blocks,
+            // but the other nodes commit them.
+            let position = (block.epoch, block.round);
+            match self.chain.get(&position) {
+                Some((existing, _)) => assert!(
+                    existing == &digest,
+                    "Seed {}: conflicting blocks committed at round {}",
+                    self.seed,
+                    block.round
+                ),
+                None => {
+                    if let Some((_, (previous, _))) = self.chain.range(..position).next_back() {
+                        assert!(
+                            previous == block.parent(),
+                            "Seed {}: block of round {} forks from the committed chain",
+                            self.seed,
+                            block.round
+                        );
+                    }
+                    if let Some((_, (_, parent))) = self.chain.range(position..).next() {
+                        assert!(
+                            parent == &digest,
+                            "Seed {}: block of round {} forks from the committed chain",
+                            self.seed,
+                            block.round
+                        );
+                    }
+                    self.chain
+                        .insert(position, (digest.clone(), block.parent().clone()));
+                }
+            }
+            // The (simulated) application's state is the last block delivered to it: it can
+            // snapshot any block, and each block attests its own snapshot.
+            if let Some(tx_snapshot) = node.tx_snapshot.as_ref() {
+                let _ = tx_snapshot.try_send((digest.clone(), Vec::new(), committed));
+            }
+            node.last_applied = Some(digest.clone());
+            node.commits.push(Commit {
+                time: self.now,
+                round: block.round,
+                digest,
+            });
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use super::*;
+use std::ops::Range;
+
+const LINK: LinkConfig = LinkConfig {
+    min_delay: 5,
+    max_delay: 50,
+    drop_probability: 0.0,
+};
+
+// The seeds to simulate. Set `SIMULATION_SEED` to replay a single (failing) seed.
+fn seeds(default: Range<u64>) -> Vec<u64> {
+    match std::env::var("SIMULATION_SEED") {
+        Ok(seed) => vec![seed.parse().expect("Invalid simulation seed")],
+        Err(_) => default.collect(),
+    }
+}
+
+#[test]
+fn simulate_synchronous_network() {
+    for seed in seeds(0..2) {
+        let mut simulation = Simulation::new(seed, LINK);
+        simulation.run(3_000);
+        simulation.check_liveness(0);
+    }
+}
+
+#[cfg(all(feature = "simulation", tokio_unstable))]
+#[test]
+fn simulate_replay() {
+    // Replaying a seed yields the same execution, faults included.
+    let link = LinkConfig {
+        drop_probability: 0.01,
+        ..LINK
+    };
+    for seed in seeds(0..1) {
+        let traces: Vec<_> = (0..2)
+            .map(|_| {
+                let mut simulation = Simulation::new(seed, link);
+                simulation.inject_random_faults(2_000);
+                simulation.run(4_000);
+                simulation.trace()
+            })
+            .collect();
+        assert!(traces[0] == traces[1], "Seed {}: replay diverged", seed);
+    }
+}
+
+#[test]
+fn simulate_crash_recovery() {
+    for seed in seeds(0..1) {
+        let mut simulation = Simulation::new(seed, LINK);
+        simulation.inject(1_000, Fault::Crash(0));
+        simulation.inject(5_000, Fault::Recover(0));
+
+        // The other nodes keep committing while the node is down.
+        simulation.run(5_000);
+        simulation.check_liveness(1_500);
+        let round = simulation.committed_round(0);
+
+        // The node catches up once it recovers.
+        simulation.run(10_000);
+        simulation.check_liveness(5_000);
+        assert!(simulation.committed_round(0) > round);
+    }
+}
+
+#[test]
+fn simulate_pruning() {
+    for seed in seeds(0..1) {
+        let mut simulation = Simulation::new(seed, LINK);
+        simulation.enable_pruning(/* checkpoint_interval */ 20, /* retention */ 10);
+        simulation.inject(1_000, Fault::Crash(0));
+        simulation.inject(5_000, Fault::Recover(0));
+
+        // The other nodes prune the history the node misses while it is down.
+        simulation.run(5_000);
+        simulation.check_liveness(1_500);
+        let round = simulation.committed_round(0);
+
+        // The node catches up from their checkpoints once it recovers.
+        simulation.run(10_000);
+        simulation.check_liveness(5_000);
+        assert!(simulation.committed_round(0) > round);
+    }
+}
+
+#[test]
+fn simulate_partition() {
+    for seed in seeds(0..1) {
+        let mut simulation = Simulation::new(seed, LINK);
+        simulation.inject(1_000, Fault::Partition(vec![vec![0, 1], vec![2, 3]]));
+        simulation.inject(3_000, Fault::Heal);
+
+        // No side of the partition has a quorum.
+        simulation.run(1_500);
+        let rounds: Vec<_> = (0..4).map(|x| simulation.committed_round(x)).collect();
+        simulation.run(3_000);
+        for (host, round) in rounds.into_iter().enumerate() {
+            assert_eq!(simulation.committed_round(host), round);
+        }
+
+        // The committee recovers once the partition heals.
+        simulation.run(8_000);
+        simulation.check_liveness(3_000);
+    }
+}
+
+#[test]
+fn simulate_reputation() {
+    for seed in seeds(0..1) {
+        let mut simulation = Simulation::new(seed, LINK);
+        let leader_election = LeaderElection::Reputation { window: 10, lag: 5 };
+        simulation.set_leader_election(leader_election);
+        simulation.inject(1_000, Fault::Crash(0));
+
+        // The other nodes keep committing while the node is down.
+        simulation.run(4_000);
+        simulation.check_liveness(1_500);
+
+        // The committee stops committing for longer than the lag, and recovers once the
+        // partition heals.
+        simulation.inject(4_000, Fault::Partition(vec![vec![1, 2], vec![3]]));
+        simulation.inject(6_000, Fault::Heal);
+        simulation.inject(6_000, Fault::Recover(0));
+        simulation.run(12_000);
+        simulation.check_liveness(7_000);
+    }
+}
+
+#[test]
+fn simulate_random_faults() {
+    let link = LinkConfig {
+        drop_probability: 0.01,
+        ..LINK
+    };
+    for seed in seeds(0..2) {
+        let mut simulation = Simulation::new(seed, link);
+        let gst = 4_000;
+        simulation.inject_random_faults(gst);
+        simulation.run(gst + 6_000);
+        simulation.check_liveness(gst);
+    }
+}
+
+#[cfg(feature = "byzantine")]
+#[test]
+fn simulate_byzantine_leader() {
+    for seed in seeds(0..2) {
+        let mut simulation = Simulation::new(seed, LINK);
+        let byzantine = Byzantine {
+            equivocate: true,
+            vote_stale_blocks: true,
+            inflate_tc: true,
+            flood_sync_requests: 10,
+            ..Byzantine::default()
+        };
+        simulation.corrupt(0, byzantine);
+
+        // Make every node time out for a while.
+        simulation.inject(1_000, Fault::Partition(vec![vec![0, 1], vec![2, 3]]));
+        simulation.inject(3_000, Fault::Heal);
+        simulation.run(8_000);
+        simulation.check_liveness(3_000);
+    }
+}
+
+#[cfg(feature = "byzantine")]
+#[test]
+fn simulate_withheld_votes() {
+    for seed in seeds(0..2) {
+        let mut simulation = Simulation::new(seed, LINK);
+        let byzantine = Byzantine {
+            equivocate: true,
+            withhold_votes: true,
+            ..Byzantine::default()
+        };
+        simulation.corrupt(0, byzantine);
+        simulation.run(5_000);
+        simulation.check_liveness(1_000);
+    }
+}
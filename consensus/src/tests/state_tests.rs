@@ -0,0 +1,49 @@
+use super::*;
+use crate::common::{keys, qc};
+use crate::messages::Timeout;
+use crypto::Hash as _;
+use std::fs;
+
+#[tokio::test]
+async fn load_missing_state() {
+    let path = ".db_test_load_missing_state";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // A fresh store does not hold any state.
+    match ConsensusState::load(&mut store).await {
+        Ok(None) => (),
+        _ => panic!("Unexpected consensus state"),
+    }
+}
+
+#[tokio::test]
+async fn persist_and_load_state() {
+    let path = ".db_test_persist_and_load_state";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Persist a state.
+    let (name, secret) = keys().pop().unwrap();
+    let state = ConsensusState {
+        round: 3,
+        last_voted_round: 2,
+        last_committed_round: 1,
+        last_committed: Digest::default(),
+        high_qc: qc(),
+        last_timeout: Some(Timeout::new_from_key(qc(), 3, name, &secret)),
+        ..ConsensusState::default()
+    };
+    assert!(state.persist(&mut store).await.is_ok());
+
+    // Ensure we read back the same state.
+    let loaded = ConsensusState::load(&mut store).await.unwrap().unwrap();
+    assert_eq!(loaded.round, state.round);
+    assert_eq!(loaded.last_voted_round, state.last_voted_round);
+    assert_eq!(loaded.last_committed_round, state.last_committed_round);
+    assert_eq!(loaded.high_qc, state.high_qc);
+    assert_eq!(
+        loaded.last_timeout.map(|x| x.digest()),
+        state.last_timeout.map(|x| x.digest())
+    );
+}
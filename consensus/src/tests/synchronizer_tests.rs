@@ -17,12 +17,14 @@ async fn get_existing_parent_block() {
     let _ = store.write(key, value).await;
 
     // Make a new synchronizer.
-    let (name, _) = keys().pop().unwrap();
+    let (name, secret) = keys().pop().unwrap();
     let (tx_loopback, _) = channel(10);
     let mut synchronizer = Synchronizer::new(
         name,
-        committee(),
+        SignatureService::new(secret),
+        watch::channel(committee()).1,
         store,
+        /* rx_reply */ channel(1).1,
         tx_loopback,
         /* sync_retry_delay */ 10_000,
     );
@@ -40,12 +42,14 @@ async fn get_genesis_parent_block() {
     let path = ".db_test_get_genesis_parent_block";
     let _ = fs::remove_dir_all(path);
     let store = Store::new(path).unwrap();
-    let (name, _) = keys().pop().unwrap();
+    let (name, secret) = keys().pop().unwrap();
     let (tx_loopback, _) = channel(1);
     let mut synchronizer = Synchronizer::new(
         name,
-        committee(),
+        SignatureService::new(secret),
+        watch::channel(committee()).1,
         store,
+        /* rx_reply */ channel(1).1,
         tx_loopback,
         /* sync_retry_delay */ 10_000,
     );
@@ -68,19 +72,28 @@ async fn get_missing_parent_block() {
     let path = ".db_test_get_missing_parent_block";
     let _ = fs::remove_dir_all(path);
     let mut store = Store::new(path).unwrap();
-    let (name, _) = keys().pop().unwrap();
+    let (name, secret) = keys().pop().unwrap();
+    let signature_service = SignatureService::new(secret);
     let (tx_loopback, mut rx_loopback) = channel(1);
     let mut synchronizer = Synchronizer::new(
         name,
-        committee.clone(),
+        signature_service.clone(),
+        watch::channel(committee.clone()).1,
         store.clone(),
+        /* rx_reply */ channel(1).1,
         tx_loopback,
         /* sync_retry_delay */ 10_000,
     );
 
     // Spawn a listener to receive our sync request.
     let address = committee.address(&block.author).unwrap();
-    let message = ConsensusMessage::SyncRequest(parent_block.digest(), name);
+    let request = Request::Sync(parent_block.digest(), 0);
+    let nonce = Nonce {
+        counter: 1,
+        ..Nonce::default()
+    };
+    let request = SignedRequest::new(request, name, block.author, nonce, signature_service).await;
+    let message = ConsensusMessage::Request(request);
     let expected = Bytes::from(bincode::serialize(&message).unwrap());
     let listener_handle = listener(address, Some(expected.clone()));
 
@@ -108,3 +121,48 @@ async fn get_missing_parent_block() {
     let delivered = rx_loopback.recv().await.unwrap();
     assert_eq!(delivered, block.clone());
 }
+
+#[tokio::test]
+async fn sync_ancestors_in_range() {
+    let committee = committee_with_base_port(12_100);
+    let mut chain = chain(keys());
+    let block = chain.pop().unwrap();
+
+    // Make a new synchronizer.
+    let path = ".db_test_sync_ancestors_in_range";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+    let (name, secret) = keys().pop().unwrap();
+    let (tx_reply, rx_reply) = channel(1);
+    let (tx_loopback, mut rx_loopback) = channel(10);
+    let mut synchronizer = Synchronizer::new(
+        name,
+        SignatureService::new(secret),
+        watch::channel(committee.clone()).1,
+        store,
+        rx_reply,
+        tx_loopback,
+        /* sync_retry_delay */ 10_000,
+    );
+
+    // Spawn a listener to receive our sync request.
+    let address = committee.address(&block.author).unwrap();
+    let listener_handle = listener(address, None);
+
+    // Ask for the parent of a block to the synchronizer. The store does not have any ancestor.
+    let ret = synchronizer.get_parent_block(&block).await;
+    assert!(ret.unwrap().is_none());
+    assert!(listener_handle.await.is_ok());
+
+    // Reply with all the missing ancestors (from the most recent one), followed by a block
+    // that does not link to them.
+    let mut reply: Vec<_> = chain.iter().rev().cloned().collect();
+    reply.push(block.clone());
+    tx_reply.send(reply).await.unwrap();
+
+    // Ensure the synchronizer only delivers the linked ancestors, starting from the oldest one.
+    for expected in chain {
+        let delivered = rx_loopback.recv().await.unwrap();
+        assert_eq!(delivered, expected);
+    }
+}
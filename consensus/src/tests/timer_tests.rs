@@ -2,8 +2,28 @@ use super::*;
 
 #[tokio::test]
 async fn schedule() {
-    let timer = Timer::new(100);
+    let timer = Timer::new(100, 1_000);
     let now = Instant::now();
     timer.await;
     assert!(now.elapsed().as_millis() > 95);
 }
+
+#[tokio::test]
+async fn back_off() {
+    let mut timer = Timer::new(100, 1_000);
+    assert_eq!(timer.delay(), 100);
+
+    // The delay doubles with every timeout certificate, up to the cap.
+    timer.back_off();
+    assert_eq!(timer.delay(), 200);
+    timer.back_off();
+    assert_eq!(timer.delay(), 400);
+    for _ in 0..100 {
+        timer.back_off();
+    }
+    assert_eq!(timer.delay(), 1_000);
+
+    // A quorum certificate brings it back to the base delay.
+    timer.restore();
+    assert_eq!(timer.delay(), 100);
+}
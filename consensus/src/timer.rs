@@ -7,21 +7,49 @@ use tokio::time::{sleep, Duration, Instant, Sleep};
 #[path = "tests/timer_tests.rs"]
 pub mod timer_tests;
 
+/// The pacemaker's timer. Its delay doubles with every consecutive round that ended with a
+/// timeout certificate (up to `max_delay`), and returns to `base_delay` as soon as a round
+/// ends with a quorum certificate.
 pub struct Timer {
-    duration: u64,
+    base_delay: u64,
+    max_delay: u64,
+    consecutive_timeouts: u32,
     sleep: Pin<Box<Sleep>>,
 }
 
 impl Timer {
-    pub fn new(duration: u64) -> Self {
-        let sleep = Box::pin(sleep(Duration::from_millis(duration)));
-        Self { duration, sleep }
+    pub fn new(base_delay: u64, max_delay: u64) -> Self {
+        let sleep = Box::pin(sleep(Duration::from_millis(base_delay)));
+        Self {
+            base_delay,
+            max_delay: max_delay.max(base_delay),
+            consecutive_timeouts: 0,
+            sleep,
+        }
+    }
+
+    /// The current timeout delay (in ms).
+    pub fn delay(&self) -> u64 {
+        2u64.checked_pow(self.consecutive_timeouts)
+            .and_then(|x| x.checked_mul(self.base_delay))
+            .map_or(self.max_delay, |x| x.min(self.max_delay))
+    }
+
+    /// Record that the last round ended with a timeout certificate.
+    pub fn back_off(&mut self) {
+        self.consecutive_timeouts = self.consecutive_timeouts.saturating_add(1);
+    }
+
+    /// Record that the last round ended with a quorum certificate.
+    pub fn restore(&mut self) {
+        self.consecutive_timeouts = 0;
     }
 
     pub fn reset(&mut self) {
+        let delay = self.delay();
         self.sleep
             .as_mut()
-            .reset(Instant::now() + Duration::from_millis(self.duration));
+            .reset(Instant::now() + Duration::from_millis(delay));
     }
 }
 
@@ -0,0 +1,23 @@
+use crate::messages::Block;
+use async_trait::async_trait;
+use mempool::Batch;
+
+/// Lets the application check the transactions of a block before we vote for it. All honest nodes
+/// must reach the same decision for a given block, so validators may only rely on deterministic
+/// data (eg. the block, its payload, and the committed chain).
+#[async_trait]
+pub trait ProposalValidator: Send + Sync + 'static {
+    /// Check the specified block, given the content of its payload (the batches are in the order
+    /// of `block.payload`). Return the reason of the rejection (if any).
+    async fn validate(&self, block: &Block, batches: &[Batch]) -> Result<(), String>;
+}
+
+/// Accepts every block.
+pub struct AcceptAll;
+
+#[async_trait]
+impl ProposalValidator for AcceptAll {
+    async fn validate(&self, _block: &Block, _batches: &[Batch]) -> Result<(), String> {
+        Ok(())
+    }
+}
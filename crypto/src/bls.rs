@@ -0,0 +1,212 @@
+use crate::{CryptoError, Digest, SecretKey};
+use blst::min_pk as bls;
+use blst::BLST_ERROR;
+use serde::{de, ser, Deserialize, Serialize};
+use std::convert::TryInto;
+use std::fmt;
+
+#[cfg(test)]
+#[path = "tests/bls_tests.rs"]
+pub mod bls_tests;
+
+// Both tags follow the proof-of-possession scheme of the IETF BLS signatures draft.
+const SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+const PROOF_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Separates the BLS keys from the ed25519 keys they are derived from.
+const KEY_INFO: &[u8] = b"hotstuff-bls-key";
+
+fn check(result: BLST_ERROR) -> Result<(), CryptoError> {
+    match result {
+        BLST_ERROR::BLST_SUCCESS => Ok(()),
+        e => Err(CryptoError::from_source(format!("{:?}", e))),
+    }
+}
+
+/// Represents a BLS public key, along with its proof of possession. The proof is checked upon
+/// decoding the key: it prevents rogue-key attacks against aggregate signatures.
+#[derive(Clone)]
+pub struct BlsPublicKey {
+    key: bls::PublicKey,
+    proof: BlsSignature,
+}
+
+impl BlsPublicKey {
+    pub fn encode_base64(&self) -> String {
+        let bytes = [&self.key.compress()[..], &self.proof.flatten()[..]].concat();
+        base64::encode(&bytes)
+    }
+
+    pub fn decode_base64(s: &str) -> Result<Self, CryptoError> {
+        let bytes = base64::decode(s).map_err(CryptoError::from_source)?;
+        if bytes.len() != 48 + 96 {
+            return Err(CryptoError::new());
+        }
+        let key = bls::PublicKey::key_validate(&bytes[..48])
+            .map_err(|e| CryptoError::from_source(format!("{:?}", e)))?;
+        let proof = BlsSignature::from_slice(&bytes[48..])?;
+        proof.verify_with_tag(&bytes[..48], &key, PROOF_DST)?;
+        Ok(Self { key, proof })
+    }
+}
+
+impl PartialEq for BlsPublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for BlsPublicKey {}
+
+impl fmt::Debug for BlsPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", base64::encode(&self.key.compress()[..]))
+    }
+}
+
+impl Serialize for BlsPublicKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_str(&self.encode_base64())
+    }
+}
+
+impl<'de> Deserialize<'de> for BlsPublicKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let value = Self::decode_base64(&s).map_err(|e| de::Error::custom(e.to_string()))?;
+        Ok(value)
+    }
+}
+
+/// Represents a BLS secret key. It is erased from memory when dropped.
+pub struct BlsSecretKey(bls::SecretKey);
+
+/// Derive the BLS key pair of a node from its (ed25519) secret key. Nodes thus only need to
+/// keep a single secret.
+pub fn derive_bls_keypair(secret: &SecretKey) -> (BlsPublicKey, BlsSecretKey) {
+    let secret = bls::SecretKey::key_gen(&secret.0[..32], KEY_INFO)
+        .expect("Failed to derive BLS secret key");
+    let key = secret.sk_to_pk();
+    let proof = BlsSignature::from(secret.sign(&key.compress(), PROOF_DST, &[]));
+    (BlsPublicKey { key, proof }, BlsSecretKey(secret))
+}
+
+/// Represents a BLS signature. Signatures over the same digest can be aggregated into a single
+/// signature of the same size.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct BlsSignature {
+    part1: [u8; 32],
+    part2: [u8; 32],
+    part3: [u8; 32],
+}
+
+impl From<bls::Signature> for BlsSignature {
+    fn from(signature: bls::Signature) -> Self {
+        let bytes = signature.compress();
+        Self {
+            part1: bytes[..32].try_into().expect("Unexpected signature length"),
+            part2: bytes[32..64]
+                .try_into()
+                .expect("Unexpected signature length"),
+            part3: bytes[64..].try_into().expect("Unexpected signature length"),
+        }
+    }
+}
+
+impl BlsSignature {
+    pub fn new(digest: &Digest, secret: &BlsSecretKey) -> Self {
+        Self::from(secret.0.sign(&digest.0, SIGNATURE_DST, &[]))
+    }
+
+    fn from_slice(bytes: &[u8]) -> Result<Self, CryptoError> {
+        if bytes.len() != 96 {
+            return Err(CryptoError::new());
+        }
+        Ok(Self {
+            part1: bytes[..32].try_into().expect("Unexpected signature length"),
+            part2: bytes[32..64]
+                .try_into()
+                .expect("Unexpected signature length"),
+            part3: bytes[64..].try_into().expect("Unexpected signature length"),
+        })
+    }
+
+    fn flatten(&self) -> [u8; 96] {
+        [self.part1, self.part2, self.part3]
+            .concat()
+            .try_into()
+            .expect("Unexpected signature length")
+    }
+
+    fn to_point(&self) -> Result<bls::Signature, CryptoError> {
+        bls::Signature::from_bytes(&self.flatten())
+            .map_err(|e| CryptoError::from_source(format!("{:?}", e)))
+    }
+
+    fn verify_with_tag(
+        &self,
+        message: &[u8],
+        key: &bls::PublicKey,
+        tag: &[u8],
+    ) -> Result<(), CryptoError> {
+        check(self.to_point()?.verify(true, message, tag, &[], key, false))
+    }
+
+    pub fn verify(&self, digest: &Digest, public_key: &BlsPublicKey) -> Result<(), CryptoError> {
+        self.verify_with_tag(&digest.0, &public_key.key, SIGNATURE_DST)
+    }
+
+    /// Aggregate signatures into a single signature.
+    pub fn aggregate<'a, I>(signatures: I) -> Result<Self, CryptoError>
+    where
+        I: IntoIterator<Item = &'a BlsSignature>,
+    {
+        let points = signatures
+            .into_iter()
+            .map(|x| x.to_point())
+            .collect::<Result<Vec<_>, _>>()?;
+        let points: Vec<_> = points.iter().collect();
+        bls::AggregateSignature::aggregate(&points, true)
+            .map(|x| Self::from(x.to_signature()))
+            .map_err(|e| CryptoError::from_source(format!("{:?}", e)))
+    }
+
+    /// Verify an aggregate signature over a single digest.
+    pub fn verify_aggregate<'a, I>(&self, digest: &Digest, keys: I) -> Result<(), CryptoError>
+    where
+        I: IntoIterator<Item = &'a BlsPublicKey>,
+    {
+        let keys: Vec<_> = keys.into_iter().map(|x| &x.key).collect();
+        check(
+            self.to_point()?
+                .fast_aggregate_verify(true, &digest.0, SIGNATURE_DST, &keys),
+        )
+    }
+
+    /// Verify an aggregate signature over several digests, each signed by a group of keys.
+    pub fn verify_aggregate_multi(
+        &self,
+        groups: &[(Digest, Vec<&BlsPublicKey>)],
+    ) -> Result<(), CryptoError> {
+        let mut messages = Vec::new();
+        let mut keys = Vec::new();
+        for (digest, group) in groups {
+            let group: Vec<_> = group.iter().map(|x| &x.key).collect();
+            let key = bls::AggregatePublicKey::aggregate(&group, false)
+                .map_err(|e| CryptoError::from_source(format!("{:?}", e)))?;
+            messages.push(&digest.0[..]);
+            keys.push(key.to_public_key());
+        }
+        let keys: Vec<_> = keys.iter().collect();
+        check(
+            self.to_point()?
+                .aggregate_verify(true, &messages, SIGNATURE_DST, &keys, false),
+        )
+    }
+}
@@ -15,6 +15,9 @@ use tokio::sync::oneshot;
 #[path = "tests/crypto_tests.rs"]
 pub mod crypto_tests;
 
+mod bls;
+pub use crate::bls::{derive_bls_keypair, BlsPublicKey, BlsSecretKey, BlsSignature};
+
 pub type CryptoError = ed25519::Error;
 
 /// Represents a hash digest (32 bytes).
@@ -72,9 +75,10 @@ impl PublicKey {
 
     pub fn decode_base64(s: &str) -> Result<Self, base64::DecodeError> {
         let bytes = base64::decode(s)?;
-        let array = bytes[..32]
-            .try_into()
-            .map_err(|_| base64::DecodeError::InvalidLength)?;
+        let array = bytes
+            .get(..32)
+            .and_then(|x| x.try_into().ok())
+            .ok_or(base64::DecodeError::InvalidLength)?;
         Ok(Self(array))
     }
 }
@@ -220,14 +224,24 @@ impl Signature {
 }
 
 /// This service holds the node's private key. It takes digests as input and returns a signature
-/// over the digest (through a oneshot channel).
+/// over the digest (through a oneshot channel). It also signs digests with the node's BLS key.
 #[derive(Clone)]
 pub struct SignatureService {
     channel: Sender<(Digest, oneshot::Sender<Signature>)>,
+    bls_channel: Sender<(Digest, oneshot::Sender<BlsSignature>)>,
 }
 
 impl SignatureService {
     pub fn new(secret: SecretKey) -> Self {
+        let (_, bls_secret) = derive_bls_keypair(&secret);
+        let (tx_bls, mut rx_bls): (Sender<(_, oneshot::Sender<_>)>, _) = channel(100);
+        tokio::spawn(async move {
+            while let Some((digest, sender)) = rx_bls.recv().await {
+                let signature = BlsSignature::new(&digest, &bls_secret);
+                let _ = sender.send(signature);
+            }
+        });
+
         let (tx, mut rx): (Sender<(_, oneshot::Sender<_>)>, _) = channel(100);
         tokio::spawn(async move {
             while let Some((digest, sender)) = rx.recv().await {
@@ -235,7 +249,20 @@ impl SignatureService {
                 let _ = sender.send(signature);
             }
         });
-        Self { channel: tx }
+        Self {
+            channel: tx,
+            bls_channel: tx_bls,
+        }
+    }
+
+    pub async fn request_bls_signature(&mut self, digest: Digest) -> BlsSignature {
+        let (sender, receiver): (oneshot::Sender<_>, oneshot::Receiver<_>) = oneshot::channel();
+        if let Err(e) = self.bls_channel.send((digest, sender)).await {
+            panic!("Failed to send message Signature Service: {}", e);
+        }
+        receiver
+            .await
+            .expect("Failed to receive signature from Signature Service")
     }
 
     pub async fn request_signature(&mut self, digest: Digest) -> Signature {
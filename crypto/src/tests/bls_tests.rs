@@ -0,0 +1,132 @@
+use super::*;
+use crate::crypto_tests::keys;
+use crate::Hash as _;
+
+fn bls_keys() -> Vec<(BlsPublicKey, BlsSecretKey)> {
+    keys()
+        .iter()
+        .map(|(_, secret)| derive_bls_keypair(secret))
+        .collect()
+}
+
+#[test]
+fn import_export_public_key() {
+    let (public_key, _) = bls_keys().pop().unwrap();
+    let export = public_key.encode_base64();
+    let import = BlsPublicKey::decode_base64(&export);
+    assert!(import.is_ok());
+    assert_eq!(import.unwrap(), public_key);
+}
+
+#[test]
+fn import_public_key_invalid_proof() {
+    // Attach the proof of possession of another key.
+    let mut keys = bls_keys();
+    let (public_key, _) = keys.pop().unwrap();
+    let (other, _) = keys.pop().unwrap();
+    let export = BlsPublicKey {
+        key: public_key.key,
+        proof: other.proof,
+    }
+    .encode_base64();
+    assert!(BlsPublicKey::decode_base64(&export).is_err());
+}
+
+#[test]
+fn derive_deterministic_keypair() {
+    let (_, secret) = keys().pop().unwrap();
+    let (public_key_1, _) = derive_bls_keypair(&secret);
+    let (public_key_2, _) = derive_bls_keypair(&secret);
+    assert_eq!(public_key_1, public_key_2);
+}
+
+#[test]
+fn verify_valid_signature() {
+    // Get a keypair.
+    let (public_key, secret_key) = bls_keys().pop().unwrap();
+
+    // Make signature.
+    let message: &[u8] = b"Hello, world!";
+    let digest = message.digest();
+    let signature = BlsSignature::new(&digest, &secret_key);
+
+    // Verify the signature.
+    assert!(signature.verify(&digest, &public_key).is_ok());
+}
+
+#[test]
+fn verify_invalid_signature() {
+    // Get a keypair.
+    let (public_key, secret_key) = bls_keys().pop().unwrap();
+
+    // Make signature.
+    let message: &[u8] = b"Hello, world!";
+    let digest = message.digest();
+    let signature = BlsSignature::new(&digest, &secret_key);
+
+    // Verify the signature.
+    let bad_message: &[u8] = b"Bad message!";
+    let digest = bad_message.digest();
+    assert!(signature.verify(&digest, &public_key).is_err());
+}
+
+#[test]
+fn verify_valid_aggregate() {
+    // Make signatures.
+    let message: &[u8] = b"Hello, world!";
+    let digest = message.digest();
+    let keys = bls_keys();
+    let signatures: Vec<_> = keys
+        .iter()
+        .take(3)
+        .map(|(_, secret)| BlsSignature::new(&digest, secret))
+        .collect();
+
+    // Aggregate the signatures and verify the aggregate.
+    let aggregate = BlsSignature::aggregate(&signatures).unwrap();
+    let public_keys = keys.iter().take(3).map(|(name, _)| name);
+    assert!(aggregate.verify_aggregate(&digest, public_keys).is_ok());
+}
+
+#[test]
+fn verify_invalid_aggregate() {
+    // Make signatures.
+    let message: &[u8] = b"Hello, world!";
+    let digest = message.digest();
+    let keys = bls_keys();
+    let signatures: Vec<_> = keys
+        .iter()
+        .take(3)
+        .map(|(_, secret)| BlsSignature::new(&digest, secret))
+        .collect();
+
+    // Verify the aggregate against the wrong set of keys.
+    let aggregate = BlsSignature::aggregate(&signatures).unwrap();
+    let public_keys = keys.iter().skip(1).map(|(name, _)| name);
+    assert!(aggregate.verify_aggregate(&digest, public_keys).is_err());
+}
+
+#[test]
+fn verify_valid_aggregate_multi() {
+    // Make signatures over two different messages.
+    let message_1: &[u8] = b"Hello, world!";
+    let message_2: &[u8] = b"Goodbye, world!";
+    let (digest_1, digest_2) = (message_1.digest(), message_2.digest());
+    let keys = bls_keys();
+    let signatures: Vec<_> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, (_, secret))| match i % 2 {
+            0 => BlsSignature::new(&digest_1, secret),
+            _ => BlsSignature::new(&digest_2, secret),
+        })
+        .collect();
+
+    // Aggregate the signatures and verify the aggregate.
+    let aggregate = BlsSignature::aggregate(&signatures).unwrap();
+    let groups = vec![
+        (digest_1, vec![&keys[0].0, &keys[2].0]),
+        (digest_2, vec![&keys[1].0, &keys[3].0]),
+    ];
+    assert!(aggregate.verify_aggregate_multi(&groups).is_ok());
+}
@@ -0,0 +1,17 @@
+use consensus::ConsensusError;
+use crypto::Digest;
+use thiserror::Error;
+
+pub type LightClientResult<T> = Result<T, LightClientError>;
+
+#[derive(Error, Debug)]
+pub enum LightClientError {
+    #[error(transparent)]
+    InvalidMessage(#[from] ConsensusError),
+
+    #[error("QC does not certify block {0}")]
+    InvalidCertificate(Digest),
+
+    #[error("Block {0} does not extend the chain we follow")]
+    UnknownParent(Digest),
+}
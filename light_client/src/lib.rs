@@ -0,0 +1,5 @@
+mod error;
+mod light_client;
+
+pub use crate::error::{LightClientError, LightClientResult};
+pub use crate::light_client::LightClient;
@@ -0,0 +1,93 @@
+use crate::error::{LightClientError, LightClientResult};
+use consensus::{Block, Committee, QC};
+use crypto::Digest;
+use crypto::Hash as _;
+use std::collections::{HashMap, HashSet};
+
+#[cfg(test)]
+#[path = "tests/light_client_tests.rs"]
+pub mod light_client_tests;
+
+/// Follows the chain from a trusted block by checking certificates against a known committee,
+/// without running a replica. It does not (yet) follow the chain across epochs.
+pub struct LightClient {
+    /// The committee certifying the blocks we follow.
+    committee: Committee,
+    /// The last block known to be final.
+    finalized: Block,
+    /// The certified blocks extending the last final block, indexed by digest.
+    certified: HashMap<Digest, Block>,
+}
+
+impl LightClient {
+    pub fn new(committee: Committee, trusted: Block) -> Self {
+        Self {
+            committee,
+            finalized: trusted,
+            certified: HashMap::new(),
+        }
+    }
+
+    /// The last block known to be final.
+    pub fn finalized(&self) -> &Block {
+        &self.finalized
+    }
+
+    /// Check a block along with the QC certifying it. The block must extend the last final block
+    /// or a certified block we already checked. Return the blocks that became final under the
+    /// 2-chain rule, starting from the oldest one.
+    pub fn process(&mut self, block: Block, qc: &QC) -> LightClientResult<Vec<Block>> {
+        let digest = block.digest();
+        if block.round <= self.finalized.round || self.certified.contains_key(&digest) {
+            return Ok(Vec::new());
+        }
+
+        // Check the block (including its embedded QC and TC) and its certificate.
+        if qc.hash != digest || qc.round != block.round {
+            return Err(LightClientError::InvalidCertificate(digest));
+        }
+        block.verify(&self.committee)?;
+        qc.verify(&self.committee)?;
+
+        // Ensure the block extends the chain we follow.
+        let parent = block.parent().clone();
+        if parent != self.finalized.digest() && !self.certified.contains_key(&parent) {
+            return Err(LightClientError::UnknownParent(digest));
+        }
+
+        // The parent is final if the block extends it at the next round.
+        let round = block.round;
+        self.certified.insert(digest, block);
+        let b0 = match self.certified.get(&parent) {
+            Some(b0) if b0.round + 1 == round => b0.clone(),
+            _ => return Ok(Vec::new()),
+        };
+
+        // Its uncommitted ancestors are final as well.
+        let mut finalized = vec![b0.clone()];
+        let last = self.finalized.digest();
+        while finalized.last().unwrap().parent() != &last {
+            let ancestor = self.certified[finalized.last().unwrap().parent()].clone();
+            finalized.push(ancestor);
+        }
+        finalized.reverse();
+        self.finalized = b0;
+        self.cleanup();
+        Ok(finalized)
+    }
+
+    /// Only keep the certified blocks extending the last final block.
+    fn cleanup(&mut self) {
+        let mut blocks: Vec<_> = self.certified.drain().map(|(_, x)| x).collect();
+        blocks.sort_by_key(|x| x.round);
+        let mut extending = HashSet::new();
+        extending.insert(self.finalized.digest());
+        for block in blocks {
+            if block.round > self.finalized.round && extending.contains(block.parent()) {
+                let digest = block.digest();
+                extending.insert(digest.clone());
+                self.certified.insert(digest, block);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use super::*;
+use consensus::Signers;
+use crypto::{derive_bls_keypair, generate_keypair, BlsSignature, PublicKey, SecretKey, Signature};
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
+
+// Fixture.
+fn keys() -> Vec<(PublicKey, SecretKey)> {
+    let mut rng = StdRng::from_seed([0; 32]);
+    (0..4).map(|_| generate_keypair(&mut rng)).collect()
+}
+
+// Fixture.
+fn committee() -> Committee {
+    Committee::new(
+        keys()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, secret))| {
+                let address = format!("127.0.0.1:{}", i).parse().unwrap();
+                let (bls_key, _) = derive_bls_keypair(&secret);
+                (name, /* stake */ 1, bls_key, address)
+            })
+            .collect(),
+        /* epoch */ 1,
+    )
+}
+
+// Fixture.
+fn make_qc(block: &Block) -> QC {
+    let qc = QC {
+        hash: block.digest(),
+        round: block.round,
+        epoch: block.epoch,
+        ..QC::default()
+    };
+    let digest = qc.digest();
+    let signatures: Vec<_> = keys()
+        .iter()
+        .map(|(_, secret)| BlsSignature::new(&digest, &derive_bls_keypair(secret).1))
+        .collect();
+    QC {
+        signers: Signers::new(keys().iter().map(|(name, _)| name), &committee()),
+        signature: BlsSignature::aggregate(&signatures).unwrap(),
+        ..qc
+    }
+}
+
+// Fixture.
+fn make_block(parent: Option<&Block>, round: u64) -> Block {
+    let (author, secret) = keys().pop().unwrap();
+    let block = Block {
+        qc: parent.map(make_qc).unwrap_or_default(),
+        author,
+        round,
+        epoch: committee().epoch,
+        ..Block::default()
+    };
+    let signature = Signature::new(&block.digest(), &secret);
+    Block { signature, ..block }
+}
+
+fn digests(blocks: &[Block]) -> Vec<Digest> {
+    blocks.iter().map(|x| x.digest()).collect()
+}
+
+// Fixture: a chain of blocks at the specified rounds.
+fn chain(rounds: &[u64]) -> Vec<Block> {
+    let mut chain: Vec<Block> = Vec::new();
+    for round in rounds {
+        let block = make_block(chain.last(), *round);
+        chain.push(block);
+    }
+    chain
+}
+
+#[test]
+fn follow_chain() {
+    let chain = chain(&[1, 2, 3, 4]);
+    let mut client = LightClient::new(committee(), chain[0].clone());
+
+    // A single certified block does not make its parent final.
+    let finalized = client.process(chain[1].clone(), &make_qc(&chain[1]));
+    assert!(finalized.unwrap().is_empty());
+
+    // Each following block finalizes its parent (2-chain rule).
+    for i in 2..4 {
+        let finalized = client.process(chain[i].clone(), &make_qc(&chain[i]));
+        assert_eq!(digests(&finalized.unwrap()), digests(&chain[i - 1..i]));
+        assert_eq!(client.finalized().digest(), chain[i - 1].digest());
+    }
+}
+
+#[test]
+fn finalize_ancestors() {
+    // The 2-chain only forms after a missing round.
+    let chain = chain(&[1, 2, 4, 5]);
+    let mut client = LightClient::new(committee(), chain[0].clone());
+    for block in &chain[1..3] {
+        let finalized = client.process(block.clone(), &make_qc(block));
+        assert!(finalized.unwrap().is_empty());
+    }
+
+    // The 2-chain finalizes its head and all its ancestors.
+    let finalized = client.process(chain[3].clone(), &make_qc(&chain[3]));
+    assert_eq!(digests(&finalized.unwrap()), digests(&chain[1..3]));
+}
+
+#[test]
+fn reject_unknown_parent() {
+    let chain = chain(&[1, 2, 3]);
+    let mut client = LightClient::new(committee(), chain[0].clone());
+    match client.process(chain[2].clone(), &make_qc(&chain[2])) {
+        Err(LightClientError::UnknownParent(digest)) => assert_eq!(digest, chain[2].digest()),
+        _ => panic!("Unexpected result"),
+    }
+}
+
+#[test]
+fn reject_invalid_certificate() {
+    let chain = chain(&[1, 2, 3]);
+    let mut client = LightClient::new(committee(), chain[0].clone());
+
+    // Certify the block with the QC of another block.
+    match client.process(chain[1].clone(), &make_qc(&chain[2])) {
+        Err(LightClientError::InvalidCertificate(digest)) => assert_eq!(digest, chain[1].digest()),
+        _ => panic!("Unexpected result"),
+    }
+
+    // Certify the block with a QC without a quorum.
+    let qc = QC {
+        signers: Signers::new(keys().iter().take(2).map(|(name, _)| name), &committee()),
+        ..make_qc(&chain[1])
+    };
+    assert!(client.process(chain[1].clone(), &qc).is_err());
+}
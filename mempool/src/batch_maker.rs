@@ -1,18 +1,17 @@
+use crate::certificate::Certificate;
+use crate::config::Committee;
 use crate::mempool::MempoolMessage;
 use crate::quorum_waiter::QuorumWaiterMessage;
+use crate::receipt_waiter::{ReceiptWaiterMessage, Subscriber};
 use bytes::Bytes;
-#[cfg(feature = "benchmark")]
-use crypto::Digest;
-use crypto::PublicKey;
-#[cfg(feature = "benchmark")]
+use crypto::{Digest, PublicKey, SignatureService};
 use ed25519_dalek::{Digest as _, Sha512};
 #[cfg(feature = "benchmark")]
 use log::info;
 use network::ReliableSender;
-#[cfg(feature = "benchmark")]
 use std::convert::TryInto as _;
-use std::net::SocketAddr;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::watch;
 use tokio::time::{sleep, Duration, Instant};
 
 #[cfg(test)]
@@ -24,41 +23,58 @@ pub type Batch = Vec<Transaction>;
 
 /// Assemble clients transactions into batches.
 pub struct BatchMaker {
+    /// The public key of this authority.
+    name: PublicKey,
+    /// Service to sign our batches.
+    signature_service: SignatureService,
+    /// The committee information (updated upon epoch change).
+    committee: watch::Receiver<Committee>,
     /// The preferred batch size (in bytes).
     batch_size: usize,
     /// The maximum delay after which to seal the batch (in ms).
     max_batch_delay: u64,
-    /// Channel to receive transactions from the network.
-    rx_transaction: Receiver<Transaction>,
+    /// Channel to receive transactions from the network (along with the client waiting for its
+    /// receipt, if any).
+    rx_transaction: Receiver<(Transaction, Option<Subscriber>)>,
     /// Output channel to deliver sealed batches to the `QuorumWaiter`.
     tx_message: Sender<QuorumWaiterMessage>,
-    /// The network addresses of the other mempools.
-    mempool_addresses: Vec<(PublicKey, SocketAddr)>,
+    /// Channel to subscribe clients to the receipts of their transactions.
+    tx_receipt_waiter: Sender<ReceiptWaiterMessage>,
     /// Holds the current batch.
     current_batch: Batch,
     /// Holds the size of the current batch (in bytes).
     current_batch_size: usize,
+    /// Holds the clients waiting for the receipts of the transactions of the current batch.
+    current_subscribers: Vec<Subscriber>,
     /// A network sender to broadcast the batches to the other mempools.
     network: ReliableSender,
 }
 
 impl BatchMaker {
+    #[allow(clippy::too_many_arguments)]
     pub fn spawn(
+        name: PublicKey,
+        signature_service: SignatureService,
+        committee: watch::Receiver<Committee>,
         batch_size: usize,
         max_batch_delay: u64,
-        rx_transaction: Receiver<Transaction>,
+        rx_transaction: Receiver<(Transaction, Option<Subscriber>)>,
         tx_message: Sender<QuorumWaiterMessage>,
-        mempool_addresses: Vec<(PublicKey, SocketAddr)>,
+        tx_receipt_waiter: Sender<ReceiptWaiterMessage>,
     ) {
         tokio::spawn(async move {
             Self {
+                name,
+                signature_service,
+                committee,
                 batch_size,
                 max_batch_delay,
                 rx_transaction,
                 tx_message,
-                mempool_addresses,
+                tx_receipt_waiter,
                 current_batch: Batch::with_capacity(batch_size * 2),
                 current_batch_size: 0,
+                current_subscribers: Vec::new(),
                 network: ReliableSender::new(),
             }
             .run()
@@ -74,9 +90,10 @@ impl BatchMaker {
         loop {
             tokio::select! {
                 // Assemble client transactions into batches of preset size.
-                Some(transaction) = self.rx_transaction.recv() => {
+                Some((transaction, subscriber)) = self.rx_transaction.recv() => {
                     self.current_batch_size += transaction.len();
                     self.current_batch.push(transaction);
+                    self.current_subscribers.extend(subscriber);
                     if self.current_batch_size >= self.batch_size {
                         self.seal().await;
                         timer.as_mut().reset(Instant::now() + Duration::from_millis(self.max_batch_delay));
@@ -111,21 +128,30 @@ impl BatchMaker {
             .filter_map(|tx| tx[1..9].try_into().ok())
             .collect();
 
-        // Serialize the batch.
+        // Serialize and sign the batch.
         self.current_batch_size = 0;
         let batch: Vec<_> = self.current_batch.drain(..).collect();
         let message = MempoolMessage::Batch(batch);
         let serialized = bincode::serialize(&message).expect("Failed to serialize our own batch");
+        let digest = Self::digest(&serialized);
+        let epoch = self.committee.borrow().epoch;
+        let signature = self
+            .signature_service
+            .request_signature(Certificate::author_digest(&digest, &self.name, epoch))
+            .await;
+
+        // Subscribe the clients to the receipts of their transactions. We do it before broadcasting
+        // the batch, so it cannot be committed before the subscription.
+        if !self.current_subscribers.is_empty() {
+            let subscribers = self.current_subscribers.drain(..).collect();
+            self.tx_receipt_waiter
+                .send(ReceiptWaiterMessage::Subscribe(digest.clone(), subscribers))
+                .await
+                .expect("Failed to send subscription");
+        }
 
         #[cfg(feature = "benchmark")]
         {
-            // NOTE: This is one extra hash that is only needed to print the following log entries.
-            let digest = Digest(
-                Sha512::digest(&serialized).as_slice()[..32]
-                    .try_into()
-                    .unwrap(),
-            );
-
             for id in tx_ids {
                 // NOTE: This log entry is used to compute performance.
                 info!(
@@ -139,18 +165,35 @@ impl BatchMaker {
             info!("Batch {:?} contains {} B", digest, size);
         }
 
-        // Broadcast the batch through the network.
-        let (names, addresses): (Vec<_>, _) = self.mempool_addresses.iter().cloned().unzip();
-        let bytes = Bytes::from(serialized.clone());
-        let handlers = self.network.broadcast(addresses, bytes).await;
+        // Broadcast the batch through the network (to the mempools of the current committee).
+        let (names, addresses): (Vec<_>, _) = self
+            .committee
+            .borrow()
+            .broadcast_addresses(&self.name)
+            .into_iter()
+            .unzip();
+        let message = MempoolMessage::SignedBatch(serialized.clone(), self.name, signature.clone());
+        let bytes = bincode::serialize(&message).expect("Failed to serialize our own batch");
+        let handlers = self.network.broadcast(addresses, Bytes::from(bytes)).await;
 
         // Send the batch through the deliver channel for further processing.
         self.tx_message
             .send(QuorumWaiterMessage {
                 batch: serialized,
+                digest,
+                signature,
                 handlers: names.into_iter().zip(handlers.into_iter()).collect(),
             })
             .await
             .expect("Failed to deliver batch");
     }
+
+    /// The digest of a serialized batch (as computed by the `Processor`).
+    fn digest(serialized: &[u8]) -> Digest {
+        Digest(
+            Sha512::digest(serialized).as_slice()[..32]
+                .try_into()
+                .unwrap(),
+        )
+    }
 }
@@ -0,0 +1,83 @@
+use crate::config::{Committee, EpochNumber};
+use crate::error::{MempoolError, MempoolResult};
+use crypto::{Digest, PublicKey, Signature};
+use ed25519_dalek::{Digest as _, Sha512};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::TryInto as _;
+
+#[cfg(test)]
+#[path = "tests/certificate_tests.rs"]
+pub mod certificate_tests;
+
+/// The prefix of the keys under which we store the certificates (batches are stored under
+/// their digest).
+const CERTIFICATE_KEY_PREFIX: &[u8] = b"certificate";
+
+/// An availability certificate: the signature of the batch's author along with the votes of
+/// other authorities, proving that a quorum stored the batch.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Certificate {
+    pub digest: Digest,
+    pub author: PublicKey,
+    /// The signature of the author over the batch (see `Certificate::author_digest`).
+    pub signature: Signature,
+    /// The votes of the other authorities (see `Certificate::vote_digest`).
+    pub votes: Vec<(PublicKey, Signature)>,
+}
+
+impl Certificate {
+    /// The store key of the certificate of the specified batch.
+    pub fn key(digest: &Digest) -> Vec<u8> {
+        [CERTIFICATE_KEY_PREFIX, &digest.0[..]].concat()
+    }
+
+    /// The digest signed by the author of a batch upon broadcasting it during the specified
+    /// epoch. Signatures of an epoch are thus worthless for the committees of the other epochs.
+    pub fn author_digest(digest: &Digest, author: &PublicKey, epoch: EpochNumber) -> Digest {
+        let mut hasher = Sha512::new();
+        hasher.update(b"batch");
+        hasher.update(epoch.to_le_bytes());
+        hasher.update(author);
+        hasher.update(digest);
+        Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+    }
+
+    /// The digest signed by the authorities voting for a batch they stored during the specified
+    /// epoch.
+    pub fn vote_digest(digest: &Digest, epoch: EpochNumber) -> Digest {
+        let mut hasher = Sha512::new();
+        hasher.update(b"vote");
+        hasher.update(epoch.to_le_bytes());
+        hasher.update(digest);
+        Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+    }
+
+    pub fn verify(&self, committee: &Committee) -> MempoolResult<()> {
+        // Ensure the certificate has a quorum (the author stored its own batch).
+        let mut weight = committee.stake(&self.author);
+        if weight == 0 {
+            return Err(MempoolError::UnknownAuthority(self.author));
+        }
+        let mut used = HashSet::from([self.author]);
+        for (name, _) in &self.votes {
+            if !used.insert(*name) {
+                return Err(MempoolError::AuthorityReuse(*name));
+            }
+            let stake = committee.stake(name);
+            if stake == 0 {
+                return Err(MempoolError::UnknownAuthority(*name));
+            }
+            weight += stake;
+        }
+        if weight < committee.quorum_threshold() {
+            return Err(MempoolError::CertificateRequiresQuorum);
+        }
+
+        // Check the signatures.
+        let author_digest = Self::author_digest(&self.digest, &self.author, committee.epoch);
+        self.signature.verify(&author_digest, &self.author)?;
+        let vote_digest = Self::vote_digest(&self.digest, committee.epoch);
+        Signature::verify_batch(&vote_digest, &self.votes).map_err(MempoolError::from)
+    }
+}
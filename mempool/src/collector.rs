@@ -0,0 +1,174 @@
+use crate::certificate::Certificate;
+use crate::config::EpochNumber;
+use crate::deduplicator::Deduplicator;
+use crate::mempool::Round;
+use crypto::Digest;
+use log::{debug, error};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryInto as _;
+use std::sync::{Arc, Mutex};
+use store::{Store, StoreError};
+use tokio::sync::mpsc::Receiver;
+
+#[cfg(test)]
+#[path = "tests/collector_tests.rs"]
+pub mod collector_tests;
+
+/// The prefix of the storage keys indexing the batches we track by position (see `index_key`).
+const INDEX_PREFIX: &[u8] = b"mempool_batches_index";
+
+/// The position (epoch and round) of the consensus when we stored a batch.
+type Position = (EpochNumber, Round);
+
+/// The storage key indexing a batch by position. Keys are ordered by position.
+fn index_key(position: Position, digest: &Digest) -> Vec<u8> {
+    let (epoch, round) = position;
+    [
+        INDEX_PREFIX,
+        &epoch.to_be_bytes(),
+        &round.to_be_bytes(),
+        &digest.0,
+    ]
+    .concat()
+}
+
+/// Parse a key made by `index_key`.
+fn parse_index_key(key: &[u8]) -> Option<(Position, Digest)> {
+    let key = key.strip_prefix(INDEX_PREFIX)?;
+    if key.len() != 16 + 8 + 32 {
+        return None;
+    }
+    let epoch = EpochNumber::from_be_bytes(key[..16].try_into().ok()?);
+    let round = Round::from_be_bytes(key[16..24].try_into().ok()?);
+    let digest = Digest(key[24..].try_into().ok()?);
+    Some(((epoch, round), digest))
+}
+
+#[derive(Debug)]
+pub enum CollectorMessage {
+    /// We stored a batch or its certificate.
+    Stored(Digest),
+    /// The consensus committed the batches (it prunes them itself).
+    Commit(Vec<Digest>),
+    /// The consensus reached the specified round.
+    Cleanup(Round),
+    /// The consensus started the specified epoch.
+    Reconfigure(EpochNumber),
+    /// The consensus pruned its history below the specified position.
+    Prune(EpochNumber, Round),
+}
+
+/// Deletes the batches (and certificates) that are not committed by the time the consensus
+/// prunes its history past them, ie. at least `retention` committed rounds after we stored them
+/// (see the consensus' `Pruner`). It tracks the batches by position in storage, so that it also
+/// deletes the batches stored before a restart. Proposers buffer certified batches until they are
+/// proposed: a batch waiting for more than `retention` rounds may thus be proposed after we
+/// deleted it, in which case its block fails to gather votes.
+pub struct Collector {
+    store: Store,
+    /// Input channel to receive notifications from the other mempool tasks.
+    rx_message: Receiver<CollectorMessage>,
+    /// The recently committed batches (we do not track them).
+    deduplicator: Arc<Mutex<Deduplicator>>,
+    /// Loosely keep track of the consensus' position.
+    position: Position,
+    /// The uncommitted batches we stored, by position (mirrored in storage).
+    batches: BTreeMap<Position, HashSet<Digest>>,
+    /// The position of each tracked batch.
+    positions: HashMap<Digest, Position>,
+}
+
+impl Collector {
+    pub fn spawn(
+        store: Store,
+        epoch: EpochNumber,
+        rx_message: Receiver<CollectorMessage>,
+        deduplicator: Arc<Mutex<Deduplicator>>,
+    ) {
+        tokio::spawn(async move {
+            let mut collector = Self {
+                store,
+                rx_message,
+                deduplicator,
+                position: (epoch, Round::default()),
+                batches: BTreeMap::new(),
+                positions: HashMap::new(),
+            };
+            if let Err(e) = collector.load().await {
+                error!("Failed to load the batches to collect: {}", e);
+            }
+            collector.run().await;
+        });
+    }
+
+    /// Load the batches we tracked before booting. We resume from the latest position we know of
+    /// (until the consensus notifies us of its round).
+    async fn load(&mut self) -> Result<(), StoreError> {
+        for (key, _) in self.store.scan(INDEX_PREFIX.to_vec()).await? {
+            if let Some((position, digest)) = parse_index_key(&key) {
+                self.track(position, digest);
+            }
+        }
+        if let Some((&(epoch, round), _)) = self.batches.iter().next_back() {
+            if epoch == self.position.0 {
+                self.position.1 = round;
+            }
+        }
+        Ok(())
+    }
+
+    fn track(&mut self, position: Position, digest: Digest) -> bool {
+        if self.positions.contains_key(&digest) {
+            return false;
+        }
+        self.positions.insert(digest.clone(), position);
+        self.batches
+            .entry(position)
+            .or_insert_with(HashSet::new)
+            .insert(digest);
+        true
+    }
+
+    async fn run(&mut self) {
+        while let Some(message) = self.rx_message.recv().await {
+            match message {
+                CollectorMessage::Stored(digest) => {
+                    if self.deduplicator.lock().unwrap().is_committed(&digest) {
+                        continue;
+                    }
+                    let position = self.position;
+                    if self.track(position, digest.clone()) {
+                        self.store
+                            .write(index_key(position, &digest), Vec::new())
+                            .await;
+                    }
+                }
+                CollectorMessage::Commit(digests) => {
+                    for digest in digests {
+                        if let Some(position) = self.positions.remove(&digest) {
+                            if let Some(batches) = self.batches.get_mut(&position) {
+                                batches.remove(&digest);
+                            }
+                            self.store.delete(index_key(position, &digest)).await;
+                        }
+                    }
+                }
+                CollectorMessage::Cleanup(round) => self.position.1 = round,
+                CollectorMessage::Reconfigure(epoch) => self.position = (epoch, Round::default()),
+                CollectorMessage::Prune(epoch, round) => {
+                    let kept = self.batches.split_off(&(epoch, round));
+                    let stale = std::mem::replace(&mut self.batches, kept);
+                    for (position, digests) in stale {
+                        for digest in digests {
+                            debug!("Deleting uncommitted batch {}", digest);
+                            self.positions.remove(&digest);
+                            self.store.delete(digest.to_vec()).await;
+                            self.store.delete(Certificate::key(&digest)).await;
+                            self.store.delete(index_key(position, &digest)).await;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
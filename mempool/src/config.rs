@@ -4,6 +4,23 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 
+// The defaults of the optional parameters (shared by parameter files and `Parameters::default`).
+fn default_sync_max_requests() -> u64 {
+    1_000
+}
+
+fn default_sync_max_bytes() -> u64 {
+    100_000_000
+}
+
+fn default_dedup_capacity() -> usize {
+    100_000
+}
+
+fn default_max_transaction_size() -> usize {
+    100_000
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct Parameters {
     /// The depth of the garbage collection (Denominated in number of rounds).
@@ -13,12 +30,30 @@ pub struct Parameters {
     /// Determine with how many nodes to sync when re-trying to send sync-request. These nodes
     /// are picked at random from the committee.
     pub sync_retry_nodes: usize,
+    /// The maximum number of batches we send each second to a single node (to reply to its sync
+    /// requests). Zero removes the limit.
+    #[serde(default = "default_sync_max_requests")]
+    pub sync_max_requests: u64,
+    /// The maximum number of bytes we send each second to a single node (to reply to its sync
+    /// requests). Zero removes the limit.
+    #[serde(default = "default_sync_max_bytes")]
+    pub sync_max_bytes: u64,
     /// The preferred batch size. The workers seal a batch of transactions when it reaches this size.
     /// Denominated in bytes.
     pub batch_size: usize,
     /// The delay after which the workers seal a batch of transactions, even if `max_batch_size`
     /// is not reached. Denominated in ms.
     pub max_batch_delay: u64,
+    /// The maximum number of recent transactions (and committed batches) we remember to reject
+    /// duplicates.
+    #[serde(default = "default_dedup_capacity")]
+    pub dedup_capacity: usize,
+    /// The maximum size of a client transaction (including its envelope). Denominated in bytes.
+    #[serde(default = "default_max_transaction_size")]
+    pub max_transaction_size: usize,
+    /// Whether clients must wrap their transactions in a signed envelope (see `SignedTransaction`).
+    #[serde(default)]
+    pub signed_transactions: bool,
 }
 
 impl Default for Parameters {
@@ -27,8 +62,13 @@ impl Default for Parameters {
             gc_depth: 50,
             sync_retry_delay: 5_000,
             sync_retry_nodes: 3,
+            sync_max_requests: default_sync_max_requests(),
+            sync_max_bytes: default_sync_max_bytes(),
             batch_size: 500_000,
             max_batch_delay: 100,
+            dedup_capacity: default_dedup_capacity(),
+            max_transaction_size: default_max_transaction_size(),
+            signed_transactions: false,
         }
     }
 }
@@ -39,15 +79,26 @@ impl Parameters {
         info!("Garbage collection depth set to {} rounds", self.gc_depth);
         info!("Sync retry delay set to {} ms", self.sync_retry_delay);
         info!("Sync retry nodes set to {} nodes", self.sync_retry_nodes);
+        info!(
+            "Sync requests quota set to {} per s",
+            self.sync_max_requests
+        );
+        info!("Sync bytes quota set to {} B per s", self.sync_max_bytes);
         info!("Batch size set to {} B", self.batch_size);
         info!("Max batch delay set to {} ms", self.max_batch_delay);
+        info!("Deduplication capacity set to {}", self.dedup_capacity);
+        info!(
+            "Max transaction size set to {} B",
+            self.max_transaction_size
+        );
+        info!("Signed transactions set to {}", self.signed_transactions);
     }
 }
 
 pub type EpochNumber = u128;
 pub type Stake = u32;
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Authority {
     /// The voting power of this authority.
     pub stake: Stake,
@@ -57,7 +108,7 @@ pub struct Authority {
     pub mempool_address: SocketAddr,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Committee {
     pub authorities: HashMap<PublicKey, Authority>,
     pub epoch: EpochNumber,
@@ -104,12 +155,15 @@ impl Committee {
         self.authorities.get(name).map(|x| x.mempool_address)
     }
 
-    /// Returns the mempool addresses of all nodes except `myself`.
+    /// Returns the mempool addresses of all nodes except `myself` (sorted by name).
     pub fn broadcast_addresses(&self, myself: &PublicKey) -> Vec<(PublicKey, SocketAddr)> {
-        self.authorities
+        let mut addresses: Vec<_> = self
+            .authorities
             .iter()
             .filter(|(name, _)| name != &myself)
             .map(|(name, x)| (*name, x.mempool_address))
-            .collect()
+            .collect();
+        addresses.sort();
+        addresses
     }
 }
@@ -0,0 +1,131 @@
+use crate::mempool::Round;
+use crypto::Digest;
+use ed25519_dalek::{Digest as _, Sha512};
+use std::collections::{HashSet, VecDeque};
+use std::convert::TryInto as _;
+
+#[cfg(test)]
+#[path = "tests/deduplicator_tests.rs"]
+pub mod deduplicator_tests;
+
+/// The hash identifying a transaction.
+pub fn transaction_digest(transaction: &[u8]) -> Digest {
+    Digest(
+        Sha512::digest(transaction).as_slice()[..32]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+/// A set of recent digests, along with the round at which we added them (oldest first).
+#[derive(Default)]
+struct Recent {
+    digests: HashSet<Digest>,
+    order: VecDeque<(Round, Digest)>,
+}
+
+impl Recent {
+    fn insert(&mut self, digest: Digest, round: Round, capacity: usize) -> bool {
+        if !self.digests.insert(digest.clone()) {
+            return false;
+        }
+        self.order.push_back((round, digest));
+        while self.order.len() > capacity {
+            if let Some((_, digest)) = self.order.pop_front() {
+                self.digests.remove(&digest);
+            }
+        }
+        true
+    }
+
+    fn cleanup(&mut self, gc_round: Round) {
+        while let Some((round, _)) = self.order.front() {
+            if *round >= gc_round {
+                break;
+            }
+            if let Some((_, digest)) = self.order.pop_front() {
+                self.digests.remove(&digest);
+            }
+        }
+    }
+
+    fn reset_rounds(&mut self) {
+        for (round, _) in self.order.iter_mut() {
+            *round = Round::default();
+        }
+    }
+}
+
+/// Remembers the hashes of the transactions we recently admitted and the digests of the batches
+/// recently committed (along with the hashes of their transactions), to reject duplicates. Entries
+/// are forgotten after `gc_depth` rounds (or earlier if there are more than `capacity` of them).
+/// A transaction submitted to several nodes is rejected by all of them once it is committed, but
+/// nodes admitting it before that may still get it committed more than once.
+pub struct Deduplicator {
+    /// The depth of the garbage collection.
+    gc_depth: Round,
+    /// The maximum number of admitted transactions (and of committed batches and transactions)
+    /// we remember.
+    capacity: usize,
+    /// Loosely keep track of the consensus's round number (only used for cleanup).
+    round: Round,
+    transactions: Recent,
+    batches: Recent,
+    committed: Recent,
+}
+
+impl Deduplicator {
+    pub fn new(gc_depth: Round, capacity: usize) -> Self {
+        Self {
+            gc_depth,
+            capacity,
+            round: Round::default(),
+            transactions: Recent::default(),
+            batches: Recent::default(),
+            committed: Recent::default(),
+        }
+    }
+
+    /// Register a transaction (identified by its hash). Returns false if we recently admitted it,
+    /// or if it was recently committed (whichever node it was submitted to).
+    pub fn admit(&mut self, transaction: Digest) -> bool {
+        !self.committed.digests.contains(&transaction)
+            && self
+                .transactions
+                .insert(transaction, self.round, self.capacity)
+    }
+
+    /// Register the batches committed by the consensus, along with (the hashes of) their
+    /// transactions.
+    pub fn commit(&mut self, batches: Vec<Digest>, transactions: Vec<Digest>) {
+        for digest in batches {
+            self.batches.insert(digest, self.round, self.capacity);
+        }
+        for digest in transactions {
+            self.committed.insert(digest, self.round, self.capacity);
+        }
+    }
+
+    /// Whether the batch was recently committed.
+    pub fn is_committed(&self, batch: &Digest) -> bool {
+        self.batches.digests.contains(batch)
+    }
+
+    /// Forget the entries older than the garbage collection depth.
+    pub fn cleanup(&mut self, round: Round) {
+        self.round = round;
+        let gc_round = round.saturating_sub(self.gc_depth);
+        self.transactions.cleanup(gc_round);
+        self.batches.cleanup(gc_round);
+        self.committed.cleanup(gc_round);
+    }
+
+    /// The round numbers restart with every new epoch: we keep the entries of the previous epoch
+    /// for another `gc_depth` rounds.
+    pub fn reconfigure(&mut self) {
+        self.round = Round::default();
+        self.transactions.reset_rounds();
+        self.batches.reset_rounds();
+        self.committed.reset_rounds();
+    }
+}
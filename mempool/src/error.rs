@@ -0,0 +1,28 @@
+use crypto::{CryptoError, Digest, PublicKey};
+use thiserror::Error;
+
+pub type MempoolResult<T> = Result<T, MempoolError>;
+
+#[derive(Error, Debug)]
+pub enum MempoolError {
+    #[error("Invalid signature")]
+    InvalidSignature(#[from] CryptoError),
+
+    #[error("Received malformed batch {0}")]
+    MalformedBatch(Digest),
+
+    #[error("Received batch or vote from unknown authority {0}")]
+    UnknownAuthority(PublicKey),
+
+    #[error("Received more than one vote from {0}")]
+    AuthorityReuse(PublicKey),
+
+    #[error("Received request addressed to {0}")]
+    MisaddressedRequest(PublicKey),
+
+    #[error("Received replayed request from {0}")]
+    ReplayedRequest(PublicKey),
+
+    #[error("Received certificate without a quorum")]
+    CertificateRequiresQuorum,
+}
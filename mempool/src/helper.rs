@@ -1,39 +1,177 @@
-use crate::config::Committee;
+use crate::config::{Committee, EpochNumber};
+use crate::error::{MempoolError, MempoolResult};
+use crate::mempool::{MempoolMessage, Round};
+use crate::processor::pruned_key;
 use bytes::Bytes;
-use crypto::{Digest, PublicKey};
+use crypto::{Digest, Hash, PublicKey, Signature, SignatureService};
+use ed25519_dalek::{Digest as _, Sha512};
 use log::{error, warn};
-use network::SimpleSender;
+use network::{RequestQuota, SimpleSender};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryInto as _;
 use store::Store;
 use tokio::sync::mpsc::Receiver;
+use tokio::sync::watch;
 
 #[cfg(test)]
 #[path = "tests/helper_tests.rs"]
 pub mod helper_tests;
 
+/// We reject the requests made more than this number of rounds before the latest request of
+/// their origin.
+pub const REPLAY_WINDOW: Round = 100;
+
+/// The maximum number of requests we remember per authority to reject replays.
+pub const MAX_REPLAY_ENTRIES: usize = 10_000;
+
+/// Distinguishes the batch requests of an authority: its epoch and (consensus) round when it made
+/// the request, and a counter. Helpers reject requests they already served, or that are too old.
+#[derive(
+    Clone, Copy, Serialize, Deserialize, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub struct Nonce {
+    pub epoch: EpochNumber,
+    pub round: Round,
+    pub counter: u64,
+}
+
+/// A request for missing batches, signed by its origin and addressed to a single authority.
+/// Helpers charge the (authenticated) origin for serving it.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BatchRequest {
+    pub digests: Vec<Digest>,
+    pub origin: PublicKey,
+    pub recipient: PublicKey,
+    pub nonce: Nonce,
+    pub signature: Signature,
+}
+
+impl BatchRequest {
+    pub async fn new(
+        digests: Vec<Digest>,
+        origin: PublicKey,
+        recipient: PublicKey,
+        nonce: Nonce,
+        mut signature_service: SignatureService,
+    ) -> Self {
+        let request = Self {
+            digests,
+            origin,
+            recipient,
+            nonce,
+            signature: Signature::default(),
+        };
+        let signature = signature_service.request_signature(request.digest()).await;
+        Self {
+            signature,
+            ..request
+        }
+    }
+
+    pub fn verify(&self, committee: &Committee, name: &PublicKey) -> MempoolResult<()> {
+        // Ensure the origin is in the committee.
+        if committee.mempool_address(&self.origin).is_none() {
+            return Err(MempoolError::UnknownAuthority(self.origin));
+        }
+
+        // Ensure the request is meant for us, so that it cannot be replayed to other authorities.
+        if &self.recipient != name {
+            return Err(MempoolError::MisaddressedRequest(self.recipient));
+        }
+
+        // Check the signature.
+        self.signature
+            .verify(&self.digest(), &self.origin)
+            .map_err(MempoolError::from)
+    }
+}
+
+impl Hash for BatchRequest {
+    fn digest(&self) -> Digest {
+        let mut hasher = Sha512::new();
+        hasher.update(b"batch_request");
+        hasher.update((self.digests.len() as u64).to_le_bytes());
+        for digest in &self.digests {
+            hasher.update(digest);
+        }
+        hasher.update(self.origin);
+        hasher.update(self.recipient);
+        hasher.update(self.nonce.epoch.to_le_bytes());
+        hasher.update(self.nonce.round.to_le_bytes());
+        hasher.update(self.nonce.counter.to_le_bytes());
+        Digest(hasher.finalize().as_slice()[..32].try_into().unwrap())
+    }
+}
+
+/// The requests an authority recently made us, to reject replays.
+#[derive(Default)]
+struct Replays {
+    /// The digests of the requests, ordered by nonce.
+    seen: BTreeSet<(Nonce, Digest)>,
+    /// We forgot the requests up to this nonce, so we reject them all.
+    floor: Option<Nonce>,
+}
+
+impl Replays {
+    /// Record a request. Fails if we already received it, or if it is too old to tell.
+    fn insert(&mut self, nonce: Nonce, digest: Digest) -> bool {
+        if self.floor.is_some_and(|x| nonce <= x) || !self.seen.insert((nonce, digest.clone())) {
+            return false;
+        }
+
+        // Forget the requests outside the window of the latest one (including, possibly, the
+        // request we just recorded).
+        let latest = self.seen.last().map(|(x, _)| *x).unwrap_or_default();
+        while let Some((oldest, _)) = self.seen.first() {
+            let stale = oldest.epoch < latest.epoch
+                || oldest.round + REPLAY_WINDOW < latest.round
+                || self.seen.len() > MAX_REPLAY_ENTRIES;
+            if !stale {
+                break;
+            }
+            self.floor = self.seen.pop_first().map(|(x, _)| x);
+        }
+        self.seen.contains(&(nonce, digest))
+    }
+}
+
 /// A task dedicated to help other authorities by replying to their batch requests.
 pub struct Helper {
-    /// The committee information.
-    committee: Committee,
+    /// The public key of this authority.
+    name: PublicKey,
+    /// The committee information (updated upon epoch change).
+    committee: watch::Receiver<Committee>,
     /// The persistent storage.
     store: Store,
-    /// Input channel to receive batch requests.
-    rx_request: Receiver<(Vec<Digest>, PublicKey)>,
+    /// Input channel to receive (signed) batch requests.
+    rx_request: Receiver<BatchRequest>,
     /// A network sender to send the batches to the other mempools.
     network: SimpleSender,
+    /// Limits the resources each authority can make us spend.
+    quota: RequestQuota<PublicKey, Digest>,
+    /// The requests each authority recently made us.
+    replays: HashMap<PublicKey, Replays>,
 }
 
 impl Helper {
     pub fn spawn(
-        committee: Committee,
+        name: PublicKey,
+        committee: watch::Receiver<Committee>,
         store: Store,
-        rx_request: Receiver<(Vec<Digest>, PublicKey)>,
+        rx_request: Receiver<BatchRequest>,
+        max_requests: u64,
+        max_bytes: u64,
     ) {
         tokio::spawn(async move {
             Self {
+                name,
                 committee,
                 store,
                 rx_request,
                 network: SimpleSender::new(),
+                quota: RequestQuota::new(max_requests, max_bytes),
+                replays: HashMap::new(),
             }
             .run()
             .await;
@@ -41,26 +179,75 @@ impl Helper {
     }
 
     async fn run(&mut self) {
-        while let Some((digests, origin)) = self.rx_request.recv().await {
-            // TODO [issue #7]: Do some accounting to prevent bad nodes from monopolizing our resources.
+        while let Some(request) = self.rx_request.recv().await {
+            // Ensure the request comes from its origin: we charge the origin for serving it.
+            if let Err(e) = request.verify(&self.committee.borrow(), &self.name) {
+                warn!(
+                    "Received invalid batch request from {}: {}",
+                    request.origin, e
+                );
+                continue;
+            }
+
+            // Ensure the request is not replayed (by another authority) to spend its quota.
+            let origin = request.origin;
+            let fresh = self
+                .replays
+                .entry(origin)
+                .or_default()
+                .insert(request.nonce, request.digest());
+            if !fresh {
+                let e = MempoolError::ReplayedRequest(origin);
+                warn!("Dropped batch request: {}", e);
+                continue;
+            }
 
             // get the requestors address.
-            let address = match self.committee.mempool_address(&origin) {
+            let address = match self.committee.borrow().mempool_address(&origin) {
                 Some(x) => x,
-                None => {
-                    warn!("Received batch request from unknown authority: {}", origin);
-                    continue;
-                }
+                None => continue,
             };
 
-            // Reply to the request (the best we can).
-            for digest in digests {
+            // Reply to the request (the best we can). We prevent bad nodes from monopolizing our
+            // resources by limiting the number of batches (and bytes) we send them.
+            let mut pruned = Vec::new();
+            for digest in request.digests {
+                if let Err(e) = self.quota.admit(&origin, digest.clone()) {
+                    warn!("Dropped batch request for {}: {}", digest, e);
+                    continue;
+                }
                 match self.store.read(digest.to_vec()).await {
-                    Ok(Some(data)) => self.network.send(address, Bytes::from(data)).await,
-                    Ok(None) => (),
+                    Ok(Some(data)) => {
+                        if let Err(e) = self.quota.charge(&origin, data.len()) {
+                            warn!("Stopped replying to {}: {}", origin, e);
+                            break;
+                        }
+                        self.network.send(address, Bytes::from(data)).await
+                    }
+                    Ok(None) => {
+                        if let Ok(Some(_)) = self.store.read(pruned_key(&digest)).await {
+                            warn!(
+                                "Cannot serve batch {} to {}: it is below our retention horizon",
+                                digest, origin
+                            );
+                            pruned.push(digest);
+                        }
+                    }
                     Err(e) => error!("{}", e),
                 }
             }
+
+            // Let the requestor know it should look for the pruned batches elsewhere.
+            if !pruned.is_empty() {
+                let message = MempoolMessage::BatchPruned(pruned, self.name);
+                let serialized =
+                    bincode::serialize(&message).expect("Failed to serialize our own message");
+                if let Err(e) = self.quota.charge(&origin, serialized.len()) {
+                    warn!("Stopped replying to {}: {}", origin, e);
+                    continue;
+                }
+                self.network.send(address, Bytes::from(serialized)).await;
+            }
         }
     }
 }
@@ -1,14 +1,27 @@
 mod batch_maker;
+mod certificate;
+mod collector;
 mod config;
+mod deduplicator;
+mod error;
 mod helper;
 mod mempool;
 mod processor;
 mod quorum_waiter;
+mod receipt_waiter;
 mod synchronizer;
+mod validator;
 
 #[cfg(test)]
 #[path = "tests/common.rs"]
 mod common;
 
+pub use crate::batch_maker::{Batch, Transaction};
+pub use crate::certificate::Certificate;
 pub use crate::config::{Committee, Parameters};
-pub use crate::mempool::{ConsensusMempoolMessage, Mempool};
+pub use crate::error::{MempoolError, MempoolResult};
+pub use crate::helper::{BatchRequest, Nonce};
+pub use crate::mempool::{ConsensusMempoolMessage, Mempool, MempoolMessage};
+pub use crate::processor::pruned_key;
+pub use crate::receipt_waiter::Receipt;
+pub use crate::validator::{AdmitAll, BasicValidator, SignedTransaction, TransactionValidator};
@@ -1,19 +1,30 @@
 use crate::batch_maker::{Batch, BatchMaker, Transaction};
-use crate::config::{Committee, Parameters};
-use crate::helper::Helper;
-use crate::processor::{Processor, SerializedBatchMessage};
+use crate::certificate::Certificate;
+use crate::collector::{Collector, CollectorMessage};
+use crate::config::{Committee, EpochNumber, Parameters};
+use crate::deduplicator::{transaction_digest, Deduplicator};
+use crate::error::{MempoolError, MempoolResult};
+use crate::helper::{BatchRequest, Helper};
+use crate::processor::{Processor, ProcessorMessage, SerializedBatchMessage};
 use crate::quorum_waiter::QuorumWaiter;
-use crate::synchronizer::Synchronizer;
+use crate::receipt_waiter::{Receipt, ReceiptWaiter, ReceiptWaiterMessage, Subscriber};
+use crate::synchronizer::{SyncReply, Synchronizer};
+use crate::validator::TransactionValidator;
 use async_trait::async_trait;
 use bytes::Bytes;
-use crypto::{Digest, PublicKey};
+use crypto::{Digest, PublicKey, Signature, SignatureService};
+use ed25519_dalek::{Digest as _, Sha512};
 use futures::sink::SinkExt as _;
 use log::{info, warn};
 use network::{MessageHandler, Receiver as NetworkReceiver, Writer};
 use serde::{Deserialize, Serialize};
+use std::convert::TryInto as _;
 use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use store::Store;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::{oneshot, watch};
 
 #[cfg(test)]
 #[path = "tests/mempool_tests.rs"]
@@ -28,8 +39,20 @@ pub type Round = u64;
 /// The message exchanged between the nodes' mempool.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum MempoolMessage {
+    /// A batch replying to a batch request (we drop the batches we did not request).
     Batch(Batch),
-    BatchRequest(Vec<Digest>, /* origin */ PublicKey),
+    /// A serialized `MempoolMessage::Batch` message broadcast by its author, along with the
+    /// author's signature (see `Certificate::author_digest`).
+    SignedBatch(
+        SerializedBatchMessage,
+        /* author */ PublicKey,
+        Signature,
+    ),
+    /// A request for missing batches, signed by its origin.
+    BatchRequest(BatchRequest),
+    /// A reply to a batch request, listing the requested batches that its origin pruned.
+    BatchPruned(Vec<Digest>, /* origin */ PublicKey),
+    Certificate(Certificate),
 }
 
 /// The messages sent by the consensus and the mempool.
@@ -39,11 +62,20 @@ pub enum ConsensusMempoolMessage {
     Synchronize(Vec<Digest>, /* target */ PublicKey),
     /// The consensus notifies the mempool of a round update.
     Cleanup(Round),
+    /// The consensus notifies the mempool that it committed the batches in the specified round.
+    Commit(Vec<Digest>, Round),
+    /// The consensus notifies the mempool that a new epoch started with the specified committee.
+    Reconfigure(Committee),
+    /// The consensus notifies the mempool that it pruned its history below the specified round
+    /// of the specified epoch: the mempool deletes the uncommitted batches it stored before.
+    Prune(EpochNumber, Round),
 }
 
 pub struct Mempool {
     /// The public key of this authority.
     name: PublicKey,
+    /// Service to sign our batches and our votes for the batches of the others.
+    signature_service: SignatureService,
     /// The committee information.
     committee: Committee,
     /// The configuration parameters.
@@ -52,33 +84,57 @@ pub struct Mempool {
     store: Store,
     /// Send messages to consensus.
     tx_consensus: Sender<Digest>,
+    /// Receives the committee of the current epoch.
+    rx_committee: watch::Receiver<Committee>,
+    /// The recent transactions and committed batches (to reject duplicates).
+    deduplicator: Arc<Mutex<Deduplicator>>,
+    /// Notifies the `Collector` of the batches we store.
+    tx_collector: Sender<CollectorMessage>,
 }
 
 impl Mempool {
-    pub fn spawn(
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn<V: TransactionValidator>(
         name: PublicKey,
+        signature_service: SignatureService,
         committee: Committee,
         parameters: Parameters,
         store: Store,
         rx_consensus: Receiver<ConsensusMempoolMessage>,
         tx_consensus: Sender<Digest>,
+        validator: V,
     ) {
         // NOTE: This log entry is used to compute performance.
         parameters.log();
 
         // Define a mempool instance.
+        let (tx_committee, rx_committee) = watch::channel(committee.clone());
+        let deduplicator = Deduplicator::new(parameters.gc_depth, parameters.dedup_capacity);
+        let (tx_collector, rx_collector) = channel(CHANNEL_CAPACITY);
         let mempool = Self {
             name,
+            signature_service,
             committee,
             parameters,
             store,
             tx_consensus,
+            rx_committee,
+            deduplicator: Arc::new(Mutex::new(deduplicator)),
+            tx_collector,
         };
 
         // Spawn all mempool tasks.
-        mempool.handle_consensus_messages(rx_consensus);
-        mempool.handle_clients_transactions();
-        mempool.handle_mempool_messages();
+        let (tx_receipt_waiter, rx_receipt_waiter) = channel(CHANNEL_CAPACITY);
+        let (tx_reply, rx_reply) = channel(CHANNEL_CAPACITY);
+        mempool.handle_consensus_messages(
+            rx_consensus,
+            rx_reply,
+            rx_collector,
+            tx_committee,
+            tx_receipt_waiter.clone(),
+        );
+        mempool.handle_clients_transactions(tx_receipt_waiter, rx_receipt_waiter, validator);
+        mempool.handle_mempool_messages(tx_reply);
 
         info!(
             "Mempool successfully booted on {}",
@@ -91,27 +147,57 @@ impl Mempool {
     }
 
     /// Spawn all tasks responsible to handle messages from the consensus.
-    fn handle_consensus_messages(&self, rx_consensus: Receiver<ConsensusMempoolMessage>) {
+    fn handle_consensus_messages(
+        &self,
+        rx_consensus: Receiver<ConsensusMempoolMessage>,
+        rx_reply: Receiver<SyncReply>,
+        rx_collector: Receiver<CollectorMessage>,
+        tx_committee: watch::Sender<Committee>,
+        tx_receipt_waiter: Sender<ReceiptWaiterMessage>,
+    ) {
         // The `Synchronizer` is responsible to keep the mempool in sync with the others. It handles the commands
-        // it receives from the consensus (which are mainly notifications that we are out of sync).
+        // it receives from the consensus (which are mainly notifications that we are out of sync), and stores the
+        // batches we requested from the other mempools. It also publishes the new committee to all other mempool
+        // tasks upon epoch change, and forwards the commit notifications to the `ReceiptWaiter`.
         Synchronizer::spawn(
             self.name,
+            self.signature_service.clone(),
             self.committee.clone(),
             self.store.clone(),
             self.parameters.gc_depth,
             self.parameters.sync_retry_delay,
             self.parameters.sync_retry_nodes,
             /* rx_message */ rx_consensus,
+            rx_reply,
+            tx_committee,
+            tx_receipt_waiter,
+            self.tx_collector.clone(),
+            self.deduplicator.clone(),
+        );
+
+        // The `Collector` deletes the batches that are not committed by the time the consensus prunes its
+        // history past them.
+        Collector::spawn(
+            self.store.clone(),
+            self.committee.epoch,
+            rx_collector,
+            self.deduplicator.clone(),
         );
     }
 
     /// Spawn all tasks responsible to handle clients transactions.
-    fn handle_clients_transactions(&self) {
+    fn handle_clients_transactions<V: TransactionValidator>(
+        &self,
+        tx_receipt_waiter: Sender<ReceiptWaiterMessage>,
+        rx_receipt_waiter: Receiver<ReceiptWaiterMessage>,
+        validator: V,
+    ) {
         let (tx_batch_maker, rx_batch_maker) = channel(CHANNEL_CAPACITY);
         let (tx_quorum_waiter, rx_quorum_waiter) = channel(CHANNEL_CAPACITY);
         let (tx_processor, rx_processor) = channel(CHANNEL_CAPACITY);
 
-        // We first receive clients' transactions from the network.
+        // We first receive clients' transactions from the network, and only keep the ones admitted by the
+        // validator (and not recently admitted).
         let mut address = self
             .committee
             .transactions_address(&self.name)
@@ -119,42 +205,55 @@ impl Mempool {
         address.set_ip("0.0.0.0".parse().unwrap());
         NetworkReceiver::spawn(
             address,
-            /* handler */ TxReceiverHandler { tx_batch_maker },
+            /* handler */
+            TxReceiverHandler::new(
+                tx_batch_maker,
+                Arc::new(validator),
+                self.deduplicator.clone(),
+            ),
         );
 
         // The transactions are sent to the `BatchMaker` that assembles them into batches. It then broadcasts
         // (in a reliable manner) the batches to all other mempools that share the same `id` as us. Finally,
         // it gathers the 'cancel handlers' of the messages and send them to the `QuorumWaiter`.
         BatchMaker::spawn(
+            self.name,
+            self.signature_service.clone(),
+            self.rx_committee.clone(),
             self.parameters.batch_size,
             self.parameters.max_batch_delay,
             /* rx_transaction */ rx_batch_maker,
             /* tx_message */ tx_quorum_waiter,
-            /* mempool_addresses */
-            self.committee.broadcast_addresses(&self.name),
+            tx_receipt_waiter,
         );
 
-        // The `QuorumWaiter` waits for 2f authorities to acknowledge reception of the batch. It then forwards
-        // the batch to the `Processor`.
+        // The `QuorumWaiter` waits for 2f authorities to vote for the batch (once they stored it). It then
+        // broadcasts the availability certificate of the batch, and forwards both to the `Processor`.
         QuorumWaiter::spawn(
-            self.committee.clone(),
-            /* stake */ self.committee.stake(&self.name),
+            self.name,
+            self.rx_committee.clone(),
             /* rx_message */ rx_quorum_waiter,
-            /* tx_batch */ tx_processor,
+            tx_processor,
         );
 
-        // The `Processor` hashes and stores the batch. It then forwards the batch's digest to the consensus.
+        // The `Processor` hashes and stores the batch and its certificate. It then forwards the batch's digest
+        // to the consensus.
         Processor::spawn(
             self.store.clone(),
-            /* rx_batch */ rx_processor,
+            /* rx_message */ rx_processor,
             /* tx_digest */ self.tx_consensus.clone(),
+            self.tx_collector.clone(),
+            self.deduplicator.clone(),
         );
 
+        // The `ReceiptWaiter` sends their receipts to the clients once their transactions are committed.
+        ReceiptWaiter::spawn(self.parameters.gc_depth, rx_receipt_waiter);
+
         info!("Mempool listening to client transactions on {}", address);
     }
 
     /// Spawn all tasks responsible to handle messages from other mempools.
-    fn handle_mempool_messages(&self) {
+    fn handle_mempool_messages(&self, tx_reply: Sender<SyncReply>) {
         let (tx_helper, rx_helper) = channel(CHANNEL_CAPACITY);
         let (tx_processor, rx_processor) = channel(CHANNEL_CAPACITY);
 
@@ -168,76 +267,269 @@ impl Mempool {
             address,
             /* handler */
             MempoolReceiverHandler {
+                committee: self.rx_committee.clone(),
+                store: self.store.clone(),
+                signature_service: self.signature_service.clone(),
                 tx_helper,
                 tx_processor,
+                tx_reply,
+                tx_collector: self.tx_collector.clone(),
             },
         );
 
         // The `Helper` is dedicated to reply to batch requests from other mempools.
         Helper::spawn(
-            self.committee.clone(),
+            self.name,
+            self.rx_committee.clone(),
             self.store.clone(),
             /* rx_request */ rx_helper,
+            self.parameters.sync_max_requests,
+            self.parameters.sync_max_bytes,
         );
 
-        // This `Processor` hashes and stores the batches we receive from the other mempools. It then forwards the
-        // batch's digest to the consensus.
+        // This `Processor` stores the certificates we receive. It then forwards the digests of the certified batches
+        // to the consensus.
         Processor::spawn(
             self.store.clone(),
-            /* rx_batch */ rx_processor,
+            /* rx_message */ rx_processor,
             /* tx_digest */ self.tx_consensus.clone(),
+            self.tx_collector.clone(),
+            self.deduplicator.clone(),
         );
 
         info!("Mempool listening to mempool messages on {}", address);
     }
 }
 
-/// Defines how the network receiver handles incoming transactions.
-#[derive(Clone)]
-struct TxReceiverHandler {
-    tx_batch_maker: Sender<Transaction>,
+/// Defines how the network receiver handles incoming transactions. We reply to every transaction we
+/// reject with a `Receipt::Rejected`. Clients opt in for the other receipts by sending an empty
+/// message over their connection: the handler then replies to each transaction of the connection
+/// we admit with a `Receipt::Admitted`, and later with its commit receipt. Commit receipts go
+/// through the outgoing channel of the connection: the handler keeps reading transactions while
+/// previous ones wait for their commit.
+struct TxReceiverHandler<V> {
+    tx_batch_maker: Sender<(Transaction, Option<Subscriber>)>,
+    /// Decides whether we admit the transactions.
+    validator: Arc<V>,
+    /// The recent transactions (to reject duplicates).
+    deduplicator: Arc<Mutex<Deduplicator>>,
+    /// Whether the client of this connection opted in for receipts.
+    receipts: AtomicBool,
+    /// The outgoing channel of the connection (see `MessageHandler::outgoing`).
+    tx_outgoing: Sender<Bytes>,
+    rx_outgoing: Option<Receiver<Bytes>>,
+}
+
+impl<V> TxReceiverHandler<V> {
+    fn new(
+        tx_batch_maker: Sender<(Transaction, Option<Subscriber>)>,
+        validator: Arc<V>,
+        deduplicator: Arc<Mutex<Deduplicator>>,
+    ) -> Self {
+        let (tx_outgoing, rx_outgoing) = channel(CHANNEL_CAPACITY);
+        Self {
+            tx_batch_maker,
+            validator,
+            deduplicator,
+            receipts: AtomicBool::new(false),
+            tx_outgoing,
+            rx_outgoing: Some(rx_outgoing),
+        }
+    }
+
+    /// Let the client know why we rejected its transaction.
+    async fn reject(
+        &self,
+        writer: &mut Writer,
+        transaction: Digest,
+        reason: String,
+    ) -> Result<(), Box<dyn Error>> {
+        warn!("Rejected transaction {}: {}", transaction, reason);
+        let rejected = Receipt::Rejected {
+            transaction,
+            reason,
+        };
+        writer
+            .send(Bytes::from(bincode::serialize(&rejected)?))
+            .await?;
+        Ok(())
+    }
+}
+
+/// The network receiver clones the handler for every connection: each clone starts without receipts
+/// and with its own outgoing channel.
+impl<V> Clone for TxReceiverHandler<V> {
+    fn clone(&self) -> Self {
+        Self::new(
+            self.tx_batch_maker.clone(),
+            self.validator.clone(),
+            self.deduplicator.clone(),
+        )
+    }
 }
 
 #[async_trait]
-impl MessageHandler for TxReceiverHandler {
-    async fn dispatch(&self, _writer: &mut Writer, message: Bytes) -> Result<(), Box<dyn Error>> {
-        // Send the transaction to the batch maker.
+impl<V: TransactionValidator> MessageHandler for TxReceiverHandler<V> {
+    async fn dispatch(&self, writer: &mut Writer, message: Bytes) -> Result<(), Box<dyn Error>> {
+        if message.is_empty() {
+            self.receipts.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        // Reject the invalid transactions, the ones we recently admitted, and the ones recently
+        // committed.
+        let digest = transaction_digest(&message);
+        if let Err(reason) = self.validator.validate(&message).await {
+            return self.reject(writer, digest, reason).await;
+        }
+        if !self.deduplicator.lock().unwrap().admit(digest.clone()) {
+            let reason = "Duplicate transaction".to_string();
+            return self.reject(writer, digest, reason).await;
+        }
+
+        if !self.receipts.load(Ordering::Relaxed) {
+            // Send the transaction to the batch maker.
+            self.tx_batch_maker
+                .send((message.to_vec(), None))
+                .await
+                .expect("Failed to send transaction");
+
+            // Give the change to schedule other tasks.
+            tokio::task::yield_now().await;
+            return Ok(());
+        }
+
+        // Send the transaction to the batch maker along with the client waiting for its receipt.
+        let (sender, receiver) = oneshot::channel();
         self.tx_batch_maker
-            .send(message.to_vec())
+            .send((message.to_vec(), Some((digest.clone(), sender))))
             .await
             .expect("Failed to send transaction");
+        let admitted = bincode::serialize(&Receipt::Admitted(digest))?;
+        writer.send(Bytes::from(admitted)).await?;
 
-        // Give the change to schedule other tasks.
-        tokio::task::yield_now().await;
+        // Send the commit receipt once the transaction is committed.
+        let tx_outgoing = self.tx_outgoing.clone();
+        tokio::spawn(async move {
+            if let Ok(receipt) = receiver.await {
+                let bytes = bincode::serialize(&receipt).expect("Failed to serialize receipt");
+                let _ = tx_outgoing.send(Bytes::from(bytes)).await;
+            }
+        });
         Ok(())
     }
+
+    fn outgoing(&mut self) -> Option<Receiver<Bytes>> {
+        self.rx_outgoing.take()
+    }
 }
 
-/// Defines how the network receiver handles incoming mempool messages.
+/// Defines how the network receiver handles incoming mempool messages. We store the signed batches
+/// of the other authorities and reply with our vote (see `QuorumWaiter`); we acknowledge all other
+/// messages.
 #[derive(Clone)]
 struct MempoolReceiverHandler {
-    tx_helper: Sender<(Vec<Digest>, PublicKey)>,
-    tx_processor: Sender<SerializedBatchMessage>,
+    committee: watch::Receiver<Committee>,
+    store: Store,
+    signature_service: SignatureService,
+    tx_helper: Sender<BatchRequest>,
+    tx_processor: Sender<ProcessorMessage>,
+    /// Delivers the replies to our batch requests to the `Synchronizer`.
+    tx_reply: Sender<SyncReply>,
+    /// Notifies the `Collector` of the batches we store.
+    tx_collector: Sender<CollectorMessage>,
+}
+
+impl MempoolReceiverHandler {
+    /// Check and store a batch signed by its author, and return our vote for it.
+    async fn vote(
+        &self,
+        batch: &[u8],
+        author: &PublicKey,
+        signature: &Signature,
+    ) -> MempoolResult<Signature> {
+        // Ensure the batch is well-formed and signed by a member of the committee.
+        let (stake, epoch) = {
+            let committee = self.committee.borrow();
+            (committee.stake(author), committee.epoch)
+        };
+        if stake == 0 {
+            return Err(MempoolError::UnknownAuthority(*author));
+        }
+        let digest = Digest(Sha512::digest(batch).as_slice()[..32].try_into().unwrap());
+        signature.verify(&Certificate::author_digest(&digest, author, epoch), author)?;
+        if !matches!(bincode::deserialize(batch), Ok(MempoolMessage::Batch(..))) {
+            return Err(MempoolError::MalformedBatch(digest));
+        }
+
+        // Store the batch before voting for it.
+        self.store
+            .clone()
+            .write(digest.to_vec(), batch.to_vec())
+            .await;
+        self.tx_collector
+            .send(CollectorMessage::Stored(digest.clone()))
+            .await
+            .expect("Failed to send batch to collector");
+        Ok(self
+            .signature_service
+            .clone()
+            .request_signature(Certificate::vote_digest(&digest, epoch))
+            .await)
+    }
 }
 
 #[async_trait]
 impl MessageHandler for MempoolReceiverHandler {
     async fn dispatch(&self, writer: &mut Writer, serialized: Bytes) -> Result<(), Box<dyn Error>> {
-        // Reply with an ACK.
-        let _ = writer.send(Bytes::from("Ack")).await;
+        // Deserialize the message and reply with our vote (or an ACK).
+        let message = bincode::deserialize(&serialized);
+        let reply = match &message {
+            Ok(MempoolMessage::SignedBatch(batch, author, signature)) => {
+                match self.vote(batch, author, signature).await {
+                    Ok(vote) => Bytes::from(bincode::serialize(&vote)?),
+                    Err(e) => {
+                        warn!("Rejected batch from {}: {}", author, e);
+                        Bytes::from("Ack")
+                    }
+                }
+            }
+            _ => Bytes::from("Ack"),
+        };
+        let _ = writer.send(reply).await;
 
-        // Deserialize and parse the message.
-        match bincode::deserialize(&serialized) {
+        // Parse the message.
+        match message {
             Ok(MempoolMessage::Batch(..)) => self
-                .tx_processor
-                .send(serialized.to_vec())
+                .tx_reply
+                .send(SyncReply::Batch(serialized.to_vec()))
                 .await
                 .expect("Failed to send batch"),
-            Ok(MempoolMessage::BatchRequest(missing, requestor)) => self
+            Ok(MempoolMessage::BatchPruned(digests, origin)) => self
+                .tx_reply
+                .send(SyncReply::Pruned(digests, origin))
+                .await
+                .expect("Failed to send pruned batches"),
+            Ok(MempoolMessage::SignedBatch(..)) => (),
+            Ok(MempoolMessage::BatchRequest(request)) => self
                 .tx_helper
-                .send((missing, requestor))
+                .send(request)
                 .await
                 .expect("Failed to send batch request"),
+            Ok(MempoolMessage::Certificate(certificate)) => {
+                let committee = self.committee.borrow().clone();
+                match certificate.verify(&committee) {
+                    Ok(()) => self
+                        .tx_processor
+                        .send(ProcessorMessage::Certificate(certificate))
+                        .await
+                        .expect("Failed to send certificate"),
+                    Err(e) => warn!(
+                        "Invalid certificate for batch {}: {}",
+                        certificate.digest, e
+                    ),
+                }
+            }
             Err(e) => warn!("Serialization error: {}", e),
         }
         Ok(())
@@ -1,7 +1,12 @@
+use crate::certificate::Certificate;
+use crate::collector::CollectorMessage;
+use crate::deduplicator::Deduplicator;
 use crypto::Digest;
 use ed25519_dalek::Digest as _;
 use ed25519_dalek::Sha512;
+use log::debug;
 use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
 use store::Store;
 use tokio::sync::mpsc::{Receiver, Sender};
 
@@ -12,27 +17,69 @@ pub mod processor_tests;
 /// Indicates a serialized `MempoolMessage::Batch` message.
 pub type SerializedBatchMessage = Vec<u8>;
 
-/// Hashes and stores batches, it then outputs the batch's digest.
+/// The store key marking a batch whose payload was pruned (batches are stored under their
+/// digest). It lets us tell apart the batches we pruned from those we never stored.
+pub fn pruned_key(digest: &Digest) -> Vec<u8> {
+    [b"pruned".to_vec(), digest.to_vec()].concat()
+}
+
+#[derive(Debug)]
+pub enum ProcessorMessage {
+    /// One of our own (certified) batches to hash and store.
+    Batch(SerializedBatchMessage),
+    /// The (verified) availability certificate of a batch.
+    Certificate(Certificate),
+}
+
+/// Hashes and stores batches. It also stores the certificates of the batches, and outputs the
+/// digests of the certified batches (unless they are already committed, in which case it drops
+/// their certificates).
 pub struct Processor;
 
 impl Processor {
     pub fn spawn(
         // The persistent storage.
         mut store: Store,
-        // Input channel to receive batches.
-        mut rx_batch: Receiver<SerializedBatchMessage>,
-        // Output channel to send out batches' digests.
+        // Input channel to receive batches and certificates.
+        mut rx_message: Receiver<ProcessorMessage>,
+        // Output channel to send out the digests of the certified batches.
         tx_digest: Sender<Digest>,
+        // Notifies the `Collector` of the batches we store.
+        tx_collector: Sender<CollectorMessage>,
+        // The recently committed batches.
+        deduplicator: Arc<Mutex<Deduplicator>>,
     ) {
         tokio::spawn(async move {
-            while let Some(batch) = rx_batch.recv().await {
-                // Hash the batch.
-                let digest = Digest(Sha512::digest(&batch).as_slice()[..32].try_into().unwrap());
+            while let Some(message) = rx_message.recv().await {
+                match message {
+                    ProcessorMessage::Batch(batch) => {
+                        // Hash the batch.
+                        let digest =
+                            Digest(Sha512::digest(&batch).as_slice()[..32].try_into().unwrap());
 
-                // Store the batch.
-                store.write(digest.to_vec(), batch).await;
+                        // Store the batch.
+                        store.write(digest.to_vec(), batch).await;
+                    }
+                    ProcessorMessage::Certificate(certificate) => {
+                        // Drop the certificates of the batches we already committed: storing
+                        // them again would outlive the pruning of the batch.
+                        let digest = certificate.digest.clone();
+                        if deduplicator.lock().unwrap().is_committed(&digest) {
+                            debug!("Batch {} is already committed", digest);
+                            continue;
+                        }
 
-                tx_digest.send(digest).await.expect("Failed to send digest");
+                        // Store the certificate.
+                        let serialized = bincode::serialize(&certificate)
+                            .expect("Failed to serialize certificate");
+                        store.write(Certificate::key(&digest), serialized).await;
+                        tx_collector
+                            .send(CollectorMessage::Stored(digest.clone()))
+                            .await
+                            .expect("Failed to send digest to collector");
+                        tx_digest.send(digest).await.expect("Failed to send digest");
+                    }
+                }
             }
         });
     }
@@ -1,10 +1,15 @@
-use crate::config::{Committee, Stake};
-use crate::processor::SerializedBatchMessage;
-use crypto::PublicKey;
+use crate::certificate::Certificate;
+use crate::config::Committee;
+use crate::mempool::MempoolMessage;
+use crate::processor::{ProcessorMessage, SerializedBatchMessage};
+use bytes::Bytes;
+use crypto::{Digest, PublicKey, Signature};
 use futures::stream::futures_unordered::FuturesUnordered;
 use futures::stream::StreamExt as _;
-use network::CancelHandler;
+use log::{debug, warn};
+use network::{CancelHandler, SimpleSender};
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::watch;
 
 #[cfg(test)]
 #[path = "tests/quorum_waiter_tests.rs"]
@@ -14,73 +19,132 @@ pub mod quorum_waiter_tests;
 pub struct QuorumWaiterMessage {
     /// A serialized `MempoolMessage::Batch` message.
     pub batch: SerializedBatchMessage,
-    /// The cancel handlers to receive the acknowledgements of our broadcast.
+    /// The digest of the batch.
+    pub digest: Digest,
+    /// Our signature over the batch (see `Certificate::author_digest`).
+    pub signature: Signature,
+    /// The cancel handlers to receive the votes of the other authorities.
     pub handlers: Vec<(PublicKey, CancelHandler)>,
 }
 
-/// The QuorumWaiter waits for 2f authorities to acknowledge reception of a batch.
+/// The QuorumWaiter waits for 2f authorities to vote for a batch (once they stored it). It then
+/// assembles their votes into an availability certificate and broadcasts it to the other mempools.
 pub struct QuorumWaiter {
-    /// The committee information.
-    committee: Committee,
-    /// The stake of this authority.
-    stake: Stake,
+    /// The public key of this authority.
+    name: PublicKey,
+    /// The committee information (updated upon epoch change).
+    committee: watch::Receiver<Committee>,
     /// Input Channel to receive commands.
     rx_message: Receiver<QuorumWaiterMessage>,
-    /// Channel to deliver batches for which we have enough acknowledgements.
-    tx_batch: Sender<SerializedBatchMessage>,
+    /// Channel to deliver the batches and their certificates.
+    tx_processor: Sender<ProcessorMessage>,
+    /// A network sender to broadcast the certificates to the other mempools.
+    network: SimpleSender,
 }
 
 impl QuorumWaiter {
     /// Spawn a new QuorumWaiter.
     pub fn spawn(
-        committee: Committee,
-        stake: Stake,
+        name: PublicKey,
+        committee: watch::Receiver<Committee>,
         rx_message: Receiver<QuorumWaiterMessage>,
-        tx_batch: Sender<Vec<u8>>,
+        tx_processor: Sender<ProcessorMessage>,
     ) {
         tokio::spawn(async move {
             Self {
+                name,
                 committee,
-                stake,
                 rx_message,
-                tx_batch,
+                tx_processor,
+                network: SimpleSender::new(),
             }
             .run()
             .await;
         });
     }
 
-    /// Helper function. It waits for a future to complete and then delivers a value.
-    async fn waiter(wait_for: CancelHandler, deliver: Stake) -> Stake {
-        let _ = wait_for.await;
-        deliver
+    /// Helper function. It waits for the reply of an authority and parses its vote.
+    async fn waiter(wait_for: CancelHandler, name: PublicKey) -> (PublicKey, Option<Signature>) {
+        let vote = wait_for
+            .await
+            .ok()
+            .and_then(|reply| bincode::deserialize(&reply).ok());
+        (name, vote)
     }
 
     /// Main loop.
     async fn run(&mut self) {
-        while let Some(QuorumWaiterMessage { batch, handlers }) = self.rx_message.recv().await {
+        while let Some(message) = self.rx_message.recv().await {
+            let QuorumWaiterMessage {
+                batch,
+                digest,
+                signature,
+                handlers,
+            } = message;
+            let committee = self.committee.borrow().clone();
             let mut wait_for_quorum: FuturesUnordered<_> = handlers
                 .into_iter()
-                .map(|(name, handler)| {
-                    let stake = self.committee.stake(&name);
-                    Self::waiter(handler, stake)
-                })
+                .map(|(name, handler)| Self::waiter(handler, name))
                 .collect();
 
-            // Wait for the first 2f nodes to send back an Ack. Then we consider the batch
-            // delivered and we send its digest to the consensus (that will include it into
-            // the dag). This should reduce the amount of synching.
-            let mut total_stake = self.stake;
-            while let Some(stake) = wait_for_quorum.next().await {
-                total_stake += stake;
-                if total_stake >= self.committee.quorum_threshold() {
-                    self.tx_batch
-                        .send(batch)
-                        .await
-                        .expect("Failed to deliver batch");
+            // Wait for the first 2f nodes to send back their vote. Then we consider the batch
+            // available and we certify it: the consensus only includes certified batches in
+            // its blocks. This should reduce the amount of synching.
+            let vote_digest = Certificate::vote_digest(&digest, committee.epoch);
+            let mut votes = Vec::new();
+            let mut total_stake = committee.stake(&self.name);
+            while let Some((name, vote)) = wait_for_quorum.next().await {
+                let vote = match vote {
+                    Some(vote) => vote,
+                    None => {
+                        debug!("{} did not vote for batch {}", name, digest);
+                        continue;
+                    }
+                };
+                if let Err(e) = vote.verify(&vote_digest, &name) {
+                    warn!("Invalid vote from {} for batch {}: {}", name, digest, e);
+                    continue;
+                }
+                votes.push((name, vote));
+                total_stake += committee.stake(&name);
+                if total_stake >= committee.quorum_threshold() {
+                    let certificate = Certificate {
+                        digest,
+                        author: self.name,
+                        signature,
+                        votes,
+                    };
+                    self.certify(&committee, batch, certificate).await;
                     break;
                 }
             }
         }
     }
+
+    /// Broadcast the certificate of our batch, and deliver both for further processing.
+    async fn certify(
+        &mut self,
+        committee: &Committee,
+        batch: SerializedBatchMessage,
+        certificate: Certificate,
+    ) {
+        debug!("Certified batch {}", certificate.digest);
+        let addresses = committee
+            .broadcast_addresses(&self.name)
+            .into_iter()
+            .map(|(_, address)| address)
+            .collect();
+        let message = MempoolMessage::Certificate(certificate.clone());
+        let bytes = bincode::serialize(&message).expect("Failed to serialize our certificate");
+        self.network.broadcast(addresses, Bytes::from(bytes)).await;
+
+        self.tx_processor
+            .send(ProcessorMessage::Batch(batch))
+            .await
+            .expect("Failed to deliver batch");
+        self.tx_processor
+            .send(ProcessorMessage::Certificate(certificate))
+            .await
+            .expect("Failed to deliver certificate");
+    }
 }
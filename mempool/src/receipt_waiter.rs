@@ -0,0 +1,119 @@
+use crate::mempool::Round;
+use crypto::Digest;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::oneshot;
+
+#[cfg(test)]
+#[path = "tests/receipt_waiter_tests.rs"]
+pub mod receipt_waiter_tests;
+
+/// The replies sent to the clients (see `TxReceiverHandler`). Only the clients that opted in for
+/// receipts receive the receipts of the transactions we admit.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Receipt {
+    /// The transaction (identified by its hash) was admitted to the mempool.
+    Admitted(Digest),
+    /// The batch containing the transaction was committed in the specified round.
+    Committed {
+        transaction: Digest,
+        batch: Digest,
+        round: Round,
+    },
+    /// The batch containing the transaction was not committed before being garbage collected.
+    /// The client may submit the transaction again.
+    Expired(Digest),
+    /// The transaction was not admitted to the mempool.
+    Rejected { transaction: Digest, reason: String },
+}
+
+/// A client waiting for the receipt of a transaction (identified by its hash).
+pub type Subscriber = (Digest, oneshot::Sender<Receipt>);
+
+#[derive(Debug)]
+pub enum ReceiptWaiterMessage {
+    /// Notify the subscribers once the batch is committed.
+    Subscribe(Digest, Vec<Subscriber>),
+    /// The consensus committed the batches in the specified round.
+    Commit(Vec<Digest>, Round),
+}
+
+/// Sends their receipts to the clients once the batches containing their transactions are
+/// committed.
+pub struct ReceiptWaiter {
+    /// The depth of the garbage collector.
+    gc_depth: Round,
+    /// Input channel to receive subscriptions and commit notifications.
+    rx_message: Receiver<ReceiptWaiterMessage>,
+    /// The round of the last commit notification (only used for cleanup).
+    round: Round,
+    /// The subscribers waiting for each batch, along with the round at which they subscribed.
+    pending: HashMap<Digest, (Round, Vec<Subscriber>)>,
+}
+
+impl ReceiptWaiter {
+    pub fn spawn(gc_depth: Round, rx_message: Receiver<ReceiptWaiterMessage>) {
+        tokio::spawn(async move {
+            Self {
+                gc_depth,
+                rx_message,
+                round: Round::default(),
+                pending: HashMap::new(),
+            }
+            .run()
+            .await;
+        });
+    }
+
+    async fn run(&mut self) {
+        while let Some(message) = self.rx_message.recv().await {
+            match message {
+                ReceiptWaiterMessage::Subscribe(batch, subscribers) => {
+                    let round = self.round;
+                    self.pending
+                        .entry(batch)
+                        .or_insert_with(|| (round, Vec::new()))
+                        .1
+                        .extend(subscribers);
+                }
+                ReceiptWaiterMessage::Commit(digests, round) => {
+                    // The round numbers restart with every new epoch.
+                    if round < self.round {
+                        for (r, _) in self.pending.values_mut() {
+                            *r = round;
+                        }
+                    }
+                    self.round = round;
+
+                    for batch in digests {
+                        if let Some((_, subscribers)) = self.pending.remove(&batch) {
+                            debug!("Sending {} receipts for batch {}", subscribers.len(), batch);
+                            for (transaction, sender) in subscribers {
+                                let receipt = Receipt::Committed {
+                                    transaction,
+                                    batch: batch.clone(),
+                                    round,
+                                };
+                                let _ = sender.send(receipt);
+                            }
+                        }
+                    }
+
+                    // Cleanup internal state.
+                    let gc_round = self.round.saturating_sub(self.gc_depth);
+                    self.pending.retain(|_, (r, subscribers)| {
+                        if *r >= gc_round {
+                            return true;
+                        }
+                        for (transaction, sender) in subscribers.drain(..) {
+                            let _ = sender.send(Receipt::Expired(transaction));
+                        }
+                        false
+                    });
+                }
+            }
+        }
+    }
+}
@@ -1,15 +1,24 @@
+use crate::collector::CollectorMessage;
 use crate::config::Committee;
+use crate::deduplicator::{transaction_digest, Deduplicator};
+use crate::helper::{BatchRequest, Nonce};
 use crate::mempool::{ConsensusMempoolMessage, MempoolMessage, Round};
+use crate::processor::SerializedBatchMessage;
+use crate::receipt_waiter::ReceiptWaiterMessage;
 use bytes::Bytes;
-use crypto::{Digest, PublicKey};
+use crypto::{Digest, PublicKey, SignatureService};
+use ed25519_dalek::{Digest as _, Sha512};
 use futures::stream::futures_unordered::FuturesUnordered;
 use futures::stream::StreamExt as _;
-use log::{debug, error};
+use log::{debug, error, warn};
 use network::SimpleSender;
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::convert::TryInto as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
 use store::{Store, StoreError};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::watch;
 use tokio::time::{sleep, Duration, Instant};
 
 #[cfg(test)]
@@ -19,10 +28,21 @@ pub mod synchronizer_tests;
 /// Resolution of the timer managing retrials of sync requests (in ms).
 const TIMER_RESOLUTION: u64 = 1_000;
 
+/// The replies of the other mempools to our batch requests.
+#[derive(Debug)]
+pub enum SyncReply {
+    /// A serialized `MempoolMessage::Batch` message.
+    Batch(SerializedBatchMessage),
+    /// The requested batches that the specified authority pruned.
+    Pruned(Vec<Digest>, PublicKey),
+}
+
 // The `Synchronizer` is responsible to keep the mempool in sync with the others.
 pub struct Synchronizer {
     /// The public key of this authority.
     name: PublicKey,
+    /// Signs our batch requests.
+    signature_service: SignatureService,
     /// The committee information.
     committee: Committee,
     // The persistent storage.
@@ -36,38 +56,65 @@ pub struct Synchronizer {
     sync_retry_nodes: usize,
     /// Input channel to receive the commands from the consensus.
     rx_message: Receiver<ConsensusMempoolMessage>,
+    /// Receives the replies of the other mempools to our batch requests.
+    rx_reply: Receiver<SyncReply>,
+    /// Publishes the committee of the current epoch to the other mempool tasks.
+    tx_committee: watch::Sender<Committee>,
+    /// Forwards the commit notifications of the consensus to the `ReceiptWaiter`.
+    tx_receipt_waiter: Sender<ReceiptWaiterMessage>,
+    /// Forwards the notifications of the consensus to the `Collector`, along with the batches
+    /// we sync.
+    tx_collector: Sender<CollectorMessage>,
+    /// The recent transactions and committed batches (cleaned up as the consensus progresses).
+    deduplicator: Arc<Mutex<Deduplicator>>,
     /// A network sender to send requests to the other mempools.
     network: SimpleSender,
-    /// Loosely keep track of the consensus's round number (only used for cleanup).
+    /// Loosely keep track of the consensus's round number (used for cleanup, and to tag our
+    /// requests, see `Nonce`).
     round: Round,
+    /// The number of requests we signed (see `Nonce`).
+    requests: u64,
     /// Keeps the digests (of batches) that are waiting to be processed by the consensus. Their
     /// processing will resume when we get the missing batches in the store or we no longer need them.
-    /// It also keeps the round number and a timestamp (`u128`) of each request we sent.
-    pending: HashMap<Digest, (Round, Sender<()>, u128)>,
+    /// It also keeps the round number of each request, and the time from which we retry it.
+    pending: HashMap<Digest, (Round, Sender<()>, Instant)>,
 }
 
 impl Synchronizer {
     #[allow(clippy::too_many_arguments)]
     pub fn spawn(
         name: PublicKey,
+        signature_service: SignatureService,
         committee: Committee,
         store: Store,
         gc_depth: Round,
         sync_retry_delay: u64,
         sync_retry_nodes: usize,
         rx_message: Receiver<ConsensusMempoolMessage>,
+        rx_reply: Receiver<SyncReply>,
+        tx_committee: watch::Sender<Committee>,
+        tx_receipt_waiter: Sender<ReceiptWaiterMessage>,
+        tx_collector: Sender<CollectorMessage>,
+        deduplicator: Arc<Mutex<Deduplicator>>,
     ) {
         tokio::spawn(async move {
             Self {
                 name,
+                signature_service,
                 committee,
                 store,
                 gc_depth,
                 sync_retry_delay,
                 sync_retry_nodes,
                 rx_message,
+                rx_reply,
+                tx_committee,
+                tx_receipt_waiter,
+                tx_collector,
+                deduplicator,
                 network: SimpleSender::new(),
                 round: Round::default(),
+                requests: 0,
                 pending: HashMap::new(),
             }
             .run()
@@ -75,6 +122,54 @@ impl Synchronizer {
         });
     }
 
+    /// Send a request for the missing batches to each of the specified authorities. We sign a
+    /// distinct request for each of them, so that they cannot replay it to one another.
+    async fn send_request(
+        &mut self,
+        missing: Vec<Digest>,
+        recipients: Vec<(PublicKey, SocketAddr)>,
+    ) {
+        for (recipient, address) in recipients {
+            self.requests += 1;
+            let nonce = Nonce {
+                epoch: self.committee.epoch,
+                round: self.round,
+                counter: self.requests,
+            };
+            let request = BatchRequest::new(
+                missing.clone(),
+                self.name,
+                recipient,
+                nonce,
+                self.signature_service.clone(),
+            )
+            .await;
+            let message = MempoolMessage::BatchRequest(request);
+            let serialized =
+                bincode::serialize(&message).expect("Failed to serialize our own message");
+            self.network.send(address, Bytes::from(serialized)).await;
+        }
+    }
+
+    /// Return the hashes of the transactions of the specified (committed) batches. The consensus
+    /// only commits batches we stored.
+    async fn transactions(&mut self, batches: &[Digest]) -> Vec<Digest> {
+        let mut transactions = Vec::new();
+        for digest in batches {
+            match self.store.read(digest.to_vec()).await {
+                Ok(Some(serialized)) => match bincode::deserialize(&serialized) {
+                    Ok(MempoolMessage::Batch(batch)) => {
+                        transactions.extend(batch.iter().map(|x| transaction_digest(x)))
+                    }
+                    _ => warn!("Committed batch {} is malformed", digest),
+                },
+                Ok(None) => debug!("Committed batch {} is not in our store", digest),
+                Err(e) => error!("{}", e),
+            }
+        }
+        transactions
+    }
+
     /// Helper function. It waits for a batch to become available in the storage
     /// and then delivers its digest.
     async fn waiter(
@@ -103,10 +198,7 @@ impl Synchronizer {
                 // Handle consensus' messages.
                 Some(message) = self.rx_message.recv() => match message {
                     ConsensusMempoolMessage::Synchronize(digests, target) => {
-                        let now = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .expect("Failed to measure time")
-                            .as_millis();
+                        let retry_at = Instant::now() + Duration::from_millis(self.sync_retry_delay);
 
                         let mut missing = Vec::new();
                         for digest in digests {
@@ -124,7 +216,7 @@ impl Synchronizer {
                             let (tx_cancel, rx_cancel) = channel(1);
                             let fut = Self::waiter(digest.clone(), self.store.clone(), deliver, rx_cancel);
                             waiting.push(fut);
-                            self.pending.insert(digest, (self.round, tx_cancel, now));
+                            self.pending.insert(digest, (self.round, tx_cancel, retry_at));
                         }
 
                         // Send sync request to a single node. If this fails, we will send it
@@ -136,13 +228,16 @@ impl Synchronizer {
                                 continue;
                             }
                         };
-                        let message = MempoolMessage::BatchRequest(missing, self.name);
-                        let serialized = bincode::serialize(&message).expect("Failed to serialize our own message");
-                        self.network.send(address, Bytes::from(serialized)).await;
+                        self.send_request(missing, vec![(target, address)]).await;
                     },
                     ConsensusMempoolMessage::Cleanup(round) => {
                         // Keep track of the consensus' round number.
                         self.round = round;
+                        self.deduplicator.lock().unwrap().cleanup(round);
+                        self.tx_collector
+                            .send(CollectorMessage::Cleanup(round))
+                            .await
+                            .expect("Failed to send cleanup notification");
 
                         // Cleanup internal state.
                         if self.round < self.gc_depth {
@@ -156,6 +251,74 @@ impl Synchronizer {
                             }
                         }
                         self.pending.retain(|_, (r, _, _)| r > &mut gc_round);
+                    },
+                    ConsensusMempoolMessage::Commit(digests, round) => {
+                        // Remember the committed transactions, so that we reject them even if
+                        // they were submitted to other nodes.
+                        let transactions = self.transactions(&digests).await;
+                        self.deduplicator
+                            .lock()
+                            .unwrap()
+                            .commit(digests.clone(), transactions);
+                        self.tx_collector
+                            .send(CollectorMessage::Commit(digests.clone()))
+                            .await
+                            .expect("Failed to send commit notification");
+                        self.tx_receipt_waiter
+                            .send(ReceiptWaiterMessage::Commit(digests, round))
+                            .await
+                            .expect("Failed to send commit notification");
+                    },
+                    ConsensusMempoolMessage::Reconfigure(committee) => {
+                        // The round numbers restart with the new epoch: cancel all pending requests.
+                        for (_, handler, _) in self.pending.values() {
+                            let _ = handler.send(()).await;
+                        }
+                        self.pending.clear();
+                        self.round = Round::default();
+                        self.deduplicator.lock().unwrap().reconfigure();
+                        self.tx_collector
+                            .send(CollectorMessage::Reconfigure(committee.epoch))
+                            .await
+                            .expect("Failed to send reconfiguration notification");
+
+                        // Publish the new committee to the other mempool tasks.
+                        self.committee = committee.clone();
+                        let _ = self.tx_committee.send(committee);
+                    },
+                    ConsensusMempoolMessage::Prune(epoch, round) => {
+                        self.tx_collector
+                            .send(CollectorMessage::Prune(epoch, round))
+                            .await
+                            .expect("Failed to send prune notification");
+                    }
+                },
+
+                // Store the batches we requested. Nothing vouches for the other batches (they
+                // are not signed by their author): drop them.
+                Some(reply) = self.rx_reply.recv() => match reply {
+                    SyncReply::Batch(batch) => {
+                        let digest = Digest(Sha512::digest(&batch).as_slice()[..32].try_into().unwrap());
+                        if self.pending.contains_key(&digest) {
+                            self.store.write(digest.to_vec(), batch).await;
+                            self.tx_collector
+                                .send(CollectorMessage::Stored(digest))
+                                .await
+                                .expect("Failed to send digest to collector");
+                        } else {
+                            debug!("Dropped unsolicited batch {}", digest);
+                        }
+                    },
+
+                    // The authority no longer has these batches: retry with the other nodes as
+                    // soon as the timer fires.
+                    SyncReply::Pruned(digests, origin) => {
+                        for digest in digests {
+                            if let Some((_, _, retry_at)) = self.pending.get_mut(&digest) {
+                                warn!("{} pruned batch {}", origin, digest);
+                                *retry_at = Instant::now();
+                            }
+                        }
                     }
                 },
 
@@ -176,29 +339,21 @@ impl Synchronizer {
                     // We optimistically sent sync requests to a single node. If this timer triggers,
                     // it means we were wrong to trust it. We are done waiting for a reply and we now
                     // broadcast the request to a bunch of other nodes (selected at random).
-                    let now = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .expect("Failed to measure time")
-                        .as_millis();
-
                     let mut retry = Vec::new();
-                    for (digest, (_, _, timestamp)) in &self.pending {
-                        if timestamp + (self.sync_retry_delay as u128) < now {
+                    let now = Instant::now();
+                    for (digest, (_, _, retry_at)) in &self.pending {
+                        if *retry_at <= now {
                             debug!("Requesting sync for batch {} (retry)", digest);
                             retry.push(digest.clone());
                         }
                     }
+                    retry.sort();
                     if !retry.is_empty() {
-                        let addresses = self.committee
-                            .broadcast_addresses(&self.name)
-                            .iter()
-                            .map(|(_, address)| *address)
-                            .collect();
-                        let message = MempoolMessage::BatchRequest(retry, self.name);
-                        let serialized = bincode::serialize(&message).expect("Failed to serialize our own message");
-                        self.network
-                            .lucky_broadcast(addresses, Bytes::from(serialized), self.sync_retry_nodes)
-                            .await;
+                        let recipients = self.network.pick(
+                            self.committee.broadcast_addresses(&self.name),
+                            self.sync_retry_nodes
+                        );
+                        self.send_request(retry, recipients).await;
                     }
 
                     // Reschedule the timer.
@@ -1,56 +1,76 @@
 use super::*;
-use crate::common::transaction;
+use crate::common::{committee, keys, transaction};
+use crypto::SignatureService;
 use tokio::sync::mpsc::channel;
 
 #[tokio::test]
 async fn make_batch() {
     let (tx_transaction, rx_transaction) = channel(1);
     let (tx_message, mut rx_message) = channel(1);
-    let dummy_addresses = vec![(PublicKey::default(), "127.0.0.1:0".parse().unwrap())];
+    let (tx_receipt_waiter, _rx_receipt_waiter) = channel(1);
+    let (name, secret) = keys().pop().unwrap();
+    let (_tx_committee, rx_committee) = watch::channel(committee());
 
     // Spawn a `BatchMaker` instance.
     BatchMaker::spawn(
+        name,
+        SignatureService::new(secret),
+        rx_committee,
         /* max_batch_size */ 200,
         /* max_batch_delay */ 1_000_000, // Ensure the timer is not triggered.
         rx_transaction,
         tx_message,
-        /* mempool_addresses */ dummy_addresses,
+        tx_receipt_waiter,
     );
 
     // Send enough transactions to seal a batch.
-    tx_transaction.send(transaction()).await.unwrap();
-    tx_transaction.send(transaction()).await.unwrap();
+    tx_transaction.send((transaction(), None)).await.unwrap();
+    tx_transaction.send((transaction(), None)).await.unwrap();
 
     // Ensure the batch is as expected.
     let expected_batch = vec![transaction(), transaction()];
-    let QuorumWaiterMessage { batch, handlers: _ } = rx_message.recv().await.unwrap();
+    let QuorumWaiterMessage {
+        batch,
+        digest,
+        signature,
+        ..
+    } = rx_message.recv().await.unwrap();
     match bincode::deserialize(&batch).unwrap() {
         MempoolMessage::Batch(batch) => assert_eq!(batch, expected_batch),
         _ => panic!("Unexpected message"),
     }
+
+    // Ensure the batch is signed.
+    let author_digest = Certificate::author_digest(&digest, &name, committee().epoch);
+    assert!(signature.verify(&author_digest, &name).is_ok());
 }
 
 #[tokio::test]
 async fn batch_timeout() {
     let (tx_transaction, rx_transaction) = channel(1);
     let (tx_message, mut rx_message) = channel(1);
-    let dummy_addresses = vec![(PublicKey::default(), "127.0.0.1:0".parse().unwrap())];
+    let (tx_receipt_waiter, _rx_receipt_waiter) = channel(1);
+    let (name, secret) = keys().pop().unwrap();
+    let (_tx_committee, rx_committee) = watch::channel(committee());
 
     // Spawn a `BatchMaker` instance.
     BatchMaker::spawn(
+        name,
+        SignatureService::new(secret),
+        rx_committee,
         /* max_batch_size */ 200,
         /* max_batch_delay */ 50, // Ensure the timer is triggered.
         rx_transaction,
         tx_message,
-        /* mempool_addresses */ dummy_addresses,
+        tx_receipt_waiter,
     );
 
     // Do not send enough transactions to seal a batch..
-    tx_transaction.send(transaction()).await.unwrap();
+    tx_transaction.send((transaction(), None)).await.unwrap();
 
     // Ensure the batch is as expected.
     let expected_batch = vec![transaction()];
-    let QuorumWaiterMessage { batch, handlers: _ } = rx_message.recv().await.unwrap();
+    let QuorumWaiterMessage { batch, .. } = rx_message.recv().await.unwrap();
     match bincode::deserialize(&batch).unwrap() {
         MempoolMessage::Batch(batch) => assert_eq!(batch, expected_batch),
         _ => panic!("Unexpected message"),
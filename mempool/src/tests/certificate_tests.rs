@@ -0,0 +1,77 @@
+use super::*;
+use crate::common::{certificate, committee, keys};
+
+#[test]
+fn verify_valid_certificate() {
+    assert!(certificate().verify(&committee()).is_ok());
+}
+
+#[test]
+fn verify_certificate_insufficient_stake() {
+    // Modify the certificate to remove one vote.
+    let mut certificate = certificate();
+    certificate.votes.pop();
+
+    // Verify the certificate.
+    match certificate.verify(&committee()) {
+        Err(MempoolError::CertificateRequiresQuorum) => (),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn verify_certificate_invalid_vote() {
+    // Modify the certificate to corrupt one vote.
+    let mut certificate = certificate();
+    certificate.votes[1].1 = Signature::default();
+
+    // Verify the certificate.
+    match certificate.verify(&committee()) {
+        Err(MempoolError::InvalidSignature(_)) => (),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn verify_certificate_vote_as_author_signature() {
+    // Replace the author's signature by its vote for the batch: votes cannot vouch for the
+    // authorship of a batch.
+    let mut certificate = certificate();
+    let (_, secret) = keys().pop().unwrap();
+    let vote_digest = Certificate::vote_digest(&certificate.digest, committee().epoch);
+    certificate.signature = Signature::new(&vote_digest, &secret);
+
+    // Verify the certificate.
+    match certificate.verify(&committee()) {
+        Err(MempoolError::InvalidSignature(_)) => (),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn verify_certificate_author_reuse() {
+    // Count the author twice: once as the author and once as a voter.
+    let mut certificate = certificate();
+    let (author, secret) = keys().pop().unwrap();
+    let vote_digest = Certificate::vote_digest(&certificate.digest, committee().epoch);
+    certificate.votes[0] = (author, Signature::new(&vote_digest, &secret));
+
+    // Verify the certificate.
+    match certificate.verify(&committee()) {
+        Err(MempoolError::AuthorityReuse(_)) => (),
+        _ => panic!("Unexpected verification result"),
+    }
+}
+
+#[test]
+fn verify_certificate_wrong_epoch() {
+    // The signatures of a certificate only hold for the epoch of the committee that made it.
+    let mut committee = committee();
+    committee.epoch += 1;
+
+    // Verify the certificate.
+    match certificate().verify(&committee) {
+        Err(MempoolError::InvalidSignature(_)) => (),
+        _ => panic!("Unexpected verification result"),
+    }
+}
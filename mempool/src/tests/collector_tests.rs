@@ -0,0 +1,87 @@
+use super::*;
+use crate::common::{batch_digest, serialized_batch};
+use std::fs;
+use tokio::sync::mpsc::channel;
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn prune_uncommitted() {
+    let (tx_message, rx_message) = channel(10);
+
+    // Create a new test store.
+    let path = ".db_test_prune_uncommitted";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Spawn a `Collector` instance.
+    let deduplicator = Arc::new(Mutex::new(Deduplicator::new(50, 1_000)));
+    Collector::spawn(store.clone(), 0, rx_message, deduplicator);
+
+    // Store two batches, and commit one of them.
+    let committed = Digest([1; 32]);
+    store
+        .write(batch_digest().to_vec(), serialized_batch())
+        .await;
+    store.write(committed.to_vec(), serialized_batch()).await;
+    tx_message.send(CollectorMessage::Cleanup(5)).await.unwrap();
+    for digest in [batch_digest(), committed.clone()].iter() {
+        tx_message
+            .send(CollectorMessage::Stored(digest.clone()))
+            .await
+            .unwrap();
+    }
+    tx_message
+        .send(CollectorMessage::Commit(vec![committed.clone()]))
+        .await
+        .unwrap();
+
+    // Prune below the batches: nothing is deleted.
+    tx_message
+        .send(CollectorMessage::Prune(0, 5))
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(100)).await;
+    assert!(store.read(batch_digest().to_vec()).await.unwrap().is_some());
+
+    // Prune past the batches: only the uncommitted one is deleted.
+    tx_message
+        .send(CollectorMessage::Prune(0, 6))
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(100)).await;
+    assert!(store.read(batch_digest().to_vec()).await.unwrap().is_none());
+    assert!(store.read(committed.to_vec()).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn prune_after_restart() {
+    let (tx_message, rx_message) = channel(10);
+
+    // Create a new test store.
+    let path = ".db_test_prune_after_restart";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Track a batch with a first `Collector` instance.
+    let deduplicator = Arc::new(Mutex::new(Deduplicator::new(50, 1_000)));
+    Collector::spawn(store.clone(), 0, rx_message, deduplicator.clone());
+    store
+        .write(batch_digest().to_vec(), serialized_batch())
+        .await;
+    tx_message
+        .send(CollectorMessage::Stored(batch_digest()))
+        .await
+        .unwrap();
+    drop(tx_message);
+    sleep(Duration::from_millis(100)).await;
+
+    // Ensure a new `Collector` instance (after a restart) deletes the batch.
+    let (tx_message, rx_message) = channel(10);
+    Collector::spawn(store.clone(), 0, rx_message, deduplicator);
+    tx_message
+        .send(CollectorMessage::Prune(0, 1))
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(100)).await;
+    assert!(store.read(batch_digest().to_vec()).await.unwrap().is_none());
+}
@@ -1,8 +1,9 @@
 use crate::batch_maker::{Batch, Transaction};
+use crate::certificate::Certificate;
 use crate::config::Committee;
 use crate::mempool::MempoolMessage;
 use bytes::Bytes;
-use crypto::{generate_keypair, Digest, PublicKey, SecretKey};
+use crypto::{generate_keypair, Digest, PublicKey, SecretKey, Signature};
 use ed25519_dalek::Digest as _;
 use ed25519_dalek::Sha512;
 use futures::sink::SinkExt as _;
@@ -30,7 +31,7 @@ pub fn committee() -> Committee {
             .map(|(i, (name, _))| {
                 let stake = 1;
                 let front = format!("127.0.0.1:{}", 100 + i).parse().unwrap();
-                let mempool = format!("127.0.0.1:{}", 100 + i).parse().unwrap();
+                let mempool = format!("127.0.0.1:{}", 104 + i).parse().unwrap();
                 (name, stake, front, mempool)
             })
             .collect(),
@@ -56,9 +57,14 @@ pub fn transaction() -> Transaction {
     vec![0; 100]
 }
 
+// Fixture
+pub fn transactions() -> Vec<Transaction> {
+    (0..2).map(|i| vec![i; 100]).collect()
+}
+
 // Fixture
 pub fn batch() -> Batch {
-    vec![transaction(), transaction()]
+    transactions()
 }
 
 // Fixture
@@ -94,3 +100,51 @@ pub fn listener(address: SocketAddr, expected: Option<Bytes>) -> JoinHandle<()>
         }
     })
 }
+
+// Fixture
+pub fn certificate() -> Certificate {
+    let (author, secret) = keys().pop().unwrap();
+    let digest = batch_digest();
+    let vote_digest = Certificate::vote_digest(&digest, committee().epoch);
+    Certificate {
+        digest: digest.clone(),
+        author,
+        signature: Signature::new(
+            &Certificate::author_digest(&digest, &author, committee().epoch),
+            &secret,
+        ),
+        votes: keys()
+            .iter()
+            .rev()
+            .skip(1)
+            .take(2)
+            .map(|(name, secret)| (*name, Signature::new(&vote_digest, secret)))
+            .collect(),
+    }
+}
+
+// Fixture
+pub fn voter(address: SocketAddr, secret: SecretKey) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let listener = TcpListener::bind(&address).await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
+        let transport = Framed::new(socket, LengthDelimitedCodec::new());
+        let (mut writer, mut reader) = transport.split();
+        match reader.next().await {
+            Some(Ok(received)) => match bincode::deserialize(&received).unwrap() {
+                MempoolMessage::SignedBatch(batch, _, _) => {
+                    let digest =
+                        Digest(Sha512::digest(&batch).as_slice()[..32].try_into().unwrap());
+                    let vote = Signature::new(
+                        &Certificate::vote_digest(&digest, committee().epoch),
+                        &secret,
+                    );
+                    let bytes = bincode::serialize(&vote).unwrap();
+                    writer.send(Bytes::from(bytes)).await.unwrap();
+                }
+                _ => panic!("Unexpected message"),
+            },
+            _ => panic!("Failed to receive network message"),
+        }
+    })
+}
@@ -0,0 +1,46 @@
+use super::*;
+
+#[test]
+fn reject_duplicates() {
+    let mut deduplicator = Deduplicator::new(/* gc_depth */ 50, /* capacity */ 1_000);
+    assert!(deduplicator.admit(Digest([1u8; 32])));
+    assert!(!deduplicator.admit(Digest([1u8; 32])));
+    assert!(deduplicator.admit(Digest([2u8; 32])));
+
+    deduplicator.commit(vec![Digest([3u8; 32])], Vec::new());
+    assert!(deduplicator.is_committed(&Digest([3u8; 32])));
+    assert!(!deduplicator.is_committed(&Digest([4u8; 32])));
+}
+
+#[test]
+fn forget_old_entries() {
+    let mut deduplicator = Deduplicator::new(/* gc_depth */ 50, /* capacity */ 2);
+    deduplicator.admit(Digest([1u8; 32]));
+    deduplicator.commit(vec![Digest([3u8; 32])], Vec::new());
+
+    // Entries are forgotten after `gc_depth` rounds.
+    deduplicator.cleanup(10);
+    deduplicator.admit(Digest([2u8; 32]));
+    deduplicator.cleanup(51);
+    assert!(deduplicator.admit(Digest([1u8; 32])));
+    assert!(!deduplicator.admit(Digest([2u8; 32])));
+    assert!(!deduplicator.is_committed(&Digest([3u8; 32])));
+
+    // And when we hold more than `capacity` of them.
+    deduplicator.admit(Digest([4u8; 32]));
+    assert!(deduplicator.admit(Digest([2u8; 32])));
+}
+
+#[test]
+fn reject_committed_transactions() {
+    let mut deduplicator = Deduplicator::new(/* gc_depth */ 50, /* capacity */ 1_000);
+
+    // A transaction submitted to another node is rejected once committed.
+    deduplicator.commit(vec![Digest([3u8; 32])], vec![Digest([1u8; 32])]);
+    assert!(!deduplicator.admit(Digest([1u8; 32])));
+    assert!(deduplicator.admit(Digest([2u8; 32])));
+
+    // Until it is forgotten after `gc_depth` rounds.
+    deduplicator.cleanup(51);
+    assert!(deduplicator.admit(Digest([1u8; 32])));
+}
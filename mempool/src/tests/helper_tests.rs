@@ -1,12 +1,13 @@
 use super::*;
 use crate::common::{batch_digest, committee_with_base_port, keys, listener, serialized_batch};
+use crate::error::MempoolError;
 use std::fs;
 use tokio::sync::mpsc::channel;
 
 #[tokio::test]
 async fn batch_reply() {
     let (tx_request, rx_request) = channel(1);
-    let (requestor, _) = keys().pop().unwrap();
+    let (requestor, secret) = keys().pop().unwrap();
     let committee = committee_with_base_port(8_000);
 
     // Create a new test store.
@@ -20,7 +21,16 @@ async fn batch_reply() {
         .await;
 
     // Spawn an `Helper` instance.
-    Helper::spawn(committee.clone(), store, rx_request);
+    let (_tx_committee, rx_committee) = watch::channel(committee.clone());
+    let (name, _) = keys().remove(0);
+    Helper::spawn(
+        name,
+        rx_committee,
+        store,
+        rx_request,
+        /* max_requests */ 10,
+        /* max_bytes */ 1_000_000,
+    );
 
     // Spawn a listener to receive the batch reply.
     let address = committee.mempool_address(&requestor).unwrap();
@@ -29,8 +39,116 @@ async fn batch_reply() {
 
     // Send a batch request.
     let digests = vec![batch_digest()];
-    tx_request.send((digests, requestor)).await.unwrap();
+    let signature_service = SignatureService::new(secret);
+    let request = BatchRequest::new(
+        digests,
+        requestor,
+        name,
+        Nonce::default(),
+        signature_service,
+    )
+    .await;
+    tx_request.send(request).await.unwrap();
 
     // Ensure the requestor received the batch (ie. it did not panic).
     assert!(handle.await.is_ok());
 }
+
+#[tokio::test]
+async fn pruned_batch_reply() {
+    let (tx_request, rx_request) = channel(1);
+    let (requestor, secret) = keys().pop().unwrap();
+    let committee = committee_with_base_port(8_100);
+
+    // Create a new test store.
+    let path = ".db_test_pruned_batch_reply";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Mark the batch as pruned (without storing it).
+    store.write(pruned_key(&batch_digest()), Vec::new()).await;
+
+    // Spawn an `Helper` instance.
+    let (_tx_committee, rx_committee) = watch::channel(committee.clone());
+    let (name, _) = keys().remove(0);
+    Helper::spawn(
+        name,
+        rx_committee,
+        store,
+        rx_request,
+        /* max_requests */ 10,
+        /* max_bytes */ 1_000_000,
+    );
+
+    // Spawn a listener to receive the reply.
+    let address = committee.mempool_address(&requestor).unwrap();
+    let message = MempoolMessage::BatchPruned(vec![batch_digest()], name);
+    let expected = Bytes::from(bincode::serialize(&message).unwrap());
+    let handle = listener(address, Some(expected));
+
+    // Send a batch request.
+    let digests = vec![batch_digest()];
+    let signature_service = SignatureService::new(secret);
+    let request = BatchRequest::new(
+        digests,
+        requestor,
+        name,
+        Nonce::default(),
+        signature_service,
+    )
+    .await;
+    tx_request.send(request).await.unwrap();
+
+    // Ensure the requestor learned the batch is pruned (ie. it did not panic).
+    assert!(handle.await.is_ok());
+}
+
+#[tokio::test]
+async fn reject_misaddressed_request() {
+    let mut keys = keys();
+    let (requestor, secret) = keys.pop().unwrap();
+    let (name, _) = keys.pop().unwrap();
+    let (other, _) = keys.pop().unwrap();
+    let committee = committee_with_base_port(8_200);
+
+    // A request addressed to another authority cannot be replayed to us.
+    let signature_service = SignatureService::new(secret);
+    let request = BatchRequest::new(
+        vec![batch_digest()],
+        requestor,
+        other,
+        Nonce::default(),
+        signature_service,
+    )
+    .await;
+    assert!(request.verify(&committee, &other).is_ok());
+    match request.verify(&committee, &name) {
+        Err(MempoolError::MisaddressedRequest(x)) => assert_eq!(x, other),
+        x => panic!("Unexpected result: {:?}", x),
+    }
+}
+
+#[test]
+fn reject_replays() {
+    let mut replays = Replays::default();
+    let nonce = |epoch, round, counter| Nonce {
+        epoch,
+        round,
+        counter,
+    };
+
+    // We serve each request once.
+    assert!(replays.insert(nonce(1, 10, 1), Digest([1u8; 32])));
+    assert!(!replays.insert(nonce(1, 10, 1), Digest([1u8; 32])));
+    assert!(replays.insert(nonce(1, 10, 2), Digest([2u8; 32])));
+
+    // We forget the requests outside the window of the latest one, and reject them thereafter.
+    assert!(replays.insert(nonce(1, 10 + REPLAY_WINDOW + 1, 3), Digest([3u8; 32])));
+    assert!(!replays.insert(nonce(1, 10, 1), Digest([1u8; 32])));
+    assert!(!replays.insert(nonce(1, 5, 4), Digest([4u8; 32])));
+
+    // Requests within the window are still welcome, but not those of previous epochs.
+    assert!(replays.insert(nonce(1, 11 + REPLAY_WINDOW, 5), Digest([5u8; 32])));
+    assert!(replays.insert(nonce(2, 1, 6), Digest([6u8; 32])));
+    assert!(!replays.insert(nonce(1, 20 + REPLAY_WINDOW, 7), Digest([7u8; 32])));
+}
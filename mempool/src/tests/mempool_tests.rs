@@ -1,11 +1,16 @@
 use super::*;
-use crate::common::{batch_digest, committee_with_base_port, keys, listener, transaction};
+use crate::common::{batch_digest, committee_with_base_port, keys, transactions, voter};
+use crate::validator::AdmitAll;
+use futures::stream::StreamExt as _;
 use network::SimpleSender;
 use std::fs;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, Duration};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
 #[tokio::test]
 async fn handle_clients_transactions() {
-    let (name, _) = keys().pop().unwrap();
+    let (name, secret) = keys().pop().unwrap();
     let committee = committee_with_base_port(11_000);
     let parameters = Parameters {
         batch_size: 200, // Two transactions.
@@ -22,25 +27,236 @@ async fn handle_clients_transactions() {
     let (tx_mempool_to_consensus, mut rx_mempool_to_consensus) = channel(1);
     Mempool::spawn(
         name,
+        SignatureService::new(secret),
         committee.clone(),
         parameters,
         store,
         rx_consensus_to_mempool,
         tx_mempool_to_consensus,
+        AdmitAll,
     );
 
-    // Spawn enough mempools' listeners to acknowledge our batches.
-    for (_, address) in committee.broadcast_addresses(&name) {
-        let _ = listener(address, /* expected */ None);
+    // Spawn enough mempools' voters to certify our batches.
+    for (name, secret) in keys().into_iter().take(3) {
+        let _ = voter(committee.mempool_address(&name).unwrap(), secret);
     }
 
     // Send enough transactions to create a batch.
     let mut network = SimpleSender::new();
     let address = committee.transactions_address(&name).unwrap();
-    network.send(address, Bytes::from(transaction())).await;
-    network.send(address, Bytes::from(transaction())).await;
+    for transaction in transactions() {
+        network.send(address, Bytes::from(transaction)).await;
+    }
 
     // Ensure the consensus got the batch digest.
     let received = rx_mempool_to_consensus.recv().await.unwrap();
     assert_eq!(batch_digest(), received);
 }
+
+#[tokio::test]
+async fn commit_receipts() {
+    let (name, secret) = keys().pop().unwrap();
+    let committee = committee_with_base_port(11_100);
+    let parameters = Parameters {
+        batch_size: 200, // Two transactions.
+        ..Parameters::default()
+    };
+
+    // Create a new test store.
+    let path = ".db_test_commit_receipts";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    // Spawn a `Mempool` instance.
+    let (tx_consensus_to_mempool, rx_consensus_to_mempool) = channel(1);
+    let (tx_mempool_to_consensus, mut rx_mempool_to_consensus) = channel(1);
+    Mempool::spawn(
+        name,
+        SignatureService::new(secret),
+        committee.clone(),
+        parameters,
+        store,
+        rx_consensus_to_mempool,
+        tx_mempool_to_consensus,
+        AdmitAll,
+    );
+
+    // Spawn enough mempools' voters to certify our batches.
+    for (name, secret) in keys().into_iter().take(3) {
+        let _ = voter(committee.mempool_address(&name).unwrap(), secret);
+    }
+
+    // Send a transaction with receipts.
+    sleep(Duration::from_millis(50)).await;
+    let address = committee.transactions_address(&name).unwrap();
+    let mut transactions = transactions().into_iter();
+    let transaction = transactions.next().unwrap();
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
+    transport.send(Bytes::new()).await.unwrap();
+    transport
+        .send(Bytes::from(transaction.clone()))
+        .await
+        .unwrap();
+
+    // Ensure the transaction is admitted.
+    let expected = Digest(
+        Sha512::digest(&transaction).as_slice()[..32]
+            .try_into()
+            .unwrap(),
+    );
+    let bytes = transport.next().await.unwrap().unwrap();
+    let receipt: Receipt = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(receipt, Receipt::Admitted(expected.clone()));
+
+    // Send another transaction over the same connection (before the first one is committed) to
+    // seal the batch, and ensure it is admitted.
+    let other = transactions.next().unwrap();
+    transport.send(Bytes::from(other.clone())).await.unwrap();
+    let other = Digest(Sha512::digest(&other).as_slice()[..32].try_into().unwrap());
+    let bytes = transport.next().await.unwrap().unwrap();
+    let receipt: Receipt = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(receipt, Receipt::Admitted(other.clone()));
+
+    // Commit the batch and ensure we receive the receipts of both transactions.
+    let digest = rx_mempool_to_consensus.recv().await.unwrap();
+    assert_eq!(digest, batch_digest());
+    let message = ConsensusMempoolMessage::Commit(vec![digest], 1);
+    tx_consensus_to_mempool.send(message).await.unwrap();
+    let mut receipts = Vec::new();
+    for _ in 0..2 {
+        let bytes = transport.next().await.unwrap().unwrap();
+        receipts.push(bincode::deserialize::<Receipt>(&bytes).unwrap());
+    }
+    for transaction in [expected.clone(), other] {
+        let committed = Receipt::Committed {
+            transaction,
+            batch: batch_digest(),
+            round: 1,
+        };
+        assert!(receipts.contains(&committed));
+    }
+
+    // Ensure we cannot submit the same transaction again.
+    transport.send(Bytes::from(transaction)).await.unwrap();
+    let bytes = transport.next().await.unwrap().unwrap();
+    let receipt: Receipt = bincode::deserialize(&bytes).unwrap();
+    let rejected = Receipt::Rejected {
+        transaction: expected,
+        reason: "Duplicate transaction".to_string(),
+    };
+    assert_eq!(receipt, rejected);
+}
+
+#[tokio::test]
+async fn reject_without_receipts() {
+    let (name, secret) = keys().pop().unwrap();
+    let committee = committee_with_base_port(11_300);
+
+    // Create a new test store.
+    let path = ".db_test_reject_without_receipts";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    // Spawn a `Mempool` instance.
+    let (_tx_consensus_to_mempool, rx_consensus_to_mempool) = channel(1);
+    let (tx_mempool_to_consensus, _rx_mempool_to_consensus) = channel(1);
+    Mempool::spawn(
+        name,
+        SignatureService::new(secret),
+        committee.clone(),
+        Parameters::default(),
+        store,
+        rx_consensus_to_mempool,
+        tx_mempool_to_consensus,
+        AdmitAll,
+    );
+
+    // Send the same transaction twice without opting in for receipts.
+    sleep(Duration::from_millis(50)).await;
+    let address = committee.transactions_address(&name).unwrap();
+    let transaction = transactions().pop().unwrap();
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
+    for _ in 0..2 {
+        transport
+            .send(Bytes::from(transaction.clone()))
+            .await
+            .unwrap();
+    }
+
+    // Ensure we only receive the rejection of the duplicate.
+    let bytes = transport.next().await.unwrap().unwrap();
+    let receipt: Receipt = bincode::deserialize(&bytes).unwrap();
+    let rejected = Receipt::Rejected {
+        transaction: Digest(
+            Sha512::digest(&transaction).as_slice()[..32]
+                .try_into()
+                .unwrap(),
+        ),
+        reason: "Duplicate transaction".to_string(),
+    };
+    assert_eq!(receipt, rejected);
+}
+
+#[tokio::test]
+async fn vote_for_signed_batches() {
+    let (name, secret) = keys().pop().unwrap();
+    let committee = committee_with_base_port(11_200);
+
+    // Create a new test store.
+    let path = ".db_test_vote_for_signed_batches";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Spawn a `Mempool` instance.
+    let (_tx_consensus_to_mempool, rx_consensus_to_mempool) = channel(1);
+    let (tx_mempool_to_consensus, _rx_mempool_to_consensus) = channel(1);
+    Mempool::spawn(
+        name,
+        SignatureService::new(secret),
+        committee.clone(),
+        Parameters::default(),
+        store.clone(),
+        rx_consensus_to_mempool,
+        tx_mempool_to_consensus,
+        AdmitAll,
+    );
+
+    // Send a batch signed by another authority.
+    sleep(Duration::from_millis(50)).await;
+    let (author, author_secret) = keys().remove(0);
+    let serialized = bincode::serialize(&MempoolMessage::Batch(transactions())).unwrap();
+    let digest = batch_digest();
+    let signature = Signature::new(
+        &Certificate::author_digest(&digest, &author, committee.epoch),
+        &author_secret,
+    );
+    let message = MempoolMessage::SignedBatch(serialized.clone(), author, signature);
+    let address = committee.mempool_address(&name).unwrap();
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
+    let bytes = bincode::serialize(&message).unwrap();
+    transport.send(Bytes::from(bytes)).await.unwrap();
+
+    // Ensure the mempool stored the batch and voted for it.
+    let bytes = transport.next().await.unwrap().unwrap();
+    let vote: Signature = bincode::deserialize(&bytes).unwrap();
+    assert!(vote
+        .verify(&Certificate::vote_digest(&digest, committee.epoch), &name)
+        .is_ok());
+    let stored = store.read(digest.to_vec()).await.unwrap();
+    assert_eq!(stored, Some(serialized.clone()));
+
+    // Ensure the mempool does not vote for batches with an invalid signature.
+    let (_, other_secret) = keys().remove(1);
+    let forged = Signature::new(
+        &Certificate::author_digest(&digest, &author, committee.epoch),
+        &other_secret,
+    );
+    let message = MempoolMessage::SignedBatch(serialized, author, forged);
+    let bytes = bincode::serialize(&message).unwrap();
+    transport.send(Bytes::from(bytes)).await.unwrap();
+    let reply = transport.next().await.unwrap().unwrap();
+    assert_eq!(reply.freeze(), Bytes::from("Ack"));
+}
@@ -1,13 +1,14 @@
 use super::*;
-use crate::common::batch;
+use crate::common::{batch, certificate};
 use crate::mempool::MempoolMessage;
 use std::fs;
 use tokio::sync::mpsc::channel;
 
 #[tokio::test]
 async fn hash_and_store() {
-    let (tx_batch, rx_batch) = channel(1);
+    let (tx_message, rx_message) = channel(1);
     let (tx_digest, mut rx_digest) = channel(1);
+    let (tx_collector, mut rx_collector) = channel(1);
 
     // Create a new test store.
     let path = ".db_test_hash_and_store";
@@ -15,12 +16,26 @@ async fn hash_and_store() {
     let mut store = Store::new(path).unwrap();
 
     // Spawn a new `Processor` instance.
-    Processor::spawn(store.clone(), rx_batch, tx_digest);
+    let deduplicator = Arc::new(Mutex::new(Deduplicator::new(50, 1_000)));
+    Processor::spawn(
+        store.clone(),
+        rx_message,
+        tx_digest,
+        tx_collector,
+        deduplicator,
+    );
 
-    // Send a batch to the `Processor`.
+    // Send a batch and its certificate to the `Processor`.
     let message = MempoolMessage::Batch(batch());
     let serialized = bincode::serialize(&message).unwrap();
-    tx_batch.send(serialized.clone()).await.unwrap();
+    tx_message
+        .send(ProcessorMessage::Batch(serialized.clone()))
+        .await
+        .unwrap();
+    tx_message
+        .send(ProcessorMessage::Certificate(certificate()))
+        .await
+        .unwrap();
 
     // Ensure the `Processor` outputs the batch's digest.
     let digest = Digest(
@@ -28,11 +43,58 @@ async fn hash_and_store() {
             .try_into()
             .unwrap(),
     );
+    match rx_collector.recv().await {
+        Some(CollectorMessage::Stored(x)) => assert_eq!(x, digest),
+        x => panic!("Unexpected collector message: {:?}", x),
+    }
     let received = rx_digest.recv().await.unwrap();
     assert_eq!(digest.clone(), received);
 
-    // Ensure the `Processor` correctly stored the batch.
+    // Ensure the `Processor` correctly stored the batch and its certificate.
     let stored_batch = store.read(digest.to_vec()).await.unwrap();
     assert!(stored_batch.is_some(), "The batch is not in the store");
     assert_eq!(stored_batch.unwrap(), serialized);
+    let stored_certificate = store.read(Certificate::key(&digest)).await.unwrap();
+    assert!(
+        stored_certificate.is_some(),
+        "The certificate is not in the store"
+    );
+}
+
+#[tokio::test]
+async fn drop_committed_certificate() {
+    let (tx_message, rx_message) = channel(1);
+    let (tx_digest, mut rx_digest) = channel(1);
+    let (tx_collector, mut rx_collector) = channel(1);
+
+    // Create a new test store.
+    let path = ".db_test_drop_committed_certificate";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Spawn a new `Processor` instance that already committed the batch.
+    let digest = certificate().digest;
+    let mut deduplicator = Deduplicator::new(50, 1_000);
+    deduplicator.commit(vec![digest.clone()], Vec::new());
+    let deduplicator = Arc::new(Mutex::new(deduplicator));
+    Processor::spawn(
+        store.clone(),
+        rx_message,
+        tx_digest,
+        tx_collector,
+        deduplicator,
+    );
+
+    // Send the certificate of the batch to the `Processor`.
+    tx_message
+        .send(ProcessorMessage::Certificate(certificate()))
+        .await
+        .unwrap();
+    drop(tx_message);
+
+    // Ensure the `Processor` neither outputs the batch's digest nor stores its certificate.
+    assert!(rx_digest.recv().await.is_none());
+    assert!(rx_collector.recv().await.is_none());
+    let stored_certificate = store.read(Certificate::key(&digest)).await.unwrap();
+    assert!(stored_certificate.is_none());
 }
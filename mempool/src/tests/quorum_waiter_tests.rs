@@ -1,7 +1,8 @@
 use super::*;
-use crate::common::{batch, committee_with_base_port, keys, listener};
+use crate::common::{batch, batch_digest, committee_with_base_port, keys, voter};
 use crate::mempool::MempoolMessage;
 use bytes::Bytes;
+use crypto::SignatureService;
 use futures::future::try_join_all;
 use network::ReliableSender;
 use tokio::sync::mpsc::channel;
@@ -9,44 +10,66 @@ use tokio::sync::mpsc::channel;
 #[tokio::test]
 async fn wait_for_quorum() {
     let (tx_message, rx_message) = channel(1);
-    let (tx_batch, mut rx_batch) = channel(1);
-    let (myself, _) = keys().pop().unwrap();
+    let (tx_processor, mut rx_processor) = channel(2);
+    let (myself, secret) = keys().pop().unwrap();
     let committee = committee_with_base_port(7_000);
 
     // Spawn a `QuorumWaiter` instance.
-    QuorumWaiter::spawn(committee.clone(), /* stake */ 1, rx_message, tx_batch);
+    let (_tx_committee, rx_committee) = watch::channel(committee.clone());
+    QuorumWaiter::spawn(myself, rx_committee, rx_message, tx_processor);
 
-    // Make a batch.
+    // Make and sign a batch.
     let message = MempoolMessage::Batch(batch());
     let serialized = bincode::serialize(&message).unwrap();
-    let expected = Bytes::from(serialized.clone());
+    let digest = batch_digest();
+    let signature = SignatureService::new(secret)
+        .request_signature(Certificate::author_digest(
+            &digest,
+            &myself,
+            committee.epoch,
+        ))
+        .await;
 
-    // Spawn enough listeners to acknowledge our batches.
+    // Spawn enough voters to certify our batch.
     let mut names = Vec::new();
     let mut addresses = Vec::new();
-    let mut listener_handles = Vec::new();
-    for (name, address) in committee.broadcast_addresses(&myself) {
-        let handle = listener(address, Some(expected.clone()));
+    let mut voter_handles = Vec::new();
+    for (name, secret) in keys().into_iter().take(3) {
+        let address = committee.mempool_address(&name).unwrap();
+        let handle = voter(address, secret);
         names.push(name);
         addresses.push(address);
-        listener_handles.push(handle);
+        voter_handles.push(handle);
     }
 
     // Broadcast the batch through the network.
-    let bytes = Bytes::from(serialized.clone());
+    let message = MempoolMessage::SignedBatch(serialized.clone(), myself, signature.clone());
+    let bytes = Bytes::from(bincode::serialize(&message).unwrap());
     let handlers = ReliableSender::new().broadcast(addresses, bytes).await;
 
     // Forward the batch along with the handlers to the `QuorumWaiter`.
     let message = QuorumWaiterMessage {
         batch: serialized.clone(),
+        digest: digest.clone(),
+        signature,
         handlers: names.into_iter().zip(handlers.into_iter()).collect(),
     };
     tx_message.send(message).await.unwrap();
 
-    // Wait for the `QuorumWaiter` to gather enough acknowledgements and output the batch.
-    let output = rx_batch.recv().await.unwrap();
-    assert_eq!(output, serialized);
+    // Wait for the `QuorumWaiter` to gather enough votes and output the batch and its certificate.
+    match rx_processor.recv().await.unwrap() {
+        ProcessorMessage::Batch(output) => assert_eq!(output, serialized),
+        _ => panic!("Unexpected message"),
+    }
+    match rx_processor.recv().await.unwrap() {
+        ProcessorMessage::Certificate(certificate) => {
+            assert_eq!(certificate.digest, digest);
+            assert_eq!(certificate.author, myself);
+            assert!(certificate.verify(&committee).is_ok());
+        }
+        _ => panic!("Unexpected message"),
+    }
 
-    // Ensure the other listeners correctly received the batch.
-    assert!(try_join_all(listener_handles).await.is_ok());
+    // Ensure the voters correctly received the batch.
+    assert!(try_join_all(voter_handles).await.is_ok());
 }
@@ -0,0 +1,44 @@
+use super::*;
+use crate::common::batch_digest;
+use tokio::sync::mpsc::channel;
+
+#[tokio::test]
+async fn commit_receipt() {
+    let (tx_message, rx_message) = channel(1);
+    ReceiptWaiter::spawn(/* gc_depth */ 50, rx_message);
+
+    // Subscribe to the receipt of a transaction.
+    let transaction = Digest([1u8; 32]);
+    let (sender, receiver) = oneshot::channel();
+    let message =
+        ReceiptWaiterMessage::Subscribe(batch_digest(), vec![(transaction.clone(), sender)]);
+    tx_message.send(message).await.unwrap();
+
+    // Commit the batch and ensure we receive the receipt.
+    let message = ReceiptWaiterMessage::Commit(vec![batch_digest()], 1);
+    tx_message.send(message).await.unwrap();
+    let expected = Receipt::Committed {
+        transaction,
+        batch: batch_digest(),
+        round: 1,
+    };
+    assert_eq!(receiver.await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn expired_receipt() {
+    let (tx_message, rx_message) = channel(1);
+    ReceiptWaiter::spawn(/* gc_depth */ 50, rx_message);
+
+    // Subscribe to the receipt of a transaction.
+    let transaction = Digest([1u8; 32]);
+    let (sender, receiver) = oneshot::channel();
+    let message =
+        ReceiptWaiterMessage::Subscribe(batch_digest(), vec![(transaction.clone(), sender)]);
+    tx_message.send(message).await.unwrap();
+
+    // Commit other batches until the batch is garbage collected.
+    let message = ReceiptWaiterMessage::Commit(Vec::new(), 100);
+    tx_message.send(message).await.unwrap();
+    assert_eq!(receiver.await.unwrap(), Receipt::Expired(transaction));
+}
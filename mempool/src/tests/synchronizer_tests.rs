@@ -1,37 +1,56 @@
 use super::*;
-use crate::common::{batch_digest, committee_with_base_port, keys, listener};
+use crate::common::{
+    batch_digest, committee_with_base_port, keys, listener, serialized_batch, transactions,
+};
 use std::fs;
 use tokio::sync::mpsc::channel;
 
 #[tokio::test]
 async fn synchronize() {
     let (tx_message, rx_message) = channel(1);
+    let (tx_reply, rx_reply) = channel(2);
+    let (tx_collector, mut rx_collector) = channel(1);
 
     let mut keys = keys();
-    let (name, _) = keys.pop().unwrap();
+    let (name, secret) = keys.pop().unwrap();
+    let signature_service = SignatureService::new(secret);
     let committee = committee_with_base_port(9_000);
 
     // Create a new test store.
     let path = ".db_test_synchronize";
     let _ = fs::remove_dir_all(path);
-    let store = Store::new(path).unwrap();
+    let mut store = Store::new(path).unwrap();
 
     // Spawn a `Synchronizer` instance.
+    let (tx_committee, _rx_committee) = watch::channel(committee.clone());
+    let (tx_receipt_waiter, _rx_receipt_waiter) = channel(1);
     Synchronizer::spawn(
         name,
+        signature_service.clone(),
         committee.clone(),
         store.clone(),
         /* gc_depth */ 50, // Not used in this test.
         /* sync_retry_delay */ 1_000_000, // Ensure it is not triggered.
         /* sync_retry_nodes */ 3, // Not used in this test.
         rx_message,
+        rx_reply,
+        tx_committee,
+        tx_receipt_waiter,
+        tx_collector,
+        Arc::new(Mutex::new(Deduplicator::new(50, 1_000))),
     );
 
     // Spawn a listener to receive our batch requests.
     let (target, _) = keys.pop().unwrap();
     let address = committee.mempool_address(&target).unwrap();
     let missing = vec![batch_digest()];
-    let message = MempoolMessage::BatchRequest(missing.clone(), name);
+    let nonce = Nonce {
+        epoch: committee.epoch,
+        round: 0,
+        counter: 1,
+    };
+    let request = BatchRequest::new(missing.clone(), name, target, nonce, signature_service).await;
+    let message = MempoolMessage::BatchRequest(request);
     let serialized = bincode::serialize(&message).unwrap();
     let handle = listener(address, Some(Bytes::from(serialized)));
 
@@ -41,4 +60,149 @@ async fn synchronize() {
 
     // Ensure the target receives the sync request.
     assert!(handle.await.is_ok());
+
+    // Reply with the requested batch, preceded by a batch we did not request.
+    let unsolicited = bincode::serialize(&MempoolMessage::Batch(vec![vec![9; 100]])).unwrap();
+    let unsolicited_digest = Digest(
+        Sha512::digest(&unsolicited).as_slice()[..32]
+            .try_into()
+            .unwrap(),
+    );
+    tx_reply.send(SyncReply::Batch(unsolicited)).await.unwrap();
+    tx_reply
+        .send(SyncReply::Batch(serialized_batch()))
+        .await
+        .unwrap();
+
+    // Ensure we only stored the batch we requested.
+    let stored = store.notify_read(batch_digest().to_vec()).await.unwrap();
+    assert_eq!(stored, serialized_batch());
+    let stored = store.read(unsolicited_digest.to_vec()).await.unwrap();
+    assert!(stored.is_none());
+
+    // Ensure the `Collector` tracks the batch we stored.
+    match rx_collector.recv().await {
+        Some(CollectorMessage::Stored(x)) => assert_eq!(x, batch_digest()),
+        x => panic!("Unexpected collector message: {:?}", x),
+    }
+}
+
+#[tokio::test]
+async fn retry_pruned() {
+    let (tx_message, rx_message) = channel(1);
+    let (tx_reply, rx_reply) = channel(1);
+    let (tx_collector, _rx_collector) = channel(1);
+
+    let mut keys = keys();
+    let (name, secret) = keys.pop().unwrap();
+    let signature_service = SignatureService::new(secret);
+    let committee = committee_with_base_port(9_100);
+
+    // Create a new test store.
+    let path = ".db_test_retry_pruned";
+    let _ = fs::remove_dir_all(path);
+    let store = Store::new(path).unwrap();
+
+    // Spawn a `Synchronizer` instance.
+    let (tx_committee, _rx_committee) = watch::channel(committee.clone());
+    let (tx_receipt_waiter, _rx_receipt_waiter) = channel(1);
+    Synchronizer::spawn(
+        name,
+        signature_service.clone(),
+        committee.clone(),
+        store,
+        /* gc_depth */ 50, // Not used in this test.
+        /* sync_retry_delay */ 1_000_000, // Ensure it is only triggered by the reply.
+        /* sync_retry_nodes */ 3, // Reach all the other nodes.
+        rx_message,
+        rx_reply,
+        tx_committee,
+        tx_receipt_waiter,
+        tx_collector,
+        Arc::new(Mutex::new(Deduplicator::new(50, 1_000))),
+    );
+
+    // Spawn listeners to receive our batch requests.
+    let (target, _) = keys.pop().unwrap();
+    let (other, _) = keys.pop().unwrap();
+    let missing = vec![batch_digest()];
+    let nonce = Nonce {
+        epoch: committee.epoch,
+        round: 0,
+        counter: 1,
+    };
+    let request = BatchRequest::new(missing.clone(), name, target, nonce, signature_service).await;
+    let message = MempoolMessage::BatchRequest(request);
+    let serialized = Bytes::from(bincode::serialize(&message).unwrap());
+    let address = committee.mempool_address(&target).unwrap();
+    let handle = listener(address, Some(serialized));
+    // The retries are signed for each recipient (in a random order).
+    let address = committee.mempool_address(&other).unwrap();
+    let retry_handle = listener(address, None);
+
+    // Send a sync request.
+    let message = ConsensusMempoolMessage::Synchronize(missing.clone(), target);
+    tx_message.send(message).await.unwrap();
+    assert!(handle.await.is_ok());
+
+    // The target replies that it pruned the batch.
+    tx_reply
+        .send(SyncReply::Pruned(missing, target))
+        .await
+        .unwrap();
+
+    // Ensure we retry with the other nodes (without waiting for `sync_retry_delay`).
+    assert!(retry_handle.await.is_ok());
+}
+
+#[tokio::test]
+async fn reject_committed_transactions() {
+    let (tx_message, rx_message) = channel(1);
+    let (_tx_reply, rx_reply) = channel(1);
+    let (tx_collector, mut rx_collector) = channel(1);
+
+    let (name, secret) = keys().pop().unwrap();
+    let committee = committee_with_base_port(9_200);
+
+    // Create a new test store holding a batch.
+    let path = ".db_test_reject_committed_transactions";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+    store
+        .write(batch_digest().to_vec(), serialized_batch())
+        .await;
+
+    // Spawn a `Synchronizer` instance.
+    let (tx_committee, _rx_committee) = watch::channel(committee.clone());
+    let (tx_receipt_waiter, _rx_receipt_waiter) = channel(1);
+    let deduplicator = Arc::new(Mutex::new(Deduplicator::new(50, 1_000)));
+    Synchronizer::spawn(
+        name,
+        SignatureService::new(secret),
+        committee,
+        store,
+        /* gc_depth */ 50, // Not used in this test.
+        /* sync_retry_delay */ 1_000_000, // Not used in this test.
+        /* sync_retry_nodes */ 3, // Not used in this test.
+        rx_message,
+        rx_reply,
+        tx_committee,
+        tx_receipt_waiter,
+        tx_collector,
+        deduplicator.clone(),
+    );
+
+    // The consensus commits the batch (its transactions were submitted to another node).
+    let message = ConsensusMempoolMessage::Commit(vec![batch_digest()], 1);
+    tx_message.send(message).await.unwrap();
+    match rx_collector.recv().await {
+        Some(CollectorMessage::Commit(x)) => assert_eq!(x, vec![batch_digest()]),
+        x => panic!("Unexpected collector message: {:?}", x),
+    }
+
+    // Ensure we reject its transactions.
+    for transaction in transactions() {
+        let digest = transaction_digest(&transaction);
+        assert!(!deduplicator.lock().unwrap().admit(digest));
+    }
 }
@@ -0,0 +1,37 @@
+use super::*;
+use crate::common::{keys, transaction};
+
+#[tokio::test]
+async fn reject_large_transactions() {
+    let parameters = Parameters {
+        max_transaction_size: 100,
+        ..Parameters::default()
+    };
+    let validator = BasicValidator::new(&parameters);
+    assert!(validator.validate(&transaction()).await.is_ok());
+    assert!(validator.validate(&[0; 101]).await.is_err());
+}
+
+#[tokio::test]
+async fn verify_signed_transactions() {
+    let parameters = Parameters {
+        signed_transactions: true,
+        ..Parameters::default()
+    };
+    let validator = BasicValidator::new(&parameters);
+    let (name, secret) = keys().pop().unwrap();
+
+    // Accept a properly signed transaction.
+    let signed = SignedTransaction::new(transaction(), name, &secret);
+    let serialized = bincode::serialize(&signed).unwrap();
+    assert!(validator.validate(&serialized).await.is_ok());
+
+    // Reject a transaction signed by someone else.
+    let (_, other_secret) = keys().remove(0);
+    let forged = SignedTransaction::new(transaction(), name, &other_secret);
+    let serialized = bincode::serialize(&forged).unwrap();
+    assert!(validator.validate(&serialized).await.is_err());
+
+    // Reject a transaction without envelope.
+    assert!(validator.validate(&transaction()).await.is_err());
+}
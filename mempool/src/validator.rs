@@ -0,0 +1,104 @@
+use crate::config::Parameters;
+use async_trait::async_trait;
+use crypto::{Digest, PublicKey, SecretKey, Signature};
+use ed25519_dalek::{Digest as _, Sha512};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto as _;
+
+#[cfg(test)]
+#[path = "tests/validator_tests.rs"]
+pub mod validator_tests;
+
+/// Decides whether the mempool admits a client transaction. It runs before the transaction is
+/// added to the current batch; the reason of a rejection is sent back to the client in a
+/// `Receipt::Rejected`. Clients that send an empty message over their connection before their
+/// transactions also receive the `Receipt::Admitted` and commit receipts of the transactions we
+/// admit.
+#[async_trait]
+pub trait TransactionValidator: Send + Sync + 'static {
+    async fn validate(&self, transaction: &[u8]) -> Result<(), String>;
+}
+
+/// Admits any transaction.
+pub struct AdmitAll;
+
+#[async_trait]
+impl TransactionValidator for AdmitAll {
+    async fn validate(&self, _transaction: &[u8]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Admits the transactions admitted by both validators (the first one runs first).
+#[async_trait]
+impl<A: TransactionValidator, B: TransactionValidator> TransactionValidator for (A, B) {
+    async fn validate(&self, transaction: &[u8]) -> Result<(), String> {
+        self.0.validate(transaction).await?;
+        self.1.validate(transaction).await
+    }
+}
+
+/// The envelope of a signed transaction: the client's ed25519 signature over the hash of the
+/// payload. Clients send envelopes serialized with bincode.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SignedTransaction {
+    pub author: PublicKey,
+    pub signature: Signature,
+    pub payload: Vec<u8>,
+}
+
+impl SignedTransaction {
+    pub fn new(payload: Vec<u8>, author: PublicKey, secret: &SecretKey) -> Self {
+        let signature = Signature::new(&Self::digest(&payload), secret);
+        Self {
+            author,
+            signature,
+            payload,
+        }
+    }
+
+    fn digest(payload: &[u8]) -> Digest {
+        Digest(Sha512::digest(payload).as_slice()[..32].try_into().unwrap())
+    }
+
+    pub fn verify(&self) -> Result<(), String> {
+        self.signature
+            .verify(&Self::digest(&self.payload), &self.author)
+            .map_err(|e| format!("Invalid signature: {}", e))
+    }
+}
+
+/// The built-in validator: it enforces the maximum transaction size and (if enabled) the signed
+/// transaction envelope.
+pub struct BasicValidator {
+    max_transaction_size: usize,
+    signed_transactions: bool,
+}
+
+impl BasicValidator {
+    pub fn new(parameters: &Parameters) -> Self {
+        Self {
+            max_transaction_size: parameters.max_transaction_size,
+            signed_transactions: parameters.signed_transactions,
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionValidator for BasicValidator {
+    async fn validate(&self, transaction: &[u8]) -> Result<(), String> {
+        if transaction.len() > self.max_transaction_size {
+            return Err(format!(
+                "Transaction too large ({} B > {} B)",
+                transaction.len(),
+                self.max_transaction_size
+            ));
+        }
+        if self.signed_transactions {
+            bincode::deserialize::<SignedTransaction>(transaction)
+                .map_err(|e| format!("Malformed envelope: {}", e))?
+                .verify()?;
+        }
+        Ok(())
+    }
+}
@@ -22,4 +22,10 @@ pub enum NetworkError {
 
     #[error("Receive unexpected ACK from {0}")]
     UnexpectedAck(SocketAddr),
+
+    #[error("Peer {0} exceeded its request quota")]
+    QuotaExceeded(String),
+
+    #[error("Received duplicate request from {0}")]
+    DuplicateRequest(String),
 }
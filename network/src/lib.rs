@@ -1,13 +1,20 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 mod error;
+mod quota;
 mod receiver;
 mod reliable_sender;
 mod simple_sender;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+mod transport;
 
 #[cfg(test)]
 #[path = "tests/common.rs"]
 pub mod common;
 
+pub use crate::error::NetworkError;
+pub use crate::quota::RequestQuota;
 pub use crate::receiver::{MessageHandler, Receiver, Writer};
 pub use crate::reliable_sender::{CancelHandler, ReliableSender};
 pub use crate::simple_sender::SimpleSender;
+pub use crate::transport::Stream;
@@ -0,0 +1,79 @@
+// Copyright(C) Facebook, Inc. and its affiliates.
+use crate::error::NetworkError;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+use tokio::time::{Duration, Instant};
+
+#[cfg(test)]
+#[path = "tests/quota_tests.rs"]
+pub mod quota_tests;
+
+/// The time window over which the quotas apply.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Accounts for the requests a node serves to each of its peers. Every second, each peer may
+/// make up to `max_requests` requests and be served up to `max_bytes` bytes. Requests identical
+/// to one already served during the current second are dropped. A zero limit disables it.
+pub struct RequestQuota<P, K> {
+    /// The maximum number of requests per peer and per second.
+    max_requests: u64,
+    /// The maximum number of bytes served per peer and per second.
+    max_bytes: u64,
+    /// The beginning of the current window.
+    window_start: Instant,
+    /// The number of requests and bytes served to each peer during the current window.
+    usage: HashMap<P, (u64, u64)>,
+    /// The requests served during the current window.
+    served: HashSet<(P, K)>,
+}
+
+impl<P, K> RequestQuota<P, K>
+where
+    P: Hash + Eq + Clone + Debug,
+    K: Hash + Eq,
+{
+    pub fn new(max_requests: u64, max_bytes: u64) -> Self {
+        Self {
+            max_requests,
+            max_bytes,
+            window_start: Instant::now(),
+            usage: HashMap::new(),
+            served: HashSet::new(),
+        }
+    }
+
+    fn exceeds(usage: u64, limit: u64) -> bool {
+        limit != 0 && usage >= limit
+    }
+
+    /// Check whether we may serve the request of the peer. If so, the request is accounted for.
+    pub fn admit(&mut self, peer: &P, request: K) -> Result<(), NetworkError> {
+        if self.window_start.elapsed() >= WINDOW {
+            self.window_start = Instant::now();
+            self.usage.clear();
+            self.served.clear();
+        }
+
+        let (requests, bytes) = self.usage.entry(peer.clone()).or_insert((0, 0));
+        if Self::exceeds(*requests, self.max_requests) || Self::exceeds(*bytes, self.max_bytes) {
+            return Err(NetworkError::QuotaExceeded(format!("{:?}", peer)));
+        }
+        if !self.served.insert((peer.clone(), request)) {
+            return Err(NetworkError::DuplicateRequest(format!("{:?}", peer)));
+        }
+        *requests += 1;
+        Ok(())
+    }
+
+    /// Account for bytes we are about to serve to the peer. Fails if the peer already spent its
+    /// bytes budget for the current window: replies spanning several messages must then stop.
+    pub fn charge(&mut self, peer: &P, bytes: usize) -> Result<(), NetworkError> {
+        let (_, x) = self.usage.entry(peer.clone()).or_insert((0, 0));
+        if Self::exceeds(*x, self.max_bytes) {
+            return Err(NetworkError::QuotaExceeded(format!("{:?}", peer)));
+        }
+        *x = x.saturating_add(bytes as u64);
+        Ok(())
+    }
+}
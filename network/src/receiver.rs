@@ -1,13 +1,15 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use crate::error::NetworkError;
+use crate::transport::{Listener, Stream};
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures::sink::SinkExt as _;
 use futures::stream::SplitSink;
 use futures::stream::StreamExt as _;
 use log::{debug, info, warn};
 use std::error::Error;
 use std::net::SocketAddr;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
 #[cfg(test)]
@@ -15,7 +17,7 @@ use tokio_util::codec::{Framed, LengthDelimitedCodec};
 pub mod receiver_tests;
 
 /// Convenient alias for the writer end of the TCP channel.
-pub type Writer = SplitSink<Framed<TcpStream, LengthDelimitedCodec>, Bytes>;
+pub type Writer = SplitSink<Framed<Stream, LengthDelimitedCodec>, Bytes>;
 
 #[async_trait]
 pub trait MessageHandler: Clone + Send + Sync + 'static {
@@ -24,6 +26,13 @@ pub trait MessageHandler: Clone + Send + Sync + 'static {
     /// forward them through the appropriate delivery channel. Then `writer` can be used to send back
     /// responses or acknowledgements to the sender machine (see unit tests for examples).
     async fn dispatch(&self, writer: &mut Writer, message: Bytes) -> Result<(), Box<dyn Error>>;
+
+    /// The messages to send to the peer outside of `dispatch` (eg. notifications). The runner of
+    /// each connection takes this channel from its clone of the handler before reading messages,
+    /// and sends the messages it receives over the connection.
+    fn outgoing(&mut self) -> Option<mpsc::Receiver<Bytes>> {
+        None
+    }
 }
 
 /// For each incoming request, we spawn a new runner responsible to receive messages and forward them
@@ -45,7 +54,7 @@ impl<Handler: MessageHandler> Receiver<Handler> {
 
     /// Main loop responsible to accept incoming connections and spawn a new runner to handle it.
     async fn run(&self) {
-        let listener = TcpListener::bind(&self.address)
+        let mut listener = Listener::bind(self.address)
             .await
             .expect("Failed to bind TCP port");
 
@@ -65,25 +74,43 @@ impl<Handler: MessageHandler> Receiver<Handler> {
 
     /// Spawn a new runner to handle a specific TCP connection. It receives messages and process them
     /// using the provided handler.
-    async fn spawn_runner(socket: TcpStream, peer: SocketAddr, handler: Handler) {
+    async fn spawn_runner(socket: Stream, peer: SocketAddr, mut handler: Handler) {
         tokio::spawn(async move {
             let transport = Framed::new(socket, LengthDelimitedCodec::new());
             let (mut writer, mut reader) = transport.split();
-            while let Some(frame) = reader.next().await {
-                match frame.map_err(|e| NetworkError::FailedToReceiveMessage(peer, e)) {
-                    Ok(message) => {
-                        if let Err(e) = handler.dispatch(&mut writer, message.freeze()).await {
-                            warn!("{}", e);
+            let mut outgoing = handler.outgoing();
+            loop {
+                tokio::select! {
+                    frame = reader.next() => match frame {
+                        Some(Ok(message)) => {
+                            if let Err(e) = handler.dispatch(&mut writer, message.freeze()).await {
+                                warn!("{}", e);
+                                return;
+                            }
+                        }
+                        Some(Err(e)) => {
+                            warn!("{}", NetworkError::FailedToReceiveMessage(peer, e));
+                            return;
+                        }
+                        None => break,
+                    },
+                    Some(message) = Self::next_outgoing(&mut outgoing) => {
+                        if let Err(e) = writer.send(message).await {
+                            warn!("{}", NetworkError::FailedToSendMessage(peer, e));
                             return;
                         }
-                    }
-                    Err(e) => {
-                        warn!("{}", e);
-                        return;
                     }
                 }
             }
             warn!("Connection closed by peer {}", peer);
         });
     }
+
+    /// Wait for the next outgoing message of the connection (if the handler has any).
+    async fn next_outgoing(outgoing: &mut Option<mpsc::Receiver<Bytes>>) -> Option<Bytes> {
+        match outgoing {
+            Some(x) => x.recv().await,
+            None => std::future::pending().await,
+        }
+    }
 }
@@ -1,17 +1,16 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use crate::error::NetworkError;
+use crate::transport::{self, Stream};
 use bytes::Bytes;
 use futures::sink::SinkExt as _;
 use futures::stream::StreamExt as _;
 use log::{info, warn};
 use rand::prelude::SliceRandom as _;
 use rand::rngs::SmallRng;
-use rand::SeedableRng as _;
 use std::cmp::min;
 use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
 use std::net::SocketAddr;
-use tokio::net::TcpStream;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::sync::oneshot;
 use tokio::time::{sleep, Duration};
@@ -45,7 +44,7 @@ impl ReliableSender {
     pub fn new() -> Self {
         Self {
             connections: HashMap::new(),
-            rng: SmallRng::from_entropy(),
+            rng: transport::rng(),
         }
     }
 
@@ -94,6 +93,8 @@ impl ReliableSender {
         data: Bytes,
         nodes: usize,
     ) -> Vec<CancelHandler> {
+        // Only the RNG decides which nodes we pick, not the order of the addresses.
+        addresses.sort();
         addresses.shuffle(&mut self.rng);
         addresses.truncate(nodes);
         self.broadcast(addresses, data).await
@@ -141,7 +142,7 @@ impl Connection {
         let mut delay = self.retry_delay;
         let mut retry = 0;
         loop {
-            match TcpStream::connect(self.address).await {
+            match transport::connect(self.address).await {
                 Ok(stream) => {
                     info!("Outgoing connection established with {}", self.address);
 
@@ -182,7 +183,7 @@ impl Connection {
     }
 
     /// Transmit messages once we have established a connection.
-    async fn keep_alive(&mut self, stream: TcpStream) -> NetworkError {
+    async fn keep_alive(&mut self, stream: Stream) -> NetworkError {
         // This buffer keeps all messages and handlers that we have successfully transmitted but for
         // which we are still waiting to receive an ACK.
         let mut pending_replies = VecDeque::new();
@@ -1,15 +1,14 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use crate::error::NetworkError;
+use crate::transport;
 use bytes::Bytes;
 use futures::sink::SinkExt as _;
 use futures::stream::StreamExt as _;
 use log::{info, warn};
 use rand::prelude::SliceRandom as _;
 use rand::rngs::SmallRng;
-use rand::SeedableRng as _;
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use tokio::net::TcpStream;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
@@ -36,7 +35,7 @@ impl SimpleSender {
     pub fn new() -> Self {
         Self {
             connections: HashMap::new(),
-            rng: SmallRng::from_entropy(),
+            rng: transport::rng(),
         }
     }
 
@@ -73,16 +72,20 @@ impl SimpleSender {
 
     /// Pick a few addresses at random (specified by `nodes`) and try (best-effort) to send the
     /// message only to them. This is useful to pick nodes with whom to sync.
-    pub async fn lucky_broadcast(
-        &mut self,
-        mut addresses: Vec<SocketAddr>,
-        data: Bytes,
-        nodes: usize,
-    ) {
-        addresses.shuffle(&mut self.rng);
-        addresses.truncate(nodes);
+    pub async fn lucky_broadcast(&mut self, addresses: Vec<SocketAddr>, data: Bytes, nodes: usize) {
+        let addresses = self.pick(addresses, nodes);
         self.broadcast(addresses, data).await
     }
+
+    /// Pick a few items at random (specified by `nodes`), eg. the peers to which we send a
+    /// distinct message.
+    pub fn pick<T: Ord>(&mut self, mut items: Vec<T>, nodes: usize) -> Vec<T> {
+        // Only the RNG decides which items we pick, not their order.
+        items.sort();
+        items.shuffle(&mut self.rng);
+        items.truncate(nodes);
+        items
+    }
 }
 
 /// A connection is responsible to establish and keep alive (if possible) a connection with a single peer.
@@ -103,7 +106,7 @@ impl Connection {
     /// Main loop trying to connect to the peer and transmit messages.
     async fn run(&mut self) {
         // Try to connect to the peer.
-        let (mut writer, mut reader) = match TcpStream::connect(self.address).await {
+        let (mut writer, mut reader) = match transport::connect(self.address).await {
             Ok(stream) => Framed::new(stream, LengthDelimitedCodec::new()).split(),
             Err(e) => {
                 warn!(
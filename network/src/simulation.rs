@@ -0,0 +1,424 @@
+// Copyright(C) Facebook, Inc. and its affiliates.
+use bytes::Bytes;
+use futures::sink::SinkExt as _;
+use futures::stream::{SplitSink, SplitStream, StreamExt as _};
+use rand::rngs::SmallRng;
+use rand::{Rng as _, SeedableRng as _};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher as _};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use tokio::io::{duplex, AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Notify;
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+#[cfg(test)]
+#[path = "tests/simulation_tests.rs"]
+pub mod simulation_tests;
+
+/// The index of a simulated host.
+pub type Host = usize;
+
+/// The capacity (in bytes) of each direction of an in-process stream.
+const STREAM_CAPACITY: usize = 1 << 20;
+
+thread_local! {
+    /// The simulated network of the current thread (if any).
+    static NETWORK: RefCell<Option<State>> = const { RefCell::new(None) };
+}
+
+/// The faults injected on every link of the simulated network.
+#[derive(Clone, Copy, Debug)]
+pub struct LinkConfig {
+    /// The minimum delay to deliver a message (in ms).
+    pub min_delay: u64,
+    /// The maximum delay to deliver a message (in ms).
+    pub max_delay: u64,
+    /// The probability to lose a message. As with TCP, losing a message resets its connection.
+    pub drop_probability: f64,
+}
+
+/// Replaces the TCP connections of the current thread by in-process streams, so that several
+/// hosts can run in the same process (typically one tokio runtime per host, all driven by the
+/// same thread). Every message goes through a link that delays or drops it; the faults only
+/// depend on the seed, the order of the connections, and the order of the messages of each
+/// connection. The network is uninstalled when this handle is dropped.
+pub struct SimulatedNetwork;
+
+impl SimulatedNetwork {
+    pub fn new(seed: u64, config: LinkConfig) -> Self {
+        NETWORK.with(|network| {
+            let mut network = network.borrow_mut();
+            assert!(network.is_none(), "A network is already simulated");
+            *network = Some(State {
+                seed,
+                config,
+                host: 0,
+                listeners: HashMap::new(),
+                partition: HashMap::new(),
+                connections: HashMap::new(),
+                open: BTreeMap::new(),
+                reset: HashSet::new(),
+                opened: 0,
+                rngs: 0,
+            });
+        });
+        Self
+    }
+
+    /// Attribute the listeners and connections made from now on to the specified host. It must be
+    /// called before running any task of that host.
+    pub fn enter(&self, host: Host) {
+        with_state(|state| state.host = host);
+    }
+
+    /// Change the faults injected on the links.
+    pub fn configure(&self, config: LinkConfig) {
+        with_state(|state| state.config = config);
+    }
+
+    /// Isolate the specified groups of hosts from each other. The hosts that are not part of any
+    /// group can only reach each other.
+    pub fn partition(&self, groups: &[Vec<Host>]) {
+        with_state(|state| {
+            state.partition = groups
+                .iter()
+                .enumerate()
+                .flat_map(|(i, group)| group.iter().map(move |host| (*host, i)))
+                .collect();
+        });
+    }
+
+    pub fn heal(&self) {
+        with_state(|state| state.partition.clear());
+    }
+
+    /// Stop accepting the connections of a crashed host, and reset the connections it holds. The
+    /// other hosts notice the resets in the order the connections were opened. The tasks of the
+    /// crashed host should not run anymore.
+    pub fn crash(&self, host: Host) {
+        let wakers = with_state(|state| {
+            state.listeners.retain(|_, x| x.host != host);
+            let crashed: Vec<_> = state
+                .open
+                .iter()
+                .filter(|(_, x)| x.source == host || x.destination == host)
+                .map(|(id, _)| *id)
+                .collect();
+            let mut wakers = Vec::new();
+            for id in crashed {
+                let connection = state.open.remove(&id).unwrap();
+                state.reset.insert(id);
+                connection.stop.notify_one();
+                wakers.extend(connection.wakers);
+            }
+            wakers
+        });
+        wakers.into_iter().flatten().for_each(Waker::wake);
+    }
+}
+
+impl Drop for SimulatedNetwork {
+    fn drop(&mut self) {
+        NETWORK.with(|network| network.borrow_mut().take());
+    }
+}
+
+fn with_state<T>(f: impl FnOnce(&mut State) -> T) -> Option<T> {
+    NETWORK.with(|network| network.borrow_mut().as_mut().map(f))
+}
+
+/// Open a simulated connection, if the current thread runs a simulated network.
+pub(crate) fn connect(address: SocketAddr) -> Option<io::Result<Stream>> {
+    with_state(|state| state.connect(address))
+}
+
+/// Listen to simulated connections, if the current thread runs a simulated network.
+pub(crate) fn bind(address: SocketAddr) -> Option<Listener> {
+    with_state(|state| {
+        let (tx_connection, rx_connection) = unbounded_channel();
+        let listener = Registration {
+            host: state.host,
+            handle: Handle::current(),
+            tx_connection,
+        };
+        state.listeners.insert(address, listener);
+        Listener(rx_connection)
+    })
+}
+
+/// Ensure a simulated connection was not reset, and register the task polling one of its ends.
+fn poll_connection(id: u64, waker: &Waker) -> io::Result<()> {
+    with_state(|state| {
+        if state.reset.contains(&id) {
+            return Err(io::ErrorKind::ConnectionReset.into());
+        }
+        if let Some(connection) = state.open.get_mut(&id) {
+            if !connection.wakers.iter().any(|x| x.will_wake(waker)) {
+                connection.wakers.push(waker.clone());
+            }
+        }
+        Ok(())
+    })
+    .unwrap_or(Ok(()))
+}
+
+/// Make a seeded RNG, if the current thread runs a simulated network.
+pub(crate) fn rng() -> Option<SmallRng> {
+    with_state(|state| {
+        state.rngs += 1;
+        SmallRng::seed_from_u64(state.derive_seed((state.host, state.rngs)))
+    })
+}
+
+struct Registration {
+    host: Host,
+    /// The runtime of the host, running the links of its incoming connections.
+    handle: Handle,
+    tx_connection: UnboundedSender<(Stream, SocketAddr)>,
+}
+
+/// A connection between two hosts, tracked to reset it when either of them crashes.
+struct Connection {
+    source: Host,
+    destination: Host,
+    /// Stops the link of the connection.
+    stop: Arc<Notify>,
+    /// The tasks polling the ends of the connection.
+    wakers: Vec<Waker>,
+}
+
+struct State {
+    seed: u64,
+    config: LinkConfig,
+    /// The host whose tasks are currently running.
+    host: Host,
+    listeners: HashMap<SocketAddr, Registration>,
+    /// The group of each host (if any).
+    partition: HashMap<Host, usize>,
+    /// The number of connections opened between each pair of hosts.
+    connections: HashMap<(Host, Host), u64>,
+    /// The open connections, by order of creation.
+    open: BTreeMap<u64, Connection>,
+    /// The connections reset by a crash.
+    reset: HashSet<u64>,
+    /// The number of connections opened.
+    opened: u64,
+    /// The number of RNGs handed out.
+    rngs: u64,
+}
+
+impl State {
+    fn derive_seed<T: Hash>(&self, value: T) -> u64 {
+        // The default hasher uses fixed keys: seeds are the same across runs.
+        let mut hasher = DefaultHasher::new();
+        (self.seed, value).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn blocked(&self, source: Host, destination: Host) -> bool {
+        self.partition.get(&source) != self.partition.get(&destination)
+    }
+
+    fn connect(&mut self, address: SocketAddr) -> io::Result<Stream> {
+        // As with TCP, listeners bound to the unspecified address accept connections to any address.
+        let source = self.host;
+        let unspecified = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), address.port());
+        let listener = self
+            .listeners
+            .get(&address)
+            .or_else(|| self.listeners.get(&unspecified));
+        let (destination, handle, tx_connection) = match listener {
+            Some(x) if !self.blocked(source, x.host) => {
+                (x.host, x.handle.clone(), x.tx_connection.clone())
+            }
+            _ => return Err(io::ErrorKind::ConnectionRefused.into()),
+        };
+
+        let counter = self.connections.entry((source, destination)).or_insert(0);
+        *counter += 1;
+        let counter = *counter;
+        let seed = self.derive_seed((source, destination, counter));
+
+        self.opened += 1;
+        let id = self.opened;
+        let (client, outgoing) = duplex(STREAM_CAPACITY);
+        let (incoming, server) = duplex(STREAM_CAPACITY);
+        let peer = SocketAddr::from(([0, 0, 0, 0], source as u16));
+        tx_connection
+            .send((Stream::Simulated(End { id, stream: server }), peer))
+            .map_err(|_| io::Error::from(io::ErrorKind::ConnectionRefused))?;
+        let stop = Arc::new(Notify::new());
+        let connection = Connection {
+            source,
+            destination,
+            stop: stop.clone(),
+            wakers: Vec::new(),
+        };
+        self.open.insert(id, connection);
+        let link = Link {
+            id,
+            source,
+            destination,
+            seed,
+        };
+        handle.spawn(link.run(outgoing, incoming, stop));
+        Ok(Stream::Simulated(End { id, stream: client }))
+    }
+}
+
+type Reader = SplitStream<Framed<DuplexStream, LengthDelimitedCodec>>;
+type Writer = SplitSink<Framed<DuplexStream, LengthDelimitedCodec>, Bytes>;
+
+/// Carries the messages of a connection (in both directions), injecting the faults of the network.
+struct Link {
+    id: u64,
+    source: Host,
+    destination: Host,
+    seed: u64,
+}
+
+impl Link {
+    async fn run(self, outgoing: DuplexStream, incoming: DuplexStream, stop: Arc<Notify>) {
+        let (client_writer, client_reader) =
+            Framed::new(outgoing, LengthDelimitedCodec::new()).split();
+        let (server_writer, server_reader) =
+            Framed::new(incoming, LengthDelimitedCodec::new()).split();
+
+        // Stop forwarding messages in both directions as soon as the connection is reset.
+        let forward = SmallRng::seed_from_u64(self.seed);
+        let backward = SmallRng::seed_from_u64(self.seed.wrapping_add(1));
+        tokio::select! {
+            () = Self::forward(self.source, self.destination, forward, client_reader, server_writer) => (),
+            () = Self::forward(self.destination, self.source, backward, server_reader, client_writer) => (),
+            () = stop.notified() => (),
+        }
+        with_state(|state| state.open.remove(&self.id));
+    }
+
+    async fn forward(
+        source: Host,
+        destination: Host,
+        mut rng: SmallRng,
+        mut reader: Reader,
+        mut writer: Writer,
+    ) {
+        let mut queue: VecDeque<(Instant, Bytes)> = VecDeque::new();
+        let mut closed = false;
+        loop {
+            let deadline = queue.front().map(|(x, _)| *x);
+            tokio::select! {
+                frame = reader.next(), if !closed => match frame {
+                    Some(Ok(frame)) => {
+                        let config = with_state(|state| {
+                            (!state.blocked(source, destination)).then_some(state.config)
+                        });
+                        let config = match config.flatten() {
+                            Some(config) if !rng.gen_bool(config.drop_probability) => config,
+                            _ => return,
+                        };
+
+                        // Messages are delivered in order.
+                        let delay = rng.gen_range(config.min_delay, config.max_delay + 1);
+                        let mut deliver_at = Instant::now() + Duration::from_millis(delay);
+                        if let Some((last, _)) = queue.back() {
+                            deliver_at = deliver_at.max(*last);
+                        }
+                        queue.push_back((deliver_at, frame.freeze()));
+                    },
+                    _ => closed = true,
+                },
+                () = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    let (_, frame) = queue.pop_front().unwrap();
+                    if writer.send(frame).await.is_err() {
+                        return;
+                    }
+                },
+                else => return,
+            }
+        }
+    }
+}
+
+/// Accepts the simulated connections to an address.
+pub struct Listener(UnboundedReceiver<(Stream, SocketAddr)>);
+
+impl Listener {
+    pub async fn accept(&mut self) -> io::Result<(Stream, SocketAddr)> {
+        match self.0.recv().await {
+            Some(connection) => Ok(connection),
+            // The host crashed: never accept any other connection.
+            None => futures::future::pending().await,
+        }
+    }
+}
+
+/// Either a TCP stream or an in-process stream.
+pub enum Stream {
+    Tcp(TcpStream),
+    Simulated(End),
+}
+
+/// One end of a simulated connection.
+pub struct End {
+    id: u64,
+    stream: DuplexStream,
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Simulated(end) => {
+                poll_connection(end.id, cx.waker())?;
+                Pin::new(&mut end.stream).poll_read(cx, buf)
+            }
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Simulated(end) => {
+                poll_connection(end.id, cx.waker())?;
+                Pin::new(&mut end.stream).poll_write(cx, buf)
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Simulated(end) => {
+                poll_connection(end.id, cx.waker())?;
+                Pin::new(&mut end.stream).poll_flush(cx)
+            }
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Simulated(end) => Pin::new(&mut end.stream).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright(C) Facebook, Inc. and its affiliates.
+use super::*;
+
+#[tokio::test]
+async fn request_quota() {
+    let mut quota = RequestQuota::new(2, 1_000);
+    assert!(quota.admit(&"peer", 1).is_ok());
+    assert!(quota.admit(&"peer", 2).is_ok());
+    assert!(quota.admit(&"peer", 3).is_err());
+
+    // Other peers have their own quota.
+    assert!(quota.admit(&"other", 3).is_ok());
+}
+
+#[tokio::test]
+async fn bytes_quota() {
+    let mut quota = RequestQuota::new(10, 1_000);
+    assert!(quota.admit(&"peer", 1).is_ok());
+    assert!(quota.charge(&"peer", 600).is_ok());
+    assert!(quota.charge(&"peer", 600).is_ok());
+
+    // The peer spent its bytes budget: we stop serving it.
+    assert!(quota.charge(&"peer", 1).is_err());
+    assert!(quota.admit(&"peer", 2).is_err());
+}
+
+#[tokio::test]
+async fn unlimited_quota() {
+    let mut quota = RequestQuota::new(0, 0);
+    for i in 0..100 {
+        assert!(quota.admit(&"peer", i).is_ok());
+        assert!(quota.charge(&"peer", 1_000_000).is_ok());
+    }
+}
+
+#[tokio::test]
+async fn duplicate_request() {
+    let mut quota = RequestQuota::new(10, 1_000);
+    assert!(quota.admit(&"peer", 1).is_ok());
+    match quota.admit(&"peer", 1) {
+        Err(NetworkError::DuplicateRequest(_)) => (),
+        _ => panic!("Failed to detect duplicate request"),
+    }
+}
+
+#[tokio::test]
+async fn reset_quota() {
+    let mut quota = RequestQuota::new(1, 1_000);
+    assert!(quota.admit(&"peer", 1).is_ok());
+    assert!(quota.admit(&"peer", 2).is_err());
+
+    // The quota is reset after a second.
+    tokio::time::sleep(WINDOW).await;
+    assert!(quota.admit(&"peer", 1).is_ok());
+}
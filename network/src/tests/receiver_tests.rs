@@ -1,6 +1,7 @@
 // Copyright(C) Facebook, Inc. and its affiliates.
 use super::*;
-use futures::sink::SinkExt as _;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpStream;
 use tokio::sync::mpsc::channel;
 use tokio::sync::mpsc::Sender;
 use tokio::time::{sleep, Duration};
@@ -25,6 +26,22 @@ impl MessageHandler for TestHandler {
     }
 }
 
+#[derive(Clone)]
+struct NotifyingHandler {
+    outgoing: Arc<Mutex<Option<mpsc::Receiver<Bytes>>>>,
+}
+
+#[async_trait]
+impl MessageHandler for NotifyingHandler {
+    async fn dispatch(&self, _writer: &mut Writer, _message: Bytes) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn outgoing(&mut self) -> Option<mpsc::Receiver<Bytes>> {
+        self.outgoing.lock().unwrap().take()
+    }
+}
+
 #[tokio::test]
 async fn receive() {
     // Make the network receiver.
@@ -46,3 +63,24 @@ async fn receive() {
     let received = message.unwrap();
     assert_eq!(received, sent);
 }
+
+#[tokio::test]
+async fn send_outgoing() {
+    // Make the network receiver.
+    let address = "127.0.0.1:4100".parse::<SocketAddr>().unwrap();
+    let (tx, rx) = channel(1);
+    let outgoing = Arc::new(Mutex::new(Some(rx)));
+    Receiver::spawn(address, NotifyingHandler { outgoing });
+    sleep(Duration::from_millis(50)).await;
+
+    // Connect to the receiver.
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
+    sleep(Duration::from_millis(50)).await;
+
+    // Ensure the receiver forwards the outgoing messages of the connection.
+    let sent = Bytes::from("Notification");
+    tx.send(sent.clone()).await.unwrap();
+    let received = transport.next().await.unwrap().unwrap();
+    assert_eq!(received.freeze(), sent);
+}
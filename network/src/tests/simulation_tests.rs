@@ -0,0 +1,125 @@
+// Copyright(C) Facebook, Inc. and its affiliates.
+use super::*;
+use crate::{MessageHandler, Receiver, SimpleSender, Writer};
+use async_trait::async_trait;
+use std::error::Error;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::{channel, Sender};
+use tokio::time::{sleep, timeout};
+
+const LINK: LinkConfig = LinkConfig {
+    min_delay: 1,
+    max_delay: 10,
+    drop_probability: 0.0,
+};
+
+#[derive(Clone)]
+struct TestHandler {
+    deliver: Sender<Bytes>,
+}
+
+#[async_trait]
+impl MessageHandler for TestHandler {
+    async fn dispatch(&self, _writer: &mut Writer, message: Bytes) -> Result<(), Box<dyn Error>> {
+        self.deliver.send(message).await.unwrap();
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn deliver_in_order() {
+    let network = SimulatedNetwork::new(0, LINK);
+
+    // Make the network receiver of host 1.
+    let address = "127.0.0.1:5000".parse::<SocketAddr>().unwrap();
+    let (tx, mut rx) = channel(10);
+    network.enter(1);
+    Receiver::spawn(address, TestHandler { deliver: tx });
+    sleep(Duration::from_millis(10)).await;
+
+    // Send messages from host 0.
+    network.enter(0);
+    let mut sender = SimpleSender::new();
+    for i in 0..5u8 {
+        sender.send(address, Bytes::from(vec![i])).await;
+    }
+
+    // Ensure the messages are received in order.
+    for i in 0..5u8 {
+        match timeout(Duration::from_secs(1), rx.recv()).await {
+            Ok(Some(message)) => assert_eq!(message, Bytes::from(vec![i])),
+            _ => panic!("Failed to receive simulated message"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn partition_hosts() {
+    let network = SimulatedNetwork::new(0, LINK);
+
+    // Make the network receiver of host 1.
+    let address = "127.0.0.1:5001".parse::<SocketAddr>().unwrap();
+    let (tx, mut rx) = channel(10);
+    network.enter(1);
+    Receiver::spawn(address, TestHandler { deliver: tx });
+    sleep(Duration::from_millis(10)).await;
+
+    // Isolate host 0 from host 1 and send a message.
+    network.partition(&[vec![0], vec![1]]);
+    network.enter(0);
+    let mut sender = SimpleSender::new();
+    sender.send(address, Bytes::from("Hello")).await;
+    assert!(timeout(Duration::from_millis(100), rx.recv())
+        .await
+        .is_err());
+
+    // The message goes through once the partition heals.
+    network.heal();
+    sender.send(address, Bytes::from("Hello")).await;
+    match timeout(Duration::from_secs(1), rx.recv()).await {
+        Ok(Some(message)) => assert_eq!(message, Bytes::from("Hello")),
+        _ => panic!("Failed to receive simulated message"),
+    }
+}
+
+#[tokio::test]
+async fn crash_host() {
+    let network = SimulatedNetwork::new(0, LINK);
+
+    // Make the network receiver of host 1, and crash it.
+    let address = "127.0.0.1:5002".parse::<SocketAddr>().unwrap();
+    let (tx, _rx) = channel(10);
+    network.enter(1);
+    Receiver::spawn(address, TestHandler { deliver: tx });
+    sleep(Duration::from_millis(10)).await;
+    network.crash(1);
+
+    // Host 0 cannot connect to it anymore.
+    network.enter(0);
+    match connect(address) {
+        Some(Err(e)) => assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused),
+        _ => panic!("Unexpected connection result"),
+    }
+}
+
+#[tokio::test]
+async fn crash_resets_connections() {
+    let network = SimulatedNetwork::new(0, LINK);
+
+    // Connect host 0 to host 1, and crash host 1.
+    let address = "127.0.0.1:5003".parse::<SocketAddr>().unwrap();
+    let (tx, _rx) = channel(10);
+    network.enter(1);
+    Receiver::spawn(address, TestHandler { deliver: tx });
+    sleep(Duration::from_millis(10)).await;
+    network.enter(0);
+    let mut stream = connect(address).unwrap().unwrap();
+    network.crash(1);
+
+    // Host 0 notices the reset of its connection.
+    let mut buffer = [0u8; 1];
+    match stream.read(&mut buffer).await {
+        Err(e) => assert_eq!(e.kind(), io::ErrorKind::ConnectionReset),
+        _ => panic!("Unexpected read result"),
+    }
+}
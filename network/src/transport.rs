@@ -0,0 +1,71 @@
+// Copyright(C) Facebook, Inc. and its affiliates.
+use rand::rngs::SmallRng;
+use rand::SeedableRng as _;
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+
+/// The byte stream underlying a connection.
+#[cfg(not(feature = "simulation"))]
+pub type Stream = TcpStream;
+
+/// The byte stream underlying a connection: either a TCP stream or, when the current thread runs
+/// a simulated network, an in-process stream.
+#[cfg(feature = "simulation")]
+pub use crate::simulation::Stream;
+
+/// Open a connection to the specified address.
+#[cfg(not(feature = "simulation"))]
+pub async fn connect(address: SocketAddr) -> io::Result<Stream> {
+    TcpStream::connect(address).await
+}
+
+/// Open a connection to the specified address.
+#[cfg(feature = "simulation")]
+pub async fn connect(address: SocketAddr) -> io::Result<Stream> {
+    match crate::simulation::connect(address) {
+        Some(result) => result,
+        None => TcpStream::connect(address).await.map(Stream::Tcp),
+    }
+}
+
+/// Make the RNG used to randomize the choice of peers. It is seeded by the simulated network (if
+/// any) so that simulations can be replayed.
+pub fn rng() -> SmallRng {
+    #[cfg(feature = "simulation")]
+    if let Some(rng) = crate::simulation::rng() {
+        return rng;
+    }
+    SmallRng::from_entropy()
+}
+
+/// Accepts the incoming connections of a network receiver.
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(feature = "simulation")]
+    Simulated(crate::simulation::Listener),
+}
+
+impl Listener {
+    pub async fn bind(address: SocketAddr) -> io::Result<Self> {
+        #[cfg(feature = "simulation")]
+        if let Some(listener) = crate::simulation::bind(address) {
+            return Ok(Self::Simulated(listener));
+        }
+        TcpListener::bind(address).await.map(Self::Tcp)
+    }
+
+    pub async fn accept(&mut self) -> io::Result<(Stream, SocketAddr)> {
+        match self {
+            #[cfg(feature = "simulation")]
+            Self::Tcp(listener) => listener
+                .accept()
+                .await
+                .map(|(stream, peer)| (Stream::Tcp(stream), peer)),
+            #[cfg(not(feature = "simulation"))]
+            Self::Tcp(listener) => listener.accept().await,
+            #[cfg(feature = "simulation")]
+            Self::Simulated(listener) => listener.accept().await,
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use crate::execution::{HashChain, Metadata, StateMachine};
+use crate::node::CHANNEL_CAPACITY;
+use async_trait::async_trait;
+use bytes::Bytes;
+use consensus::{Block, Proposal, ProposalBuilder, ProposalValidator};
+use crypto::{Digest, Hash as _, PublicKey};
+use futures::sink::SinkExt as _;
+use futures::stream::StreamExt as _;
+use log::{error, info};
+use mempool::{Batch, Transaction, TransactionValidator};
+use network::{MessageHandler, Receiver as NetworkReceiver, Writer};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::process;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{channel, Sender};
+use tokio::sync::oneshot;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+#[cfg(test)]
+#[path = "tests/abci_tests.rs"]
+pub mod abci_tests;
+
+/// The header of a block, as seen by the application.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub digest: Digest,
+    /// The author of the block (encoded as a base64 string).
+    pub author: PublicKey,
+    pub epoch: u128,
+    pub round: u64,
+    /// The metadata of the application's proposal (without the state roots of the node).
+    pub metadata: Option<Vec<u8>>,
+}
+
+impl From<&Block> for Header {
+    fn from(block: &Block) -> Self {
+        Self {
+            digest: block.digest(),
+            author: block.author,
+            epoch: block.epoch,
+            round: block.round,
+            metadata: Metadata::decode(block).ok().and_then(|x| x.application),
+        }
+    }
+}
+
+/// The requests of the node to an external application. The node opens two TCP connections to
+/// the application: the mempool sends `CheckTx` requests on the first, and consensus and the
+/// executor send all other requests on the second (so that clients cannot delay our proposals
+/// and votes). The application must serve both connections concurrently. Both ends exchange
+/// frames of `LengthDelimitedCodec`: a 4-byte big-endian length followed by the payload.
+/// Payloads are encoded with bincode's default configuration: integers are little-endian, enum
+/// variants are prefixed by their index (u32), and strings and sequences by their length (u64).
+/// The application answers each request, in order, with the `Response` variant of the same name.
+/// The node stops if a connection drops or if the application answers with an unexpected frame.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Request {
+    /// Check a transaction submitted by a client.
+    CheckTx(Transaction),
+    /// Select the payload of our next proposal among the digests of the candidate batches, and
+    /// the metadata to include in the block (if any).
+    BuildProposal { round: u64, candidates: Vec<Digest> },
+    /// Check a proposal and its transactions before voting for it.
+    ValidateProposal {
+        header: Header,
+        transactions: Vec<Transaction>,
+    },
+    /// Execute the transactions of the next committed block.
+    DeliverBlock {
+        header: Header,
+        transactions: Vec<Transaction>,
+    },
+    /// Persist the effects of the delivered block and return the new state root.
+    Commit,
+    /// Serialize the application's state (to include it in a checkpoint).
+    Snapshot,
+    /// Replace the application's state with a snapshot and return its state root.
+    Restore(Vec<u8>),
+}
+
+/// The responses of the application (see `Request`).
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Response {
+    CheckTx(Result<(), String>),
+    BuildProposal {
+        payload: Vec<Digest>,
+        metadata: Option<Vec<u8>>,
+    },
+    ValidateProposal(Result<(), String>),
+    DeliverBlock,
+    Commit(Digest),
+    Snapshot(Vec<u8>),
+    Restore(Result<Digest, String>),
+}
+
+/// A connection to an external application, serving one request at a time (see `Request`).
+#[derive(Clone)]
+struct Connection {
+    tx_request: Sender<(Request, oneshot::Sender<Response>)>,
+}
+
+impl Connection {
+    async fn open(address: SocketAddr) -> io::Result<Self> {
+        let stream = TcpStream::connect(address).await?;
+        let mut transport = Framed::new(stream, LengthDelimitedCodec::new());
+
+        let (tx_request, mut rx_request) =
+            channel::<(Request, oneshot::Sender<Response>)>(CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            while let Some((request, reply)) = rx_request.recv().await {
+                match Self::exchange(&mut transport, &request).await {
+                    Ok(response) => {
+                        let _ = reply.send(response);
+                    }
+                    Err(e) => fail(&format!(
+                        "Failed to communicate with the application: {}",
+                        e
+                    )),
+                }
+            }
+        });
+        Ok(Self { tx_request })
+    }
+
+    async fn exchange(
+        transport: &mut Framed<TcpStream, LengthDelimitedCodec>,
+        request: &Request,
+    ) -> io::Result<Response> {
+        let bytes = bincode::serialize(request).expect("Failed to serialize request");
+        transport.send(Bytes::from(bytes)).await?;
+        let frame = match transport.next().await {
+            Some(frame) => frame?,
+            None => return Err(io::ErrorKind::UnexpectedEof.into()),
+        };
+        bincode::deserialize(&frame).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    async fn call(&self, request: Request) -> Response {
+        let (sender, receiver) = oneshot::channel();
+        self.tx_request
+            .send((request, sender))
+            .await
+            .expect("Failed to send request to the application");
+        receiver
+            .await
+            .expect("Failed to receive response from the application")
+    }
+}
+
+/// We cannot make progress without the application: log the failure and stop the node.
+fn fail(message: &str) -> ! {
+    error!("{}", message);
+    process::exit(1)
+}
+
+fn unexpected(request: &str, response: Response) -> ! {
+    fail(&format!(
+        "Unexpected response of the application to {}: {:?}",
+        request, response
+    ))
+}
+
+/// The connections to an external application. They forward the hooks of the mempool, of
+/// consensus and of the executor to the application.
+#[derive(Clone)]
+pub struct Application {
+    mempool: Connection,
+    consensus: Connection,
+}
+
+impl Application {
+    pub async fn connect(address: SocketAddr) -> io::Result<Self> {
+        let mempool = Connection::open(address).await?;
+        let consensus = Connection::open(address).await?;
+        info!("Connected to the application at {}", address);
+        Ok(Self { mempool, consensus })
+    }
+}
+
+#[async_trait]
+impl TransactionValidator for Application {
+    async fn validate(&self, transaction: &[u8]) -> Result<(), String> {
+        match self
+            .mempool
+            .call(Request::CheckTx(transaction.to_vec()))
+            .await
+        {
+            Response::CheckTx(result) => result,
+            response => unexpected("CheckTx", response),
+        }
+    }
+}
+
+#[async_trait]
+impl ProposalBuilder for Application {
+    async fn build(&mut self, round: u64, candidates: Vec<Digest>) -> Proposal {
+        match self
+            .consensus
+            .call(Request::BuildProposal { round, candidates })
+            .await
+        {
+            Response::BuildProposal { payload, metadata } => Proposal { payload, metadata },
+            response => unexpected("BuildProposal", response),
+        }
+    }
+}
+
+#[async_trait]
+impl ProposalValidator for Application {
+    async fn validate(&self, block: &Block, batches: &[Batch]) -> Result<(), String> {
+        let request = Request::ValidateProposal {
+            header: Header::from(block),
+            transactions: batches.concat(),
+        };
+        match self.consensus.call(request).await {
+            Response::ValidateProposal(result) => result,
+            response => unexpected("ValidateProposal", response),
+        }
+    }
+}
+
+#[async_trait]
+impl StateMachine for Application {
+    async fn execute(&mut self, block: &Block, transactions: Vec<Transaction>) -> Digest {
+        let request = Request::DeliverBlock {
+            header: Header::from(block),
+            transactions,
+        };
+        match self.consensus.call(request).await {
+            Response::DeliverBlock => (),
+            response => unexpected("DeliverBlock", response),
+        }
+        match self.consensus.call(Request::Commit).await {
+            Response::Commit(root) => root,
+            response => unexpected("Commit", response),
+        }
+    }
+
+    async fn snapshot(&mut self) -> Vec<u8> {
+        match self.consensus.call(Request::Snapshot).await {
+            Response::Snapshot(snapshot) => snapshot,
+            response => unexpected("Snapshot", response),
+        }
+    }
+
+    async fn restore(&mut self, snapshot: &[u8]) -> Result<Digest, String> {
+        match self
+            .consensus
+            .call(Request::Restore(snapshot.to_vec()))
+            .await
+        {
+            Response::Restore(result) => result,
+            response => unexpected("Restore", response),
+        }
+    }
+}
+
+/// A reference application speaking the protocol, to stand in for external applications. It
+/// admits any non-empty transaction, proposes all candidates, accepts all proposals, and keeps a
+/// hash chain over the transactions it executes (as the built-in application).
+#[derive(Default)]
+pub struct ReferenceApplication {
+    state: HashChain,
+    delivered: Vec<Transaction>,
+}
+
+impl ReferenceApplication {
+    /// Serve a fresh application on the specified address.
+    pub fn spawn(address: SocketAddr) {
+        let handler = ApplicationHandler {
+            application: Arc::new(Mutex::new(Self::default())),
+        };
+        NetworkReceiver::spawn(address, handler);
+        info!("Application listening on {}", address);
+    }
+
+    fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::CheckTx(transaction) if transaction.is_empty() => {
+                Response::CheckTx(Err("Empty transaction".to_string()))
+            }
+            Request::CheckTx(_) => Response::CheckTx(Ok(())),
+            Request::BuildProposal { candidates, .. } => Response::BuildProposal {
+                payload: candidates,
+                metadata: None,
+            },
+            Request::ValidateProposal { .. } => Response::ValidateProposal(Ok(())),
+            Request::DeliverBlock { transactions, .. } => {
+                self.delivered.extend(transactions);
+                Response::DeliverBlock
+            }
+            Request::Commit => {
+                let transactions: Vec<_> = self.delivered.drain(..).collect();
+                Response::Commit(self.state.apply(&transactions))
+            }
+            Request::Snapshot => Response::Snapshot(self.state.serialize()),
+            Request::Restore(snapshot) => {
+                Response::Restore(HashChain::deserialize(&snapshot).map(|state| {
+                    self.state = state;
+                    self.state.root()
+                }))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ApplicationHandler {
+    application: Arc<Mutex<ReferenceApplication>>,
+}
+
+#[async_trait]
+impl MessageHandler for ApplicationHandler {
+    async fn dispatch(&self, writer: &mut Writer, message: Bytes) -> Result<(), Box<dyn Error>> {
+        let request = bincode::deserialize(&message)?;
+        let response = self.application.lock().unwrap().handle(request);
+        let bytes = bincode::serialize(&response)?;
+        writer.send(Bytes::from(bytes)).await?;
+        Ok(())
+    }
+}
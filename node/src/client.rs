@@ -95,7 +95,9 @@ impl Client {
             ));
         }
 
-        // Connect to the mempool.
+        // Connect to the mempool. We do not opt in for receipts (by sending an empty message before
+        // our transactions): the mempool then only replies to the transactions it rejects, and we
+        // never read these replies.
         let stream = TcpStream::connect(self.target)
             .await
             .context(format!("failed to connect to {}", self.target))?;
@@ -1,5 +1,8 @@
 use consensus::{Committee as ConsensusCommittee, Parameters as ConsensusParameters};
-use crypto::{generate_keypair, generate_production_keypair, PublicKey, SecretKey};
+use crypto::{
+    derive_bls_keypair, generate_keypair, generate_production_keypair, BlsPublicKey, PublicKey,
+    SecretKey,
+};
 use mempool::{Committee as MempoolCommittee, Parameters as MempoolParameters};
 use rand::rngs::StdRng;
 use rand::SeedableRng as _;
@@ -10,6 +13,10 @@ use std::io::BufWriter;
 use std::io::Write as _;
 use thiserror::Error;
 
+#[cfg(test)]
+#[path = "tests/config_tests.rs"]
+pub mod config_tests;
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("Failed to read config file '{file}': {message}")]
@@ -25,10 +32,20 @@ pub trait Export: Serialize + DeserializeOwned {
             let data = fs::read(path)?;
             Ok(serde_json::from_slice(data.as_slice())?)
         };
-        reader().map_err(|e| ConfigError::ReadError {
+        let read = reader().map_err(|e| ConfigError::ReadError {
             file: path.to_string(),
             message: e.to_string(),
-        })
+        })?;
+        read.check().map_err(|message| ConfigError::ReadError {
+            file: path.to_string(),
+            message,
+        })?;
+        Ok(read)
+    }
+
+    /// Check the consistency of the file's content (we refuse to read it otherwise).
+    fn check(&self) -> Result<(), String> {
+        Ok(())
     }
 
     fn write(&self, path: &str) -> Result<(), ConfigError> {
@@ -53,18 +70,28 @@ pub struct Parameters {
     pub mempool: MempoolParameters,
 }
 
-impl Export for Parameters {}
+impl Export for Parameters {
+    fn check(&self) -> Result<(), String> {
+        self.consensus.check()
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Secret {
     pub name: PublicKey,
+    pub bls_name: BlsPublicKey,
     pub secret: SecretKey,
 }
 
 impl Secret {
     pub fn new() -> Self {
         let (name, secret) = generate_production_keypair();
-        Self { name, secret }
+        let (bls_name, _) = derive_bls_keypair(&secret);
+        Self {
+            name,
+            bls_name,
+            secret,
+        }
     }
 }
 
@@ -74,7 +101,12 @@ impl Default for Secret {
     fn default() -> Self {
         let mut rng = StdRng::from_seed([0; 32]);
         let (name, secret) = generate_keypair(&mut rng);
-        Self { name, secret }
+        let (bls_name, _) = derive_bls_keypair(&secret);
+        Self {
+            name,
+            bls_name,
+            secret,
+        }
     }
 }
 
@@ -0,0 +1,524 @@
+use async_trait::async_trait;
+use consensus::{Block, Checkpoint, CommittedBlock, Proposal, ProposalBuilder, ProposalValidator};
+use crypto::{Digest, Hash as _};
+use ed25519_dalek::Digest as _;
+use ed25519_dalek::Sha512;
+use log::{debug, error, info, warn};
+use mempool::{Batch, MempoolMessage, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
+use store::keys::SNAPSHOT_KEY;
+use store::Store;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+#[cfg(test)]
+#[path = "tests/execution_tests.rs"]
+pub mod execution_tests;
+
+/// The number of recent state roots we keep to check the ones carried by committed blocks.
+const ROOTS_CAPACITY: usize = 1_000;
+
+/// The maximum number of state roots a proposal may carry (see `Roots::claims`).
+const MAX_CLAIMS: usize = 2;
+
+/// The replicated application. Its state must only depend on the sequence of transactions it
+/// executes: replicas detect diverging executions by comparing their state roots.
+#[async_trait]
+pub trait StateMachine: Send + 'static {
+    /// Execute the transactions of a committed block and return the new state root.
+    async fn execute(&mut self, block: &Block, transactions: Vec<Transaction>) -> Digest;
+
+    /// Serialize the application's state.
+    async fn snapshot(&mut self) -> Vec<u8>;
+
+    /// Replace the application's state with a snapshot and return its state root.
+    async fn restore(&mut self, snapshot: &[u8]) -> Result<Digest, String>;
+}
+
+/// The state root after executing a committed block. Leaders carry it in the metadata of their
+/// proposals so that it is covered by their QC.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct StateRoot {
+    pub block: Digest,
+    pub epoch: u128,
+    pub round: u64,
+    pub root: Digest,
+}
+
+impl StateRoot {
+    fn position(&self) -> (u128, u64) {
+        (self.epoch, self.round)
+    }
+}
+
+/// The metadata of the proposals: the state roots claimed by the leader, along with the metadata
+/// of the application's builder.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Metadata {
+    pub roots: Vec<StateRoot>,
+    pub application: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    /// Decode the metadata of a block.
+    pub fn decode(block: &Block) -> Result<Self, String> {
+        match &block.metadata {
+            Some(bytes) => {
+                bincode::deserialize(bytes).map_err(|e| format!("Malformed metadata: {}", e))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn encode(&self) -> Option<Vec<u8>> {
+        if self.roots.is_empty() && self.application.is_none() {
+            return None;
+        }
+        Some(bincode::serialize(self).expect("Failed to serialize metadata"))
+    }
+}
+
+/// A snapshot of the application's state along with the (committed) block it follows.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub state_root: StateRoot,
+    pub state: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Load the latest snapshot from storage (if any).
+    pub async fn load(store: &mut Store) -> Option<Self> {
+        match store.read(SNAPSHOT_KEY.to_vec()).await {
+            Ok(Some(bytes)) => Some(bincode::deserialize(&bytes).expect("Corrupted snapshot")),
+            Ok(None) => None,
+            Err(e) => panic!("Failed to load snapshot: {}", e),
+        }
+    }
+
+    /// Write the snapshot to storage, replacing the previous one.
+    pub async fn persist(&self, store: &mut Store) {
+        let bytes = bincode::serialize(self).expect("Failed to serialize snapshot");
+        if let Err(e) = store.sync_write(SNAPSHOT_KEY.to_vec(), bytes).await {
+            panic!("Failed to persist snapshot: {}", e);
+        }
+    }
+}
+
+/// The state roots we recently computed.
+#[derive(Default)]
+pub struct Roots {
+    latest: Option<StateRoot>,
+    /// The state root of our latest snapshot. Leaders keep claiming it so that a committed block
+    /// attests it (see `Executor::attest`).
+    snapshot: Option<StateRoot>,
+    executed: HashMap<Digest, Digest>,
+    order: VecDeque<Digest>,
+}
+
+impl Roots {
+    /// Record the state root of a block we executed.
+    fn record(&mut self, state_root: StateRoot) {
+        self.executed
+            .insert(state_root.block.clone(), state_root.root.clone());
+        self.order.push_back(state_root.block.clone());
+        if self.order.len() > ROOTS_CAPACITY {
+            if let Some(digest) = self.order.pop_front() {
+                self.executed.remove(&digest);
+            }
+        }
+        self.latest = Some(state_root);
+    }
+
+    /// Forget all roots and start over from the root of the snapshot we restored.
+    fn reset(&mut self, state_root: StateRoot) {
+        *self = Self::default();
+        self.record(state_root.clone());
+        self.snapshot = Some(state_root);
+    }
+
+    /// The state roots leaders claim in their proposals: the latest one and the one of our
+    /// latest snapshot.
+    fn claims(&self) -> Vec<StateRoot> {
+        let snapshot = self
+            .snapshot
+            .iter()
+            .filter(|x| Some(*x) != self.latest.as_ref());
+        self.latest.iter().chain(snapshot).cloned().collect()
+    }
+
+    /// Check a state root claimed by a committed block against ours. Committed blocks only
+    /// claim the roots of blocks committed before them: we skip the ones we no longer remember
+    /// (or never executed, if we installed a checkpoint).
+    fn check(&self, claim: &StateRoot) -> Result<(), String> {
+        match self.executed.get(&claim.block) {
+            Some(root) if *root != claim.root => Err(format!(
+                "State root {} of block {} differs from ours ({})",
+                claim.root, claim.block, root
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Carry the state roots we computed in the proposals of the application's builder, along with
+/// the metadata of its proposals.
+pub struct StateRootBuilder<B> {
+    inner: B,
+    roots: Arc<Mutex<Roots>>,
+}
+
+impl<B: ProposalBuilder> StateRootBuilder<B> {
+    pub fn new(inner: B, roots: Arc<Mutex<Roots>>) -> Self {
+        Self { inner, roots }
+    }
+}
+
+#[async_trait]
+impl<B: ProposalBuilder> ProposalBuilder for StateRootBuilder<B> {
+    async fn build(&mut self, round: u64, candidates: Vec<Digest>) -> Proposal {
+        let mut proposal = self.inner.build(round, candidates).await;
+        let metadata = Metadata {
+            roots: self.roots.lock().unwrap().claims(),
+            application: proposal.metadata.take(),
+        };
+        proposal.metadata = metadata.encode();
+        proposal
+    }
+}
+
+/// Refuse to vote for proposals whose metadata is malformed, and otherwise defer to the
+/// application's validator. The application reads its own metadata through `Metadata::decode`.
+/// We do not check the state roots of proposals against ours: our vote would depend on how far
+/// we executed the chain. The `Executor` checks them once the proposals are committed.
+pub struct StateRootValidator<V> {
+    inner: V,
+}
+
+impl<V: ProposalValidator> StateRootValidator<V> {
+    pub fn new(inner: V) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl<V: ProposalValidator> ProposalValidator for StateRootValidator<V> {
+    async fn validate(&self, block: &Block, batches: &[Batch]) -> Result<(), String> {
+        self.check(block)?;
+        self.inner.validate(block, batches).await
+    }
+}
+
+impl<V> StateRootValidator<V> {
+    fn check(&self, block: &Block) -> Result<(), String> {
+        let metadata = Metadata::decode(block)?;
+        if metadata.roots.len() > MAX_CLAIMS {
+            return Err(format!("Too many state roots ({})", metadata.roots.len()));
+        }
+        Ok(())
+    }
+}
+
+/// Execute the committed blocks in order and keep the application's state durably. The state is
+/// persisted every time we execute the block of a checkpoint: upon restart, we only re-execute
+/// the blocks committed after it. The snapshot is attached to our checkpoint once a committed
+/// block attests its state root.
+pub struct Executor<S> {
+    state_machine: S,
+    store: Store,
+    roots: Arc<Mutex<Roots>>,
+    rx_commit: Receiver<CommittedBlock>,
+    rx_checkpoint: Receiver<Checkpoint>,
+    tx_snapshot: Sender<(Digest, Vec<u8>, CommittedBlock)>,
+    latest: Option<StateRoot>,
+    /// Our latest snapshot, until a committed block attests it.
+    pending: Option<Snapshot>,
+    /// The number of state roots claimed by committed blocks that differ from ours.
+    diverged: u64,
+}
+
+impl<S: StateMachine> Executor<S> {
+    /// Restore the application from its latest snapshot (if any). Returns the digest of the last
+    /// block reflected in the state: consensus re-delivers the blocks committed after it.
+    pub async fn restore(
+        state_machine: &mut S,
+        store: &mut Store,
+        roots: &Arc<Mutex<Roots>>,
+    ) -> Option<Digest> {
+        let snapshot = Snapshot::load(store).await?;
+        let root = state_machine
+            .restore(&snapshot.state)
+            .await
+            .expect("Failed to restore our own snapshot");
+        assert_eq!(root, snapshot.state_root.root, "Corrupted snapshot");
+        info!("Restored state after block {}", snapshot.state_root.block);
+        let digest = snapshot.state_root.block.clone();
+        roots.lock().unwrap().reset(snapshot.state_root);
+        Some(digest)
+    }
+
+    pub fn spawn(
+        state_machine: S,
+        store: Store,
+        roots: Arc<Mutex<Roots>>,
+        rx_commit: Receiver<CommittedBlock>,
+        rx_checkpoint: Receiver<Checkpoint>,
+        tx_snapshot: Sender<(Digest, Vec<u8>, CommittedBlock)>,
+    ) {
+        let latest = roots.lock().unwrap().latest.clone();
+        tokio::spawn(async move {
+            let mut executor = Self {
+                state_machine,
+                store,
+                roots,
+                rx_commit,
+                rx_checkpoint,
+                tx_snapshot,
+                latest,
+                pending: None,
+                diverged: 0,
+            };
+
+            // We may have crashed before a committed block attested our latest snapshot.
+            let checkpoint = Checkpoint::load(&mut executor.store)
+                .await
+                .expect("Failed to load checkpoint");
+            if let Some(snapshot) = Snapshot::load(&mut executor.store).await {
+                if checkpoint.is_some_and(|x| {
+                    x.snapshot.is_none() && x.committed.block.digest() == snapshot.state_root.block
+                }) {
+                    executor.pending = Some(snapshot);
+                }
+            }
+            executor.run().await
+        });
+    }
+
+    /// Read the transactions of the block from the store. Committed blocks only reference
+    /// batches that we already stored.
+    async fn transactions(&mut self, block: &Block) -> Vec<Transaction> {
+        let mut transactions = Vec::new();
+        for x in &block.payload {
+            let serialized = self
+                .store
+                .notify_read(x.to_vec())
+                .await
+                .expect("Failed to read batch from store");
+            match bincode::deserialize(&serialized) {
+                Ok(MempoolMessage::Batch(batch)) => transactions.extend(batch),
+                _ => panic!("Committed block {} references an invalid batch", block),
+            }
+        }
+        transactions
+    }
+
+    async fn execute(&mut self, committed: CommittedBlock) {
+        self.execute_block(&committed.block).await;
+        self.attest(committed).await;
+    }
+
+    async fn execute_block(&mut self, block: &Block) {
+        let position = (block.epoch, block.round);
+        if self
+            .latest
+            .as_ref()
+            .is_some_and(|x| x.position() >= position)
+        {
+            return;
+        }
+
+        // Check the state roots the block claims: it is committed, so a quorum certified them.
+        // We cannot tell whether we or the leader are at fault, so we keep going.
+        let claims = Metadata::decode(block).map_or_else(|_| Vec::new(), |x| x.roots);
+        for claim in &claims {
+            if let Err(e) = self.roots.lock().unwrap().check(claim) {
+                self.diverged += 1;
+                error!(
+                    "Execution diverged, claimed by block {}: {} ({} divergences so far)",
+                    block, e, self.diverged
+                );
+            }
+        }
+
+        let transactions = self.transactions(block).await;
+        let root = self.state_machine.execute(block, transactions).await;
+        debug!("Executed {:?} -> {}", block, root);
+        let state_root = StateRoot {
+            block: block.digest(),
+            epoch: block.epoch,
+            round: block.round,
+            root,
+        };
+        self.roots.lock().unwrap().record(state_root.clone());
+        self.latest = Some(state_root.clone());
+
+        // Snapshot our state if consensus made a checkpoint of this block.
+        let checkpoint = Checkpoint::load(&mut self.store)
+            .await
+            .expect("Failed to load checkpoint");
+        if checkpoint.is_some_and(|x| x.committed.block.digest() == state_root.block) {
+            self.snapshot(state_root).await;
+        }
+    }
+
+    async fn snapshot(&mut self, state_root: StateRoot) {
+        let snapshot = Snapshot {
+            state: self.state_machine.snapshot().await,
+            state_root,
+        };
+        snapshot.persist(&mut self.store).await;
+        self.roots.lock().unwrap().snapshot = Some(snapshot.state_root.clone());
+        self.pending = Some(snapshot);
+    }
+
+    /// Attach our pending snapshot to our checkpoint if the committed block claims its state root.
+    async fn attest(&mut self, committed: CommittedBlock) {
+        let attested = self.pending.as_ref().is_some_and(|snapshot| {
+            Metadata::decode(&committed.block).is_ok_and(|x| x.roots.contains(&snapshot.state_root))
+        });
+        if !attested {
+            return;
+        }
+        let snapshot = self.pending.take().unwrap();
+        let message = (snapshot.state_root.block, snapshot.state, committed);
+        if let Err(e) = self.tx_snapshot.send(message).await {
+            warn!(
+                "Failed to send snapshot through the snapshot channel: {}",
+                e
+            );
+        }
+    }
+
+    /// Restore our state from the snapshot of a checkpoint. Its attestation (certified by
+    /// consensus) claims the state root of the checkpoint's block: we keep our state if the
+    /// snapshot does not match it.
+    async fn install(&mut self, checkpoint: Checkpoint) {
+        let block = &checkpoint.committed.block;
+        let digest = block.digest();
+        let (snapshot, attestation) = match (&checkpoint.snapshot, &checkpoint.attestation) {
+            (Some(x), Some(y)) => (x, y),
+            _ => {
+                warn!(
+                    "Checkpoint {} carries no attested snapshot, keeping our state",
+                    block
+                );
+                return;
+            }
+        };
+        let certified = match Metadata::decode(&attestation.block)
+            .map(|x| x.roots.into_iter().find(|x| x.block == digest))
+        {
+            Ok(Some(x)) => x.root,
+            _ => {
+                warn!(
+                    "Attestation of checkpoint {} claims no state root for it, keeping our state",
+                    block
+                );
+                return;
+            }
+        };
+
+        let backup = self.state_machine.snapshot().await;
+        let root = match self.state_machine.restore(snapshot).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to restore snapshot of checkpoint {}: {}", block, e);
+                return;
+            }
+        };
+        if root != certified {
+            warn!(
+                "Rejected snapshot of checkpoint {}: its state root is {}, the certified one is {}",
+                block, root, certified
+            );
+            self.state_machine
+                .restore(&backup)
+                .await
+                .expect("Failed to restore our own state");
+            return;
+        }
+        info!("Restored state from checkpoint {}", block);
+        let state_root = StateRoot {
+            block: digest,
+            epoch: block.epoch,
+            round: block.round,
+            root,
+        };
+        self.roots.lock().unwrap().reset(state_root.clone());
+        self.latest = Some(state_root.clone());
+        self.pending = None;
+        Snapshot {
+            state_root,
+            state: snapshot.clone(),
+        }
+        .persist(&mut self.store)
+        .await;
+    }
+
+    async fn run(&mut self) {
+        loop {
+            // Consensus notifies us of a checkpoint before delivering the blocks extending it.
+            tokio::select! {
+                biased;
+                Some(checkpoint) = self.rx_checkpoint.recv() => self.install(checkpoint).await,
+                Some(committed) = self.rx_commit.recv() => self.execute(committed).await,
+                else => break,
+            }
+        }
+    }
+}
+
+/// A minimal application: its state is a hash chain over all the transactions it executed.
+#[derive(Default)]
+pub struct HashChain {
+    root: Digest,
+    transactions: u64,
+}
+
+impl HashChain {
+    /// Extend the chain with the specified transactions and return its new root.
+    pub fn apply(&mut self, transactions: &[Transaction]) -> Digest {
+        if transactions.is_empty() {
+            return self.root.clone();
+        }
+        let mut hasher = Sha512::new();
+        hasher.update(&self.root);
+        for x in transactions {
+            hasher.update((x.len() as u64).to_le_bytes());
+            hasher.update(x);
+        }
+        self.root = Digest(hasher.finalize().as_slice()[..32].try_into().unwrap());
+        self.transactions += transactions.len() as u64;
+        self.root.clone()
+    }
+
+    pub fn root(&self) -> Digest {
+        self.root.clone()
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        bincode::serialize(&(&self.root, self.transactions)).expect("Failed to serialize state")
+    }
+
+    pub fn deserialize(snapshot: &[u8]) -> Result<Self, String> {
+        let (root, transactions) = bincode::deserialize(snapshot).map_err(|e| e.to_string())?;
+        Ok(Self { root, transactions })
+    }
+}
+
+#[async_trait]
+impl StateMachine for HashChain {
+    async fn execute(&mut self, _block: &Block, transactions: Vec<Transaction>) -> Digest {
+        self.apply(&transactions)
+    }
+
+    async fn snapshot(&mut self) -> Vec<u8> {
+        self.serialize()
+    }
+
+    async fn restore(&mut self, snapshot: &[u8]) -> Result<Digest, String> {
+        *self = Self::deserialize(snapshot)?;
+        Ok(self.root.clone())
+    }
+}
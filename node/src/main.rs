@@ -1,16 +1,25 @@
+mod abci;
 mod config;
+mod execution;
 mod node;
 
+use crate::abci::{Application, ReferenceApplication};
 use crate::config::Export as _;
-use crate::config::{Committee, Secret};
+use crate::config::{Committee, Parameters, Secret};
+use crate::execution::HashChain;
 use crate::node::Node;
+use anyhow::{Context, Result};
 use clap::{crate_name, crate_version, App, AppSettings, SubCommand};
-use consensus::Committee as ConsensusCommittee;
+use consensus::{
+    AcceptAll, Byzantine, Committee as ConsensusCommittee, Parameters as ConsensusParameters,
+    ProposeAll,
+};
 use env_logger::Env;
 use futures::future::join_all;
 use log::error;
-use mempool::Committee as MempoolCommittee;
+use mempool::{AdmitAll, Committee as MempoolCommittee};
 use std::fs;
+use std::net::SocketAddr;
 use tokio::task::JoinHandle;
 
 #[tokio::main]
@@ -30,12 +39,26 @@ async fn main() {
                 .args_from_usage("--keys=<FILE> 'The file containing the node keys'")
                 .args_from_usage("--committee=<FILE> 'The file containing committee information'")
                 .args_from_usage("--parameters=[FILE] 'The file containing the node parameters'")
-                .args_from_usage("--store=<PATH> 'The path where to create the data store'"),
+                .args_from_usage("--store=<PATH> 'The path where to create the data store'")
+                .args_from_usage(
+                    "--next-committee=[FILE] 'The file containing the committee of the next epoch'",
+                )
+                .args_from_usage(
+                    "--app=[ADDR] 'The address of an external application (instead of the built-in one)'",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("app")
+                .about("Runs the reference application")
+                .args_from_usage("--address=<ADDR> 'The address to listen to'"),
         )
         .subcommand(
             SubCommand::with_name("deploy")
                 .about("Deploys a network of nodes locally")
-                .args_from_usage("--nodes=<INT> 'The number of nodes to deploy'"),
+                .args_from_usage("--nodes=<INT> 'The number of nodes to deploy'")
+                .args_from_usage(
+                    "--byzantine=[INT] 'The number of Byzantine nodes among them (at most f)'",
+                ),
         )
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .get_matches();
@@ -64,10 +87,50 @@ async fn main() {
             let committee_file = subm.value_of("committee").unwrap();
             let parameters_file = subm.value_of("parameters");
             let store_path = subm.value_of("store").unwrap();
-            match Node::new(committee_file, key_file, store_path, parameters_file).await {
+            let next_committee_file = subm.value_of("next-committee");
+            let node = match subm.value_of("app") {
+                Some(address) => match connect_application(address).await {
+                    Ok(app) => {
+                        Node::new(
+                            committee_file,
+                            key_file,
+                            store_path,
+                            parameters_file,
+                            app.clone(),
+                            app.clone(),
+                            app.clone(),
+                            app,
+                        )
+                        .await
+                    }
+                    Err(e) => {
+                        error!("{:#}", e);
+                        return;
+                    }
+                },
+                None => {
+                    Node::new(
+                        committee_file,
+                        key_file,
+                        store_path,
+                        parameters_file,
+                        HashChain::default(),
+                        AcceptAll,
+                        ProposeAll,
+                        AdmitAll,
+                    )
+                    .await
+                }
+            };
+            match node {
                 Ok(mut node) => {
+                    if let Some(filename) = next_committee_file {
+                        if let Err(e) = node.reconfigure(filename).await {
+                            error!("{}", e);
+                        }
+                    }
                     tokio::spawn(async move {
-                        node.analyze_block().await;
+                        node.analyze_evidence().await;
                     })
                     .await
                     .expect("Failed to analyze committed blocks");
@@ -75,15 +138,29 @@ async fn main() {
                 Err(e) => error!("{}", e),
             }
         }
+        ("app", Some(subm)) => match subm.value_of("address").unwrap().parse::<SocketAddr>() {
+            Ok(address) => {
+                ReferenceApplication::spawn(address);
+                futures::future::pending::<()>().await;
+            }
+            Err(e) => error!("Invalid address: {}", e),
+        },
         ("deploy", Some(subm)) => {
             let nodes = subm.value_of("nodes").unwrap();
-            match nodes.parse::<usize>() {
-                Ok(nodes) if nodes > 1 => match deploy_testbed(nodes) {
-                    Ok(handles) => {
-                        let _ = join_all(handles).await;
+            let byzantine = subm.value_of("byzantine").unwrap_or("0");
+            match (nodes.parse::<usize>(), byzantine.parse::<usize>()) {
+                (Ok(nodes), Ok(byzantine)) if nodes > 1 && 3 * byzantine < nodes => {
+                    match deploy_testbed(nodes, byzantine) {
+                        Ok(handles) => {
+                            let _ = join_all(handles).await;
+                        }
+                        Err(e) => error!("Failed to deploy testbed: {}", e),
                     }
-                    Err(e) => error!("Failed to deploy testbed: {}", e),
-                },
+                }
+                (Ok(nodes), _) if nodes > 1 => error!(
+                    "The number of Byzantine nodes must be at most {}",
+                    (nodes - 1) / 3
+                ),
                 _ => error!("The number of nodes must be a positive integer"),
             }
         }
@@ -91,7 +168,25 @@ async fn main() {
     }
 }
 
-fn deploy_testbed(nodes: usize) -> Result<Vec<JoinHandle<()>>, Box<dyn std::error::Error>> {
+/// Connect to the external application listening on the specified address.
+async fn connect_application(address: &str) -> Result<Application> {
+    let address = address
+        .parse::<SocketAddr>()
+        .context(format!("Invalid application address '{}'", address))?;
+    Application::connect(address).await.context(format!(
+        "Failed to connect to the application at {}",
+        address
+    ))
+}
+
+fn deploy_testbed(
+    nodes: usize,
+    byzantine: usize,
+) -> Result<Vec<JoinHandle<()>>, Box<dyn std::error::Error>> {
+    if byzantine > 0 && !cfg!(feature = "byzantine") {
+        return Err("Byzantine nodes require the 'byzantine' feature".into());
+    }
+
     let keys: Vec<_> = (0..nodes).map(|_| Secret::new()).collect();
 
     // Print the committee file.
@@ -115,8 +210,9 @@ fn deploy_testbed(nodes: usize) -> Result<Vec<JoinHandle<()>>, Box<dyn std::erro
             .map(|(i, key)| {
                 let name = key.name;
                 let stake = 1;
+                let bls_name = key.bls_name.clone();
                 let addresses = format!("127.0.0.1:{}", 25_200 + i).parse().unwrap();
-                (name, stake, addresses)
+                (name, stake, bls_name, addresses)
             })
             .collect(),
         epoch,
@@ -140,11 +236,48 @@ fn deploy_testbed(nodes: usize) -> Result<Vec<JoinHandle<()>>, Box<dyn std::erro
             let store_path = format!("db_{}", i);
             let _ = fs::remove_dir_all(&store_path);
 
+            // The first nodes are Byzantine. They do not withhold their votes: they would then
+            // behave like crashed nodes as far as voting is concerned.
+            let parameters_file = if i < byzantine {
+                let parameters_file = format!("parameters_{}.json", i);
+                let _ = fs::remove_file(&parameters_file);
+                let byzantine = Byzantine {
+                    equivocate: true,
+                    vote_stale_blocks: true,
+                    inflate_tc: true,
+                    flood_sync_requests: 10,
+                    ..Byzantine::default()
+                };
+                Parameters {
+                    consensus: ConsensusParameters {
+                        byzantine,
+                        ..ConsensusParameters::default()
+                    },
+                    ..Parameters::default()
+                }
+                .write(&parameters_file)?;
+                Some(parameters_file)
+            } else {
+                None
+            };
+
             Ok(tokio::spawn(async move {
-                match Node::new(committee_file, &key_file, &store_path, None).await {
+                let parameters = parameters_file.as_deref();
+                match Node::new(
+                    committee_file,
+                    &key_file,
+                    &store_path,
+                    parameters,
+                    HashChain::default(),
+                    AcceptAll,
+                    ProposeAll,
+                    AdmitAll,
+                )
+                .await
+                {
                     Ok(mut node) => {
-                        // Sink the commit channel.
-                        while node.commit.recv().await.is_some() {}
+                        // Sink the evidence channel.
+                        node.analyze_evidence().await;
                     }
                     Err(e) => error!("{}", e),
                 }
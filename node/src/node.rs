@@ -1,29 +1,47 @@
 use crate::config::Export as _;
 use crate::config::{Committee, ConfigError, Parameters, Secret};
-use consensus::{Block, Consensus};
+use crate::execution::{Executor, Roots, StateMachine, StateRootBuilder, StateRootValidator};
+use consensus::{Consensus, Evidence, ProposalBuilder, ProposalValidator, Reconfiguration};
 use crypto::SignatureService;
-use log::info;
-use mempool::Mempool;
+use log::{info, warn};
+use mempool::{BasicValidator, Mempool, TransactionValidator};
+use std::sync::{Arc, Mutex};
 use store::Store;
-use tokio::sync::mpsc::{channel, Receiver};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 /// The default channel capacity for this module.
 pub const CHANNEL_CAPACITY: usize = 1_000;
 
 pub struct Node {
-    pub commit: Receiver<Block>,
+    pub evidence: Receiver<Evidence>,
+    tx_reconfiguration: Sender<Reconfiguration>,
 }
 
 impl Node {
-    pub async fn new(
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new<S, V, B, T>(
         committee_file: &str,
         key_file: &str,
         store_path: &str,
         parameters: Option<&str>,
-    ) -> Result<Self, ConfigError> {
+        mut state_machine: S,
+        validator: V,
+        builder: B,
+        transaction_validator: T,
+    ) -> Result<Self, ConfigError>
+    where
+        S: StateMachine,
+        V: ProposalValidator,
+        B: ProposalBuilder,
+        T: TransactionValidator,
+    {
         let (tx_commit, rx_commit) = channel(CHANNEL_CAPACITY);
+        let (tx_evidence, rx_evidence) = channel(CHANNEL_CAPACITY);
         let (tx_consensus_to_mempool, rx_consensus_to_mempool) = channel(CHANNEL_CAPACITY);
         let (tx_mempool_to_consensus, rx_mempool_to_consensus) = channel(CHANNEL_CAPACITY);
+        let (tx_reconfiguration, rx_reconfiguration) = channel(CHANNEL_CAPACITY);
+        let (tx_snapshot, rx_snapshot) = channel(CHANNEL_CAPACITY);
+        let (tx_checkpoint, rx_checkpoint) = channel(CHANNEL_CAPACITY);
 
         // Read the committee and secret key from file.
         let committee = Committee::read(committee_file)?;
@@ -38,19 +56,46 @@ impl Node {
         };
 
         // Make the data store.
-        let store = Store::new(store_path).expect("Failed to create store");
+        let mut store = Store::new(store_path).expect("Failed to create store");
 
         // Run the signature service.
         let signature_service = SignatureService::new(secret_key);
 
-        // Make a new mempool.
+        // Make a new mempool. It checks the size (and signature) of the clients' transactions before
+        // consulting the application.
+        let transaction_validator = (
+            BasicValidator::new(&parameters.mempool),
+            transaction_validator,
+        );
         Mempool::spawn(
             name,
+            signature_service.clone(),
             committee.mempool,
             parameters.mempool,
             store.clone(),
             rx_consensus_to_mempool,
             tx_mempool_to_consensus,
+            transaction_validator,
+        );
+
+        // Make the leader election module.
+        let leader_elector = parameters.consensus.leader_election.elector(
+            committee.consensus.clone(),
+            parameters.consensus.commit_rule,
+        );
+
+        // Restore the application from its latest snapshot and run the executor. Leaders carry the
+        // state roots of the application in their proposals, and the executor checks them once
+        // they are committed.
+        let roots = Arc::new(Mutex::new(Roots::default()));
+        let last_applied = Executor::restore(&mut state_machine, &mut store, &roots).await;
+        Executor::spawn(
+            state_machine,
+            store.clone(),
+            roots.clone(),
+            rx_commit,
+            rx_checkpoint,
+            tx_snapshot,
         );
 
         // Run the consensus core.
@@ -60,22 +105,49 @@ impl Node {
             parameters.consensus,
             signature_service,
             store,
+            leader_elector,
+            StateRootValidator::new(validator),
+            StateRootBuilder::new(builder, roots),
             rx_mempool_to_consensus,
             tx_consensus_to_mempool,
+            rx_reconfiguration,
+            rx_snapshot,
             tx_commit,
+            tx_evidence,
+            tx_checkpoint,
+            last_applied,
         );
 
         info!("Node {} successfully booted", name);
-        Ok(Self { commit: rx_commit })
+        Ok(Self {
+            evidence: rx_evidence,
+            tx_reconfiguration,
+        })
+    }
+
+    /// Submit the committees of the next epoch (read from file). The node only votes for the
+    /// reconfigurations submitted by its operator.
+    pub async fn reconfigure(&self, committee_file: &str) -> Result<(), ConfigError> {
+        let committee = Committee::read(committee_file)?;
+        let reconfiguration = Reconfiguration {
+            consensus: committee.consensus,
+            mempool: committee.mempool,
+        };
+        self.tx_reconfiguration
+            .send(reconfiguration)
+            .await
+            .expect("Failed to send reconfiguration");
+        Ok(())
     }
 
     pub fn print_key_file(filename: &str) -> Result<(), ConfigError> {
         Secret::new().write(filename)
     }
 
-    pub async fn analyze_block(&mut self) {
-        while let Some(_block) = self.commit.recv().await {
-            // This is where we can further process committed block.
+    pub async fn analyze_evidence(&mut self) {
+        while let Some(evidence) = self.evidence.recv().await {
+            // This is where we can punish misbehaving authorities.
+            warn!("Received evidence {:?}", evidence);
         }
     }
 }
@@ -0,0 +1,43 @@
+use super::*;
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn reference_application() {
+    let address = "127.0.0.1:16700".parse::<SocketAddr>().unwrap();
+    ReferenceApplication::spawn(address);
+    sleep(Duration::from_millis(50)).await;
+    let mut application = Application::connect(address).await.unwrap();
+
+    // Check transactions.
+    let valid = TransactionValidator::validate(&application, &[1u8; 10]).await;
+    assert!(valid.is_ok());
+    let invalid = TransactionValidator::validate(&application, &[]).await;
+    assert!(invalid.is_err());
+
+    // Build and validate a proposal.
+    let candidates = vec![Digest([1u8; 32]), Digest([2u8; 32])];
+    let proposal = application.build(1, candidates.clone()).await;
+    assert_eq!(proposal.payload, candidates);
+    assert_eq!(proposal.metadata, None);
+    let block = Block {
+        round: 1,
+        payload: candidates,
+        ..Block::default()
+    };
+    let batch = vec![vec![1u8; 10], vec![2u8; 10]];
+    let batches = std::slice::from_ref(&batch);
+    assert!(ProposalValidator::validate(&application, &block, batches)
+        .await
+        .is_ok());
+
+    // Ensure the application computes the same state root as the built-in one.
+    let mut expected = HashChain::default();
+    let root = application.execute(&block, batch.clone()).await;
+    assert_eq!(root, expected.apply(&batch));
+
+    // Restore a fresh application from a snapshot.
+    let snapshot = application.snapshot().await;
+    assert_eq!(snapshot, expected.serialize());
+    let mut fresh = Application::connect(address).await.unwrap();
+    assert_eq!(fresh.restore(&snapshot).await, Ok(root));
+}
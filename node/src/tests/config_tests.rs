@@ -0,0 +1,36 @@
+use super::*;
+use serde_json::{json, Value};
+
+#[test]
+fn parameters_defaults() {
+    // A parameters file omitting the optional parameters.
+    let file = json!({
+        "consensus": {
+            "timeout_delay": 5_000,
+            "sync_retry_delay": 10_000,
+        },
+        "mempool": {
+            "gc_depth": 50,
+            "sync_retry_delay": 5_000,
+            "sync_retry_nodes": 3,
+            "batch_size": 500_000,
+            "max_batch_delay": 100,
+        },
+    });
+    let parameters: Parameters = serde_json::from_value(file).unwrap();
+
+    // The node behaves as if it was started without a parameters file.
+    let parsed: Value = serde_json::to_value(&parameters).unwrap();
+    let expected: Value = serde_json::to_value(&Parameters::default()).unwrap();
+    assert_eq!(parsed, expected);
+}
+
+#[test]
+fn parameters_retention_without_checkpoints() {
+    let mut parameters = Parameters::default();
+    parameters.consensus.retention = 100;
+    assert!(parameters.check().is_err());
+
+    parameters.consensus.checkpoint_interval = 1_000;
+    assert!(parameters.check().is_ok());
+}
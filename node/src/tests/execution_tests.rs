@@ -0,0 +1,236 @@
+use super::*;
+use consensus::{AcceptAll, ProposeAll, QC};
+use std::fs;
+use tokio::sync::mpsc::channel;
+
+fn state_root(block: Digest, round: u64, root: Digest) -> StateRoot {
+    StateRoot {
+        block,
+        epoch: 0,
+        round,
+        root,
+    }
+}
+
+#[tokio::test]
+async fn execute_and_snapshot() {
+    let store_path = ".db_test_execute_and_snapshot";
+    let _ = fs::remove_dir_all(store_path);
+    let mut store = Store::new(store_path).unwrap();
+
+    // Make a committed block whose payload is in the store, and a checkpoint of it.
+    let batch = vec![vec![1u8; 10], vec![2u8; 10]];
+    let serialized = bincode::serialize(&MempoolMessage::Batch(batch.clone())).unwrap();
+    let digest = Digest([1u8; 32]);
+    store.write(digest.to_vec(), serialized).await;
+    let block = Block {
+        round: 1,
+        payload: vec![digest],
+        ..Block::default()
+    };
+    let committed = CommittedBlock {
+        block: block.clone(),
+        descendants: Vec::new(),
+        qc: QC::default(),
+    };
+    Checkpoint {
+        committed: committed.clone(),
+        snapshot: None,
+        attestation: None,
+    }
+    .persist(&mut store)
+    .await;
+
+    // Execute the block.
+    let roots = Arc::new(Mutex::new(Roots::default()));
+    let (tx_commit, rx_commit) = channel(1);
+    let (_tx_checkpoint, rx_checkpoint) = channel(1);
+    let (tx_snapshot, mut rx_snapshot) = channel(1);
+    Executor::spawn(
+        HashChain::default(),
+        store.clone(),
+        roots.clone(),
+        rx_commit,
+        rx_checkpoint,
+        tx_snapshot,
+    );
+    tx_commit.send(committed).await.unwrap();
+
+    // Commit a block attesting the state root of the checkpoint's block.
+    let mut expected = HashChain::default();
+    let root = expected.apply(&batch);
+    let metadata = Metadata {
+        roots: vec![state_root(block.digest(), 1, root.clone())],
+        application: None,
+    };
+    let attestation = CommittedBlock {
+        block: Block {
+            round: 2,
+            metadata: metadata.encode(),
+            ..Block::default()
+        },
+        descendants: Vec::new(),
+        qc: QC::default(),
+    };
+    tx_commit.send(attestation.clone()).await.unwrap();
+
+    // Ensure the executor sends its snapshot for the checkpoint, along with the attestation.
+    let (snapshot_digest, snapshot, attested_by) = rx_snapshot.recv().await.unwrap();
+    assert_eq!(snapshot_digest, block.digest());
+    assert_eq!(snapshot, expected.serialize());
+    assert_eq!(attested_by.block.digest(), attestation.block.digest());
+
+    // Ensure a restarting node restores its state from the snapshot.
+    let mut state_machine = HashChain::default();
+    let roots = Arc::new(Mutex::new(Roots::default()));
+    let last_applied = Executor::restore(&mut state_machine, &mut store, &roots).await;
+    assert_eq!(last_applied, Some(block.digest()));
+    assert_eq!(state_machine.serialize(), expected.serialize());
+    assert_eq!(roots.lock().unwrap().latest.as_ref().unwrap().root, root);
+}
+
+#[test]
+fn detect_diverging_root() {
+    let mut roots = Roots::default();
+    roots.record(state_root(Digest([1u8; 32]), 1, Digest([10u8; 32])));
+
+    // Claims about blocks we executed are checked against our roots.
+    assert!(roots
+        .check(&state_root(Digest([1u8; 32]), 1, Digest([10u8; 32])))
+        .is_ok());
+    assert!(roots
+        .check(&state_root(Digest([1u8; 32]), 1, Digest([11u8; 32])))
+        .is_err());
+
+    // Claims about blocks we did not execute are skipped (and not remembered).
+    assert!(roots
+        .check(&state_root(Digest([2u8; 32]), 2, Digest([20u8; 32])))
+        .is_ok());
+    roots.record(state_root(Digest([2u8; 32]), 2, Digest([21u8; 32])));
+    assert!(roots
+        .check(&state_root(Digest([2u8; 32]), 2, Digest([21u8; 32])))
+        .is_ok());
+}
+
+#[tokio::test]
+async fn validate_independently_of_execution() {
+    // A proposal claims a state root for a block we did not execute yet.
+    let metadata = Metadata {
+        roots: vec![state_root(Digest([1u8; 32]), 1, Digest([10u8; 32]))],
+        application: None,
+    };
+    let block = Block {
+        round: 2,
+        metadata: metadata.encode(),
+        ..Block::default()
+    };
+
+    // Ensure validators accept it, and reject proposals with too many state roots.
+    let validator = StateRootValidator::new(AcceptAll);
+    assert!(validator.validate(&block, &[]).await.is_ok());
+    let metadata = Metadata {
+        roots: vec![state_root(Digest([1u8; 32]), 1, Digest([10u8; 32])); MAX_CLAIMS + 1],
+        application: None,
+    };
+    let block = Block {
+        round: 2,
+        metadata: metadata.encode(),
+        ..Block::default()
+    };
+    assert!(validator.validate(&block, &[]).await.is_err());
+}
+
+#[tokio::test]
+async fn reject_uncertified_snapshot() {
+    let store_path = ".db_test_reject_uncertified_snapshot";
+    let _ = fs::remove_dir_all(store_path);
+    let store = Store::new(store_path).unwrap();
+
+    // Make a checkpoint whose attestation claims a different state root than its snapshot's.
+    let block = Block {
+        round: 1,
+        ..Block::default()
+    };
+    let mut state = HashChain::default();
+    state.apply(&[vec![1u8; 10]]);
+    let metadata = Metadata {
+        roots: vec![state_root(block.digest(), 1, Digest([9u8; 32]))],
+        application: None,
+    };
+    let checkpoint = Checkpoint {
+        committed: CommittedBlock {
+            block: block.clone(),
+            descendants: Vec::new(),
+            qc: QC::default(),
+        },
+        snapshot: Some(state.serialize()),
+        attestation: Some(CommittedBlock {
+            block: Block {
+                round: 2,
+                metadata: metadata.encode(),
+                ..Block::default()
+            },
+            descendants: Vec::new(),
+            qc: QC::default(),
+        }),
+    };
+
+    // Send the checkpoint to the executor.
+    let roots = Arc::new(Mutex::new(Roots::default()));
+    let (_tx_commit, rx_commit) = channel(1);
+    let (tx_checkpoint, rx_checkpoint) = channel(1);
+    let (tx_snapshot, _rx_snapshot) = channel(1);
+    Executor::spawn(
+        HashChain::default(),
+        store.clone(),
+        roots.clone(),
+        rx_commit,
+        rx_checkpoint,
+        tx_snapshot,
+    );
+    tx_checkpoint.send(checkpoint).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    // Ensure the executor kept its state.
+    assert!(roots.lock().unwrap().latest.is_none());
+}
+
+#[tokio::test]
+async fn keep_application_metadata() {
+    // Make a builder whose proposals carry application metadata.
+    struct MetadataBuilder;
+
+    #[async_trait]
+    impl ProposalBuilder for MetadataBuilder {
+        async fn build(&mut self, round: u64, candidates: Vec<Digest>) -> Proposal {
+            let mut proposal = ProposeAll.build(round, candidates).await;
+            proposal.metadata = Some(b"application".to_vec());
+            proposal
+        }
+    }
+
+    let roots = Arc::new(Mutex::new(Roots::default()));
+    roots
+        .lock()
+        .unwrap()
+        .record(state_root(Digest([1u8; 32]), 1, Digest([10u8; 32])));
+    let mut builder = StateRootBuilder::new(MetadataBuilder, roots);
+    let proposal = builder.build(2, Vec::new()).await;
+
+    // Ensure the proposal carries both our state root and the application's metadata.
+    let block = Block {
+        round: 2,
+        metadata: proposal.metadata,
+        ..Block::default()
+    };
+    let metadata = Metadata::decode(&block).unwrap();
+    assert_eq!(
+        metadata.roots,
+        vec![state_root(Digest([1u8; 32]), 1, Digest([10u8; 32]))]
+    );
+    assert_eq!(metadata.application, Some(b"application".to_vec()));
+
+    // Ensure validators accept it.
+    let validator = StateRootValidator::new(AcceptAll);
+    assert!(validator.validate(&block, &[]).await.is_ok());
+}
@@ -0,0 +1,17 @@
+//! The reserved keys of the store. Blocks, batches and the other items are stored under their
+//! digest (32 bytes): the keys below are of other lengths and thus cannot collide with them.
+
+/// The consensus state (see `consensus::ConsensusState`).
+pub const STATE_KEY: &[u8] = b"consensus_state";
+
+/// Our latest consensus checkpoint.
+pub const CHECKPOINT_KEY: &[u8] = b"consensus_checkpoint";
+
+/// Our retention horizon, below which the history is pruned.
+pub const HORIZON_KEY: &[u8] = b"consensus_horizon";
+
+/// The bound on the counters of our signed requests (see `consensus::Nonce`).
+pub const NONCE_KEY: &[u8] = b"consensus_nonce";
+
+/// The application's latest snapshot.
+pub const SNAPSHOT_KEY: &[u8] = b"execution_snapshot";
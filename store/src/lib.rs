@@ -1,7 +1,10 @@
 use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::{channel, Sender};
 use tokio::sync::oneshot;
 
+pub mod keys;
+
 #[cfg(test)]
 #[path = "tests/store_tests.rs"]
 pub mod store_tests;
@@ -14,8 +17,11 @@ type Value = Vec<u8>;
 
 pub enum StoreCommand {
     Write(Key, Value),
+    SyncWrite(Key, Value, oneshot::Sender<StoreResult<()>>),
     Read(Key, oneshot::Sender<StoreResult<Option<Value>>>),
     NotifyRead(Key, oneshot::Sender<StoreResult<Value>>),
+    Delete(Key),
+    Scan(Key, oneshot::Sender<StoreResult<Vec<(Key, Value)>>>),
 }
 
 #[derive(Clone)]
@@ -23,22 +29,111 @@ pub struct Store {
     channel: Sender<StoreCommand>,
 }
 
+/// The storage backend of a store.
+trait Database: Send + 'static {
+    fn put(&self, key: &[u8], value: &[u8], sync: bool) -> StoreResult<()>;
+    fn get(&self, key: &[u8]) -> StoreResult<Option<Value>>;
+    fn delete(&self, key: &[u8]) -> StoreResult<()>;
+    /// Return the entries whose key starts with the prefix, ordered by key.
+    fn scan(&self, prefix: &[u8]) -> StoreResult<Vec<(Key, Value)>>;
+}
+
+impl Database for rocksdb::DB {
+    fn put(&self, key: &[u8], value: &[u8], sync: bool) -> StoreResult<()> {
+        let mut options = rocksdb::WriteOptions::default();
+        options.set_sync(sync);
+        self.put_opt(key, value, &options)
+    }
+
+    fn get(&self, key: &[u8]) -> StoreResult<Option<Value>> {
+        rocksdb::DB::get(self, key)
+    }
+
+    fn delete(&self, key: &[u8]) -> StoreResult<()> {
+        rocksdb::DB::delete(self, key)
+    }
+
+    fn scan(&self, prefix: &[u8]) -> StoreResult<Vec<(Key, Value)>> {
+        // We do not configure a prefix extractor: the iterator runs past the prefix.
+        Ok(self
+            .prefix_iterator(prefix)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect())
+    }
+}
+
+/// An in-memory database. It outlives the stores opened on it, which is useful to simulate
+/// crash-recoveries (see `Store::new_in_memory`).
+#[derive(Clone, Default)]
+pub struct MemoryDb(Arc<Mutex<HashMap<Key, Value>>>);
+
+impl Database for MemoryDb {
+    fn put(&self, key: &[u8], value: &[u8], _sync: bool) -> StoreResult<()> {
+        let mut map = self.0.lock().expect("Failed to lock in-memory database");
+        map.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, key: &[u8]) -> StoreResult<Option<Value>> {
+        let map = self.0.lock().expect("Failed to lock in-memory database");
+        Ok(map.get(key).cloned())
+    }
+
+    fn delete(&self, key: &[u8]) -> StoreResult<()> {
+        let mut map = self.0.lock().expect("Failed to lock in-memory database");
+        map.remove(key);
+        Ok(())
+    }
+
+    fn scan(&self, prefix: &[u8]) -> StoreResult<Vec<(Key, Value)>> {
+        let map = self.0.lock().expect("Failed to lock in-memory database");
+        let mut entries: Vec<_> = map
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        entries.sort();
+        Ok(entries)
+    }
+}
+
 impl Store {
     pub fn new(path: &str) -> StoreResult<Self> {
         let db = rocksdb::DB::open_default(path)?;
+        Ok(Self::spawn(db))
+    }
+
+    /// Make a store keeping its data in memory rather than on disk.
+    pub fn new_in_memory(db: MemoryDb) -> Self {
+        Self::spawn(db)
+    }
+
+    fn spawn<D: Database>(db: D) -> Self {
         let mut obligations = HashMap::<_, VecDeque<oneshot::Sender<_>>>::new();
         let (tx, mut rx) = channel(100);
         tokio::spawn(async move {
             while let Some(command) = rx.recv().await {
                 match command {
                     StoreCommand::Write(key, value) => {
-                        let _ = db.put(&key, &value);
+                        let _ = db.put(&key, &value, /* sync */ false);
                         if let Some(mut senders) = obligations.remove(&key) {
                             while let Some(s) = senders.pop_front() {
                                 let _ = s.send(Ok(value.clone()));
                             }
                         }
                     }
+                    StoreCommand::SyncWrite(key, value, sender) => {
+                        let response = db.put(&key, &value, /* sync */ true);
+                        if response.is_ok() {
+                            if let Some(mut senders) = obligations.remove(&key) {
+                                while let Some(s) = senders.pop_front() {
+                                    let _ = s.send(Ok(value.clone()));
+                                }
+                            }
+                        }
+                        let _ = sender.send(response);
+                    }
                     StoreCommand::Read(key, sender) => {
                         let response = db.get(&key);
                         let _ = sender.send(response);
@@ -55,10 +150,17 @@ impl Store {
                             }
                         }
                     }
+                    StoreCommand::Delete(key) => {
+                        let _ = db.delete(&key);
+                    }
+                    StoreCommand::Scan(prefix, sender) => {
+                        let response = db.scan(&prefix);
+                        let _ = sender.send(response);
+                    }
                 }
             }
         });
-        Ok(Self { channel: tx })
+        Self { channel: tx }
     }
 
     pub async fn write(&mut self, key: Key, value: Value) {
@@ -67,6 +169,28 @@ impl Store {
         }
     }
 
+    /// Remove a value (if any). Reading it afterwards returns `None`.
+    pub async fn delete(&mut self, key: Key) {
+        if let Err(e) = self.channel.send(StoreCommand::Delete(key)).await {
+            panic!("Failed to send Delete command to store: {}", e);
+        }
+    }
+
+    /// Write a value and wait until it has been flushed to disk.
+    pub async fn sync_write(&mut self, key: Key, value: Value) -> StoreResult<()> {
+        let (sender, receiver) = oneshot::channel();
+        if let Err(e) = self
+            .channel
+            .send(StoreCommand::SyncWrite(key, value, sender))
+            .await
+        {
+            panic!("Failed to send SyncWrite command to store: {}", e);
+        }
+        receiver
+            .await
+            .expect("Failed to receive reply to SyncWrite command from store")
+    }
+
     pub async fn read(&mut self, key: Key) -> StoreResult<Option<Value>> {
         let (sender, receiver) = oneshot::channel();
         if let Err(e) = self.channel.send(StoreCommand::Read(key, sender)).await {
@@ -77,6 +201,17 @@ impl Store {
             .expect("Failed to receive reply to Read command from store")
     }
 
+    /// Read all the entries whose key starts with the specified prefix, ordered by key.
+    pub async fn scan(&mut self, prefix: Key) -> StoreResult<Vec<(Key, Value)>> {
+        let (sender, receiver) = oneshot::channel();
+        if let Err(e) = self.channel.send(StoreCommand::Scan(prefix, sender)).await {
+            panic!("Failed to send Scan command to store: {}", e);
+        }
+        receiver
+            .await
+            .expect("Failed to receive reply to Scan command from store")
+    }
+
     pub async fn notify_read(&mut self, key: Key) -> StoreResult<Value> {
         let (sender, receiver) = oneshot::channel();
         if let Err(e) = self
@@ -30,6 +30,24 @@ async fn read_write_value() {
     assert_eq!(read_value.unwrap(), value);
 }
 
+#[tokio::test]
+async fn sync_write_value() {
+    // Create new store.
+    let path = ".db_test_sync_write_value";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Write value to the store and wait for the write to complete.
+    let key = vec![0u8, 1u8, 2u8, 3u8];
+    let value = vec![4u8, 5u8, 6u8, 7u8];
+    assert!(store.sync_write(key.clone(), value.clone()).await.is_ok());
+
+    // Read value.
+    let result = store.read(key).await;
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), Some(value));
+}
+
 #[tokio::test]
 async fn read_unknown_key() {
     // Create new store.
@@ -44,6 +62,44 @@ async fn read_unknown_key() {
     assert!(result.unwrap().is_none());
 }
 
+#[tokio::test]
+async fn delete_value() {
+    // Create new store.
+    let path = ".db_test_delete_value";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Write value to the store, then delete it.
+    let key = vec![0u8, 1u8, 2u8, 3u8];
+    let value = vec![4u8, 5u8, 6u8, 7u8];
+    store.write(key.clone(), value).await;
+    store.delete(key.clone()).await;
+
+    // Ensure the value is gone.
+    let result = store.read(key).await;
+    assert!(result.is_ok());
+    assert!(result.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn scan_prefix() {
+    // Create new store.
+    let path = ".db_test_scan_prefix";
+    let _ = fs::remove_dir_all(path);
+    let mut store = Store::new(path).unwrap();
+
+    // Write values under two different prefixes.
+    store.write(vec![1u8, 2u8], vec![0u8]).await;
+    store.write(vec![1u8, 1u8], vec![1u8]).await;
+    store.write(vec![2u8, 0u8], vec![2u8]).await;
+
+    // Ensure we only read the values of the prefix, ordered by key.
+    let result = store.scan(vec![1u8]).await;
+    assert!(result.is_ok());
+    let expected = vec![(vec![1u8, 1u8], vec![1u8]), (vec![1u8, 2u8], vec![0u8])];
+    assert_eq!(result.unwrap(), expected);
+}
+
 #[tokio::test]
 async fn read_notify() {
     // Create new store.
@@ -71,3 +127,19 @@ async fn read_notify() {
     store.write(key, value).await;
     assert!(handle.await.is_ok());
 }
+
+#[tokio::test]
+async fn reopen_in_memory_store() {
+    // Write a value to an in-memory store.
+    let db = MemoryDb::default();
+    let mut store = Store::new_in_memory(db.clone());
+    let key = vec![0u8, 1u8, 2u8, 3u8];
+    let value = vec![4u8, 5u8, 6u8, 7u8];
+    store.sync_write(key.clone(), value.clone()).await.unwrap();
+
+    // Open another store on the same database and read the value.
+    let mut store = Store::new_in_memory(db);
+    let result = store.read(key).await;
+    assert!(result.is_ok());
+    assert_eq!(result.unwrap(), Some(value));
+}